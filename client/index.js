@@ -1,6 +1,408 @@
-var socket = new WebSocket("/refresh-ws");
-socket.onmessage = function (message) {
-  if (message.data === "refresh") {
-    window.location.reload();
+var root = document.getElementById("root");
+var generation = Number(root.dataset.generation);
+var scrollKey = "mdflc-scroll:" + root.dataset.page;
+// not `status`, which would be window.status and only ever hold a string
+var statusIndicator = document.getElementById("status");
+var minDelay = 250;
+var maxDelay = 10000;
+var stopped = false;
+
+function setStatus(state, title) {
+  if (statusIndicator) {
+    statusIndicator.dataset.state = state;
+    statusIndicator.title = title;
   }
-};
+}
+
+// remembers where the reader was, relative to the nearest heading above them
+function saveScroll() {
+  var heading = null;
+  var headings = root.querySelectorAll(
+    "h1[id], h2[id], h3[id], h4[id], h5[id], h6[id]",
+  );
+  for (var i = 0; i < headings.length; i++) {
+    if (headings[i].getBoundingClientRect().top > 0) {
+      break;
+    }
+    heading = headings[i];
+  }
+  var position = {
+    y: window.scrollY,
+    heading: heading && heading.id,
+    offset: heading ? -heading.getBoundingClientRect().top : 0,
+  };
+  try {
+    sessionStorage.setItem(scrollKey, JSON.stringify(position));
+  } catch (e) {}
+}
+
+// prefers the heading, as line positions shift when content changes above it
+function restoreScroll() {
+  var position;
+  try {
+    position = JSON.parse(sessionStorage.getItem(scrollKey));
+    sessionStorage.removeItem(scrollKey);
+  } catch (e) {}
+  if (!position) {
+    return;
+  }
+  history.scrollRestoration = "manual";
+  var heading = position.heading && document.getElementById(position.heading);
+  if (heading) {
+    var top = heading.getBoundingClientRect().top + window.scrollY;
+    window.scrollTo(0, top + position.offset);
+  } else {
+    window.scrollTo(0, position.y);
+  }
+}
+
+function reload() {
+  saveScroll();
+  invalidate(window.location.pathname);
+  window.location.reload();
+}
+
+// with --offline, pages stay readable offline through sw.js, which is removed again once
+// the server runs without it
+function setupOffline() {
+  if (!("serviceWorker" in navigator)) {
+    return;
+  }
+  fetch("/manifest.json", { method: "HEAD", cache: "no-store" })
+    .then(function (response) {
+      if (response.ok) {
+        return navigator.serviceWorker.register("/sw.js", { scope: "/" }).then(function () {
+          return navigator.serviceWorker.ready.then(function (registration) {
+            // picks up pages changed since the worker last looked
+            registration.active.postMessage({ type: "prefetch" });
+          });
+        });
+      }
+      if (response.status === 404) {
+        return navigator.serviceWorker.getRegistrations().then(function (registrations) {
+          registrations.forEach(function (registration) {
+            registration.unregister();
+          });
+        });
+      }
+    })
+    .catch(function () {});
+}
+
+// drops the offline copy of a changed page
+function invalidate(url) {
+  var worker = navigator.serviceWorker && navigator.serviceWorker.controller;
+  if (worker) {
+    worker.postMessage({ type: "invalidate", url: url });
+  }
+}
+
+// refetches every stylesheet, keeping the page and its state
+function reloadStyles() {
+  var links = document.querySelectorAll('link[rel="stylesheet"]');
+  for (var i = 0; i < links.length; i++) {
+    var url = new URL(links[i].href);
+    url.searchParams.set("v", generation);
+    links[i].href = url.toString();
+  }
+}
+
+// follows `page`, calling onChange when something changed, and after reconnecting if
+// catchUp is set, as changes made while disconnected are never sent
+function connect(page, onChange, catchUp) {
+  var delay = minDelay;
+  var opened = false;
+  open();
+
+  function open() {
+    var scheme = location.protocol === "https:" ? "wss://" : "ws://";
+    var socket = new WebSocket(scheme + location.host + "/refresh-ws");
+    socket.onopen = function () {
+      delay = minDelay;
+      stopped = false;
+      setStatus("connected", "live reload connected");
+      // sockets that never subscribe are closed by the server
+      socket.send(JSON.stringify({ type: "subscribe", page: page, generation: generation }));
+      if (opened && catchUp) {
+        onChange();
+      }
+      opened = true;
+    };
+    socket.onmessage = function (message) {
+      var data = JSON.parse(message.data);
+      if (data.type === "shutdown") {
+        stopped = true;
+      } else if (data.type === "css") {
+        generation = data.generation;
+        reloadStyles();
+      } else if (data.type === "refresh" || data.generation > generation) {
+        onChange();
+      }
+    };
+    socket.onclose = function () {
+      if (stopped) {
+        setStatus("disconnected", "server stopped, waiting for it to restart");
+      } else {
+        // tells a server that is gone apart from a socket that can't get through
+        fetch("/api/ping", { cache: "no-store" }).then(
+          function (response) {
+            setStatus(
+              response.ok ? "reconnecting" : "disconnected",
+              response.ok ? "reconnecting to live reload" : "server unreachable",
+            );
+          },
+          function () {
+            setStatus("disconnected", "server unreachable");
+          },
+        );
+      }
+      setTimeout(open, delay);
+      delay = Math.min(delay * 2, maxDelay);
+    };
+  }
+}
+
+// the columns of /compare, each refreshed on its own when its page changes
+var columns = root.querySelectorAll(".compare-column");
+
+function refreshColumn(column) {
+  var page = encodeURI(column.dataset.page);
+  fetch("/api/hash/" + page, { cache: "no-store" })
+    .then(function (response) {
+      return response.ok ? response.json() : { hash: "" };
+    })
+    .then(function (probe) {
+      if (probe.hash === column.dataset.hash) {
+        return;
+      }
+      invalidate("/" + page);
+      // the normal page, or the 404 page for pages that are gone
+      return fetch("/" + page, { cache: "no-store" })
+        .then(function (response) {
+          return response.text();
+        })
+        .then(function (html) {
+          var body = column.querySelector(".compare-body");
+          var doc = new DOMParser().parseFromString(html, "text/html");
+          body.innerHTML = doc.getElementById("root").innerHTML;
+          column.dataset.hash = probe.hash;
+          renderMath(body);
+          renderDiagrams(body);
+        });
+    })
+    .catch(function () {});
+}
+
+// keeps the columns at the same relative position
+function syncScroll() {
+  var syncing = null;
+  for (var i = 0; i < columns.length; i++) {
+    columns[i].addEventListener("scroll", function (event) {
+      var source = event.target;
+      if (syncing && syncing !== source) {
+        syncing = null;
+        return;
+      }
+      var range = source.scrollHeight - source.clientHeight;
+      var ratio = range > 0 ? source.scrollTop / range : 0;
+      for (var j = 0; j < columns.length; j++) {
+        var other = columns[j];
+        if (other !== source) {
+          syncing = source;
+          other.scrollTop = ratio * (other.scrollHeight - other.clientHeight);
+        }
+      }
+    });
+  }
+}
+
+// the pages before and after this one, in the order listings show them
+var nav = null;
+
+function navLink(link, className, label) {
+  var a = document.createElement("a");
+  a.className = className;
+  a.href = "/" + encodeURI(link.key);
+  a.textContent = label;
+  return a;
+}
+
+function loadNav() {
+  fetch("/api/nav/" + encodeURI(root.dataset.page), { cache: "no-store" })
+    .then(function (response) {
+      return response.ok ? response.json() : null;
+    })
+    .then(function (data) {
+      nav = data;
+      if (!nav || !(nav.prev || nav.next)) {
+        return;
+      }
+      var footer = document.createElement("nav");
+      footer.className = "page-nav";
+      if (nav.prev) {
+        footer.appendChild(navLink(nav.prev, "page-nav-prev", "\u2190 " + nav.prev.title));
+      }
+      if (nav.next) {
+        footer.appendChild(navLink(nav.next, "page-nav-next", nav.next.title + " \u2192"));
+      }
+      root.after(footer);
+    })
+    .catch(function () {});
+}
+
+document.addEventListener("keydown", function (event) {
+  var target = event.target;
+  if (
+    !nav ||
+    event.ctrlKey ||
+    event.metaKey ||
+    event.altKey ||
+    target.isContentEditable ||
+    /^(INPUT|TEXTAREA|SELECT)$/.test(target.tagName)
+  ) {
+    return;
+  }
+  var link = event.key === "n" ? nav.next : event.key === "p" ? nav.prev : null;
+  if (link) {
+    window.location.href = "/" + encodeURI(link.key);
+  }
+});
+
+// partial render problems can be dismissed until the next reload
+var problems = document.querySelector(".problems");
+if (problems) {
+  problems.querySelector(".problems-dismiss").onclick = function () {
+    problems.remove();
+  };
+}
+
+// the server only sends when a page was modified, cached pages would show a stale
+// relative time otherwise
+var modified = document.querySelector(".modified time");
+var modifiedAgo = null;
+
+function ago(time) {
+  var units = [["d", 86400], ["h", 3600], ["m", 60], ["s", 1]];
+  var elapsed = Math.floor((Date.now() - Date.parse(time)) / 1000);
+  if (!(elapsed >= 5)) {
+    return "just now";
+  }
+  for (var i = 0; i < units.length; i++) {
+    if (elapsed >= units[i][1]) {
+      return Math.floor(elapsed / units[i][1]) + units[i][0] + " ago";
+    }
+  }
+}
+
+function showModified() {
+  if (!modifiedAgo) {
+    modifiedAgo = document.createElement("span");
+    modified.after(modifiedAgo);
+  }
+  modifiedAgo.textContent = " (" + ago(modified.dateTime) + ")";
+}
+
+if (modified) {
+  showModified();
+  setInterval(showModified, 30000);
+}
+
+function loadScript(src, onload, onerror) {
+  var script = document.createElement("script");
+  script.src = src;
+  script.onload = onload;
+  script.onerror = onerror;
+  document.head.appendChild(script);
+}
+
+// math is marked by the server with --math, the bundled katex is only fetched for pages
+// that have any, and renders to mathml so it needs neither stylesheets nor fonts
+function renderMath(container) {
+  var math = container.querySelectorAll("[data-math]");
+  if (!math.length) {
+    return;
+  }
+  loadScript("/vendor/katex.min.js", function () {
+    for (var i = 0; i < math.length; i++) {
+      katex.render(math[i].textContent, math[i], {
+        displayMode: math[i].dataset.math === "display",
+        output: "mathml",
+        throwOnError: false,
+      });
+    }
+  });
+}
+
+// mermaid fences are left as their source by the server, every refresh reloads the
+// page, which draws them again from the new source with the bundled mermaid
+function renderDiagrams(container) {
+  var diagrams = container.querySelectorAll("pre.mermaid");
+  if (!diagrams.length) {
+    return;
+  }
+  loadScript(
+    "/vendor/mermaid.min.js",
+    function () {
+      mermaid.initialize({ startOnLoad: false });
+      mermaid.run({ nodes: diagrams }).catch(function (e) {
+        diagramsFailed(diagrams, e && e.message ? e.message : String(e));
+      });
+    },
+    function () {
+      diagramsFailed(diagrams, "unable to load mermaid");
+    },
+  );
+}
+
+// notes the failure above every diagram left undrawn, styled like failed fences
+function diagramsFailed(diagrams, message) {
+  for (var i = 0; i < diagrams.length; i++) {
+    if (diagrams[i].querySelector("svg")) {
+      continue;
+    }
+    var note = document.createElement("div");
+    note.className = "fence-error";
+    note.setAttribute("role", "alert");
+    note.textContent = "mermaid: " + message;
+    diagrams[i].before(note);
+  }
+}
+
+// with --edit-tasks checkboxes are numbered, checking one writes it to the page's file,
+// which refreshes the page like any other change
+function setupTasks(container, page) {
+  container.querySelectorAll("input[data-task]").forEach(function (box) {
+    box.addEventListener("change", function () {
+      fetch("/api/task/" + encodeURI(page), {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ index: Number(box.dataset.task), checked: box.checked }),
+      })
+        .then(function (response) {
+          if (!response.ok) {
+            throw new Error(response.statusText);
+          }
+        })
+        .catch(function () {
+          box.checked = !box.checked;
+        });
+    });
+  });
+}
+
+restoreScroll();
+setupOffline();
+renderMath(root);
+renderDiagrams(root);
+if (columns.length) {
+  syncScroll();
+  columns.forEach(function (column) {
+    connect(column.dataset.page, function () {
+      refreshColumn(column);
+    }, true);
+  });
+} else {
+  loadNav();
+  setupTasks(root, root.dataset.page);
+  connect(root.dataset.page, reload, false);
+}
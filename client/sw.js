@@ -0,0 +1,134 @@
+// keeps pages readable offline, registered by index.js when the server runs with --offline
+//
+// requests go to the network first, so nothing stale is shown while online, the cache is
+// only used once the network fails
+var CACHE = "mdflc";
+// the manifest hash of prefetched responses, refetched once it changes
+var HASH_HEADER = "x-mdflc-hash";
+
+self.addEventListener("install", function () {
+  self.skipWaiting();
+});
+
+self.addEventListener("activate", function (event) {
+  event.waitUntil(self.clients.claim().then(prefetch));
+});
+
+self.addEventListener("message", function (event) {
+  var data = event.data || {};
+  if (data.type === "invalidate") {
+    event.waitUntil(
+      caches.open(CACHE).then(function (cache) {
+        return cache.delete(data.url, { ignoreVary: true });
+      }),
+    );
+  } else if (data.type === "prefetch") {
+    event.waitUntil(prefetch());
+  }
+});
+
+// live data is never cached
+var uncached = /^\/(api|diff|compare|healthz|refresh-ws|manifest\.json|sw\.js)(\/|$)/;
+
+self.addEventListener("fetch", function (event) {
+  var request = event.request;
+  var url = new URL(request.url);
+  if (
+    request.method !== "GET" ||
+    url.origin !== location.origin ||
+    url.search ||
+    uncached.test(url.pathname)
+  ) {
+    return;
+  }
+  event.respondWith(
+    fetch(request).then(
+      function (response) {
+        if (response.ok) {
+          var copy = response.clone();
+          caches.open(CACHE).then(function (cache) {
+            cache.put(request, copy);
+          });
+        }
+        return response;
+      },
+      function () {
+        // pages vary on Accept, which differs between navigations and prefetches
+        return caches.match(request, { ignoreVary: true }).then(function (cached) {
+          return cached || Response.error();
+        });
+      },
+    ),
+  );
+});
+
+// caches the assets, and as many pages as the manifest's prefetch size allows
+function prefetch() {
+  return fetch("/manifest.json", { cache: "no-store" })
+    .then(function (response) {
+      return response.ok ? response.json() : null;
+    })
+    .then(function (manifest) {
+      if (!manifest) {
+        return;
+      }
+      var known = {};
+      var entries = manifest.assets.slice();
+      var budget = manifest.prefetch || 0;
+      manifest.assets.concat(manifest.pages).forEach(function (entry) {
+        known[new URL(entry.url, location.origin).pathname] = true;
+      });
+      manifest.pages.forEach(function (page) {
+        if (page.size <= budget) {
+          budget -= page.size;
+          entries.push(page);
+        }
+      });
+      return caches.open(CACHE).then(function (cache) {
+        return cache
+          .keys()
+          .then(function (requests) {
+            // pages that were removed
+            return Promise.all(
+              requests
+                .filter(function (request) {
+                  return !known[new URL(request.url).pathname];
+                })
+                .map(function (request) {
+                  return cache.delete(request);
+                }),
+            );
+          })
+          .then(function () {
+            return Promise.all(
+              entries.map(function (entry) {
+                return refresh(cache, entry);
+              }),
+            );
+          });
+      });
+    })
+    .catch(function () {});
+}
+
+// fetches `entry` again, unless the cached copy has the same hash
+function refresh(cache, entry) {
+  return cache.match(entry.url, { ignoreVary: true }).then(function (cached) {
+    if (cached && cached.headers.get(HASH_HEADER) === entry.hash) {
+      return;
+    }
+    return fetch(entry.url, { cache: "no-store" }).then(function (response) {
+      if (!response.ok) {
+        return;
+      }
+      return response.blob().then(function (body) {
+        var headers = new Headers(response.headers);
+        // the body was already decoded
+        headers.delete("content-encoding");
+        headers.delete("content-length");
+        headers.set(HASH_HEADER, entry.hash);
+        return cache.put(entry.url, new Response(body, { headers: headers }));
+      });
+    });
+  });
+}
@@ -0,0 +1,226 @@
+use std::{fmt, future::Future, str::FromStr};
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Serialize, Serializer};
+
+tokio::task_local! {
+    /// Who is making the request being handled, see [`scope`]
+    static VIEWER: Viewer;
+}
+
+/// Keys under a prefix that are only served with the right credentials
+#[derive(Clone, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    /// The protected prefix, without leading or trailing slashes
+    pub prefix: String,
+    /// The user and password allowed in, `None` if nobody is
+    #[serde(serialize_with = "serialize_credentials")]
+    pub credentials: Option<(String, String)>,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Parses `<prefix>` or `<prefix>:<user>:<password>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, credentials) = match s.split_once(':') {
+            None => (s, None),
+            Some((prefix, rest)) => {
+                let (user, password) = rest
+                    .split_once(':')
+                    .ok_or_else(|| "expected <prefix>[:<user>:<password>]".to_owned())?;
+                if user.is_empty() {
+                    return Err("the user must not be empty".to_owned());
+                }
+                (prefix, Some((user.to_owned(), password.to_owned())))
+            }
+        };
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            return Err("the protected prefix must not be empty".to_owned());
+        }
+        Ok(Self {
+            prefix: prefix.to_owned(),
+            credentials,
+        })
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rule")
+            .field("prefix", &self.prefix)
+            .field("user", &self.credentials.as_ref().map(|(user, _)| user))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shows who may enter without giving the password away
+#[allow(clippy::ref_option)] // serde passes fields by reference
+fn serialize_credentials<S: Serializer>(
+    credentials: &Option<(String, String)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    credentials
+        .as_ref()
+        .map(|(user, _)| user)
+        .serialize(serializer)
+}
+
+impl Rule {
+    /// Whether `key` is under this rule's prefix
+    #[must_use]
+    pub fn covers(&self, key: &str) -> bool {
+        key.strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Whether `viewer` gave this rule's credentials
+    #[must_use]
+    pub fn admits(&self, viewer: &Viewer) -> bool {
+        match (&self.credentials, &viewer.credentials) {
+            (Some((user, password)), Some(given)) => matches(user, password, given),
+            _ => false,
+        }
+    }
+}
+
+/// The user and password every request must give, see [`crate::settings::Settings::auth`]
+#[derive(Clone, PartialEq, Eq, Serialize)]
+pub struct Login {
+    pub user: String,
+    #[serde(skip)]
+    pub password: String,
+}
+
+impl FromStr for Login {
+    type Err = String;
+
+    /// Parses `<user>:<password>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, password) = s
+            .split_once(':')
+            .ok_or_else(|| "expected <user>:<password>".to_owned())?;
+        if user.is_empty() {
+            return Err("the user must not be empty".to_owned());
+        }
+        Ok(Self {
+            user: user.to_owned(),
+            password: password.to_owned(),
+        })
+    }
+}
+
+impl fmt::Debug for Login {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Login")
+            .field("user", &self.user)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Login {
+    /// Whether `viewer` logged in as this user
+    #[must_use]
+    pub fn admits(&self, viewer: &Viewer) -> bool {
+        viewer
+            .credentials
+            .as_ref()
+            .is_some_and(|given| matches(&self.user, &self.password, given))
+    }
+}
+
+/// Compares credentials in constant time, so timing doesn't tell how much was right
+fn matches(user: &str, password: &str, (given_user, given_password): &(String, String)) -> bool {
+    // both are compared even when the user is wrong
+    constant_time_eq(user, given_user) & constant_time_eq(password, given_password)
+}
+
+/// Whether `a` equals `b`, taking as long however early they differ
+///
+/// Only the length of `b` may be learned from the time taken.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let differences = (0..b.len()).fold(a.len() ^ b.len(), |acc, i| {
+        acc | usize::from(a.get(i).copied().unwrap_or(0) ^ b[i])
+    });
+    differences == 0
+}
+
+/// What a request is allowed to see, see [`Access::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict<'a> {
+    Allowed,
+    /// The request must authenticate for the rule's prefix
+    Unauthorized(&'a Rule),
+    /// Nobody may see the key
+    Denied,
+}
+
+/// The `--protect` rules, consulted by everything that serves or lists keys
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Access {
+    rules: Vec<Rule>,
+}
+
+impl Access {
+    #[must_use]
+    pub const fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    /// Decides whether `viewer` may see `key`, every rule covering it must admit them
+    #[must_use]
+    pub fn check(&self, key: &str, viewer: &Viewer) -> Verdict<'_> {
+        let key = key.trim_matches('/');
+        let mut covering = self.rules.iter().filter(|rule| rule.covers(key));
+        match covering.find(|rule| !rule.admits(viewer)) {
+            None => Verdict::Allowed,
+            Some(rule) if rule.credentials.is_some() => Verdict::Unauthorized(rule),
+            Some(_) => Verdict::Denied,
+        }
+    }
+
+    /// Whether the request being handled may see `key`
+    ///
+    /// Work done outside of a request, such as scanning and the console, sees everything.
+    #[must_use]
+    pub fn permits(&self, key: &str) -> bool {
+        self.rules.is_empty()
+            || VIEWER
+                .try_with(|viewer| self.check(key, viewer) == Verdict::Allowed)
+                .unwrap_or(true)
+    }
+}
+
+/// Who is making a request, as far as [`Access`] is concerned
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Viewer {
+    /// The user and password of a basic `Authorization` header
+    pub credentials: Option<(String, String)>,
+}
+
+impl Viewer {
+    /// Reads the viewer's credentials from basic `Authorization` headers
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let credentials = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|decoded| {
+                let (user, password) = decoded.split_once(':')?;
+                Some((user.to_owned(), password.to_owned()))
+            });
+        Self { credentials }
+    }
+}
+
+/// Runs `f` on behalf of `viewer`, see [`Access::permits`]
+pub async fn scope<F: Future>(viewer: Viewer, f: F) -> F::Output {
+    VIEWER.scope(viewer, f).await
+}
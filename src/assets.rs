@@ -0,0 +1,174 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use axum::{
+    http::{
+        header::{
+            CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG, X_CONTENT_TYPE_OPTIONS,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{is_cached, render};
+
+/// A directory served read-only under a url prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    /// The url prefix, without leading or trailing slashes
+    pub prefix: String,
+    pub dir: PathBuf,
+}
+
+impl FromStr for Mount {
+    type Err = String;
+
+    /// Parses `<url-prefix>=<dir>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, dir) = s
+            .split_once('=')
+            .ok_or_else(|| "expected <url-prefix>=<dir>".to_owned())?;
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            return Err("the url prefix must not be empty".to_owned());
+        }
+        Ok(Self {
+            prefix: prefix.to_owned(),
+            dir: dir.into(),
+        })
+    }
+}
+
+impl Mount {
+    /// Whether `key` would be served by this mount
+    #[must_use]
+    pub fn shadows(&self, key: &str) -> bool {
+        key.strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+/// The url prefix files within base are served under, see [`asset_url`]
+pub const ASSET_PREFIX: &str = "assets";
+
+/// Resolves a relative `dest`, such as an image's, on the page at `key` into the url
+/// the file is served at under [`ASSET_PREFIX`]
+///
+/// Returns `None` for other sites, root relative and fragment only urls, and paths
+/// escaping base.
+#[must_use]
+pub fn asset_url(key: &str, dest: &str) -> Option<String> {
+    let scheme = dest.split_once(':').map(|(scheme, _)| scheme);
+    if dest.is_empty()
+        || dest.starts_with(['/', '#', '?'])
+        || scheme.is_some_and(|s| !s.contains('/'))
+    {
+        return None;
+    }
+    let end = dest.find(['?', '#']).unwrap_or(dest.len());
+    let (path, rest) = dest.split_at(end);
+    let segments = render::resolve_path(key, path)?;
+    Some(format!("/{ASSET_PREFIX}/{}{rest}", segments.join("/")))
+}
+
+/// Guesses the content type of a file from its extension
+#[must_use]
+pub fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("md" | "markdown") => "text/markdown; charset=utf-8",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("wav") => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Resolves `path` within `dir`, refusing anything that escapes it
+#[must_use]
+pub fn resolve(dir: &Path, path: &str) -> Option<PathBuf> {
+    let path = dir.join(path.trim_start_matches('/')).canonicalize().ok()?;
+    (path.starts_with(dir) && path.is_file()).then_some(path)
+}
+
+/// Serves the file at `path` within `dir`, 404ing for anything outside of it
+///
+/// `dir` must be canonical. Files that could run script, such as html and svg, are
+/// served sandboxed, and types are never sniffed.
+#[must_use]
+pub fn serve_file(dir: &Path, path: &str, headers: &HeaderMap) -> Response {
+    let Some(path) = resolve(dir, path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Ok(meta) = fs::metadata(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    let etag = format!("W/\"{:x}-{modified:x}\"", meta.len());
+    if is_cached(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+
+    let Ok(bytes) = fs::read(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let content_type = content_type(&path);
+    let mut response = (
+        [
+            (CONTENT_TYPE, content_type.to_owned()),
+            (CACHE_CONTROL, "no-cache".to_owned()),
+            (ETAG, etag),
+        ],
+        bytes,
+    )
+        .into_response();
+    let headers = response.headers_mut();
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    // files from an untrusted base must not run script on the server's origin
+    if is_active(content_type) {
+        headers.insert(CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+    }
+    response
+}
+
+/// Whether a file of `content_type` may run script when opened on its own
+fn is_active(content_type: &str) -> bool {
+    ["text/html", "image/svg+xml", "application/xml"]
+        .iter()
+        .any(|active| content_type.starts_with(active))
+}
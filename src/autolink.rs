@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use pulldown_cmark::{CowStr, Event, LinkType, Tag, TagEnd};
+
+/// Punctuation that ends the sentence around a url rather than the url itself
+const TRAILING: &[char] = &[
+    '.', ',', ':', ';', '!', '?', '*', '_', '~', '\'', '"', '’', '”',
+];
+
+/// The ranges of the bare `http://` and `https://` urls in `text`
+///
+/// Urls start after whitespace or an opening bracket or quote, and run until whitespace
+/// or `<`. Trailing punctuation is left out, as is a `)`, `]` or `}` closing a bracket
+/// opened before the url, so `(see https://example.com).` links only `https://example.com`.
+#[must_use]
+pub fn find(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = text[rest..].find("http").map(|i| rest + i) {
+        rest = start + 1;
+        let bounded = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || "([{<\"'*_~‘“".contains(c));
+        let scheme = ["https://", "http://"]
+            .into_iter()
+            .find(|scheme| text[start..].starts_with(scheme));
+        let Some(scheme) = scheme.filter(|_| bounded) else {
+            continue;
+        };
+        let len = text[start..]
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .unwrap_or(text.len() - start);
+        let mut url = &text[start..start + len];
+        loop {
+            if let Some(trimmed) = url.strip_suffix(TRAILING) {
+                url = trimmed;
+            } else if unbalanced(url) {
+                url = &url[..url.len() - 1];
+            } else {
+                break;
+            }
+        }
+        // the scheme alone, or followed by punctuation, isn't a link
+        if !url[scheme.len()..].starts_with(char::is_alphanumeric) {
+            continue;
+        }
+        urls.push(start..start + url.len());
+        rest = start + url.len();
+    }
+    urls
+}
+
+/// Whether `url` ends with a bracket it never opened
+fn unbalanced(url: &str) -> bool {
+    [('(', ')'), ('[', ']'), ('{', '}')]
+        .into_iter()
+        .any(|(open, close)| {
+            url.ends_with(close) && url.matches(close).count() > url.matches(open).count()
+        })
+}
+
+/// Links bare urls in text events, see [`crate::render::Config::autolink`]
+///
+/// Inline code is its own event, so it is never touched, and text within code blocks,
+/// links and images is left alone. Expects merged text events, as the parser splits
+/// text at characters like `_`.
+#[must_use]
+pub fn apply(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    // how many code blocks, links and images the event is within
+    let mut within = 0_usize;
+    for event in events {
+        match &event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Link { .. } | Tag::Image { .. }) => {
+                within += 1;
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::Link | TagEnd::Image) => {
+                within = within.saturating_sub(1);
+            }
+            Event::Text(text) if within == 0 => {
+                let urls = find(text);
+                if !urls.is_empty() {
+                    link(text, &urls, &mut out);
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        out.push(event);
+    }
+    out
+}
+
+/// Pushes `text` to `out`, with the `urls` within it as links
+fn link(text: &str, urls: &[Range<usize>], out: &mut Vec<Event<'_>>) {
+    let mut copied = 0;
+    for range in urls {
+        if copied < range.start {
+            out.push(Event::Text(CowStr::from(
+                text[copied..range.start].to_owned(),
+            )));
+        }
+        let url = &text[range.clone()];
+        out.push(Event::Start(Tag::Link {
+            link_type: LinkType::Autolink,
+            dest_url: CowStr::from(url.to_owned()),
+            title: CowStr::Borrowed(""),
+            id: CowStr::Borrowed(""),
+        }));
+        out.push(Event::Text(CowStr::from(url.to_owned())));
+        out.push(Event::End(TagEnd::Link));
+        copied = range.end;
+    }
+    if copied < text.len() {
+        out.push(Event::Text(CowStr::from(text[copied..].to_owned())));
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Tag};
+
+/// An event along with the range of the source it was parsed from
+pub type Spanned<'a> = (Event<'a>, Range<usize>);
+
+/// The index of the event ending the block starting at `i`
+pub fn end(events: &[Spanned], i: usize) -> usize {
+    let mut depth = 0_usize;
+    for (j, (event, _)) in events.iter().enumerate().skip(i) {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        if depth == 0 {
+            return j;
+        }
+    }
+    events.len() - 1
+}
+
+/// Whether `event` starts a block holding other blocks
+pub const fn is_container(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Start(Tag::BlockQuote(_) | Tag::List(_) | Tag::Item | Tag::FootnoteDefinition(_))
+    )
+}
+
+/// How far into its line the source at `offset` starts, past any indentation
+pub fn column(text: &str, offset: usize) -> usize {
+    let rest = &text[offset..];
+    let offset = offset + rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    let line = text[..offset].rfind('\n').map_or(0, |n| n + 1);
+    text[line..offset].chars().count()
+}
+
+/// The ranges of the events on each line of the paragraph at `start..end`
+///
+/// Lines are split at the soft breaks between them, which are left out. Empty unless the
+/// block is a paragraph.
+pub fn lines(events: &[Spanned], start: usize, end: usize) -> Vec<Range<usize>> {
+    if !matches!(events[start].0, Event::Start(Tag::Paragraph)) || end - start < 2 {
+        return Vec::new();
+    }
+    let mut lines = Vec::new();
+    let mut line = start + 1;
+    let mut depth = 0_usize;
+    for (i, (event, _)) in events.iter().enumerate().take(end - 1).skip(start + 1) {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::SoftBreak if depth == 0 => {
+                lines.push(line..i);
+                line = i + 1;
+            }
+            _ => (),
+        }
+    }
+    lines.push(line..end - 1);
+    lines
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// The summary looked for in base when `--summary` isn't given
+pub const SUMMARY_FILE: &str = "SUMMARY.md";
+
+/// One line of a summary, see [`Book::parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Chapter(Chapter),
+    /// A `---` between chapters
+    Separator,
+    /// A heading grouping the chapters after it
+    PartTitle(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    /// The page the chapter links to, `None` for drafts
+    pub key: Option<String>,
+    /// How deeply the chapter's list is nested, `0` for prefix and suffix chapters
+    pub depth: usize,
+}
+
+/// The structure of an mdBook `SUMMARY.md`
+///
+/// Listed pages come first in listings and navigation, in the order listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Book {
+    pub items: Vec<Item>,
+    /// The position of every linked chapter within `items`, the first if listed twice
+    positions: HashMap<String, usize>,
+}
+
+impl Book {
+    /// Parses the summary at `key`, whose links are relative to it
+    ///
+    /// The title heading is skipped, later headings are part titles. Chapters are the
+    /// links of list items, or of paragraphs outside of lists, and list items linking
+    /// nowhere, like `- [Title]()`, are drafts.
+    #[must_use]
+    pub fn parse(key: &str, text: &str) -> Self {
+        let mut items = Vec::new();
+        let mut depth = 0;
+        let mut chapter: Option<Chapter> = None;
+        let mut heading: Option<String> = None;
+        let mut titled = false;
+        for event in Parser::new(text) {
+            match event {
+                Event::Start(Tag::Heading { .. }) => heading = Some(String::new()),
+                Event::End(TagEnd::Heading(_)) => {
+                    let title = heading.take().unwrap_or_default();
+                    if titled || !items.is_empty() {
+                        items.push(Item::PartTitle(title.trim().to_owned()));
+                    }
+                    titled = true;
+                }
+                Event::Rule => items.push(Item::Separator),
+                Event::Start(Tag::List(_)) => {
+                    items.extend(chapter.take().map(Item::Chapter));
+                    depth += 1;
+                }
+                Event::End(TagEnd::List(_)) => depth -= 1,
+                Event::Start(Tag::Item) => {
+                    chapter = Some(Chapter {
+                        title: String::new(),
+                        key: None,
+                        depth,
+                    });
+                }
+                Event::Start(Tag::Link { dest_url, .. }) => {
+                    let chapter = chapter.get_or_insert_with(|| Chapter {
+                        title: String::new(),
+                        key: None,
+                        depth,
+                    });
+                    chapter.key = chapter_key(key, &dest_url);
+                }
+                Event::End(TagEnd::Item) => items.extend(chapter.take().map(Item::Chapter)),
+                Event::End(TagEnd::Link) if depth == 0 => {
+                    items.extend(chapter.take().map(Item::Chapter));
+                }
+                Event::Text(text) | Event::Code(text) => {
+                    if let Some(heading) = &mut heading {
+                        heading.push_str(&text);
+                    } else if let Some(chapter) = &mut chapter {
+                        chapter.title.push_str(&text);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut positions = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if let Item::Chapter(Chapter { key: Some(key), .. }) = item {
+                positions.entry(key.clone()).or_insert(i);
+            }
+        }
+        Self { items, positions }
+    }
+
+    /// Every chapter, in the order listed
+    pub fn chapters(&self) -> impl Iterator<Item = &Chapter> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Chapter(chapter) => Some(chapter),
+            _ => None,
+        })
+    }
+
+    /// The page of the first chapter that isn't a draft
+    #[must_use]
+    pub fn first(&self) -> Option<&str> {
+        self.chapters().find_map(|chapter| chapter.key.as_deref())
+    }
+
+    /// Where `key` is listed, pages listed earlier come first
+    #[must_use]
+    pub fn position(&self, key: &str) -> Option<usize> {
+        self.positions.get(key).copied()
+    }
+
+    #[must_use]
+    pub fn lists(&self, key: &str) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// The nearest chapter `key` is nested within that isn't a draft
+    ///
+    /// Part titles and separators end the search, chapters only nest within lists.
+    #[must_use]
+    pub fn parent(&self, key: &str) -> Option<&str> {
+        let i = self.position(key)?;
+        let Item::Chapter(chapter) = &self.items[i] else {
+            return None;
+        };
+        let mut depth = chapter.depth;
+        for item in self.items[..i].iter().rev() {
+            let Item::Chapter(parent) = item else {
+                return None;
+            };
+            if parent.depth == 0 {
+                return None;
+            }
+            if parent.depth < depth {
+                if parent.key.is_some() {
+                    return parent.key.as_deref();
+                }
+                depth = parent.depth;
+            }
+        }
+        None
+    }
+
+    /// The first chapter nested directly within `key` that isn't a draft
+    #[must_use]
+    pub fn first_child(&self, key: &str) -> Option<&str> {
+        let i = self.position(key)?;
+        let Item::Chapter(chapter) = &self.items[i] else {
+            return None;
+        };
+        if chapter.depth == 0 {
+            return None;
+        }
+        self.items[i + 1..]
+            .iter()
+            .map_while(|item| match item {
+                Item::Chapter(child) if child.depth > chapter.depth => Some(child),
+                _ => None,
+            })
+            .find_map(|child| child.key.as_deref())
+    }
+}
+
+/// Resolves a chapter's link on the summary at `key` into the key of its page
+///
+/// Returns `None` for drafts and links to other sites.
+fn chapter_key(key: &str, dest: &str) -> Option<String> {
+    let path = dest.split(['#', '?']).next().unwrap_or_default();
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    if path.is_empty() || path.contains("://") || path.starts_with("mailto:") {
+        return None;
+    }
+    let mut segments: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        key.split('/').collect()
+    };
+    // the summary itself isn't a directory
+    segments.pop();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let target = segments.join("/");
+    Some(target.strip_suffix(".md").unwrap_or(&target).to_owned())
+}
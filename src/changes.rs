@@ -0,0 +1,154 @@
+use std::{collections::VecDeque, fmt::Write, sync::Mutex};
+
+use pulldown_cmark_escape::{escape_href, escape_html};
+use serde::Serialize;
+
+use crate::{time::Timestamp, ChangeKind, MutexExt};
+
+/// The maximum number of changes kept, older changes are dropped first
+pub const MAX_CHANGES: usize = 256;
+
+/// The key of the built-in recent changes page, see [`render_recent`]
+pub const RECENT_KEY: &str = "recent";
+
+/// How many changes the recent changes page shows
+pub const RECENT_PAGE_LEN: usize = 50;
+
+/// How many [`ChangeEvent`]s a subscriber may fall behind by, see [`crate::Api::subscribe`]
+///
+/// As many are kept for subscribers catching up, see [`EventLog`].
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Sent to subscribers whenever served content changes, see [`crate::Api::subscribe`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangeEvent {
+    /// The key of the page, or the url path of a file within a mount, starting with a slash
+    pub key: String,
+    pub kind: ChangeKind,
+    /// The new source hash of the page, see [`crate::CacheKey::source`]
+    ///
+    /// `None` for removed pages and files within mounts.
+    pub hash: Option<u64>,
+    /// The update generation the change was made in, see [`crate::Api::generation`]
+    pub generation: u64,
+    pub timestamp: Timestamp,
+    /// Whether every change of the generation was to a stylesheet within a mount
+    ///
+    /// Clients can then reload their styles instead of the whole page.
+    pub styles_only: bool,
+}
+
+/// A page being added, modified or removed
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub key: String,
+    /// The title of the page, from before it was removed for [`ChangeKind::Removed`]
+    pub title: String,
+    pub kind: ChangeKind,
+    pub timestamp: Timestamp,
+}
+
+/// A bounded log of page changes
+#[derive(Debug, Default)]
+pub struct ChangeLog {
+    list: Mutex<VecDeque<Change>>,
+}
+
+impl ChangeLog {
+    pub fn push(&self, key: &str, title: String, kind: ChangeKind) {
+        let mut list = self.list.unlock();
+        if list.len() == MAX_CHANGES {
+            list.pop_front();
+        }
+        list.push_back(Change {
+            key: key.to_owned(),
+            title,
+            kind,
+            timestamp: Timestamp::now(),
+        });
+    }
+
+    /// Returns the last `n` changes, newest first
+    #[must_use]
+    pub fn recent(&self, n: usize) -> Vec<Change> {
+        self.list.unlock().iter().rev().take(n).cloned().collect()
+    }
+}
+
+/// The last [`EVENT_CAPACITY`] events, see [`crate::Api::subscribe_since`]
+#[derive(Debug)]
+pub struct EventLog {
+    list: VecDeque<ChangeEvent>,
+    /// Every event made after this generation is still kept
+    complete_after: u64,
+}
+
+impl EventLog {
+    /// An empty log for a server starting at `generation`
+    #[must_use]
+    pub const fn new(generation: u64) -> Self {
+        Self {
+            list: VecDeque::new(),
+            complete_after: generation,
+        }
+    }
+
+    pub fn push(&mut self, event: ChangeEvent) {
+        if self.list.len() == EVENT_CAPACITY {
+            if let Some(dropped) = self.list.pop_front() {
+                self.complete_after = self.complete_after.max(dropped.generation);
+            }
+        }
+        self.list.push_back(event);
+    }
+
+    /// The events made after `generation`, oldest first, `None` if some were dropped
+    ///
+    /// Generations from before the server started can't be caught up on either.
+    #[must_use]
+    pub fn since(&self, generation: u64) -> Option<Vec<ChangeEvent>> {
+        if generation < self.complete_after {
+            return None;
+        }
+        let events = self.list.iter().filter(|e| e.generation > generation);
+        Some(events.cloned().collect())
+    }
+}
+
+/// Renders `changes` as a list, removed pages aren't linked
+#[must_use]
+pub fn render_recent(changes: &[Change], now: Timestamp) -> String {
+    let mut html = String::from("<h1>Recent changes</h1>\n");
+    if changes.is_empty() {
+        html.push_str("<p>Nothing has changed since the server started.</p>\n");
+        return html;
+    }
+    html.push_str("<ul class=\"recent\">\n");
+    for change in changes {
+        let kind = change.kind.name();
+        let title = if change.title.is_empty() {
+            &change.key
+        } else {
+            &change.title
+        };
+        let _ = write!(html, "<li class=\"{kind}\">");
+        if change.kind == ChangeKind::Removed {
+            let _ = escape_html(&mut html, title);
+        } else {
+            html.push_str("<a href=\"/");
+            let _ = escape_href(&mut html, &change.key);
+            html.push_str("\">");
+            let _ = escape_html(&mut html, title);
+            html.push_str("</a>");
+        }
+        let _ = writeln!(
+            html,
+            " <span class=\"change-kind\">{kind}</span> \
+            <time class=\"change-time\" datetime=\"{}\">{}</time></li>",
+            change.timestamp,
+            change.timestamp.ago(now)
+        );
+    }
+    html.push_str("</ul>\n");
+    html
+}
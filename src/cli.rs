@@ -2,12 +2,14 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     fmt::Debug,
+    io::{self, Write},
     net::SocketAddr,
     path::PathBuf,
+    time::Duration,
 };
 
 use anyhow::{bail, ensure, Context, Ok as AnyOk};
-use clap::Parser;
+use clap::{builder::PossibleValuesParser, Parser, ValueEnum};
 use easy_sgr::{Color::*, Style::*};
 use rustyline::{
     completion::Completer,
@@ -21,21 +23,362 @@ use rustyline::{
 };
 use watchexec::Watchexec;
 
-use crate::{Api, MutexExt};
+/// Writes a line to the output of a console command, see [`Output`]
+macro_rules! outln {
+    ($o:expr, $($arg:tt)*) => {{
+        let _ = writeln!($o.out, $($arg)*);
+    }};
+}
+
+/// Writes a line to the error output of a console command, see [`Output`]
+macro_rules! errln {
+    ($o:expr, $($arg:tt)*) => {{
+        let _ = writeln!($o.err(), $($arg)*);
+    }};
+}
+
+use crate::{
+    access::{Login, Rule},
+    assets::Mount,
+    changes::MAX_CHANGES,
+    options, pager,
+    render::{self, RawHtml},
+    settings::{self, Fallback},
+    time::Timestamp,
+    watch::WatchState,
+    Api, MutexExt, ScanPreview,
+};
 
 /// host a markdown file server
 #[derive(Parser, Debug)]
 #[command(name = "mdflc")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Args {
     /// The base path to read
     #[arg(default_value = "./")]
     pub base: PathBuf,
     /// The markdown file to treat as index, relative to base
-    #[arg(short, long, default_value = "index.md")]
-    pub index: PathBuf,
+    ///
+    /// Defaults to `index.md`, or to the first chapter of the summary if there is one.
+    #[arg(short, long)]
+    pub index: Option<PathBuf>,
+    /// An mdBook `SUMMARY.md` ordering and nesting the pages, relative to base
+    ///
+    /// Defaults to `SUMMARY.md` in base if it exists. Pages it doesn't list are still
+    /// served, after the listed ones.
+    #[arg(long, value_name = "PATH")]
+    pub summary: Option<PathBuf>,
+    /// A file of `term: definition` lines, or a definition list, explaining terms
+    ///
+    /// The first use of each term on a page, outside of code, headings and links, is
+    /// wrapped in an `<abbr>` showing its definition. Edits to the file rerender every page.
+    #[arg(long, value_name = "PATH")]
+    pub glossary: Option<PathBuf>,
+    /// Only match glossary terms written in the same case
+    #[arg(long)]
+    pub glossary_case_sensitive: bool,
+    /// A stylesheet linked from every page after the built-in one, served at `/custom.css`
+    ///
+    /// Edits to the file restyle open pages without reloading them.
+    #[arg(long, value_name = "PATH")]
+    pub css: Option<PathBuf>,
     /// The address to run on
     #[arg(short, long, default_value = "0.0.0.0:6464")]
     pub addr: SocketAddr,
+    /// A pem file of the certificate chain to serve over https with, along with `--tls-key`
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// A pem file of the private key of `--tls-cert`
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// The html title of each page, `{title}` is replaced by the page's title
+    #[arg(long, default_value = "{title}")]
+    pub title_template: String,
+    /// Allow `?opt=-name,+name` on pages to re-render them with adjusted markdown options
+    #[arg(long)]
+    pub debug_render: bool,
+    /// Drop raw html from markdown instead of passing it through
+    ///
+    /// Unlike sanitizing, which cleans the rendered output, this never lets any
+    /// markup from the markdown through, making it much cheaper.
+    /// Code blocks and inline code are unaffected.
+    #[arg(long)]
+    pub no_raw_html: bool,
+    /// Show html dropped by --no-raw-html as escaped inline code
+    #[arg(long, requires = "no_raw_html")]
+    pub show_stripped_html: bool,
+    /// Render `$...$` and `$$...$$` as math in the browser, using katex
+    #[arg(long)]
+    pub math: bool,
+    /// End every heading with a `¶` link to itself, shown when the heading is hovered
+    #[arg(long)]
+    pub permalinks: bool,
+    /// Replace `:shortcode:`s like `:sparkles:` with their emoji, outside of code
+    #[arg(long)]
+    pub emoji: bool,
+    /// Number headings like `1.`, `1.1` and `1.1.1`, in pages and their tables of contents
+    ///
+    /// Pages can set `number_headings: false` or `number_headings: true` in their front
+    /// matter to override this.
+    #[arg(long)]
+    pub number_headings: bool,
+    /// Show a table of contents above every page
+    ///
+    /// Pages can set `toc: false` or `toc: true` in their front matter to override this.
+    #[arg(long)]
+    pub toc: bool,
+    /// Number the lines of fenced code blocks and label them with their language
+    ///
+    /// The numbers are drawn by the stylesheet, so copying the code leaves them out.
+    #[arg(long)]
+    pub code_line_numbers: bool,
+    /// Let task list checkboxes be checked from the browser, writing the change to the file
+    #[arg(long)]
+    pub edit_tasks: bool,
+    /// Clean rendered pages of scripts, event handlers, iframes and other unsafe html
+    ///
+    /// Unlike --no-raw-html, formatting written as raw html is kept.
+    #[arg(long)]
+    pub sanitize: bool,
+    /// Fail pages with partial problems instead of listing them in a banner
+    ///
+    /// Partial problems include failing fence handlers and links to missing headings.
+    #[arg(long)]
+    pub strict: bool,
+    /// Check links to other sites in the background once scanned, marking dead ones
+    ///
+    /// Results are kept for an hour, enter `links check` to check again sooner.
+    #[arg(long)]
+    pub check_external_links: bool,
+    /// Leave the live reload connection status out of pages
+    #[arg(long)]
+    pub no_status_indicator: bool,
+    /// Open links to other sites in the same tab, instead of a new one
+    #[arg(long)]
+    pub no_external_blank: bool,
+    /// Link bare `http://` and `https://` urls in text, outside of code
+    #[arg(long)]
+    pub autolink: bool,
+    /// Break lines at every newline, as in notes from tools that treat newlines as `<br>`
+    ///
+    /// Enter `hard-breaks` in the console to switch back and forth.
+    #[arg(long)]
+    pub hard_breaks: bool,
+    /// Render pages as strict commonmark, without tables, footnotes or any other extension
+    ///
+    /// Shows how pages look on renderers without extensions. Enter `commonmark` in the
+    /// console to switch back and forth.
+    #[arg(long)]
+    pub strict_commonmark: bool,
+    /// The colors of pages, `auto` follows the reader's system
+    ///
+    /// Enter `theme dark` and the like in the console to switch.
+    #[arg(long, value_name = "THEME", default_value = "light")]
+    pub theme: settings::Theme,
+    /// An html file used as the template of every page instead of the built-in one
+    ///
+    /// It must have a `{{md}}` placeholder for the page's content, see the built-in
+    /// `client/index.html` for the others. Edits to the file reload every open page.
+    #[arg(long, value_name = "PATH")]
+    pub template: Option<PathBuf>,
+    /// Fail to start when the template has placeholders for disabled features,
+    /// lacks placeholders for enabled ones, or has unknown ones
+    #[arg(long)]
+    pub strict_template: bool,
+    /// Serve the markdown of this git revision read-only, instead of the files in base
+    ///
+    /// Base must be within a git repository, the served commit follows the ref as it moves.
+    #[arg(long, value_name = "REV")]
+    pub git_ref: Option<String>,
+    /// What to serve for urls that match no page, `/api/` routes always get a plain 404
+    #[arg(long, value_enum, default_value_t = Fallback::NotFound)]
+    pub fallback: Fallback,
+    /// Keep visited pages readable offline, using a service worker
+    #[arg(long)]
+    pub offline: bool,
+    /// Also fetch pages ahead of time for offline reading, up to this size, such as 512K or 4M
+    #[arg(long, value_name = "SIZE", requires = "offline", value_parser = settings::parse_size)]
+    pub offline_prefetch: Option<u64>,
+    /// Render pages for at most this many seconds at startup, for slow filesystems
+    ///
+    /// The index and recently changed, shallow directories are scanned first. Pages found
+    /// after that are listed right away, and rendered once requested or the walk is done.
+    #[arg(long, value_name = "SECONDS")]
+    pub scan_budget: Option<u64>,
+    /// Print long console output directly instead of paging it
+    #[arg(long)]
+    pub no_pager: bool,
+    /// Serve a directory read-only under a url prefix, may be repeated
+    #[arg(long, value_name = "URL-PREFIX=DIR")]
+    pub mount: Vec<Mount>,
+    /// Only serve pages under a url prefix to requests with the given basic auth
+    /// credentials, may be repeated
+    ///
+    /// Without credentials nobody is served the pages. Protected pages are left out of
+    /// listings, navigation, recent changes and the api for everyone else.
+    #[arg(long, value_name = "PREFIX[:USER:PASSWORD]")]
+    pub protect: Vec<Rule>,
+    /// Only serve requests with the given basic auth credentials, `/healthz` excepted
+    ///
+    /// Set `MDFLC_AUTH` instead to keep the password out of the shell's history.
+    #[arg(
+        long,
+        value_name = "USER:PASSWORD",
+        env = "MDFLC_AUTH",
+        hide_env_values = true
+    )]
+    pub auth: Option<Login>,
+    /// Refresh clients when files within mounts change
+    #[arg(long)]
+    pub watch_mounts: bool,
+    /// Shut down when the file watcher fails instead of retrying
+    #[arg(long)]
+    pub exit_on_watch_failure: bool,
+    /// How many seconds to keep retrying a failed file watcher
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    pub watch_retry: u64,
+    /// Wait for changes to stop for this many milliseconds before refreshing pages
+    #[arg(long, value_name = "MS", default_value_t = 150)]
+    pub debounce: u64,
+    /// But refresh at least this often, in milliseconds, while changes keep coming
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub max_debounce: u64,
+    /// The file extensions of markdown files, comma separated, most preferred first
+    ///
+    /// When files differ only by extension, the first is served and the rest are reported.
+    /// Files ending in `.txt` are always served as plain text.
+    #[arg(
+        long,
+        value_name = "EXTENSIONS",
+        value_delimiter = ',',
+        default_value = "md,markdown,mdown"
+    )]
+    pub extensions: Vec<String>,
+    /// Serve csv files as pages with a table, see `--max-csv-rows`
+    #[arg(long)]
+    pub csv: bool,
+    /// Follow symlinks within base, watching their targets too
+    ///
+    /// Pages keep the path of the link, not of its target.
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    /// Skip files larger than this, such as 512K or 4M
+    #[arg(long, value_name = "SIZE", default_value = "4M", value_parser = settings::parse_size)]
+    pub max_file_size: u64,
+    /// Render pages whose html is larger than this for every request instead of caching them
+    #[arg(long, value_name = "SIZE", default_value = "32M", value_parser = settings::parse_size)]
+    pub max_html_size: u64,
+    /// Skip files nested in more than this many directories below base
+    #[arg(long, value_name = "DEPTH", default_value_t = 16)]
+    pub max_depth: usize,
+    /// Show at most this many rows of a csv file, served with `--csv` or included with a
+    /// `csv-include` fence
+    #[arg(long, value_name = "ROWS", default_value_t = render::DEFAULT_MAX_CSV_ROWS)]
+    pub max_csv_rows: usize,
+    /// Close refresh websockets whose client doesn't subscribe within this many seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub ws_grace: u64,
+    /// Close refresh websockets after this many seconds, making clients reconnect
+    #[arg(long, value_name = "SECONDS")]
+    pub ws_max_lifetime: Option<u64>,
+    /// Enable only these markdown extensions, comma separated, all of them by default
+    #[arg(
+        long,
+        value_name = "EXTENSIONS",
+        value_delimiter = ',',
+        value_parser = extension_parser()
+    )]
+    pub md_options: Option<Vec<String>>,
+    /// Disable a markdown extension, may be repeated, applied after --md-options
+    #[arg(long, value_name = "EXTENSION", value_parser = extension_parser())]
+    pub disable_ext: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Subcommand>,
+}
+
+/// Tools that work with a running server instead of starting one
+#[derive(clap::Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Subcommand {
+    /// Open the console of a running server, through its control socket
+    ///
+    /// Enter commands as in the server's own console, `quit` stops the server,
+    /// Ctrl-D leaves it running.
+    Attach {
+        /// The port of the server, needed when more than one is running
+        port: Option<u16>,
+    },
+}
+
+fn extension_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(options::MD_OPTIONS.iter().map(|(name, _)| *name))
+}
+
+impl Args {
+    /// The render settings chosen by these arguments
+    #[must_use]
+    pub fn render_config(&self) -> render::Config {
+        let raw_html = match (self.no_raw_html, self.show_stripped_html) {
+            (false, _) => RawHtml::Keep,
+            (true, false) => RawHtml::Strip,
+            (true, true) => RawHtml::Show,
+        };
+        let mut config = render::Config {
+            raw_html,
+            max_html_size: usize::try_from(self.max_html_size).unwrap_or(usize::MAX),
+            math: self.math,
+            permalinks: self.permalinks,
+            toc: self.toc,
+            emoji: self.emoji,
+            sanitize: self.sanitize,
+            edit_tasks: self.edit_tasks,
+            max_csv_rows: self.max_csv_rows,
+            code_line_numbers: self.code_line_numbers,
+            external_blank: !self.no_external_blank,
+            autolink: self.autolink,
+            number_headings: self.number_headings,
+            hard_breaks: self.hard_breaks,
+            ..render::Config::default()
+        };
+        if let Some(names) = &self.md_options {
+            config.options = names
+                .iter()
+                .filter_map(|name| options::parse_option(name))
+                .collect();
+        }
+        for name in &self.disable_ext {
+            if let Some(option) = options::parse_option(name) {
+                config.options.remove(option);
+            }
+        }
+        config
+    }
+}
+
+/// Where console commands write, the server's own terminal or an attached session
+pub struct Output<'a> {
+    pub out: &'a mut dyn Write,
+    /// Errors go to `out` when `None`
+    err: Option<&'a mut dyn Write>,
+    /// Whether long output may be paged, see [`pager::page`]
+    pager: bool,
+}
+
+impl<'a> Output<'a> {
+    /// Writes everything to `out` without paging, as attached sessions need
+    pub fn new(out: &'a mut dyn Write) -> Self {
+        Self {
+            out,
+            err: None,
+            pager: false,
+        }
+    }
+
+    fn err(&mut self) -> &mut dyn Write {
+        match &mut self.err {
+            Some(err) => *err,
+            None => self.out,
+        }
+    }
 }
 
 /// Reads console
@@ -46,13 +389,19 @@ pub fn read_console(api: &Api, wx: &Watchexec) -> anyhow::Result<()> {
     let config = Config::default();
     let mut rl: Editor<(), MemHistory> =
         Editor::with_history(config, MemHistory::with_config(config))?;
+    let (mut stdout, mut stderr) = (io::stdout(), io::stderr());
+    let mut output = Output {
+        out: &mut stdout,
+        err: Some(&mut stderr),
+        pager: api.settings.pager,
+    };
 
     loop {
         match rl.readline(">> ") {
             Ok(s) => {
                 rl.history_mut().add(&s)?;
                 let s = s.trim_start();
-                if !s.is_empty() && handle_ci(api, wx, s) {
+                if !s.is_empty() && handle_ci(api, wx, s, &mut output) {
                     break;
                 }
             }
@@ -158,7 +507,7 @@ pub enum CommandPath {
         /// The first unit
         start: SmartStr,
         /// The descendent paths
-        paths: Vec<CommandPath>,
+        paths: Vec<Self>,
     },
 }
 
@@ -166,7 +515,7 @@ impl CommandPath {
     /// parse yeah
     #[must_use]
     #[allow(dead_code)]
-    pub fn parse(&self, _s: &str) -> Option<Match> {
+    pub fn parse(&self, _s: &str) -> Option<Match<'_>> {
         // match self {
         //     CommandPath::Unit { long, short } => todo!(),
         //     CommandPath::Multi { start, paths } => todo!(),
@@ -238,43 +587,178 @@ where
 
 /// returns true if program should stop
 #[must_use]
-pub fn handle_ci(api: &Api, wx: &Watchexec, s: &str) -> bool {
+pub fn handle_ci(api: &Api, wx: &Watchexec, s: &str, o: &mut Output) -> bool {
     match s {
-        "help" | "h" => println!(
-            "\
-            enter {BlueFg}[s]et [p]ath {{PATH}}{Reset} to set a new path to serve (resets index)\n\
-            enter {BlueFg}[s]et [i]ndex {{PATH}}{Reset} to set a new path to serve (resets index)\n\
-            enter {BlueFg}[h]elp{Reset} to show help (this text)\n\
-            enter {BlueFg}[p]ath{Reset} to show path\n\
-            enter {BlueFg}[i]ndex{Reset} to show index\n\
-            enter {BlueFg}[o]pen{Reset} to open client in browser\n\
-            enter {BlueFg}[u]rl{Reset} to show server url\n\
-            enter {BlueFg}[c]lear{Reset} clear screen\n\
-            enter {BlueFg}[q]uit{Reset} to quit\
-            "
-        ),
+        "help" | "h" => print_help(o),
         "open" | "o" => {
-            if webbrowser::open(&api.url).is_ok() {
-                println!("{GreenFg}Opening browser...{Reset}");
+            if webbrowser::open(&api.settings.url).is_ok() {
+                outln!(o, "{GreenFg}Opening browser...{Reset}");
             } else {
-                eprintln!("{YellowFg}Unable to open browser{Reset}");
+                errln!(o, "{YellowFg}Unable to open browser{Reset}");
+            }
+        }
+        "path" | "p" => outln!(o, "{BlueFg}{}{Reset}", api.base.unlock().display()),
+        "index" | "i" => outln!(o, "{BlueFg}{}{Reset}", api.index.unlock()),
+        "list" | "l" => {
+            let lines = api.list().into_iter().map(|(key, alias)| {
+                let alias = alias.map(|a| format!(" -> {BlueFg}{a}{Reset}"));
+                format!("{BlueFg}{key}{Reset}{}", alias.unwrap_or_default())
+            });
+            page(o, lines);
+        }
+        "warnings" | "w" => print_warnings(api, o),
+        "log" => {
+            let changes = api.changes.recent(MAX_CHANGES);
+            if changes.is_empty() {
+                outln!(o, "{GreenFg}no changes{Reset}");
+            }
+            let now = Timestamp::now();
+            let lines = changes.into_iter().map(|c| {
+                format!(
+                    "{} {YellowFg}{}{Reset} {BlueFg}{}{Reset}",
+                    c.timestamp.ago(now),
+                    c.kind.name(),
+                    c.key
+                )
+            });
+            page(o, lines);
+        }
+        s if s.starts_with("inspect ") => {
+            let key = s.split_once(' ').map_or("", |(_, key)| key.trim());
+            print_inspection(api, key, o);
+        }
+        "stats" => print_stats(api, o),
+        "clients" => print_clients(api, o),
+        "links" => print_links(api, o),
+        "links check" => check_links(api, o),
+        "status" => print_status(api, o),
+        "config" => print_config(api, o),
+        "commonmark" => {
+            let commonmark = !api.is_commonmark();
+            match api.set_commonmark(commonmark) {
+                Ok(()) if commonmark => outln!(o, "{GreenFg}rendering strict CommonMark{Reset}"),
+                Ok(()) => outln!(o, "{GreenFg}rendering with extensions{Reset}"),
+                Err(e) => errln!(o, "{YellowFg}rerendering failed: \"{e}\"{Reset}"),
             }
         }
-        "path" | "p" => println!("{BlueFg}{}{Reset}", api.base.unlock().display()),
-        "index" | "i" => println!("{BlueFg}{}{Reset}", api.index.unlock()),
-        "clear" | "c" => scroll(),
-        "url" | "u" => println!("{BlueFg}{}{Reset}", api.url),
+        s if s == "theme" || s.starts_with("theme ") => theme(api, s, o),
+        "hard-breaks" => {
+            let hard_breaks = !api.is_hard_breaks();
+            match api.set_hard_breaks(hard_breaks) {
+                Ok(()) if hard_breaks => outln!(o, "{GreenFg}breaking every line{Reset}"),
+                Ok(()) => outln!(o, "{GreenFg}breaking lines as markdown says{Reset}"),
+                Err(e) => errln!(o, "{YellowFg}rerendering failed: \"{e}\"{Reset}"),
+            }
+        }
+        "rescan --dry-run" => match api.preview_scan(None) {
+            Ok(preview) => print_preview(&preview, "rescan", o),
+            Err(e) => errln!(o, "{YellowFg}dry run failed: \"{e}\"{Reset}"),
+        },
+        "rescan" => match api.rescan() {
+            Ok(()) => outln!(
+                o,
+                "{GreenFg}rescanned {}{Reset}",
+                api.base.unlock().display()
+            ),
+            Err(e) => errln!(o, "{YellowFg}rescan failed: \"{e}\"{Reset}"),
+        },
+        "clear" | "c" => {
+            let _ = write!(o.out, "{CLEAR}");
+            let _ = o.out.flush();
+        }
+        "url" | "u" => outln!(o, "{BlueFg}{}{Reset}", api.settings.url),
         "quit" | "q" => return true,
-        s => match set_path(s, api, wx) {
+        s => match set_limit(s, api, o).and_then(|set| {
+            if set {
+                Ok(true)
+            } else {
+                set_path(s, api, wx, o)
+            }
+        }) {
             Ok(true) => (),
-            Ok(false) => eprintln!("{YellowFg}unknown input: \"{s}\"{Reset}"),
-            Err(e) => eprintln!("{YellowFg}input resulted in error: \"{e}\"{Reset}",),
+            Ok(false) => errln!(o, "{YellowFg}unknown input: \"{s}\"{Reset}"),
+            Err(e) => errln!(o, "{YellowFg}input resulted in error: \"{e}\"{Reset}"),
         },
     }
     false
 }
 
-fn set_path(s: &str, api: &Api, wx: &Watchexec) -> anyhow::Result<bool> {
+fn print_help(o: &mut Output) {
+    outln!(
+        o,
+        "\
+        enter {BlueFg}[s]et [p]ath {{PATH}}{Reset} to set a new path to serve (resets index)\n\
+        enter {BlueFg}[s]et [p]ath --dry-run {{PATH}}{Reset} to preview which pages it would serve\n\
+        enter {BlueFg}[s]et [i]ndex {{PATH}}{Reset} to set a new path to serve (resets index)\n\
+        enter {BlueFg}set max-file-size {{SIZE}}{Reset} to change the file size limit\n\
+        enter {BlueFg}set max-depth {{DEPTH}}{Reset} to change the nesting limit\n\
+        enter {BlueFg}[h]elp{Reset} to show help (this text)\n\
+        enter {BlueFg}[p]ath{Reset} to show path\n\
+        enter {BlueFg}[i]ndex{Reset} to show index\n\
+        enter {BlueFg}[l]ist{Reset} to list served pages\n\
+        enter {BlueFg}[w]arnings{Reset} to list warnings\n\
+        enter {BlueFg}log{Reset} to list recently changed pages\n\
+        enter {BlueFg}inspect {{KEY}}{Reset} to show everything known about a page\n\
+        enter {BlueFg}stats{Reset} to show server statistics\n\
+        enter {BlueFg}clients{Reset} to list connected refresh sockets\n\
+        enter {BlueFg}links{Reset} to list checked links to other sites\n\
+        enter {BlueFg}links check{Reset} to check links to other sites again\n\
+        enter {BlueFg}status{Reset} to show server status\n\
+        enter {BlueFg}config{Reset} to show the settings in effect\n\
+        enter {BlueFg}commonmark{Reset} to switch between strict CommonMark and extensions\n\
+        enter {BlueFg}hard-breaks{Reset} to switch between breaking lines at every newline or not\n\
+        enter {BlueFg}theme [light|dark|auto]{Reset} to show or change the colors of pages\n\
+        enter {BlueFg}rescan{Reset} to reread every file\n\
+        enter {BlueFg}rescan --dry-run{Reset} to preview which pages a rescan would change\n\
+        enter {BlueFg}[o]pen{Reset} to open client in browser\n\
+        enter {BlueFg}[u]rl{Reset} to show server url\n\
+        enter {BlueFg}[c]lear{Reset} clear screen\n\
+        enter {BlueFg}[q]uit{Reset} to quit\
+        "
+    );
+}
+
+/// Handles `theme`, showing the theme or switching to the one named after it
+fn theme(api: &Api, s: &str, o: &mut Output) {
+    let name = s.split_once(' ').map_or("", |(_, name)| name.trim());
+    if name.is_empty() {
+        outln!(o, "{BlueFg}{}{Reset}", api.theme().name());
+        return;
+    }
+    match settings::Theme::from_str(name, true) {
+        Ok(theme) => {
+            api.set_theme(theme);
+            outln!(o, "{GreenFg}pages are now {}{Reset}", theme.name());
+        }
+        Err(_) => errln!(
+            o,
+            "{YellowFg}unknown theme, expected light, dark or auto{Reset}"
+        ),
+    }
+}
+
+/// Handles `set max-file-size` and `set max-depth`, taking effect on the next change or rescan
+fn set_limit(s: &str, api: &Api, o: &mut Output) -> anyhow::Result<bool> {
+    let Some(s) = s.strip_prefix("set ").map(str::trim_start) else {
+        return Ok(false);
+    };
+    let mut limits = api.limits();
+    if let Some(size) = s.strip_prefix("max-file-size ") {
+        limits.max_file_size = settings::parse_size(size).map_err(anyhow::Error::msg)?;
+    } else if let Some(depth) = s.strip_prefix("max-depth ") {
+        limits.max_depth = depth.trim().parse().context("invalid depth")?;
+    } else {
+        return Ok(false);
+    }
+    api.set_limits(limits);
+    outln!(
+        o,
+        "{GreenFg}limits updated, enter {BlueFg}rescan{GreenFg} to apply them to every file{Reset}"
+    );
+    AnyOk(true)
+}
+
+fn set_path(s: &str, api: &Api, wx: &Watchexec, o: &mut Output) -> anyhow::Result<bool> {
     enum Kind {
         Path,
         Index,
@@ -307,34 +791,54 @@ fn set_path(s: &str, api: &Api, wx: &Watchexec) -> anyhow::Result<bool> {
     };
     expect_space(path)?;
     let path = path.trim_start();
+    let (dry_run, path) = match path.strip_prefix("--dry-run") {
+        Some(path) => {
+            expect_space(path)?;
+            (true, path.trim_start())
+        }
+        None => (false, path),
+    };
     ensure!(!path.is_empty(), "inputted path was empty");
+    let input = path;
     let path = PathBuf::from(path)
         .canonicalize()
         .context("invalid inputted path")?;
 
     match kind {
+        Kind::Index if dry_run => bail!("--dry-run only applies to set path"),
+        Kind::Path if dry_run => {
+            let preview = api.preview_scan(Some(path))?;
+            print_preview(&preview, &format!("set path {input}"), o);
+        }
         Kind::Path if *api.base.unlock() == path => {
-            println!("already using the given path.");
+            outln!(o, "already using the given path.");
         }
         Kind::Path => {
-            wx.config.pathset([path.clone()]);
-            println!("current base path is now {BlueFg}{}{Reset}", path.display());
+            outln!(
+                o,
+                "current base path is now {BlueFg}{}{Reset}",
+                path.display()
+            );
             *api.base.unlock() = path;
+            wx.config.pathset(api.watch_paths());
         }
-        Kind::Index if !path.extension().is_some_and(|e| e == "md") => {
+        Kind::Index if api.settings.extensions.extension(&path).is_none() => {
             bail!("Invalid path extension for markdown");
         }
         Kind::Index => {
-            let path = path
-                .strip_prefix(api.base.unlock().as_path())
-                .context("index must be a path within base")?
-                .to_str()
-                .context("only utf8 paths allowed")?
-                .to_owned();
+            let base = api.base.unlock().clone();
+            path.strip_prefix(&base)
+                .context("index must be a path within base")?;
+            let key = api
+                .settings
+                .extensions
+                .key(&base, &path)
+                .context("only utf8 paths allowed")?;
+            let path = format!("{key}.md");
             if *api.index.unlock() == path {
-                println!("already using the given index.");
+                outln!(o, "already using the given index.");
             } else {
-                println!("current index path is now {BlueFg}{}{Reset}", path);
+                outln!(o, "current index path is now {BlueFg}{path}{Reset}");
                 *api.index.unlock() = path;
             }
         }
@@ -343,7 +847,328 @@ fn set_path(s: &str, api: &Api, wx: &Watchexec) -> anyhow::Result<bool> {
     AnyOk(true)
 }
 
+/// Shows the output of a command, see [`pager::page`]
+fn page(o: &mut Output, lines: impl Iterator<Item = String>) {
+    if !o.pager {
+        for line in lines {
+            outln!(o, "{line}");
+        }
+    } else if let Err(e) = pager::page(&lines.collect::<Vec<_>>(), true) {
+        errln!(o, "{YellowFg}pager error: \"{e}\"{Reset}");
+    }
+}
+
+fn print_status(api: &Api, o: &mut Output) {
+    outln!(o, "base: {BlueFg}{}{Reset}", api.base.unlock().display());
+    outln!(o, "url: {BlueFg}{}{Reset}", api.settings.url);
+    if let Some(revision) = api.revision() {
+        outln!(
+            o,
+            "mode: {BlueFg}git ref{Reset}, serving {BlueFg}{}{Reset} at {BlueFg}{}{Reset} \
+            \"{}\" read-only",
+            revision.rev,
+            revision.commit,
+            revision.summary
+        );
+    }
+    let scan = api.scan_status();
+    if scan.walking || scan.rendered < scan.found {
+        let percent = scan
+            .percent
+            .map_or_else(|| "?".to_owned(), |percent| percent.to_string());
+        outln!(
+            o,
+            "scan: {BlueFg}{percent}%{Reset}, {BlueFg}{}/{}{Reset} pages rendered{}",
+            scan.rendered,
+            scan.found,
+            if scan.walking { ", still walking" } else { "" },
+        );
+    } else {
+        outln!(
+            o,
+            "scan: {GreenFg}done{Reset}, {BlueFg}{}{Reset} pages",
+            scan.found
+        );
+    }
+    let state = api.watch_state();
+    match &state {
+        WatchState::Watching => outln!(o, "watcher: {GreenFg}watching{Reset}"),
+        WatchState::Degraded(reason) | WatchState::Failed(reason) => {
+            outln!(o, "watcher: {YellowFg}{}{Reset} ({reason})", state.name());
+        }
+    }
+    let queue = api.updates.stats();
+    outln!(
+        o,
+        "queue: {BlueFg}{}/{}{Reset} waiting, last lag {BlueFg}{:?}{Reset}, \
+        {BlueFg}{}{Reset} coalesced, {BlueFg}{}{Reset} overflows",
+        queue.depth,
+        queue.capacity,
+        Duration::from_micros(queue.lag_micros),
+        queue.coalesced,
+        queue.overflows,
+    );
+    for mount in &api.settings.mounts {
+        outln!(
+            o,
+            "mount: {BlueFg}/{}/{Reset} -> {BlueFg}{}{Reset}",
+            mount.prefix,
+            mount.dir.display()
+        );
+    }
+}
+
+fn print_warnings(api: &Api, o: &mut Output) {
+    let warnings = api.warnings.list();
+    if warnings.is_empty() {
+        outln!(o, "{GreenFg}no warnings{Reset}");
+    }
+    let now = Timestamp::now();
+    let lines = warnings.into_iter().map(|w| {
+        format!(
+            "{} {YellowFg}[{}]{Reset} {BlueFg}{}{Reset}: {}",
+            w.timestamp.ago(now),
+            w.category.name(),
+            w.key,
+            w.message
+        )
+    });
+    page(o, lines);
+}
+
+fn print_inspection(api: &Api, key: &str, o: &mut Output) {
+    let Some(info) = api.inspect(key) else {
+        errln!(o, "{YellowFg}no page at \"{key}\"{Reset}");
+        return;
+    };
+    let list = |items: &[String]| {
+        if items.is_empty() {
+            "none".to_owned()
+        } else {
+            items.join(", ")
+        }
+    };
+    let size = info
+        .size
+        .map_or_else(|| "unreadable".to_owned(), |size| format!("{size} bytes"));
+    let modified = info.modified.map_or_else(
+        || "unknown".to_owned(),
+        |m| format!("{m} ({})", m.ago(Timestamp::now())),
+    );
+    let html = if info.streamed {
+        "streamed, over the size limit".to_owned()
+    } else {
+        format!("{} bytes", info.html_size)
+    };
+    let stale = if info.stale { " (stale)" } else { "" };
+    let broken = info.links.iter().filter(|l| l.broken).count();
+
+    let fields = [
+        ("key", info.key),
+        ("title", info.title),
+        ("path", info.path.display().to_string()),
+        ("size", size),
+        ("modified", modified),
+        ("hash", info.hash),
+        ("html", html),
+        (
+            "render",
+            format!("{}µs ({})", info.render_micros, info.render_kind.name()),
+        ),
+        ("generation", format!("{}{stale}", info.render_generation)),
+        ("headings", info.headings.to_string()),
+        ("links", format!("{} ({broken} broken)", info.links.len())),
+        ("referrers", list(&info.referrers)),
+        ("aliases", list(&info.aliases)),
+        ("features", list(&info.features)),
+        (
+            "tags",
+            list(info.front_matter.as_ref().map_or(&[], |f| &f.tags)),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, value)| format!("{name:>10}: {BlueFg}{value}{Reset}"));
+    let error = info
+        .error
+        .map(|error| format!("{:>10}: {RedFg}{error}{Reset}", "error"));
+    let links = info.links.into_iter().map(|link| {
+        let flag = if link.broken {
+            format!(" {RedFg}broken anchor{Reset}")
+        } else {
+            String::new()
+        };
+        format!("{:>12}{BlueFg}{}{Reset}{flag}", "", link.url)
+    });
+    let warnings = info
+        .warnings
+        .into_iter()
+        .map(|w| format!("{YellowFg}[{}]{Reset} {}", w.category.name(), w.message));
+    page(o, fields.chain(error).chain(links).chain(warnings));
+}
+
+/// The most keys of each kind listed by [`print_preview`]
+const PREVIEW_SAMPLE: usize = 10;
+
+/// Shows what a rescan would change, see [`Api::preview_scan`]
+fn print_preview(preview: &ScanPreview, confirm: &str, o: &mut Output) {
+    let sample = |keys: &[String]| {
+        let mut sample = keys
+            .iter()
+            .take(PREVIEW_SAMPLE)
+            .map(|key| format!("  {BlueFg}{key}{Reset}"))
+            .collect::<Vec<_>>();
+        if keys.len() > PREVIEW_SAMPLE {
+            sample.push(format!("  and {} more", keys.len() - PREVIEW_SAMPLE));
+        }
+        sample
+    };
+    let mut lines = vec![format!(
+        "would add {GreenFg}{}{Reset}, remove {RedFg}{}{Reset} and keep {BlueFg}{}{Reset} pages",
+        preview.added.len(),
+        preview.removed.len(),
+        preview.kept,
+    )];
+    if !preview.added.is_empty() {
+        lines.push(format!("{GreenFg}added:{Reset}"));
+        lines.extend(sample(&preview.added));
+    }
+    if !preview.removed.is_empty() {
+        lines.push(format!("{RedFg}removed:{Reset}"));
+        lines.extend(sample(&preview.removed));
+    }
+    lines.push(format!("enter {BlueFg}{confirm}{Reset} to apply"));
+    page(o, lines.into_iter());
+}
+
+fn print_clients(api: &Api, o: &mut Output) {
+    let clients = api.clients.list();
+    if clients.is_empty() {
+        outln!(o, "{GreenFg}no clients{Reset}");
+    }
+    let now = Timestamp::now();
+    let lines = clients.into_iter().map(|c| {
+        let addr = c
+            .addr
+            .map_or_else(|| "unknown".to_owned(), |a| a.to_string());
+        let page = c.page.unwrap_or_else(|| "unsubscribed".to_owned());
+        format!(
+            "{:>4} {BlueFg}{addr:<21}{Reset} {BlueFg}{page}{Reset}, \
+            connected {}, {} sent",
+            c.id,
+            c.connected.ago(now),
+            c.sent,
+        )
+    });
+    page(o, lines);
+}
+
+/// Prints the settings in effect along with the template's placeholders and mismatches
+fn print_config(api: &Api, o: &mut Output) {
+    let mut lines = api.settings().lines();
+    let template = api.template();
+    let placeholders: Vec<_> = template
+        .placeholders()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    lines.push(("template.placeholders".to_owned(), placeholders.join(", ")));
+    let mismatches = template.check(&api.settings);
+    lines.extend(
+        mismatches
+            .iter()
+            .map(|m| ("template.mismatch".to_owned(), m.to_string())),
+    );
+    let lines = lines
+        .into_iter()
+        .map(|(name, value)| format!("{name}: {BlueFg}{value}{Reset}"));
+    page(o, lines);
+}
+
+fn print_links(api: &Api, o: &mut Output) {
+    let results = api.external.results();
+    if api.external.is_running() {
+        outln!(
+            o,
+            "{GreenFg}a check is running, results may be incomplete{Reset}"
+        );
+    }
+    if results.is_empty() {
+        outln!(
+            o,
+            "{GreenFg}no links checked, enter {BlueFg}links check{GreenFg} to check them{Reset}"
+        );
+    }
+    let now = Timestamp::now();
+    let lines = results.into_iter().map(|r| {
+        let checked = r.checked.ago(now);
+        r.dead.map_or_else(
+            || {
+                format!(
+                    "{GreenFg}ok{Reset}   {BlueFg}{}{Reset}, checked {checked}",
+                    r.url
+                )
+            },
+            |reason| {
+                format!(
+                    "{RedFg}dead{Reset} {BlueFg}{}{Reset}, {reason}, checked {checked}",
+                    r.url
+                )
+            },
+        )
+    });
+    page(o, lines);
+}
+
+fn check_links(api: &Api, o: &mut Output) {
+    api.external.request(true);
+    outln!(
+        o,
+        "{GreenFg}checking {} external links in the background...{Reset}",
+        api.external_urls().len()
+    );
+}
+
+fn print_stats(api: &Api, o: &mut Output) {
+    let stats = api.render_stats(5);
+    outln!(
+        o,
+        "pages: {BlueFg}{}{Reset}, sockets: {BlueFg}{}{Reset}, \
+        generation: {BlueFg}{}{Reset}, warnings: {BlueFg}{}{Reset}",
+        stats.pages,
+        api.sockets(),
+        api.generation(),
+        api.warnings.len(),
+    );
+    outln!(
+        o,
+        "fresh pages: {BlueFg}{}{Reset}, stale pages: {BlueFg}{}{Reset}",
+        stats.pages - stats.stale,
+        stats.stale,
+    );
+    outln!(
+        o,
+        "render times: p50 {BlueFg}{:?}{Reset}, p90 {BlueFg}{:?}{Reset}, \
+        p99 {BlueFg}{:?}{Reset}, max {BlueFg}{:?}{Reset}",
+        stats.p50,
+        stats.p90,
+        stats.p99,
+        stats.max,
+    );
+    outln!(
+        o,
+        "compressed pages: {BlueFg}{}{Reset}, holding {BlueFg}{}{Reset} bytes",
+        stats.compressed,
+        stats.compressed_bytes,
+    );
+    for (key, time, kind) in stats.slowest {
+        outln!(o, "  {BlueFg}{key}{Reset} {time:?} ({})", kind.name());
+    }
+}
+
+/// Clears the screen, moving the cursor to the top left
+const CLEAR: &str = "\x1B[2J\x1B[1;1H";
+
 pub(crate) fn scroll() {
-    print!("\x1B[2J\x1B[1;1H");
+    print!("{CLEAR}");
     let _ = std::io::Write::flush(&mut std::io::stdout());
 }
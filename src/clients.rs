@@ -0,0 +1,93 @@
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::time::Timestamp;
+
+/// A connected refresh socket, see [`Clients`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Client {
+    pub id: u64,
+    /// The remote address, `None` when the server doesn't record them
+    pub addr: Option<SocketAddr>,
+    pub connected: Timestamp,
+    /// The page the client subscribed for, `None` until it subscribes
+    pub page: Option<String>,
+    /// The number of messages sent to the client
+    pub sent: u64,
+}
+
+/// The open refresh sockets
+#[derive(Debug, Default)]
+pub struct Clients {
+    map: DashMap<u64, Client>,
+    next_id: AtomicU64,
+}
+
+impl Clients {
+    /// Adds a client for as long as the returned guard lives
+    #[must_use]
+    pub fn register(&self, addr: Option<SocketAddr>) -> ClientGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let client = Client {
+            id,
+            addr,
+            connected: Timestamp::now(),
+            page: None,
+            sent: 0,
+        };
+        self.map.insert(id, client);
+        ClientGuard { clients: self, id }
+    }
+
+    /// Every connected client, oldest first
+    #[must_use]
+    pub fn list(&self) -> Vec<Client> {
+        let mut list: Vec<_> = self.map.iter().map(|c| c.value().clone()).collect();
+        list.sort_unstable_by_key(|c| c.id);
+        list
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Removes its client when dropped, even if the connection died mid-send
+#[derive(Debug)]
+pub struct ClientGuard<'a> {
+    clients: &'a Clients,
+    id: u64,
+}
+
+impl ClientGuard<'_> {
+    /// Records the page the client subscribed for
+    pub fn subscribe(&self, page: Option<String>) {
+        if let Some(mut client) = self.clients.map.get_mut(&self.id) {
+            client.page = page;
+        }
+    }
+
+    /// Counts a message sent to the client
+    pub fn sent(&self) {
+        if let Some(mut client) = self.clients.map.get_mut(&self.id) {
+            client.sent += 1;
+        }
+    }
+}
+
+impl Drop for ClientGuard<'_> {
+    fn drop(&mut self) {
+        self.clients.map.remove(&self.id);
+    }
+}
@@ -0,0 +1,111 @@
+use std::io::Write;
+
+use axum::{
+    body::{self, Body, Bytes, HttpBody},
+    extract::Request,
+    http::{
+        header::{
+            ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, UPGRADE, VARY,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use flate2::{write::GzEncoder, Compression};
+
+/// Pages whose html is smaller than this, in bytes, are never compressed ahead of time
+pub const MIN_SIZE: usize = 32 * 1024;
+
+/// Other responses smaller than this, in bytes, are sent as they are, see [`responses`]
+pub const MIN_RESPONSE_SIZE: u64 = 1024;
+
+/// A page compressed with gzip, see [`crate::Page::gzip`]
+#[derive(Debug, Clone)]
+pub struct Gzipped {
+    /// The update generation the page was templated at, see [`crate::Api::generation`]
+    pub generation: u64,
+    pub bytes: Bytes,
+}
+
+/// Whether the request's `Accept-Encoding` allows gzip
+///
+/// Encodings with a quality of zero are refused, wildcards count as gzip.
+#[must_use]
+pub fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let refused = parts.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            !refused && (name.eq_ignore_ascii_case("gzip") || name == "*")
+        })
+}
+
+/// Compresses `bytes` with gzip
+#[must_use]
+pub fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len() / 4), Compression::default());
+    // writing into a vec can't fail
+    let _ = encoder.write_all(bytes);
+    encoder.finish().unwrap_or_default()
+}
+
+/// Compresses responses with gzip when the client accepts it
+///
+/// Websocket upgrades, event streams and other bodies of unknown size are left alone, as
+/// are responses under [`MIN_RESPONSE_SIZE`], already encoded ones such as pages compressed
+/// ahead of time, and content that doesn't compress, like images.
+pub async fn responses(request: Request, next: Next) -> Response {
+    let accepts = accepts_gzip(request.headers()) && !request.headers().contains_key(UPGRADE);
+    let response = next.run(request).await;
+    let size = response.body().size_hint().exact();
+    if !accepts
+        || response.status() != StatusCode::OK
+        || response.headers().contains_key(CONTENT_ENCODING)
+        || !is_compressible(response.headers())
+        || size.is_none_or(|size| size < MIN_RESPONSE_SIZE)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let headers = &mut parts.headers;
+    headers.remove(CONTENT_LENGTH);
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    // the compressed bytes differ, so a strong tag no longer holds
+    if let Some(etag) = headers
+        .get(ETAG)
+        .filter(|etag| etag.as_bytes().starts_with(b"\""))
+        .and_then(|etag| HeaderValue::from_bytes(&[b"W/", etag.as_bytes()].concat()).ok())
+    {
+        headers.insert(ETAG, etag);
+    }
+    Response::from_parts(parts, Body::from(gzip(&bytes)))
+}
+
+/// Whether the response's `Content-Type` is text, which compresses well
+fn is_compressible(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence != "text/event-stream"
+        && (essence.starts_with("text/")
+            || essence.ends_with("json")
+            || essence.ends_with("javascript")
+            || essence.ends_with("xml")
+            || essence == "image/x-icon")
+}
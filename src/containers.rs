@@ -0,0 +1,162 @@
+use std::{mem, ops::Range};
+
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+use crate::blocks::{self, Spanned};
+
+/// Wraps blocks between `::: name` and `:::` lines in a `<div class="name">`
+///
+/// Fences are lines of three or more colons, those opening a container followed by its
+/// name. A closing fence needs at least as many colons as the innermost open container,
+/// so containers nest, and code blocks are passed over whole, `:::` within them included.
+/// Fences that are never matched are left as text.
+///
+/// Works on the parser's events along with their source ranges. Fences are found in
+/// paragraphs, and a container's fences must be within the same block, such as the same
+/// blockquote.
+pub fn apply<'a>(text: &str, events: impl IntoIterator<Item = Spanned<'a>>) -> Vec<Spanned<'a>> {
+    let mut containers = Containers {
+        text,
+        events: events.into_iter().collect(),
+        out: Vec::new(),
+    };
+    containers.blocks(0, containers.events.len());
+    containers.out
+}
+
+/// A line that may open or close a container
+#[derive(Debug)]
+struct Fence {
+    /// The index of the paragraph the fence is in
+    block: usize,
+    /// The fence's events within the paragraph
+    line: Range<usize>,
+    colons: usize,
+    /// The class of the container opened, `None` for closing fences
+    name: Option<String>,
+    matched: bool,
+}
+
+struct Containers<'t, 'a> {
+    text: &'t str,
+    events: Vec<Spanned<'a>>,
+    out: Vec<Spanned<'a>>,
+}
+
+impl Containers<'_, '_> {
+    /// Passes on the blocks within `start..end`, wrapping containers in divs
+    fn blocks(&mut self, start: usize, end: usize) {
+        let mut fences = self.fences(start, end);
+        let mut open: Vec<usize> = Vec::new();
+        for i in 0..fences.len() {
+            if fences[i].name.is_some() {
+                open.push(i);
+            } else if let Some(&opener) = open.last() {
+                if fences[opener].colons <= fences[i].colons {
+                    open.pop();
+                    fences[opener].matched = true;
+                    fences[i].matched = true;
+                }
+            }
+        }
+        fences.retain(|fence| fence.matched);
+
+        let mut fences = fences.into_iter().peekable();
+        let mut i = start;
+        while i < end {
+            let next = blocks::end(&self.events, i) + 1;
+            if fences.peek().is_none_or(|fence| fence.block != i) {
+                if blocks::is_container(&self.events[i].0) && next - i > 1 {
+                    self.take(i..i + 1);
+                    self.blocks(i + 1, next - 1);
+                    self.take(next - 1..next);
+                } else {
+                    self.take(i..next);
+                }
+                i = next;
+                continue;
+            }
+
+            // the paragraph is split around its fences, leaving out the soft breaks
+            let mut rest = i + 1;
+            while let Some(fence) = fences.next_if(|fence| fence.block == i) {
+                self.paragraph(rest..fence.line.start.saturating_sub(1).max(rest));
+                let html = fence.name.map_or_else(
+                    || "</div>\n".to_owned(),
+                    |name| format!("<div class=\"{name}\">\n"),
+                );
+                let range = self.events[fence.line.start].1.clone();
+                self.out.push((Event::Html(CowStr::from(html)), range));
+                rest = fence.line.end + 1;
+            }
+            self.paragraph(rest..next - 1);
+            i = next;
+        }
+    }
+
+    /// Every fence within the blocks at `start..end`, in order
+    fn fences(&self, start: usize, end: usize) -> Vec<Fence> {
+        let mut fences = Vec::new();
+        let mut i = start;
+        while i < end {
+            let next = blocks::end(&self.events, i) + 1;
+            for line in blocks::lines(&self.events, i, next) {
+                if let Some((colons, name)) = self.fence(&line) {
+                    fences.push(Fence {
+                        block: i,
+                        line,
+                        colons,
+                        name,
+                        matched: false,
+                    });
+                }
+            }
+            i = next;
+        }
+        fences
+    }
+
+    /// The colons and name of the fence on `line`, if it is one
+    fn fence(&self, line: &Range<usize>) -> Option<(usize, Option<String>)> {
+        let events = &self.events[line.clone()];
+        if !events
+            .iter()
+            .all(|(event, _)| matches!(event, Event::Text(_)))
+        {
+            return None;
+        }
+        let source = events.first()?.1.start..events.last()?.1.end;
+        let source = self.text[source].trim();
+        let name = source.trim_start_matches(':');
+        let colons = source.len() - name.len();
+        let name = name.trim();
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if colons < 3 || !name.chars().all(valid) {
+            return None;
+        }
+        Some((
+            colons,
+            Some(name.to_owned()).filter(|name| !name.is_empty()),
+        ))
+    }
+
+    /// Passes on the events at `range` as a paragraph of their own, unless there are none
+    fn paragraph(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let span = self.events[range.start].1.clone();
+        self.out.push((Event::Start(Tag::Paragraph), span.clone()));
+        self.take(range);
+        self.out.push((Event::End(TagEnd::Paragraph), span));
+    }
+
+    /// Moves the events at `range` to the output
+    fn take(&mut self, range: Range<usize>) {
+        for i in range {
+            let (event, span) = &mut self.events[i];
+            self.out
+                .push((mem::replace(event, Event::SoftBreak), span.clone()));
+        }
+    }
+}
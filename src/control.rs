@@ -0,0 +1,260 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        net::UnixStream as StdUnixStream,
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use easy_sgr::{Color::*, Style::*};
+use nix::unistd::getuid;
+use rustyline::{error::ReadlineError, history::MemHistory, Config, Editor};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+use watchexec::Watchexec;
+
+use crate::{
+    cli::{self, Output},
+    Api,
+};
+
+/// Ends the output of every command sent over the control socket
+///
+/// Output never holds it, so clients read up to it before prompting again.
+pub const END_OF_OUTPUT: &str = "\u{4}";
+
+/// The directory control sockets and pid files are kept in
+///
+/// `$XDG_RUNTIME_DIR` is private to its user already, otherwise a directory only the
+/// user may enter is made within the temporary directory.
+#[must_use]
+pub fn runtime_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let user = env::var("USER").unwrap_or_else(|_| "user".to_owned());
+    env::temp_dir().join(format!("mdflc-{user}"))
+}
+
+/// The control socket and pid file of the server on `port`, removed once dropped
+#[derive(Debug)]
+pub struct ControlFiles {
+    pub socket: PathBuf,
+    /// Holds the server's pid, then the path of its socket, one per line
+    pub pid_file: PathBuf,
+}
+
+impl ControlFiles {
+    #[must_use]
+    pub fn socket_path(dir: &Path, port: u16) -> PathBuf {
+        dir.join(format!("mdflc-{port}.sock"))
+    }
+
+    #[must_use]
+    pub fn pid_path(dir: &Path, port: u16) -> PathBuf {
+        dir.join(format!("mdflc-{port}.pid"))
+    }
+}
+
+impl Drop for ControlFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket);
+        let _ = fs::remove_file(&self.pid_file);
+    }
+}
+
+/// Listens on the control socket of the server on `port` within `dir`
+///
+/// `dir` is made if missing, and must be private to the user either way. The socket
+/// may only be used by its owner. A socket left behind by a server that is gone is
+/// replaced, one still in use is an error.
+pub fn listen(dir: &Path, port: u16) -> anyhow::Result<(UnixListener, ControlFiles)> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("unable to create {}", dir.display()))?;
+    check_private(dir)?;
+    let socket = ControlFiles::socket_path(dir, port);
+    if StdUnixStream::connect(&socket).is_ok() {
+        bail!("another server is listening on {}", socket.display());
+    }
+    let _ = fs::remove_file(&socket);
+    let files = ControlFiles {
+        socket,
+        pid_file: ControlFiles::pid_path(dir, port),
+    };
+    let listener = UnixListener::bind(&files.socket)
+        .with_context(|| format!("unable to listen on {}", files.socket.display()))?;
+    fs::set_permissions(&files.socket, fs::Permissions::from_mode(0o600))?;
+    let pid = format!("{}\n{}\n", std::process::id(), files.socket.display());
+    fs::write(&files.pid_file, pid)?;
+    Ok((listener, files))
+}
+
+/// Fails unless `dir` belongs to the current user and no one else may enter it
+///
+/// A directory made by someone else first would let them take over the socket.
+fn check_private(dir: &Path) -> anyhow::Result<()> {
+    let meta = fs::metadata(dir).with_context(|| format!("unable to read {}", dir.display()))?;
+    if meta.uid() != getuid().as_raw() {
+        bail!("{} belongs to another user", dir.display());
+    }
+    if meta.mode() & 0o077 != 0 {
+        bail!("{} may be entered by other users", dir.display());
+    }
+    Ok(())
+}
+
+/// Serves console sessions on `listener` until the server stops
+///
+/// Any number of sessions may be attached at once, `quit` is passed on to `quit`.
+pub async fn serve(
+    api: Arc<Api>,
+    wx: Arc<Watchexec>,
+    listener: UnixListener,
+    quit: mpsc::Sender<()>,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("{YellowFg}control socket error: \"{e}\"{Reset}");
+                continue;
+            }
+        };
+        let session = session(api.clone(), wx.clone(), stream, quit.clone());
+        tokio::spawn(async move {
+            println!("{BlueFg}console attached{Reset}");
+            if let Err(e) = session.await {
+                eprintln!("{YellowFg}console session error: \"{e}\"{Reset}");
+            }
+            println!("{BlueFg}console detached{Reset}");
+        });
+    }
+}
+
+/// Runs the commands of one attached console, one per line, until it disconnects
+async fn session(
+    api: Arc<Api>,
+    wx: Arc<Watchexec>,
+    stream: UnixStream,
+    quit: mpsc::Sender<()>,
+) -> anyhow::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = AsyncBufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim().to_owned();
+        let (api, wx) = (api.clone(), wx.clone());
+        // commands block, some for as long as a rescan takes
+        let (output, stop) = tokio::task::spawn_blocking(move || {
+            let mut output = Vec::new();
+            let stop =
+                !line.is_empty() && cli::handle_ci(&api, &wx, &line, &mut Output::new(&mut output));
+            (output, stop)
+        })
+        .await?;
+        write.write_all(&output).await?;
+        write
+            .write_all(format!("{END_OF_OUTPUT}\n").as_bytes())
+            .await?;
+        if stop {
+            let _ = quit.send(()).await;
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the control socket of the server on `port`, or of the only one running
+pub fn find(dir: &Path, port: Option<u16>) -> anyhow::Result<PathBuf> {
+    let pid_files = port.map_or_else(
+        || pid_files(dir),
+        |port| vec![ControlFiles::pid_path(dir, port)],
+    );
+    // pid files of servers that are gone are skipped
+    let mut sockets: Vec<_> = pid_files
+        .iter()
+        .filter_map(|pid_file| {
+            let text = fs::read_to_string(pid_file).ok()?;
+            let socket = PathBuf::from(text.lines().nth(1)?);
+            StdUnixStream::connect(&socket).ok().map(|_| socket)
+        })
+        .collect();
+    sockets.sort();
+    match (sockets.len(), port) {
+        (1, _) => Ok(sockets.remove(0)),
+        (0, Some(port)) => bail!("no server is running on port {port}"),
+        (0, None) => bail!("no server is running"),
+        _ => {
+            let list: Vec<_> = sockets.iter().map(|s| s.display().to_string()).collect();
+            bail!(
+                "more than one server is running, pick one by its port: {}",
+                list.join(", ")
+            )
+        }
+    }
+}
+
+/// Every pid file within `dir`, including those of servers that are gone
+fn pid_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            let stem = path.file_stem().and_then(|n| n.to_str());
+            stem.is_some_and(|n| n.starts_with("mdflc-"))
+                && path.extension().is_some_and(|e| e == "pid")
+        })
+        .collect()
+}
+
+/// Opens a console on the server on `port`, see [`cli::Subcommand::Attach`]
+pub fn attach(port: Option<u16>) -> anyhow::Result<()> {
+    let socket = find(&runtime_dir(), port)?;
+    let stream = StdUnixStream::connect(&socket)
+        .with_context(|| format!("unable to connect to {}", socket.display()))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    println!(
+        "{GreenFg}attached to {BlueFg}{}{GreenFg}, enter {BlueFg}help{GreenFg} for commands, \
+        Ctrl-D to detach{Reset}",
+        socket.display()
+    );
+
+    let config = Config::default();
+    let mut rl: Editor<(), MemHistory> =
+        Editor::with_history(config, MemHistory::with_config(config))?;
+    loop {
+        let line = match rl.readline(">> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => break,
+            Err(e) => return Err(e.into()),
+        };
+        rl.add_history_entry(&line)?;
+        writeln!(writer, "{}", line.trim())?;
+        let mut stdout = io::stdout().lock();
+        loop {
+            let mut output = String::new();
+            if reader.read_line(&mut output)? == 0 {
+                println!("{BlueFg}the server closed the console{Reset}");
+                return Ok(());
+            }
+            if output.trim_end_matches('\n') == END_OF_OUTPUT {
+                break;
+            }
+            stdout.write_all(output.as_bytes())?;
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
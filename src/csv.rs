@@ -0,0 +1,140 @@
+use std::fmt::{self, Write as _};
+
+use pulldown_cmark_escape::escape_html;
+
+/// Why a csv file couldn't be read, see [`parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvError {
+    /// The line the problem was found on, counting from one
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// Reads the records of `text`, the first being the header
+///
+/// Fields are separated by commas and may be quoted, with `""` standing for a quote
+/// within them. Blank lines are skipped, every record must have as many fields as
+/// the header.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, CsvError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    // the line the record being read started on
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = text
+        .strip_prefix('\u{feff}')
+        .unwrap_or(text)
+        .chars()
+        .peekable();
+    let error = |line, message: &str| CsvError {
+        line,
+        message: message.to_owned(),
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                    continue;
+                }
+                quoted = false;
+                if !matches!(chars.peek(), None | Some(',' | '\n' | '\r')) {
+                    return Err(error(line, "unexpected text after a quoted field"));
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            '"' => return Err(error(line, "quote within an unquoted field")),
+            '\n' | '\r' if !quoted => {
+                if c == '\r' {
+                    chars.next_if_eq(&'\n');
+                }
+                line += 1;
+                if record.is_empty() && field.is_empty() {
+                    start = line;
+                    continue;
+                }
+                record.push(std::mem::take(&mut field));
+                check_width(&records, &record, start)?;
+                records.push(std::mem::take(&mut record));
+                start = line;
+            }
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(error(start, "a quoted field is never closed"));
+    }
+    if !record.is_empty() || !field.is_empty() {
+        record.push(field);
+        check_width(&records, &record, start)?;
+        records.push(record);
+    }
+    if records.is_empty() {
+        return Err(error(1, "there is no header row"));
+    }
+    Ok(records)
+}
+
+fn check_width(records: &[Vec<String>], record: &[String], line: usize) -> Result<(), CsvError> {
+    match records.first() {
+        Some(header) if header.len() != record.len() => Err(CsvError {
+            line,
+            message: format!(
+                "{} fields where the header has {}",
+                record.len(),
+                header.len()
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `records` as a table, the first as its header, leaving out rows past `max_rows`
+///
+/// A note below the table says how many rows were left out.
+#[must_use]
+pub fn table_html(records: &[Vec<String>], max_rows: usize) -> String {
+    let mut html = String::from("<table class=\"csv\"><thead><tr>");
+    let rows = records.get(1..).unwrap_or_default();
+    for cell in records.first().into_iter().flatten() {
+        html.push_str("<th>");
+        let _ = escape_html(&mut html, cell);
+        html.push_str("</th>");
+    }
+    html.push_str("</tr></thead><tbody>\n");
+    for row in rows.iter().take(max_rows) {
+        html.push_str("<tr>");
+        for cell in row {
+            html.push_str("<td>");
+            let _ = escape_html(&mut html, cell);
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody></table>\n");
+    if rows.len() > max_rows {
+        let _ = writeln!(
+            html,
+            "<p class=\"csv-truncated\">Showing the first {max_rows} of {} rows.</p>",
+            rows.len()
+        );
+    }
+    html
+}
@@ -0,0 +1,172 @@
+use std::{mem, ops::Range};
+
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+use crate::blocks::{self, Spanned};
+
+/// Turns paragraphs like `Term\n: definition` into definition lists
+///
+/// Every line of such a paragraph before the first starting with `: ` is a term, every
+/// line starting with `: ` begins a definition, and the other lines continue the one
+/// before them. A paragraph of only definitions continues the terms or list before it.
+/// Blocks indented past the terms that follow the last definition are part of it, so
+/// definitions may hold several paragraphs and lists of their own.
+///
+/// Works on the parser's events along with their source ranges, which tell how far blocks
+/// are indented. Definitions without further blocks are left tight, without paragraphs.
+pub fn apply<'a>(text: &str, events: impl IntoIterator<Item = Spanned<'a>>) -> Vec<Event<'a>> {
+    let mut lists = Lists {
+        text,
+        events: events.into_iter().collect(),
+        out: Vec::new(),
+    };
+    lists.blocks(0, lists.events.len());
+    lists.out
+}
+
+struct Lists<'t, 'a> {
+    text: &'t str,
+    events: Vec<Spanned<'a>>,
+    out: Vec<Event<'a>>,
+}
+
+impl Lists<'_, '_> {
+    /// Passes on the blocks within `start..end`, making definition lists out of them
+    fn blocks(&mut self, start: usize, end: usize) {
+        // the column of the open list's terms
+        let mut open: Option<usize> = None;
+        let mut i = start;
+        while i < end {
+            let next = blocks::end(&self.events, i) + 1;
+            let column = self.column(i);
+            let lines = blocks::lines(&self.events, i, next);
+            let terms = lines
+                .iter()
+                .position(|line| self.defines(line.start))
+                .filter(|&first| first > 0 || open == Some(column))
+                .or_else(|| {
+                    let defined = !lines.is_empty() && self.defined_by_next(next, end, column);
+                    defined.then_some(lines.len())
+                });
+            let Some(terms) = terms else {
+                if open.take().is_some() {
+                    self.html("</dl>\n");
+                }
+                self.block(i, next);
+                i = next;
+                continue;
+            };
+            if open.is_none() {
+                self.html("<dl>\n");
+                open = Some(column);
+            }
+            for line in &lines[..terms] {
+                self.html("<dt>");
+                self.take(line.clone());
+                self.html("</dt>\n");
+            }
+
+            if terms == lines.len() {
+                // the definitions follow in a paragraph of their own
+                i = next;
+                continue;
+            }
+            let definitions = self.definitions(&lines[terms..]);
+            let (last, rest) = definitions.split_last().unwrap_or((&(0..0), &[]));
+            for definition in rest {
+                self.html("<dd>");
+                self.take(definition.clone());
+                self.html("</dd>\n");
+            }
+            // blocks indented past the terms belong to the last definition
+            let mut more = next;
+            while more < end && self.column(more) > column {
+                more = blocks::end(&self.events, more) + 1;
+            }
+            if more == next {
+                self.html("<dd>");
+                self.take(last.clone());
+            } else {
+                self.html("<dd>\n");
+                self.out.push(Event::Start(Tag::Paragraph));
+                self.take(last.clone());
+                self.out.push(Event::End(TagEnd::Paragraph));
+                self.blocks(next, more);
+            }
+            self.html("</dd>\n");
+            i = more;
+        }
+        if open.is_some() {
+            self.html("</dl>\n");
+        }
+    }
+
+    /// Passes on the block at `start..end`, looking for lists within containers
+    fn block(&mut self, start: usize, end: usize) {
+        if blocks::is_container(&self.events[start].0) && end - start > 1 {
+            self.take(start..start + 1);
+            self.blocks(start + 1, end - 1);
+            self.take(end - 1..end);
+        } else {
+            self.take(start..end);
+        }
+    }
+
+    /// Whether the block at `next` is a paragraph of definitions, as when a blank line
+    /// separates terms from their definitions
+    fn defined_by_next(&self, next: usize, end: usize, column: usize) -> bool {
+        next + 1 < end
+            && matches!(self.events[next].0, Event::Start(Tag::Paragraph))
+            && self.column(next) == column
+            && self.defines(next + 1)
+    }
+
+    /// How far into its line the event at `i` starts
+    fn column(&self, i: usize) -> usize {
+        blocks::column(self.text, self.events[i].1.start)
+    }
+
+    /// Whether the line starting with the event at `i` begins a definition
+    fn defines(&self, i: usize) -> bool {
+        let Some((Event::Text(text), _)) = self.events.get(i) else {
+            return false;
+        };
+        text.strip_prefix(':')
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+    }
+
+    /// Groups `lines` into definitions, removing the `:` each starts with
+    ///
+    /// The soft breaks between lines of a definition are kept.
+    fn definitions(&mut self, lines: &[Range<usize>]) -> Vec<Range<usize>> {
+        let mut definitions: Vec<Range<usize>> = Vec::new();
+        for line in lines {
+            if self.defines(line.start) {
+                let text = match &self.events[line.start].0 {
+                    Event::Text(text) => text[1..].trim_start().to_owned(),
+                    _ => String::new(),
+                };
+                self.events[line.start].0 = Event::Text(CowStr::from(text));
+                definitions.push(line.clone());
+            } else if let Some(definition) = definitions.last_mut() {
+                definition.end = line.end;
+            }
+        }
+        definitions
+    }
+
+    /// Moves the events at `range` to the output
+    fn take(&mut self, range: Range<usize>) {
+        for i in range {
+            let event = mem::replace(&mut self.events[i].0, Event::SoftBreak);
+            // the `:` of a definition may have been all of its first text
+            if !matches!(&event, Event::Text(text) if text.is_empty()) {
+                self.out.push(event);
+            }
+        }
+    }
+
+    fn html(&mut self, html: &'static str) {
+        self.out.push(Event::Html(CowStr::Borrowed(html)));
+    }
+}
@@ -0,0 +1,317 @@
+use std::borrow::Cow;
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+use crate::render::Summary;
+
+/// Shortcodes and their emoji, a common subset of GitHub's, sorted by name
+const EMOJI: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("1234", "🔢"),
+    ("alarm_clock", "⏰"),
+    ("angry", "😠"),
+    ("ant", "🐜"),
+    ("apple", "🍎"),
+    ("arrow_down", "⬇️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("art", "🎨"),
+    ("astonished", "😲"),
+    ("baby", "👶"),
+    ("balloon", "🎈"),
+    ("bang", "❗"),
+    ("bee", "🐝"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bowtie", "🎀"),
+    ("brain", "🧠"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("cat", "🐱"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("check", "✔️"),
+    ("checkered_flag", "🏁"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("cloud", "☁️"),
+    ("coffee", "☕"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("cow", "🐮"),
+    ("crab", "🦀"),
+    ("crossed_fingers", "🤞"),
+    ("cry", "😢"),
+    ("crystal_ball", "🔮"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("door", "🚪"),
+    ("dragon", "🐉"),
+    ("droplet", "💧"),
+    ("earth_africa", "🌍"),
+    ("earth_americas", "🌎"),
+    ("earth_asia", "🌏"),
+    ("egg", "🥚"),
+    ("email", "📧"),
+    ("envelope", "✉️"),
+    ("exclamation", "❗"),
+    ("eyes", "👀"),
+    ("facepalm", "🤦"),
+    ("fire", "🔥"),
+    ("fish", "🐟"),
+    ("flashlight", "🔦"),
+    ("floppy_disk", "💾"),
+    ("flushed", "😳"),
+    ("fox_face", "🦊"),
+    ("frog", "🐸"),
+    ("gear", "⚙️"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("grey_question", "❔"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("hammer", "🔨"),
+    ("hammer_and_wrench", "🛠️"),
+    ("hand", "✋"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heavy_check_mark", "✔️"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_plus_sign", "➕"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("information_source", "ℹ️"),
+    ("innocent", "😇"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("keyboard", "⌨️"),
+    ("kiss", "💋"),
+    ("koala", "🐨"),
+    ("label", "🏷️"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("link", "🔗"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("lollipop", "🍭"),
+    ("loudspeaker", "📢"),
+    ("love_letter", "💌"),
+    ("mag", "🔍"),
+    ("mailbox", "📫"),
+    ("man_shrugging", "🤷‍♂️"),
+    ("memo", "📝"),
+    ("microscope", "🔬"),
+    ("moon", "🌔"),
+    ("mortar_board", "🎓"),
+    ("mouse", "🐭"),
+    ("muscle", "💪"),
+    ("mushroom", "🍄"),
+    ("musical_note", "🎵"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("notebook", "📓"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("open_mouth", "😮"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("paperclip", "📎"),
+    ("partying_face", "🥳"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "👆"),
+    ("poop", "💩"),
+    ("pray", "🙏"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("rage", "😡"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("recycle", "♻️"),
+    ("red_circle", "🔴"),
+    ("relaxed", "☺️"),
+    ("relieved", "😌"),
+    ("repeat", "🔁"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("rose", "🌹"),
+    ("rotating_light", "🚨"),
+    ("runner", "🏃"),
+    ("sad", "😞"),
+    ("scissors", "✂️"),
+    ("scream", "😱"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("shield", "🛡️"),
+    ("shrug", "🤷"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("smirk", "😏"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("snowflake", "❄️"),
+    ("sob", "😭"),
+    ("sparkles", "✨"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("stop_sign", "🛑"),
+    ("stuck_out_tongue", "😛"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sweat", "😓"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("tired_face", "😫"),
+    ("toolbox", "🧰"),
+    ("trophy", "🏆"),
+    ("truck", "🚚"),
+    ("turtle", "🐢"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("upside_down_face", "🙃"),
+    ("v", "✌️"),
+    ("warning", "⚠️"),
+    ("wave", "👋"),
+    ("whale", "🐳"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yawning_face", "🥱"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zipper_mouth_face", "🤐"),
+    ("zzz", "💤"),
+];
+
+/// The emoji for `name`, the shortcode without its colons
+#[must_use]
+pub fn lookup(name: &str) -> Option<&'static str> {
+    EMOJI
+        .binary_search_by_key(&name, |(name, _)| name)
+        .ok()
+        .map(|i| EMOJI[i].1)
+}
+
+/// Replaces every known `:shortcode:` in `text`, leaving unknown ones as they are
+#[must_use]
+pub fn replace(text: &str) -> Cow<'_, str> {
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut rest = 0;
+    while let Some(start) = text[rest..].find(':').map(|i| rest + i) {
+        let name_start = start + 1;
+        let Some(len) = text[name_start..].find(|c: char| !is_shortcode_char(c)) else {
+            break;
+        };
+        let end = name_start + len;
+        let emoji = (text[end..].starts_with(':') && len > 0)
+            .then(|| lookup(&text[name_start..end]))
+            .flatten();
+        let Some(emoji) = emoji else {
+            // the closing colon may open the next shortcode
+            rest = name_start;
+            continue;
+        };
+        out.push_str(&text[copied..start]);
+        out.push_str(emoji);
+        copied = end + 1;
+        rest = copied;
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(&text[copied..]);
+    Cow::Owned(out)
+}
+
+const fn is_shortcode_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-')
+}
+
+/// Replaces shortcodes in text events, leaving code blocks alone, see [`crate::render::Config::emoji`]
+///
+/// Inline code is its own event, so it is never touched. Expects merged text events,
+/// as the parser splits text at characters like `_`, and ids already assigned to headings.
+#[derive(Debug, Default)]
+pub struct Emoji {
+    in_code_block: bool,
+}
+
+impl Emoji {
+    /// Replaces shortcodes in `events`, and in the title and excerpt summarized from them
+    pub fn apply<'a>(events: Vec<Event<'a>>, summary: &mut Summary) -> Vec<Event<'a>> {
+        if let Some(heading) = &mut summary.heading {
+            *heading = replace(heading).into_owned();
+        }
+        summary.excerpt = replace(&summary.excerpt).into_owned();
+        let mut emoji = Self::default();
+        events.into_iter().map(|e| emoji.map(e)).collect()
+    }
+
+    fn map<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => self.in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => self.in_code_block = false,
+            Event::Text(text) if !self.in_code_block => {
+                return match replace(&text) {
+                    Cow::Borrowed(_) => Event::Text(text),
+                    Cow::Owned(replaced) => Event::Text(replaced.into()),
+                };
+            }
+            _ => (),
+        }
+        event
+    }
+}
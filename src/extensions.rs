@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::ensure;
+use serde::Serialize;
+
+use crate::ordering::ORDER_FILE;
+
+/// The markdown extensions recognized unless others are given, most preferred first
+pub const DEFAULT_MARKDOWN: &[&str] = &["md", "markdown", "mdown"];
+
+/// The extension of plain text files, served as preformatted pages, see
+/// [`crate::render::render_text`]
+pub const TEXT: &str = "txt";
+
+/// The extension of csv files, served as tables when enabled, see
+/// [`crate::render::render_csv`]
+pub const CSV: &str = "csv";
+
+/// The extensions of the files served as pages, see [`crate::settings::Settings::extensions`]
+///
+/// Files with different extensions but the same key collide, the one whose extension
+/// comes first is served and the rest are left out, see [`Extensions::shadowed_by`].
+/// Plain text comes after markdown, and csv last.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Extensions {
+    markdown: Vec<String>,
+    csv: bool,
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self {
+            markdown: DEFAULT_MARKDOWN.iter().map(|&ext| ext.to_owned()).collect(),
+            csv: false,
+        }
+    }
+}
+
+impl Extensions {
+    /// Recognizes `markdown` as markdown extensions, in order of preference
+    ///
+    /// Extensions may start with a `.`, and those given twice are only kept once.
+    pub fn new(markdown: &[String]) -> anyhow::Result<Self> {
+        let mut kept: Vec<String> = Vec::new();
+        for ext in markdown {
+            let ext = ext.trim().trim_start_matches('.');
+            ensure!(!ext.is_empty(), "markdown extensions can't be empty");
+            ensure!(
+                !ext.contains(['/', '\\']),
+                "\"{ext}\" isn't a file extension"
+            );
+            ensure!(ext != TEXT, "{TEXT} files are always plain text");
+            ensure!(ext != CSV, "{CSV} files are served as tables with --csv");
+            if !kept.iter().any(|k| k == ext) {
+                kept.push(ext.to_owned());
+            }
+        }
+        ensure!(
+            !kept.is_empty(),
+            "at least one markdown extension is needed"
+        );
+        Ok(Self {
+            markdown: kept,
+            csv: false,
+        })
+    }
+
+    /// Serves csv files as pages too
+    #[must_use]
+    pub const fn with_csv(mut self, csv: bool) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    /// Whether csv files are served as pages
+    #[must_use]
+    pub const fn csv(&self) -> bool {
+        self.csv
+    }
+
+    /// The markdown extensions, most preferred first
+    #[must_use]
+    pub fn markdown(&self) -> &[String] {
+        &self.markdown
+    }
+
+    /// Every extension of pages, most preferred first
+    fn all(&self) -> impl Iterator<Item = &str> {
+        let csv = self.csv.then_some(CSV);
+        self.markdown
+            .iter()
+            .map(String::as_str)
+            .chain([TEXT])
+            .chain(csv)
+    }
+
+    /// The page extension `path` ends with, `None` if it isn't a page
+    ///
+    /// Order files list pages rather than being pages, see [`ORDER_FILE`].
+    #[must_use]
+    pub fn extension<'a>(&self, path: &'a Path) -> Option<&'a str> {
+        let name = path.file_name()?.to_str()?;
+        if name == ORDER_FILE {
+            return None;
+        }
+        let (stem, ext) = name.rsplit_once('.')?;
+        let known = self.all().any(|known| known == ext);
+        (known && !stem.is_empty()).then_some(ext)
+    }
+
+    /// Derives the [`crate::MdFiles`] key of the file at `path`
+    ///
+    /// When `base` is itself a file, its key is the file's stem.
+    #[must_use]
+    pub fn key<'a>(&self, base: &Path, path: &'a Path) -> Option<&'a str> {
+        if base == path {
+            return path.file_stem()?.to_str();
+        }
+        let ext = self.extension(path)?;
+        let relative = path.strip_prefix(base).ok()?.to_str()?;
+        relative.strip_suffix(ext)?.strip_suffix('.')
+    }
+
+    /// Strips the extension of a page from the end of `path`, such as `docs/setup.md`
+    /// into `docs/setup`
+    #[must_use]
+    pub fn strip<'a>(&self, path: &'a str) -> &'a str {
+        self.extension(Path::new(path))
+            .and_then(|ext| path.strip_suffix(ext)?.strip_suffix('.'))
+            .unwrap_or(path)
+    }
+
+    /// The files a page at `key` may be read from, most preferred first
+    pub fn paths<'a>(&'a self, base: &'a Path, key: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        self.all().map(move |ext| base.join(format!("{key}.{ext}")))
+    }
+
+    /// The files sharing the key of `path` that are served instead of it, as their
+    /// extension comes first
+    pub fn preferred_over<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = PathBuf> + 'a {
+        let ext = self.extension(path);
+        self.all()
+            .take_while(move |&preferred| ext.is_some_and(|ext| preferred != ext))
+            .map(|preferred| path.with_extension(preferred))
+    }
+
+    /// The existing file sharing the key of `path` that is served instead of it, see
+    /// [`Extensions::preferred_over`]
+    #[must_use]
+    pub fn shadowed_by(&self, path: &Path) -> Option<PathBuf> {
+        self.preferred_over(path)
+            .find(|preferred| preferred.is_file())
+    }
+
+    /// The existing file sharing the key of `path` that is served once `path` is gone,
+    /// the first by extension
+    #[must_use]
+    pub fn fallback(&self, path: &Path) -> Option<PathBuf> {
+        self.extension(path)?;
+        self.all()
+            .map(|ext| path.with_extension(ext))
+            .find(|other| other != path && other.is_file())
+    }
+}
+
+/// Whether the file at `path` is plain text rather than markdown, see [`TEXT`]
+#[must_use]
+pub fn is_text(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == TEXT)
+}
+
+/// Whether the file at `path` is a csv file rather than markdown, see [`CSV`]
+#[must_use]
+pub fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == CSV)
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use easy_sgr::{Color::*, Style::*};
+use reqwest::{Client, StatusCode, Url};
+use serde::Serialize;
+use tokio::{
+    sync::{Notify, Semaphore},
+    task::JoinSet,
+    time::{sleep_until, Instant},
+};
+
+use crate::{time::Timestamp, MutexExt};
+
+/// How long a link's result is reused before it is checked again
+pub const RESULT_TTL: Duration = Duration::from_hours(1);
+/// How long a single link may take to respond
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How many links are checked at once
+const CONCURRENCY: usize = 4;
+/// The shortest time between two requests to the same host
+const HOST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether `url` points outside of the served pages
+#[must_use]
+pub fn is_external(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// The outcome of checking an external link, see [`ExternalLinks`]
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStatus {
+    pub url: String,
+    /// Why the link is dead, `None` if it responded
+    pub dead: Option<String>,
+    pub checked: Timestamp,
+}
+
+impl LinkStatus {
+    const fn is_fresh(&self, now: Timestamp) -> bool {
+        now.0.saturating_sub(self.checked.0) < RESULT_TTL.as_secs()
+    }
+}
+
+/// The results of checking external links, along with requests to check them
+#[derive(Debug, Default)]
+pub struct ExternalLinks {
+    results: DashMap<String, LinkStatus>,
+    requested: Notify,
+    /// Whether the requested check ignores [`RESULT_TTL`]
+    force: AtomicBool,
+    running: AtomicBool,
+}
+
+impl ExternalLinks {
+    /// Why `url` is dead, `None` if it responded or wasn't checked
+    #[must_use]
+    pub fn dead(&self, url: &str) -> Option<String> {
+        self.results.get(url)?.dead.clone()
+    }
+
+    /// Every checked link, dead ones first
+    #[must_use]
+    pub fn results(&self) -> Vec<LinkStatus> {
+        let mut results: Vec<_> = self.results.iter().map(|r| r.value().clone()).collect();
+        results
+            .sort_unstable_by(|a, b| (a.dead.is_none(), &a.url).cmp(&(b.dead.is_none(), &b.url)));
+        results
+    }
+
+    /// Whether a check is in progress
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Asks for a check, see [`crate::Api::serve_link_checks`]
+    ///
+    /// Requests made while a check runs start another once it finishes.
+    pub fn request(&self, force: bool) {
+        self.force.fetch_or(force, Ordering::AcqRel);
+        self.requested.notify_one();
+    }
+
+    /// Waits for [`ExternalLinks::request`], returning whether the check is forced
+    pub async fn requested(&self) -> bool {
+        self.requested.notified().await;
+        self.force.swap(false, Ordering::AcqRel)
+    }
+
+    /// Checks every url in `urls`, returning the ones whose result changed
+    ///
+    /// Urls checked within [`RESULT_TTL`] are skipped unless `force` is set.
+    /// Dropping the future cancels the check.
+    pub async fn check(&self, urls: impl IntoIterator<Item = String>, force: bool) -> Vec<String> {
+        self.running.store(true, Ordering::Release);
+        let _running = Running(&self.running);
+        let now = Timestamp::now();
+        let client = match Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("mdflc/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("{YellowFg}unable to check external links: {e}{Reset}");
+                return Vec::new();
+            }
+        };
+        let permits = Arc::new(Semaphore::new(CONCURRENCY));
+        let hosts = Arc::new(Mutex::new(HashMap::new()));
+
+        // dropping the set aborts whatever is left of it
+        let mut checks = JoinSet::new();
+        for url in urls {
+            if !force && self.results.get(&url).is_some_and(|r| r.is_fresh(now)) {
+                continue;
+            }
+            let (client, permits, hosts) = (client.clone(), permits.clone(), hosts.clone());
+            checks.spawn(async move {
+                wait_for_host(&hosts, &url).await;
+                // the semaphore is never closed
+                let _permit = permits.acquire().await.ok();
+                let dead = check_link(&client, &url).await;
+                (url, dead)
+            });
+        }
+
+        let mut changed = Vec::new();
+        while let Some(result) = checks.join_next().await {
+            let Ok((url, dead)) = result else {
+                continue;
+            };
+            let status = LinkStatus {
+                url: url.clone(),
+                dead,
+                checked: Timestamp::now(),
+            };
+            let previous = self.results.insert(url.clone(), status.clone());
+            if previous.map_or_else(|| status.dead.is_some(), |p| p.dead != status.dead) {
+                changed.push(url);
+            }
+        }
+        changed.sort_unstable();
+        changed
+    }
+}
+
+/// Clears the running flag when dropped, including when the check is cancelled
+struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Waits until `url`'s host may be requested again, see [`HOST_INTERVAL`]
+async fn wait_for_host(hosts: &Mutex<HashMap<String, Instant>>, url: &str) {
+    let Some(host) = Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_owned))
+    else {
+        return;
+    };
+    sleep_until(reserve(hosts, host)).await;
+}
+
+/// Reserves the next time `host` may be requested
+fn reserve(hosts: &Mutex<HashMap<String, Instant>>, host: String) -> Instant {
+    let now = Instant::now();
+    let mut hosts = hosts.unlock();
+    let next = hosts.entry(host).or_insert(now);
+    let start = (*next).max(now);
+    *next = start + HOST_INTERVAL;
+    drop(hosts);
+    start
+}
+
+/// Requests `url`, returning why it is dead, if it is
+///
+/// Servers that refuse `HEAD` are asked again with `GET`.
+async fn check_link(client: &Client, url: &str) -> Option<String> {
+    let mut response = client.head(url).send().await;
+    let refused = |status| {
+        matches!(
+            status,
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        )
+    };
+    if response.as_ref().is_ok_and(|r| refused(r.status())) {
+        response = client.get(url).send().await;
+    }
+    match response {
+        Ok(response)
+            if response.status().is_client_error() || response.status().is_server_error() =>
+        {
+            Some(format!("responded with {}", response.status()))
+        }
+        Ok(_) => None,
+        Err(e) if e.is_timeout() => Some(format!("timed out after {TIMEOUT:?}")),
+        Err(e) => Some(format!("{:#}", anyhow::Error::new(e.without_url()))),
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use serde::Serialize;
+
+use crate::extensions::Extensions;
+
+/// The mode git uses for symlinks, which the filesystem walk doesn't follow either
+const SYMLINK_MODE: i32 = 0o120_000;
+
+/// The commit served in git ref mode, see [`crate::settings::Settings::git_ref`]
+///
+/// Only ids are kept, the repository is opened again for every read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Revision {
+    /// The revision as given, e.g. a branch name
+    pub rev: String,
+    /// The full id of the commit `rev` resolved to
+    pub commit: String,
+    /// The first line of the commit's message
+    pub summary: String,
+    /// The repository's git directory, where refs live
+    #[serde(skip)]
+    pub git_dir: PathBuf,
+    #[serde(skip)]
+    base: PathBuf,
+    /// `base` relative to the work tree, empty if they are the same
+    #[serde(skip)]
+    prefix: PathBuf,
+}
+
+impl Revision {
+    /// Resolves `rev` within the repository containing `base`, which must be canonical
+    pub fn resolve(base: &Path, rev: &str) -> anyhow::Result<Self> {
+        let repo = Repository::discover(base)
+            .with_context(|| format!("\"{}\" is not within a git repository", base.display()))?;
+        let Some(workdir) = repo.workdir() else {
+            bail!("\"{}\" is within a bare git repository", base.display());
+        };
+        let workdir = workdir.canonicalize()?;
+        let prefix = base.strip_prefix(&workdir).with_context(|| {
+            format!(
+                "\"{}\" is outside the work tree of its repository",
+                base.display()
+            )
+        })?;
+        let commit = repo
+            .revparse_single(rev)
+            .with_context(|| format!("no ref or commit named \"{rev}\" in {}", workdir.display()))?
+            .peel_to_commit()
+            .with_context(|| format!("\"{rev}\" doesn't point to a commit"))?;
+
+        Ok(Self {
+            rev: rev.to_owned(),
+            commit: commit.id().to_string(),
+            summary: commit.summary().unwrap_or_default().to_owned(),
+            git_dir: repo.path().to_owned(),
+            base: base.to_owned(),
+            prefix: prefix.to_owned(),
+        })
+    }
+
+    /// The abbreviated commit id
+    #[must_use]
+    pub fn short(&self) -> &str {
+        &self.commit[..self.commit.len().min(10)]
+    }
+
+    /// Calls `found` with the key, path and source of every markdown file within base
+    ///
+    /// Paths are where the files would be in the work tree, so keys match the ones
+    /// [`crate::find_md`] derives. Submodules can't be read, they are passed to
+    /// `submodule` by path instead.
+    pub fn find_md(
+        &self,
+        extensions: &Extensions,
+        mut submodule: impl FnMut(&Path),
+        mut found: impl FnMut(&str, &Path, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let repo = Repository::open(&self.git_dir)?;
+        let tree = self.tree(&repo)?;
+        if self.prefix.as_os_str().is_empty() {
+            return self.walk(&repo, &tree, extensions, &mut submodule, &mut found);
+        }
+        let entry = tree
+            .get_path(&self.prefix)
+            .with_context(|| self.missing(&self.prefix))?;
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let tree = entry.to_object(&repo)?.peel_to_tree()?;
+                self.walk(&repo, &tree, extensions, &mut submodule, &mut found)
+            }
+            Some(ObjectType::Blob) => {
+                let key = extensions
+                    .key(&self.base, &self.base)
+                    .context("only utf8 paths allowed")?;
+                let text = read_blob(&repo, entry.id(), &self.prefix)?;
+                found(key, &self.base, &text)
+            }
+            _ => bail!(
+                "\"{}\" is a submodule in {}, serve it from within the submodule instead",
+                self.prefix.display(),
+                self.rev
+            ),
+        }
+    }
+
+    fn walk(
+        &self,
+        repo: &Repository,
+        tree: &Tree,
+        extensions: &Extensions,
+        submodule: &mut impl FnMut(&Path),
+        found: &mut impl FnMut(&str, &Path, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut blobs = Vec::new();
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            let Some(name) = entry.name() else {
+                return TreeWalkResult::Ok;
+            };
+            let path = self.base.join(dir).join(name);
+            match entry.kind() {
+                Some(ObjectType::Commit) => submodule(&path),
+                Some(ObjectType::Blob) if entry.filemode() != SYMLINK_MODE => {
+                    blobs.push((path, entry.id()));
+                }
+                _ => (),
+            }
+            TreeWalkResult::Ok
+        })?;
+
+        let files: HashSet<PathBuf> = blobs.iter().map(|(path, _)| path.clone()).collect();
+        for (path, id) in blobs {
+            let Some(key) = extensions.key(&self.base, &path) else {
+                continue;
+            };
+            if extensions
+                .preferred_over(&path)
+                .any(|preferred| files.contains(&preferred))
+            {
+                continue;
+            }
+            let text = read_blob(repo, id, &path)?;
+            found(key, &path, &text)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the file at `path`, a path within base as if it were in the work tree
+    pub fn read(&self, path: &Path) -> anyhow::Result<String> {
+        let repo = Repository::open(&self.git_dir)?;
+        let id = self.entry(&repo, path)?.id();
+        read_blob(&repo, id, path)
+    }
+
+    /// The size of the file at `path` in bytes, see [`Revision::read`]
+    pub fn size(&self, path: &Path) -> anyhow::Result<u64> {
+        let repo = Repository::open(&self.git_dir)?;
+        let id = self.entry(&repo, path)?.id();
+        let (size, _) = repo.odb()?.read_header(id)?;
+        Ok(size as u64)
+    }
+
+    fn entry(&self, repo: &Repository, path: &Path) -> anyhow::Result<git2::TreeEntry<'static>> {
+        let relative = path
+            .strip_prefix(&self.base)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or_else(|| self.prefix.clone(), |p| self.prefix.join(p));
+        let entry = self
+            .tree(repo)?
+            .get_path(&relative)
+            .with_context(|| self.missing(&relative))?;
+        Ok(entry)
+    }
+
+    fn tree<'a>(&self, repo: &'a Repository) -> anyhow::Result<Tree<'a>> {
+        let id = self.commit.parse()?;
+        Ok(repo.find_commit(id)?.tree()?)
+    }
+
+    fn missing(&self, path: &Path) -> String {
+        format!(
+            "\"{}\" doesn't exist in {} ({})",
+            path.display(),
+            self.rev,
+            self.short()
+        )
+    }
+}
+
+fn read_blob(repo: &Repository, id: git2::Oid, path: &Path) -> anyhow::Result<String> {
+    let blob = repo.find_blob(id)?;
+    String::from_utf8(blob.content().to_owned())
+        .with_context(|| format!("\"{}\" isn't valid utf8", path.display()))
+}
@@ -0,0 +1,172 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use pulldown_cmark_escape::escape_html;
+
+/// Terms explained wherever pages use them, see [`Glossary::apply`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Glossary {
+    /// Longest first, so `pull request` is preferred over `pull`
+    terms: Vec<Term>,
+    case_sensitive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub name: String,
+    pub definition: String,
+}
+
+impl Glossary {
+    /// Reads the terms of a glossary file
+    ///
+    /// Terms are written as `term: definition` lines, or as a definition list of `Term`
+    /// lines followed by `: definition` lines. Headings and other lines are skipped, as
+    /// are definitions after a term's first.
+    #[must_use]
+    pub fn parse(text: &str, case_sensitive: bool) -> Self {
+        let mut terms: Vec<Term> = Vec::new();
+        let mut add = |term: &str, definition: &str| {
+            let term = term.trim().trim_matches(['*', '_', '`']).trim();
+            let definition = definition.trim();
+            let known = terms.iter().any(|t| t.name == term);
+            if !term.is_empty() && !definition.is_empty() && !known {
+                terms.push(Term {
+                    name: term.to_owned(),
+                    definition: definition.to_owned(),
+                });
+            }
+        };
+        // the terms of a definition list waiting for their definition
+        let mut pending: Vec<&str> = Vec::new();
+        let mut continued = false;
+        for line in text.lines().map(str::trim) {
+            if let Some(definition) = line.strip_prefix(": ") {
+                for term in &pending {
+                    add(term, definition);
+                }
+                continued = false;
+            } else if line.is_empty() {
+                continued = false;
+            } else if line.starts_with('#') {
+                pending.clear();
+            } else if let Some((term, definition)) = line.split_once(": ") {
+                add(term, definition);
+                pending.clear();
+            } else {
+                if !continued {
+                    pending.clear();
+                }
+                pending.push(line);
+                continued = true;
+            }
+        }
+        terms.sort_by_key(|t| std::cmp::Reverse(t.name.len()));
+        Self {
+            terms,
+            case_sensitive,
+        }
+    }
+
+    #[must_use]
+    pub const fn terms(&self) -> &[Term] {
+        self.terms.as_slice()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Wraps the first use of each term in an `<abbr>` titled with its definition
+    ///
+    /// Terms are only found as whole words, and outside of code, headings, links and
+    /// images. Unless the glossary is case sensitive, ascii letters match either case.
+    /// Expects merged text events, as the parser splits text at characters like `_`.
+    #[must_use]
+    pub fn apply<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut unused: Vec<&Term> = self.terms.iter().collect();
+        let mut out = Vec::with_capacity(events.len());
+        // how many code blocks, headings, links and images the event is within
+        let mut within = 0_usize;
+        for event in events {
+            match &event {
+                Event::Start(
+                    Tag::CodeBlock(_) | Tag::Heading { .. } | Tag::Link { .. } | Tag::Image { .. },
+                ) => within += 1,
+                Event::End(
+                    TagEnd::CodeBlock | TagEnd::Heading(_) | TagEnd::Link | TagEnd::Image,
+                ) => within = within.saturating_sub(1),
+                Event::Text(text)
+                    if within == 0
+                        && !unused.is_empty()
+                        && self.explain(text, &mut unused, &mut out) =>
+                {
+                    continue;
+                }
+                _ => (),
+            }
+            out.push(event);
+        }
+        out
+    }
+
+    /// Pushes `text` to `out` with the `unused` terms within it explained, returning
+    /// whether any were
+    fn explain(&self, text: &str, unused: &mut Vec<&Term>, out: &mut Vec<Event<'_>>) -> bool {
+        let folded = if self.case_sensitive {
+            text.to_owned()
+        } else {
+            text.to_ascii_lowercase()
+        };
+        let mut copied = 0;
+        loop {
+            let found = unused
+                .iter()
+                .enumerate()
+                .filter_map(|(i, term)| Some((self.find(&folded, copied, &term.name)?, i)))
+                .min();
+            let Some((start, i)) = found else {
+                break;
+            };
+            let term = unused.remove(i);
+            let end = start + term.name.len();
+            if copied < start {
+                out.push(Event::Text(CowStr::from(text[copied..start].to_owned())));
+            }
+            let mut html = String::from("<abbr title=\"");
+            let _ = escape_html(&mut html, &term.definition);
+            html.push_str("\">");
+            out.push(Event::InlineHtml(CowStr::from(html)));
+            out.push(Event::Text(CowStr::from(text[start..end].to_owned())));
+            out.push(Event::InlineHtml(CowStr::Borrowed("</abbr>")));
+            copied = end;
+        }
+        if copied == 0 {
+            return false;
+        }
+        if copied < text.len() {
+            out.push(Event::Text(CowStr::from(text[copied..].to_owned())));
+        }
+        true
+    }
+
+    /// Where `term` is first found in `folded` as a whole word, at or past `from`
+    fn find(&self, folded: &str, from: usize, term: &str) -> Option<usize> {
+        let term = if self.case_sensitive {
+            term.to_owned()
+        } else {
+            term.to_ascii_lowercase()
+        };
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let mut at = from;
+        while let Some(start) = folded[at..].find(&term).map(|i| at + i) {
+            let end = start + term.len();
+            let before = folded[..start].chars().next_back();
+            let after = folded[end..].chars().next();
+            if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                return Some(start);
+            }
+            at = start + folded[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+}
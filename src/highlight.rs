@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+use pulldown_cmark_escape::escape_html;
+
+/// The most occurrences of a term marked on one page
+pub const MAX_MARKS: usize = 100;
+
+/// The longest term marked, in characters
+pub const MAX_TERM_LEN: usize = 100;
+
+/// Elements whose text isn't shown, and so is never marked
+const HIDDEN: &[&str] = &["script", "style", "template", "textarea"];
+
+/// Wraps the occurrences of `term` within the text of `html` in `<mark>`
+///
+/// Tags, attributes and the text of elements like `script` are left alone, and the
+/// term matches in any case. Only the first [`MAX_MARKS`] occurrences are marked, and
+/// blank terms or terms over [`MAX_TERM_LEN`] leave the html as it is.
+#[must_use]
+pub fn apply<'a>(html: &'a str, term: &str) -> Cow<'a, str> {
+    let term = term.trim();
+    if term.is_empty() || term.chars().count() > MAX_TERM_LEN {
+        return Cow::Borrowed(html);
+    }
+    let mut escaped = String::new();
+    let _ = escape_html(&mut escaped, term);
+    let term = units(&escaped);
+
+    let mut out = String::with_capacity(html.len());
+    let mut marks = 0;
+    let mut rest = html;
+    while !rest.is_empty() {
+        let text_len = rest.find('<').unwrap_or(rest.len());
+        let (text, tags) = rest.split_at(text_len);
+        if marks < MAX_MARKS {
+            marks += mark(text, &term, MAX_MARKS - marks, &mut out);
+        } else {
+            out.push_str(text);
+        }
+        let tag_len = tag_len(tags);
+        out.push_str(&tags[..tag_len]);
+        rest = &tags[tag_len..];
+    }
+    if marks == 0 {
+        Cow::Borrowed(html)
+    } else {
+        Cow::Owned(out)
+    }
+}
+
+/// The length of the tag `html` starts with, along with the text of the element it
+/// opens when that text is hidden
+fn tag_len(html: &str) -> usize {
+    if html.is_empty() {
+        return 0;
+    }
+    if html.starts_with("<!--") {
+        return html.find("-->").map_or(html.len(), |end| end + 3);
+    }
+    let end = tag_end(html);
+    let name: String = html[1..end]
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if !HIDDEN.contains(&name.as_str()) {
+        return end;
+    }
+    let close = format!("</{name}");
+    html[end..]
+        .to_ascii_lowercase()
+        .find(&close)
+        .map_or(html.len(), |i| end + i)
+}
+
+/// Where the tag `html` starts with ends, skipping over quoted attributes
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => (),
+        }
+    }
+    html.len()
+}
+
+/// Pushes `text` to `out` with at most `max` occurrences of `term` marked, returning how
+/// many were
+fn mark(text: &str, term: &[&str], max: usize, out: &mut String) -> usize {
+    let text = units(text);
+    let mut marks = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let found = marks < max
+            && text.len() - i >= term.len()
+            && text[i..i + term.len()]
+                .iter()
+                .zip(term)
+                .all(|(a, b)| same(a, b));
+        if found {
+            out.push_str("<mark>");
+            out.extend(text[i..i + term.len()].iter().copied());
+            out.push_str("</mark>");
+            marks += 1;
+            i += term.len();
+        } else {
+            out.push_str(text[i]);
+            i += 1;
+        }
+    }
+    marks
+}
+
+/// Splits escaped text into characters and whole character references, like `&amp;`,
+/// so a term never matches part of a reference
+fn units(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '&' {
+            rest.find(';')
+                .filter(|&end| end <= 32)
+                .filter(|&end| {
+                    rest[1..end]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                })
+                .map_or(1, |end| end + 1)
+        } else {
+            c.len_utf8()
+        };
+        units.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    units
+}
+
+/// Whether two units are the same, in any case
+fn same(a: &str, b: &str) -> bool {
+    let lower = |c: char| c.to_lowercase();
+    a == b || (!a.starts_with('&') && a.chars().flat_map(lower).eq(b.chars().flat_map(lower)))
+}
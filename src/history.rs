@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use pulldown_cmark_escape::escape_html;
+use similar::{ChangeTag, TextDiff};
+
+use crate::MutexExt;
+
+/// The most markdown source kept across every page, in bytes
+pub const MAX_ARCHIVE_BYTES: usize = 16 << 20;
+
+/// The last two versions of a page's source
+#[derive(Debug, Default)]
+struct Versions {
+    current: String,
+    previous: Option<String>,
+}
+
+impl Versions {
+    fn size(&self) -> usize {
+        self.current.len() + self.previous.as_ref().map_or(0, String::len)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    pages: HashMap<String, Versions>,
+    /// keys, least recently changed first
+    order: VecDeque<String>,
+    size: usize,
+}
+
+/// Keeps the source each page had one change back, for [`render_diff`]
+///
+/// Only changes seen while running are kept, the oldest are evicted past [`MAX_ARCHIVE_BYTES`].
+#[derive(Debug, Default)]
+pub struct Archive {
+    inner: Mutex<Inner>,
+}
+
+impl Archive {
+    /// Records `source` as the latest version of `key`
+    pub fn record(&self, key: &str, source: &str) {
+        let mut guard = self.inner.unlock();
+        let inner = &mut *guard;
+        let versions = inner.pages.entry(key.to_owned()).or_default();
+        inner.size -= versions.size();
+        let previous = std::mem::replace(&mut versions.current, source.to_owned());
+        versions.previous = Some(previous).filter(|p| !p.is_empty());
+        inner.size += versions.size();
+
+        inner.order.retain(|k| k != key);
+        inner.order.push_back(key.to_owned());
+        while inner.size > MAX_ARCHIVE_BYTES {
+            let Some(oldest) = inner.order.pop_front() else {
+                break;
+            };
+            if let Some(versions) = inner.pages.remove(&oldest) {
+                inner.size -= versions.size();
+            }
+        }
+        drop(guard);
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.inner.unlock();
+        if let Some(versions) = inner.pages.remove(key) {
+            inner.size -= versions.size();
+            inner.order.retain(|k| k != key);
+        }
+    }
+
+    /// Returns the previous and current source of `key`, if it changed twice while running
+    #[must_use]
+    pub fn versions(&self, key: &str) -> Option<(String, String)> {
+        let inner = self.inner.unlock();
+        let versions = inner.pages.get(key)?;
+        let versions = (versions.previous.clone()?, versions.current.clone());
+        drop(inner);
+        Some(versions)
+    }
+}
+
+/// Renders an inline line diff from `old` to `new`
+#[must_use]
+pub fn render_diff(old: &str, new: &str) -> String {
+    let mut html = String::from("<pre class=\"diff\">");
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        let (class, sign) = match change.tag() {
+            ChangeTag::Delete => ("diff-removed", '-'),
+            ChangeTag::Insert => ("diff-added", '+'),
+            ChangeTag::Equal => ("diff-equal", ' '),
+        };
+        html.push_str("<span class=\"");
+        html.push_str(class);
+        html.push_str("\">");
+        html.push(sign);
+        html.push(' ');
+        let _ = escape_html(&mut html, change.value().trim_end_matches('\n'));
+        html.push_str("</span>");
+    }
+    html.push_str("</pre>");
+    html
+}
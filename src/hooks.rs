@@ -0,0 +1,92 @@
+use std::{collections::HashMap, fmt};
+
+use pulldown_cmark_escape::escape_html;
+
+/// Rewrites the html of the page at a key, see [`Hooks::post_render`]
+pub type PostRender = Box<dyn Fn(&str, String) -> anyhow::Result<String> + Send + Sync>;
+
+/// Turns the info string and body of a fenced code block into html, see [`Hooks::fence_handler`]
+pub type FenceHandler = Box<dyn Fn(&str, &str) -> anyhow::Result<String> + Send + Sync>;
+
+/// Processing added by programs embedding mdflc, see [`crate::ApiBuilder`]
+///
+/// The default hooks only hold the built-in fence handlers.
+pub struct Hooks {
+    post_render: Vec<PostRender>,
+    fences: HashMap<String, FenceHandler>,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        let mut hooks = Self {
+            post_render: Vec::new(),
+            fences: HashMap::new(),
+        };
+        hooks.fence_handler("mermaid", Box::new(mermaid));
+        hooks
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("post_render", &self.post_render.len())
+            .field("fences", &self.fences.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Hooks {
+    /// Adds a hook run on every page's html after rendering, before it is templated and cached
+    ///
+    /// Hooks run in order, on whichever thread renders the page, including the file watcher's.
+    /// They should be fast and must not block on async work; anything slow belongs on
+    /// [`tokio::task::spawn_blocking`] or a thread of its own, with the result fed back
+    /// through a cache the hook reads from.
+    ///
+    /// An error replaces the page with an explanation and records a warning,
+    /// the server keeps running.
+    pub fn post_render(&mut self, hook: PostRender) {
+        self.post_render.push(hook);
+    }
+
+    /// Renders fenced code blocks of `lang` with `handler` instead of as code
+    ///
+    /// `lang` is matched against the first word of the info string, replacing any
+    /// previous handler, including built-in ones. Errors are shown in place of the block
+    /// and listed at the top of the page, see [`crate::render::Summary::problems`].
+    /// Like [`Hooks::post_render`], handlers run on whichever thread renders the page.
+    pub fn fence_handler(&mut self, lang: &str, handler: FenceHandler) {
+        self.fences.insert(lang.to_owned(), handler);
+    }
+
+    /// Renders a fenced code block with the handler for its language, if there is one
+    #[must_use]
+    pub fn fence(&self, info: &str, body: &str) -> Option<anyhow::Result<String>> {
+        let lang = info.split_whitespace().next()?;
+        let handler = self.fences.get(lang)?;
+        Some(handler(info, body))
+    }
+
+    /// Whether any post render hooks were added
+    #[must_use]
+    pub fn has_post_render(&self) -> bool {
+        !self.post_render.is_empty()
+    }
+
+    /// Runs every post render hook on `html`, the output of rendering the page at `key`
+    pub fn apply(&self, key: &str, html: String) -> anyhow::Result<String> {
+        self.post_render
+            .iter()
+            .try_fold(html, |html, hook| hook(key, html))
+    }
+}
+
+/// Leaves mermaid diagrams for the client to draw
+#[allow(clippy::unnecessary_wraps)]
+fn mermaid(_: &str, body: &str) -> anyhow::Result<String> {
+    let mut html = String::from("<pre class=\"mermaid\">");
+    let _ = escape_html(&mut html, body);
+    html.push_str("</pre>\n");
+    Ok(html)
+}
@@ -0,0 +1,156 @@
+use std::{borrow::Cow, collections::HashSet, path::PathBuf};
+
+use anyhow::{ensure, Context};
+
+use crate::render::{normalize_source, split_front_matter, Targets};
+
+/// How deeply includes may nest, the page itself counted
+pub const MAX_DEPTH: usize = 8;
+
+/// A page's text with the files it includes written in place, see [`expand`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Expanded<'a> {
+    pub text: Cow<'a, str>,
+    /// Every file included, however deeply, whether or not it could be read
+    pub files: HashSet<PathBuf>,
+    /// The directives that failed, as written, along with why
+    pub failed: Vec<(String, String)>,
+}
+
+/// Writes the markdown files named by `{{include path}}` lines in place of those lines
+///
+/// Paths are relative to the including file, or to base when they start with `/`, and the
+/// front matter of included files is left out. A directive may follow the indentation or
+/// `>` of a list item or blockquote, which every included line is given too, but is left
+/// alone within fenced code blocks. Directives that fail, such as those including a file
+/// already being included, are kept as written, see [`Expanded::failed`].
+#[must_use]
+pub fn expand<'a>(text: &'a str, key: &str, targets: &dyn Targets) -> Expanded<'a> {
+    if !text.contains("{{include") {
+        return Expanded {
+            text: Cow::Borrowed(text),
+            ..Expanded::default()
+        };
+    }
+    let mut expanded = Expanded::default();
+    let mut stack: Vec<PathBuf> = targets
+        .resolve_include(key, &format!("/{key}.md"))
+        .into_iter()
+        .collect();
+    let mut out = String::with_capacity(text.len());
+    expanded.write(&mut out, text, "", key, targets, &mut stack);
+    expanded.text = Cow::Owned(out);
+    expanded
+}
+
+impl Expanded<'_> {
+    /// Writes `text` to `out` with every line after `prefix`, including the files its
+    /// directives name
+    fn write(
+        &mut self,
+        out: &mut String,
+        text: &str,
+        prefix: &str,
+        key: &str,
+        targets: &dyn Targets,
+        stack: &mut Vec<PathBuf>,
+    ) {
+        // the character and length of the open code fence
+        let mut fence: Option<(char, usize)> = None;
+        for line in text.lines() {
+            let content = line.trim_start_matches(|c: char| c.is_whitespace() || c == '>');
+            if let Some(open) = fence {
+                if closes(content, open) {
+                    fence = None;
+                }
+            } else if let Some(opened) = opens(content) {
+                fence = Some(opened);
+            } else if let Some(path) = directive(content) {
+                match self.read(path, key, targets, stack) {
+                    Ok((file, text, included)) => {
+                        let nested = format!("{prefix}{}", &line[..line.len() - content.len()]);
+                        stack.push(file);
+                        self.write(out, &text, &nested, &included, targets, stack);
+                        stack.pop();
+                        continue;
+                    }
+                    Err(e) => {
+                        let written = content.trim_end();
+                        if !self.failed.iter().any(|(d, _)| d == written) {
+                            self.failed.push((written.to_owned(), format!("{e:#}")));
+                        }
+                    }
+                }
+            }
+            out.push_str(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    /// The file `path` names, its markdown, and the key paths within it are relative to
+    fn read(
+        &mut self,
+        path: &str,
+        key: &str,
+        targets: &dyn Targets,
+        stack: &[PathBuf],
+    ) -> anyhow::Result<(PathBuf, String, String)> {
+        ensure!(!path.is_empty(), "no file was named");
+        ensure!(
+            stack.len() < MAX_DEPTH,
+            "includes nest over {MAX_DEPTH} deep"
+        );
+        let file = targets.resolve_include(key, path)?;
+        ensure!(!stack.contains(&file), "{path} is already being included");
+        self.files.insert(file.clone());
+        let text = targets
+            .read_include(&file)
+            .with_context(|| format!("unable to read {path}"))?;
+        let text = normalize_source(&text);
+        let (_, body) = split_front_matter(&text);
+        Ok((file, body.to_owned(), included_key(key, path)))
+    }
+}
+
+/// The path named by the directive on `line`, if it is one
+fn directive(line: &str) -> Option<&str> {
+    let inner = line.trim_end().strip_prefix("{{")?.strip_suffix("}}")?;
+    let path = inner.trim().strip_prefix("include")?;
+    (path.is_empty() || path.starts_with(char::is_whitespace)).then(|| path.trim())
+}
+
+/// The character and length of the code fence `line` opens, if it does
+fn opens(line: &str) -> Option<(char, usize)> {
+    let c = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    let info = &line[len..];
+    (len >= 3 && !(c == '`' && info.contains('`'))).then_some((c, len))
+}
+
+/// Whether `line` closes the code fence `open`
+fn closes(line: &str, (c, len): (char, usize)) -> bool {
+    let line = line.trim_end();
+    line.len() >= len && line.chars().all(|l| l == c)
+}
+
+/// The key that paths within the file `path`, included by the page at `key`, are
+/// relative to
+fn included_key(key: &str, path: &str) -> String {
+    let mut segments: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        key.split('/').collect()
+    };
+    segments.pop();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
@@ -13,86 +13,341 @@
 #![cfg(unix)]
 
 use std::{
-    collections::HashSet,
-    fs,
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::Infallible,
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
     io::IsTerminal,
     net::SocketAddr,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex, MutexGuard,
     },
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{ensure, Context, Ok as AnyOk};
+use access::{Verdict, Viewer};
+use anyhow::{bail, ensure, Context, Ok as AnyOk};
+use assets::{Mount, ASSET_PREFIX};
 use axum::{
-    extract::{Path as AxumPath, State, WebSocketUpgrade},
+    body::{Body, Bytes},
+    extract::connect_info::IntoMakeServiceWithConnectInfo,
+    extract::{
+        ws::{CloseFrame, Message, WebSocket},
+        ConnectInfo, Path as AxumPath, Query, Request, State, WebSocketUpgrade,
+    },
     http::{
-        header::{CONTENT_TYPE, LOCATION},
-        StatusCode,
+        header::{
+            CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LOCATION, VARY,
+            WWW_AUTHENTICATE,
+        },
+        HeaderMap, HeaderName, HeaderValue, StatusCode, Uri,
     },
-    response::{Html, IntoResponse},
-    routing::get,
-    Router,
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{get, post, MethodRouter},
+    Json, Router,
 };
+use book::{Book, SUMMARY_FILE};
+use changes::{ChangeEvent, ChangeLog, EventLog, EVENT_CAPACITY, RECENT_KEY};
 use clap::Parser;
+use clients::{ClientGuard, Clients};
 use dashmap::DashMap;
 use easy_sgr::{Color::*, Style::*};
-use pulldown_cmark::{html::write_html_fmt, Options};
+use extensions::Extensions;
+use external::ExternalLinks;
+use gitref::Revision;
+use glossary::Glossary;
+use history::Archive;
+use hooks::Hooks;
+use negotiate::Format;
+use pulldown_cmark_escape::{escape_href, escape_html};
+use render::{normalize_source, render_csv, render_page, render_text, FrontMatter, Targets};
+use scan::{Disk, Progress, ScanStatus, Walk};
+use serde::Serialize;
+use settings::{Fallback, Limits, Settings, Theme};
+use special::{Special, NOT_FOUND_KEY};
+use symlinks::Link;
+use tasks::{TaskEdit, TaskError};
+use tls::Tls;
 use tokio::{
     net::TcpListener,
-    sync::{oneshot, Notify},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc, Notify,
+    },
 };
 use tokio::{signal, task::JoinHandle};
-use walkdir::{DirEntry, WalkDir};
-use watchexec::{action::ActionHandler, error::CriticalError, Config, Watchexec};
+use tokio_stream::wrappers::ReceiverStream;
+use updates::{QueueStats, UpdateQueue};
+use walkdir::WalkDir;
+use warnings::{Category, Warnings};
+use watch::WatchState;
+use watchexec::{action::ActionHandler, Config, Watchexec};
 
+/// restricting who may see pages under a prefix
+pub mod access;
+/// serving files from disk
+pub mod assets;
+/// linking bare urls in text
+pub mod autolink;
+/// walking the parser's events block by block
+mod blocks;
+/// mdBook `SUMMARY.md` tables of contents
+pub mod book;
+/// recently changed pages
+pub mod changes;
 /// the cli
 pub mod cli;
+/// connected refresh sockets
+pub mod clients;
+/// compressing pages ahead of time
+pub mod compress;
+/// `::: name` fenced divs
+pub mod containers;
+/// the control socket `mdflc attach` connects to
+pub mod control;
+/// reading csv files into html tables
+pub mod csv;
+/// `Term` and `: definition` lists
+pub mod definitions;
+/// `:shortcode:` emoji
+pub mod emoji;
+/// which files are pages, and the keys they are served at
+pub mod extensions;
+/// checking links to other sites
+pub mod external;
+/// serving a git revision instead of the work tree
+pub mod gitref;
+/// terms explained wherever pages use them
+pub mod glossary;
+/// marking search terms on pages
+pub mod highlight;
+/// previous versions of pages
+pub mod history;
+/// hooks for programs embedding mdflc
+pub mod hooks;
+/// embedding markdown files within others
+pub mod includes;
+/// serving markdown source to clients that ask for it
+pub mod negotiate;
+/// caching pages for reading offline
+pub mod offline;
+/// markdown options
+pub mod options;
+/// the order pages are listed in
+pub mod ordering;
+/// console output paging
+pub mod pager;
+/// markdown rendering
+pub mod render;
+/// cleaning rendered html with `--sanitize`
+pub mod sanitize;
+/// the initial scan of base under a time budget
+pub mod scan;
+/// full text search of pages at `/api/search`
+pub mod search;
+/// resolved server settings
+pub mod settings;
+/// pages with special behavior
+pub mod special;
+/// following symlinks within base
+pub mod symlinks;
+/// editing task lists on disk with `--edit-tasks`
+pub mod tasks;
+/// timestamps for people and machines
+pub mod time;
+/// serving over https with `--tls-cert` and `--tls-key`
+pub mod tls;
+/// changes waiting to be rendered
+pub mod updates;
+/// collected warnings
+pub mod warnings;
+/// file watcher supervision
+pub mod watch;
 
 // TODO: Create own markdown parser
 // TODO: Add ability to add/remove/list paths
-// TODO: add glossery, etc.
 // TODO: create utility for making ext traits
 // TODO: create intermixed version of anyhow & thiserror
 // add seamless intermixing between the transparent and
 // opaque error types
-// TODO: user added custom css
 // TODO: create new spa-like loading system
 pub async fn run() -> anyhow::Result<()> {
     let args = cli::Args::parse();
+    if let Some(cli::Subcommand::Attach { port }) = args.command {
+        return tokio::task::spawn_blocking(move || control::attach(port)).await?;
+    }
+    Api::builder(args).run().await
+}
 
-    ensure!(
-        args.base.try_exists().unwrap_or(false),
-        "The given path \"{}\" does not exist",
-        args.base.display()
-    );
+/// Configures an [`Api`] beyond what the cli offers, for programs embedding mdflc
+#[derive(Debug)]
+pub struct ApiBuilder {
+    args: cli::Args,
+    hooks: Hooks,
+}
+
+impl ApiBuilder {
+    /// Rewrites every page's html after rendering, see [`Hooks::post_render`]
+    #[must_use]
+    pub fn post_render(
+        mut self,
+        hook: impl Fn(&str, String) -> anyhow::Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.post_render(Box::new(hook));
+        self
+    }
+
+    /// Renders fenced code blocks of `lang` with `handler`, see [`Hooks::fence_handler`]
+    #[must_use]
+    pub fn fence_handler(
+        mut self,
+        lang: &str,
+        handler: impl Fn(&str, &str) -> anyhow::Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.fence_handler(lang, Box::new(handler));
+        self
+    }
 
-    let port = args.addr.port();
-    let addr = args.addr;
-    let tcp_listener = TcpListener::bind(addr).await?;
+    pub fn build(self) -> anyhow::Result<Api> {
+        Api::with_hooks(&self.args, self.hooks)
+    }
+
+    /// Builds the [`Api`] and serves it until shut down, the way the mdflc binary does
+    pub async fn run(self) -> anyhow::Result<()> {
+        let args = &self.args;
+        ensure!(
+            args.base.try_exists().unwrap_or(false),
+            "The given path \"{}\" does not exist",
+            args.base.display()
+        );
+
+        // a broken certificate fails before anything is served
+        let tls = Tls::from_args(args)
+            .map(|tls| tls.server_config())
+            .transpose()?;
+        let port = args.addr.port();
+        let tcp_listener = TcpListener::bind(args.addr).await?;
+
+        let api = Arc::new(self.build()?);
+        serve(api, port, tcp_listener, tls).await
+    }
+}
 
-    let api = Arc::new(Api::new(addr, &args.index, &args.base)?);
+/// Serves `router` on `tcp_listener` until `shutdown` completes, over https if given a
+/// `tls` config
+async fn listen(
+    tcp_listener: TcpListener,
+    router: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    tls: Option<Arc<rustls::ServerConfig>>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let Some(tls) = tls else {
+        return axum::serve(tcp_listener, router)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .context("axum server error");
+    };
+    let handle = axum_server::Handle::new();
+    let closing = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        closing.graceful_shutdown(None);
+    });
+    let config = axum_server::tls_rustls::RustlsConfig::from_config(tls);
+    axum_server::from_tcp_rustls(tcp_listener.into_std()?, config)
+        .handle(handle)
+        .serve(router)
+        .await
+        .context("axum server error")
+}
 
+/// Serves `api` on `tcp_listener`, along with the watcher and the console
+///
+/// Connections are accepted over https when given a `tls` config.
+async fn serve(
+    api: Arc<Api>,
+    port: u16,
+    tcp_listener: TcpListener,
+    tls: Option<Arc<rustls::ServerConfig>>,
+) -> anyhow::Result<()> {
     cli::scroll();
     println!(
-        "{GreenFg}mdflc started with port {port} and path {}.{Reset}",
+        "{GreenFg}mdflc started at {BlueFg}{}{GreenFg} with path {}, scanning...{Reset}",
+        api.settings.url,
         api.base.unlock().display()
     );
+    if let Some(revision) = api.revision() {
+        println!(
+            "{GreenFg}serving {BlueFg}{}{GreenFg} at {BlueFg}{}{GreenFg} read-only, \
+            the work tree is ignored{Reset}",
+            revision.rev,
+            revision.short()
+        );
+    }
+    let scan_api = api.clone();
+    let scan_handle = tokio::task::spawn_blocking(move || {
+        let start = Instant::now();
+        scan_api.scan()?;
+        print_scan_summary(&scan_api, start.elapsed());
+        if scan_api.settings.check_external_links {
+            scan_api.external.request(false);
+        }
+        AnyOk(())
+    });
 
-    let wx = api.watcher()?;
-    let wx_handle = wx.main();
+    let links_api = api.clone();
+    let links_handle = tokio::spawn(async move { links_api.serve_link_checks().await });
+    let updates_handle = tokio::spawn(api.clone().process_updates());
 
-    let (console_stop, console_recv) = oneshot::channel();
+    let wx = Arc::new(api.watcher()?);
+    let policy = api.settings.watch;
+    let wx_handle = tokio::spawn(watch::supervise(
+        api.clone(),
+        (*wx.config).clone(),
+        wx.main(),
+        policy,
+    ));
+
+    let (console_stop, console_recv) = mpsc::channel(1);
     let stdin_api = api.clone();
+    // lets consoles be attached later, as when started without a terminal
+    let control = {
+        let port = tcp_listener.local_addr().map_or(port, |addr| addr.port());
+        match control::listen(&control::runtime_dir(), port) {
+            Ok((listener, files)) => {
+                println!(
+                    "{GreenFg}console socket at {BlueFg}{}{GreenFg}, \
+                    enter {BlueFg}mdflc attach{GreenFg} to use it{Reset}",
+                    files.socket.display()
+                );
+                let serve = control::serve(api.clone(), wx.clone(), listener, console_stop.clone());
+                Some((tokio::spawn(serve), files))
+            }
+            Err(e) => {
+                eprintln!("{YellowFg}no console socket: {e:#}{Reset}");
+                None
+            }
+        }
+    };
 
     let router = router(api.clone());
+    let closing_api = api.clone();
     let server_handle = tokio::task::spawn(async {
-        axum::serve(tcp_listener, router)
-            .with_graceful_shutdown(signal(console_recv, wx_handle))
-            .await
-            .context("axum server error")
+        let router = router.into_make_service_with_connect_info::<SocketAddr>();
+        let shutdown = async move {
+            signal(console_recv, wx_handle, scan_handle).await;
+            // event streams never finish by themselves
+            closing_api.close();
+        };
+        listen(tcp_listener, router, tls, shutdown).await
     });
 
     if std::io::stdin().is_terminal() {
@@ -101,307 +356,4606 @@ pub async fn run() -> anyhow::Result<()> {
             if let Err(e) = cli::read_console(&stdin_api, &wx) {
                 eprintln!("{YellowFg}interactive console shutdown: {Reset}{RedFg}\"{e}\"{Reset}");
             } else {
-                let _ = console_stop.send(());
+                let _ = console_stop.blocking_send(());
             }
         });
     }
 
     server_handle.await??;
     api.server_closed.notify_waiters();
+    if let Some((handle, files)) = control {
+        handle.abort();
+        drop(files);
+    }
+    links_handle.abort();
+    // changes still queued are abandoned, they are picked up by the next scan
+    updates_handle.abort();
 
     println!("{BlueFg}mdflc stopped{Reset}");
     AnyOk(())
 }
 
+/// Prints the outcome of the initial scan, see [`Api::scan`]
+fn print_scan_summary(api: &Api, elapsed: Duration) {
+    println!(
+        "{GreenFg}scanned {} pages in {elapsed:.2?}{Reset}",
+        api.md.len()
+    );
+    if !api.warnings.is_empty() {
+        println!(
+            "{YellowFg}{} warnings, enter {BlueFg}warnings{YellowFg} to show them{Reset}",
+            api.warnings.len()
+        );
+    }
+    for alias in &api.aliases {
+        println!(
+            "{GreenFg}serving {BlueFg}{}{key}{GreenFg} as {BlueFg}{}{alias}{Reset}",
+            api.settings.url,
+            api.settings.url,
+            key = alias.value(),
+            alias = alias.key(),
+        );
+    }
+}
+
 pub fn router(api: Arc<Api>) -> Router {
-    let index_css = get(([(CONTENT_TYPE, "text/css")], INDEX_CSS));
-    let index_js = get(([(CONTENT_TYPE, "text/javascript")], INDEX_JS));
-    let favicon = get(([(CONTENT_TYPE, "image/x-icon")], FAVICON));
-    Router::new()
+    let css = |css: &'static str| embedded("text/css; charset=utf-8", css.as_bytes());
+    let index_js = embedded("text/javascript; charset=utf-8", INDEX_JS.as_bytes());
+    let favicon = embedded("image/x-icon", FAVICON);
+    let mut router = Router::new();
+    for mount in &api.settings.mounts {
+        let dir = mount.dir.clone();
+        let handler = |AxumPath(path): AxumPath<String>, headers: HeaderMap| async move {
+            assets::serve_file(&dir, &path, &headers)
+        };
+        router = router.route(&format!("/{}/*path", mount.prefix), get(handler));
+    }
+    router
         .route("/", get(handle_index))
-        .route("/index.css", index_css)
+        .route("/index.css", css(INDEX_CSS))
+        .route("/theme-light.css", css(THEME_LIGHT_CSS))
+        .route("/theme-dark.css", css(THEME_DARK_CSS))
         .route("/index.js", index_js)
+        .route(
+            "/vendor/katex.min.js",
+            embedded("text/javascript; charset=utf-8", KATEX_JS.as_bytes()),
+        )
+        .route(
+            "/vendor/mermaid.min.js",
+            embedded("text/javascript; charset=utf-8", MERMAID_JS.as_bytes()),
+        )
         .route("/favicon.ico", favicon)
-        .route("/:md", get(handle_md))
+        .route(&format!("/{CUSTOM_CSS}"), get(handle_custom_css))
+        .route("/manifest.json", get(handle_manifest))
+        .route("/sw.js", get(handle_service_worker))
+        .route("/*md", get(handle_md))
+        .route("/diff/*md", get(handle_diff))
+        .route("/raw/*md", get(handle_raw))
+        .route("/compare", get(handle_compare))
+        .route("/api/hash/*md", get(handle_hash))
+        .route("/api/file/*md", get(handle_file))
+        .route("/api/inspect/*md", get(handle_inspect))
+        .route("/api/meta/*md", get(handle_meta))
+        .route("/api/nav/*md", get(handle_nav))
+        .route("/api/task/*md", post(handle_task))
+        .route("/api/pages", get(handle_pages))
+        .route("/api/search", get(handle_search))
+        .route("/api/warnings", get(handle_warnings))
+        .route("/api/clients", get(handle_clients))
+        .route("/api/config", get(handle_config))
+        .route("/api/ping", get(handle_ping))
+        .route("/healthz", get(handle_health))
         .route("/refresh-ws", get(handle_ws))
+        .route("/api/events", get(handle_events))
+        .route(&format!("/{ASSET_PREFIX}/*path"), get(handle_asset))
+        .fallback(handle_fallback)
+        .layer(middleware::from_fn_with_state(api.clone(), guard))
+        .layer(middleware::from_fn_with_state(api.clone(), authenticate))
+        .layer(middleware::from_fn(compress::responses))
         .with_state(api)
 }
 
-pub async fn handle_index(State(api): ApiState) -> impl IntoResponse {
-    (StatusCode::SEE_OTHER, [(LOCATION, &*api.index.unlock())]).into_response()
+/// Refuses requests without the `--auth` login, see [`Settings::auth`]
+///
+/// Health checks are let through, as monitors rarely have credentials.
+async fn authenticate(State(api): ApiState, request: Request, next: Next) -> Response {
+    let Some(login) = &api.settings.auth else {
+        return next.run(request).await;
+    };
+    if request.uri().path() == "/healthz" || login.admits(&Viewer::from_headers(request.headers()))
+    {
+        return next.run(request).await;
+    }
+    let challenge = [(WWW_AUTHENTICATE, r#"Basic realm="mdflc", charset="UTF-8""#)];
+    (
+        StatusCode::UNAUTHORIZED,
+        challenge,
+        "authentication required",
+    )
+        .into_response()
 }
 
-async fn handle_md(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
-    api.get_md(&url).map_or_else(
-        || (StatusCode::NOT_FOUND, Html(api.template.not_found.clone())),
-        |html| (StatusCode::OK, Html(html)),
-    )
+/// Routes followed by the url of a page, see [`guard`]
+const PAGE_ROUTES: &[&str] = &[
+    "assets/",
+    "diff/",
+    "raw/",
+    "api/hash/",
+    "api/file/",
+    "api/inspect/",
+    "api/meta/",
+    "api/nav/",
+    "api/task/",
+];
+
+/// Checks the page a request is for against [`Settings::access`], then handles it on
+/// behalf of its [`Viewer`]
+///
+/// Everything that lists or resolves keys consults [`Api::visible`] with that viewer, so
+/// protected pages are left out of listings rather than refused.
+async fn guard(State(api): ApiState, request: Request, next: Next) -> Response {
+    let viewer = Viewer::from_headers(request.headers());
+    let path = percent_encoding::percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let path = path.trim_start_matches('/');
+    let url = PAGE_ROUTES
+        .iter()
+        .find_map(|route| path.strip_prefix(route))
+        .unwrap_or(path);
+    let key = clean_url(url, &api.settings.extensions);
+    // aliases are as protected as the page they point to
+    let target = api.aliases.get(key).map(|r| r.value().clone());
+    let key = target
+        .as_deref()
+        .filter(|_| !api.md.contains_key(key))
+        .unwrap_or(key);
+    match api.settings.access.check(key, &viewer) {
+        Verdict::Allowed => access::scope(viewer, next.run(request)).await,
+        Verdict::Unauthorized(rule) => {
+            let challenge = format!("Basic realm=\"/{}\", charset=\"UTF-8\"", rule.prefix);
+            let headers = [(WWW_AUTHENTICATE, challenge)];
+            (StatusCode::UNAUTHORIZED, headers, "authentication required").into_response()
+        }
+        Verdict::Denied => (StatusCode::FORBIDDEN, "this page is not shared").into_response(),
+    }
 }
 
-pub async fn handle_ws(ws: WebSocketUpgrade, State(api): ApiState) -> impl IntoResponse {
-    ws.on_upgrade(|mut socket| async move {
-        println!("{BlueFg}refresh socket opened{Reset}");
+/// Serves a file within base, such as an image a page shows, see [`assets::asset_url`]
+///
+/// Hidden files, and files within hidden directories such as `.git`, aren't served.
+/// When base is a single file, only the files beside it are, not its whole directory tree.
+async fn handle_asset(
+    AxumPath(path): AxumPath<String>,
+    headers: HeaderMap,
+    State(api): ApiState,
+) -> Response {
+    let segments: Vec<&str> = path.trim_start_matches('/').split(['/', '\\']).collect();
+    if segments.iter().any(|segment| segment.starts_with('.')) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let base = api.base.unlock().clone();
+    if base.is_file() && segments.len() > 1 {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let dir = if base.is_file() {
+        base.parent().unwrap_or(&base)
+    } else {
+        &base
+    };
+    assets::serve_file(dir, &path, &headers)
+}
 
-        api.sockets.fetch_add(1, Ordering::Relaxed);
-        #[allow(clippy::redundant_pub_crate)]
-        let _ = tokio::select! {
-            biased;
-            () = api.server_closed.notified() => socket.close().await,
-            () = async { while socket.recv().await.is_some() {} } => Ok(()),
-            () = api.update.notified() => socket.send("refresh".into()).await,
-        };
-        api.sockets.fetch_sub(1, Ordering::Relaxed);
+/// Where the stylesheet given with `--css` is served, see [`Settings::css`]
+pub const CUSTOM_CSS: &str = "custom.css";
 
-        println!("{BlueFg}refresh socket closed{Reset}");
-    })
+/// Serves the stylesheet given with `--css`
+///
+/// A stylesheet that can't be read is served empty, so pages keep their built-in styles.
+async fn handle_custom_css(State(api): ApiState) -> Response {
+    let Some(path) = &api.settings.css else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let css = fs::read_to_string(path).unwrap_or_default();
+    let headers = [
+        (CONTENT_TYPE, "text/css; charset=utf-8"),
+        (CACHE_CONTROL, "no-cache"),
+    ];
+    (headers, css).into_response()
 }
 
-/// a collection of paths to parsed markdown files
-pub type MdFiles = Arc<DashMap<String, String>>;
+/// Lists what the service worker caches, only with `--offline`, see [`Api::manifest`]
+pub async fn handle_manifest(State(api): ApiState) -> Response {
+    if !api.settings.offline {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    ([(CACHE_CONTROL, "no-cache")], Json(api.manifest())).into_response()
+}
 
-const INDEX_HTML: &str = include_str!("../client/index.html");
-const INDEX_CSS: &str = include_str!("../client/index.css");
-const INDEX_JS: &str = include_str!("../client/index.js");
-const FAVICON: &[u8] = include_bytes!("../client/favicon.ico");
+/// Lets the service worker control every page, not just those under its own url
+const SERVICE_WORKER_ALLOWED: HeaderName = HeaderName::from_static("service-worker-allowed");
 
-/// The finishing of this future indicates a shutdown signal
-///
-/// # Panics
+/// Serves the service worker that caches pages for offline reading, only with `--offline`
+pub async fn handle_service_worker(State(api): ApiState) -> Response {
+    if !api.settings.offline {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let headers = [
+        (CONTENT_TYPE, "text/javascript; charset=utf-8"),
+        (CACHE_CONTROL, "no-cache"),
+        (SERVICE_WORKER_ALLOWED, "/"),
+    ];
+    (headers, offline::SERVICE_WORKER).into_response()
+}
+
+/// Serves a file built into the binary, tagged with the hash of its contents
+fn embedded(content_type: &'static str, body: &'static [u8]) -> MethodRouter<Arc<Api>> {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    get(move |headers: HeaderMap| {
+        let etag = etag.clone();
+        async move {
+            if is_cached(&headers, &etag) {
+                return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+            }
+            let headers = [
+                (CONTENT_TYPE, content_type.to_owned()),
+                (CACHE_CONTROL, "no-cache".to_owned()),
+                (ETAG, etag),
+            ];
+            (headers, body).into_response()
+        }
+    })
+}
+
+/// Whether `If-None-Match` lists `etag`, so the client already holds the response
 ///
-/// Panics if either the `ctrl_c` signal or `sigterm`
-/// signal for unix fails to be installed
-#[allow(clippy::cognitive_complexity)]
-pub async fn signal(
-    console_recv: oneshot::Receiver<()>,
-    wx_handle: JoinHandle<Result<(), CriticalError>>,
-) {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("failed to install Ctrl+C handler");
-    };
+/// Tags are compared weakly, ignoring a `W/` prefix on either side.
+pub(crate) fn is_cached(headers: &HeaderMap, etag: &str) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    let etag = weak(etag);
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || weak(tag) == etag)
+}
 
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("failed to install signal handler")
-            .recv()
-            .await;
-    };
+pub async fn handle_index(State(api): ApiState) -> impl IntoResponse {
+    (StatusCode::SEE_OTHER, [(LOCATION, &*api.index.unlock())]).into_response()
+}
 
-    #[allow(clippy::redundant_pub_crate)]
-    let () = tokio::select! {
-        () = ctrl_c => {
-            println!("{BlueFg}Ctrl-C received, app shutdown commencing{Reset}");
-        },
-        () = terminate => {
-            println!("{BlueFg}SIGTERM received, app shutdown commencing{Reset}");
-        },
-        e = console_recv => {
-            e.context("stdin error").unwrap();
-            println!("{BlueFg}Console exit recieved, app shutdown commencing{Reset}");
-        },
-        e = wx_handle => {
-            e.context("Handle Error").unwrap().context("Watchexec Error").unwrap();
-            println!("{BlueFg}Watchexec handle stopped{Reset}");
+async fn handle_md(
+    url: AxumPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(api): ApiState,
+) -> Response {
+    // pages are matched by every url, but never serve for the api's
+    if is_api(&url) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Some(overrides) = query.get("opt").filter(|_| api.settings.debug_render) {
+        return handle_debug_render(&url, overrides, &api);
+    }
+    let mut response = match negotiate::format(query.get("format").map(String::as_str), &headers) {
+        Ok(Format::Html) => {
+            let highlight = query.get("highlight").map(String::as_str);
+            html_response(&url, highlight, &headers, api)
+        }
+        Ok(Format::Markdown) => markdown_response(&url, &headers, &api),
+        Err(format) => {
+            let body = format!("unknown format \"{format}\", expected md or html");
+            return (StatusCode::BAD_REQUEST, body).into_response();
         }
     };
+    // html and markdown share the url
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    response
 }
 
-type ApiState = State<Arc<Api>>;
+/// Sends the rendered page at `url`, see [`negotiate::Format::Html`]
+///
+/// Pages asked to highlight a term are never compressed ahead of time, and streamed
+/// pages are sent without highlighting. Both go without an `ETag`, see [`Api::etag`].
+fn html_response(
+    url: &str,
+    highlight: Option<&str>,
+    headers: &HeaderMap,
+    api: Arc<Api>,
+) -> Response {
+    if let Some(key) = api.streamed_key(url) {
+        return stream_md(api, key);
+    }
+    if let Some(term) = highlight {
+        return api.get_md_highlighted(url, term).map_or_else(
+            || api.fallback(url),
+            |html| (StatusCode::OK, Html(html)).into_response(),
+        );
+    }
+    // tagged before rendering, so a page changed meanwhile is never sent as unmodified
+    let etag = api.etag(url);
+    if let Some(etag) = etag.clone().filter(|etag| is_cached(headers, etag)) {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+    let mut response = page_response(url, headers, &api);
+    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        let headers = response.headers_mut();
+        headers.insert(ETAG, etag);
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    }
+    response
+}
 
-#[derive(Debug)]
-pub struct Api {
-    /// server urls
-    url: String,
-    #[allow(dead_code)]
-    addr: SocketAddr,
-    /// parsed md files
-    md: MdFiles,
-    /// the served route and the default
-    base: Mutex<PathBuf>,
-    index: Mutex<String>,
-    /// html templating
-    template: Template,
-    /// The number of opened websockets
-    sockets: AtomicUsize,
-    /// The number of opened websockets
-    update: Notify,
-    server_closed: Notify,
+/// Sends the page at `url`, compressed if the client accepts it
+fn page_response(url: &str, headers: &HeaderMap, api: &Api) -> Response {
+    if let Some(bytes) = api
+        .get_md_gzip(url)
+        .filter(|_| compress::accepts_gzip(headers))
+    {
+        let headers = [
+            (CONTENT_TYPE, "text/html; charset=utf-8"),
+            (CONTENT_ENCODING, "gzip"),
+            (VARY, "accept-encoding"),
+        ];
+        return (headers, bytes).into_response();
+    }
+    api.get_md(url)
+        .or_else(|| (clean_url(url, &api.settings.extensions) == RECENT_KEY).then(|| api.recent()))
+        .or_else(|| api.directory(url))
+        .map_or_else(
+            || api.fallback(url),
+            |html| (StatusCode::OK, Html(html)).into_response(),
+        )
 }
 
-impl Api {
-    pub fn new(addr: SocketAddr, index: &Path, base: &Path) -> anyhow::Result<Self> {
-        let base = base.canonicalize().context("invalid base path")?;
-        let index = index
-            .canonicalize()
-            .context("invalid index path")?
-            .strip_prefix(&base)
-            .context("index must be a path within base")?
-            .to_str()
-            .context("only utf8 paths allowed")?
-            .to_owned();
+/// Sends the markdown source of a page whatever the request accepts, see
+/// [`markdown_response`]
+async fn handle_raw(url: AxumPath<String>, headers: HeaderMap, State(api): ApiState) -> Response {
+    markdown_response(&url, &headers, &api)
+}
 
-        Ok(Self {
-            url: format!("http://localhost:{}/", addr.port()),
-            addr,
-            md: initialize_md(&base)?,
-            base: base.into(),
-            index: index.into(),
-            sockets: AtomicUsize::default(),
-            template: Template::default(),
-            update: Notify::default(),
-            server_closed: Notify::default(),
-        })
+/// Sends the markdown source of the page at `url`, see [`negotiate::Format::Markdown`]
+///
+/// The source is read from disk for every request, so it is what the file holds right
+/// now even when the watcher hasn't caught up.
+///
+/// Its `ETag` is prefixed with `md-`, so it never matches the html's.
+fn markdown_response(url: &str, headers: &HeaderMap, api: &Api) -> Response {
+    let text = match api.get_source(url) {
+        Some(Ok(text)) => text,
+        Some(Err(e)) => {
+            let body = format!("unable to read the source of {url}: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, body).into_response();
+        }
+        None => return (StatusCode::NOT_FOUND, format!("no page at {url}")).into_response(),
+    };
+    let etag = format!("\"md-{:016x}\"", hash_source(&text));
+    if is_cached(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
     }
+    let headers = [
+        (CONTENT_TYPE, "text/markdown; charset=utf-8".to_owned()),
+        (CACHE_CONTROL, "no-cache".to_owned()),
+        (ETAG, etag),
+    ];
+    (headers, text).into_response()
+}
 
-    #[must_use]
-    pub fn get_md(&self, url: &str) -> Option<String> {
-        self.md
-            .get(clean_url(url))
-            .map(|r| self.template.html(r.value()))
+/// Answers urls no route matches, see [`Api::fallback`]
+async fn handle_fallback(uri: Uri, State(api): ApiState) -> Response {
+    if is_api(uri.path()) {
+        return StatusCode::NOT_FOUND.into_response();
     }
+    api.fallback(uri.path())
+}
 
-    /// Handles file updates made by [`watchexec`]
-    pub fn file_update(&self, h: &ActionHandler) -> anyhow::Result<()> {
-        // don't read files twice
-        let mut files = HashSet::new();
+/// Whether `url` is under `/api/`, where unknown urls get a plain 404
+fn is_api(url: &str) -> bool {
+    let url = url.trim_start_matches('/');
+    url == "api" || url.starts_with("api/")
+}
 
-        for (path, _) in h.paths() {
-            if !path.is_file() {
-                continue;
-            }
+/// Renders a page once with adjusted markdown options, bypassing [`MdFiles`]
+fn handle_debug_render(url: &str, overrides: &str, api: &Api) -> Response {
+    let config = api.render_config();
+    let options = match options::apply_overrides(config.options, overrides) {
+        Ok(options) => render::Config { options, ..config },
+        Err(name) => {
+            let names = options::option_names();
+            let body = format!("unknown markdown option \"{name}\", expected one of: {names}");
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+    };
+    let Some((key, page)) = api
+        .resolve_key(clean_url(url, &api.settings.extensions))
+        .and_then(|key| Some((key.clone(), api.md.get(&key)?.clone())))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
 
-            if !files.insert(path) {
-                continue;
-            }
+    let renderer = Renderer {
+        config: options,
+        ..api.renderer()
+    };
+    let mut preview = Page::default();
+    let result = api
+        .read_source(&page.path)
+        .and_then(|text| write_md(&mut preview, &key, &page.path, &text, &renderer));
+    if let Err(e) = result {
+        let body = format!("unable to render {}: {e}", page.path.display());
+        return (StatusCode::INTERNAL_SERVER_ERROR, body).into_response();
+    }
+    if preview.streamed {
+        let body = "the rendered html is over the size limit, debug renders aren't streamed";
+        return (StatusCode::PAYLOAD_TOO_LARGE, body).into_response();
+    }
 
-            let Some(key) = path
-                .strip_prefix(self.base.unlock().as_path())
-                .ok()
-                .and_then(Path::to_str)
-                .and_then(|s| s.strip_suffix(".md"))
-            else {
-                continue;
-            };
+    let html = api.template().html(&Slots {
+        md: &preview.html,
+        title: &api.title(&page.title),
+        page: &key,
+        generation: api.generation(),
+        description: &page.description,
+        image: page.image(),
+        banner: &preview.banner(),
+        toc: &preview.toc,
+        class: &preview.class(),
+        words: preview.words,
+        modified: api.page_modified(&preview),
+    });
+    let header = HeaderValue::from_str(overrides).unwrap_or(HeaderValue::from_static("invalid"));
+    ([(DEBUG_OPTIONS_HEADER, header)], Html(html)).into_response()
+}
 
-            write_md_from_file(&mut self.md.entry(key.to_owned()).or_default(), path)?;
-        }
+/// How much html [`stream_md`] buffers before sending it
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
 
-        // send update only once
-        if !files.is_empty() && self.sockets.load(Ordering::Relaxed) != 0 {
-            self.update.notify_waiters();
+/// Renders a page that is too large to cache straight into the response
+///
+/// The body has no known length, so it is sent chunked as the render goes.
+fn stream_md(api: Arc<Api>, key: String) -> Response {
+    let (tx, rx) = mpsc::channel::<Result<String, std::io::Error>>(4);
+    tokio::task::spawn_blocking(move || {
+        let Some((path, title, toc, class, words, modified)) = api.md.get(&key).map(|p| {
+            let (path, title, toc) = (p.path.clone(), p.title.clone(), p.toc.clone());
+            (path, title, toc, p.class(), p.words, api.page_modified(&p))
+        }) else {
+            return;
+        };
+        let title = api.title(&title);
+        let (head, tail) = api.template().split(&Slots {
+            title: &title,
+            page: &key,
+            toc: &toc,
+            class: &class,
+            words,
+            modified,
+            generation: api.generation(),
+            ..Slots::default()
+        });
+        if tx.blocking_send(Ok(head)).is_err() {
+            return;
+        }
+        let mut writer = ChunkWriter {
+            tx: &tx,
+            buf: String::with_capacity(STREAM_CHUNK_SIZE),
+        };
+        let Renderer {
+            config,
+            targets,
+            hooks,
+            ..
+        } = api.renderer();
+        let result = api
+            .read_source(&path)
+            .and_then(|text| {
+                let text = normalize_source(&text);
+                render::stream_page(&mut writer, &text, &config, &key, targets, hooks)
+            })
+            .and_then(|_| writer.flush());
+        match result {
+            Ok(()) => {
+                let _ = tx.blocking_send(Ok(tail));
+            }
+            // the client went away
+            Err(_) if tx.is_closed() => (),
+            Err(e) => {
+                // the status is already sent, cut the response short instead
+                api.warnings.push(Category::Render, &key, e.to_string());
+                let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+            }
         }
+    });
+    let body = Body::from_stream(ReceiverStream::new(rx));
+    ([(CONTENT_TYPE, "text/html; charset=utf-8")], body).into_response()
+}
 
+/// Sends what is written in chunks of [`STREAM_CHUNK_SIZE`], failing once the receiver is gone
+struct ChunkWriter<'a> {
+    tx: &'a mpsc::Sender<Result<String, std::io::Error>>,
+    buf: String,
+}
+
+impl ChunkWriter<'_> {
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let chunk = std::mem::replace(&mut self.buf, String::with_capacity(STREAM_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| anyhow::anyhow!("the client disconnected"))
+    }
+}
+
+impl std::fmt::Write for ChunkWriter<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.buf.push_str(s);
+        if self.buf.len() >= STREAM_CHUNK_SIZE {
+            self.flush().map_err(|_| std::fmt::Error)?;
+        }
         Ok(())
     }
+}
 
-    fn watcher(self: &Arc<Self>) -> anyhow::Result<Watchexec> {
-        let wx_api = self.clone();
-        let config = Config::default();
+/// Set on responses rendered with [`handle_debug_render`]
+pub const DEBUG_OPTIONS_HEADER: &str = "x-mdflc-debug-options";
 
-        config.throttle(Duration::from_millis(100));
-        config.pathset([self.base.unlock().clone()]);
-        config.on_action(move |h| {
-            if let Err(e) = wx_api.file_update(&h) {
-                eprintln!("{RedFg}{e}{Reset}");
-            }
-            h
-        });
+/// Shows how a page changed in its last update
+pub async fn handle_diff(url: AxumPath<String>, State(api): ApiState) -> Response {
+    api.diff(&url).map_or_else(
+        || (StatusCode::NOT_FOUND, Html(api.not_found())).into_response(),
+        |html| Html(html).into_response(),
+    )
+}
 
-        Ok(Watchexec::with_config(config)?)
-    }
+/// Shows the pages `left` and `right` side by side, see [`Api::compare`]
+async fn handle_compare(
+    Query(query): Query<HashMap<String, String>>,
+    State(api): ApiState,
+) -> Response {
+    let (Some(left), Some(right)) = (query.get("left"), query.get("right")) else {
+        let body = "expected the pages to compare, as in /compare?left=old&right=new";
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    };
+    Html(api.compare(left, right)).into_response()
 }
 
-#[must_use]
-pub fn clean_url(url: &str) -> &str {
-    let url = url.strip_prefix('/').unwrap_or(url);
-    let url = url.strip_suffix(".md").unwrap_or(url);
-    url
+/// The response of [`handle_hash`]
+#[derive(Debug, Serialize)]
+pub struct HashProbe {
+    /// The hash the page's `ETag` is made from, see [`Page::etag`]
+    pub hash: String,
+    pub generation: u64,
+    /// The `ETag` the page is sent with, `None` for streamed pages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
 }
 
-pub fn initialize_md(base: &Path) -> anyhow::Result<MdFiles> {
-    let md = MdFiles::default();
+/// Returns the content hash of a page, without rendering it
+pub async fn handle_hash(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
+    let generation = api.generation();
+    let Some((hash, streamed)) = api.get_etag_hash(&url) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let probe = HashProbe {
+        hash: format!("{hash:016x}"),
+        generation,
+        etag: (!streamed).then(|| page_etag(hash, generation)),
+    };
+    ([(CACHE_CONTROL, "no-cache")], Json(probe)).into_response()
+}
 
-    if base.is_file() {
-        let mut value = String::new();
-        write_md_from_file(&mut value, base)?;
-        md.insert("index".into(), value);
-        return Ok(md);
-    }
+pub async fn handle_file(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
+    api.get_info(&url).map_or_else(
+        || StatusCode::NOT_FOUND.into_response(),
+        |info| Json(info).into_response(),
+    )
+}
 
-    let filter = |file: Result<DirEntry, _>| {
-        let file = file.ok().filter(|f| f.file_type().is_file())?;
-        file.path()
-            .strip_prefix(base)
-            .ok()?
-            .to_str()?
-            .strip_suffix(".md")
-            .map(String::from)
-            .map(|s| (s, file))
-    };
+pub async fn handle_inspect(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
+    api.inspect(&url).map_or_else(
+        || StatusCode::NOT_FOUND.into_response(),
+        |inspection| ([(CACHE_CONTROL, "no-cache")], Json(inspection)).into_response(),
+    )
+}
+
+pub async fn handle_meta(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
+    api.meta(&url).map_or_else(
+        || StatusCode::NOT_FOUND.into_response(),
+        |meta| ([(CACHE_CONTROL, "no-cache")], Json(meta)).into_response(),
+    )
+}
 
-    for (key, file) in WalkDir::new(base).into_iter().filter_map(filter) {
-        let mut value = String::new();
-        write_md_from_file(&mut value, file.path())?;
-        md.insert(key, value);
+pub async fn handle_nav(url: AxumPath<String>, State(api): ApiState) -> impl IntoResponse {
+    api.nav(&url).map_or_else(
+        || {
+            let error = serde_json::json!({ "error": "page not found" });
+            (StatusCode::NOT_FOUND, Json(error)).into_response()
+        },
+        |nav| ([(CACHE_CONTROL, "no-cache")], Json(nav)).into_response(),
+    )
+}
+
+/// Checks or unchecks a task list item in the source of a page, only with `--edit-tasks`
+///
+/// Clients are refreshed once the watcher picks up the change, as with any other edit.
+pub async fn handle_task(
+    url: AxumPath<String>,
+    State(api): ApiState,
+    Json(edit): Json<TaskEdit>,
+) -> Response {
+    if !api.render_config().edit_tasks {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match api.edit_task(&url, edit) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (e.status(), e.to_string()).into_response(),
     }
+}
 
-    Ok(md)
+/// The response of [`handle_health`]
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub serving: bool,
+    /// false while the initial scan is still running
+    pub ready: bool,
+    pub watch: WatchState,
+    /// Changes waiting to be rendered
+    pub queue: QueueStats,
+    /// The commit being served in git ref mode
+    pub revision: Option<Revision>,
+    /// How far the initial scan got
+    pub scan: ScanStatus,
 }
 
-pub fn write_md_from_file(out: &mut String, path: &Path) -> anyhow::Result<()> {
-    let text = fs::read_to_string(path)?;
-    let parser_iter = pulldown_cmark::Parser::new_ext(&text, Options::all());
-    let additional = out.capacity().saturating_sub(text.len());
+pub async fn handle_health(State(api): ApiState) -> impl IntoResponse {
+    Json(Health {
+        serving: true,
+        ready: api.is_ready(),
+        watch: api.watch_state(),
+        queue: api.updates.stats(),
+        revision: api.revision(),
+        scan: api.scan_status(),
+    })
+}
 
-    out.reserve(additional);
-    out.clear();
-    write_html_fmt(out, parser_iter)?;
-    Ok(())
+pub async fn handle_config(State(api): ApiState) -> impl IntoResponse {
+    Json(api.settings())
 }
 
-#[derive(Debug, Clone)]
-pub struct Template {
-    before: &'static str,
-    after: &'static str,
-    not_found: String,
+/// Lets clients check whether the server is reachable, without touching the websocket
+pub async fn handle_ping() -> impl IntoResponse {
+    ([(CACHE_CONTROL, "no-cache")], "pong")
 }
 
-impl Default for Template {
-    fn default() -> Self {
-        let replace = "{{md}}";
+/// Lists every page, or those whose key starts with `?prefix=`, see [`Api::pages`]
+async fn handle_pages(
+    Query(query): Query<HashMap<String, String>>,
+    State(api): ApiState,
+) -> impl IntoResponse {
+    let prefix = query.get("prefix").map_or("", String::as_str);
+    ([(CACHE_CONTROL, "no-cache")], Json(api.pages(prefix)))
+}
 
-        let Some(start) = INDEX_HTML.find(replace) else {
-            unreachable!("the index.html included with the binary is invalid");
-        };
-        let Some(before) = INDEX_HTML.get(..start) else {
-            unreachable!("the index.html included with the binary is invalid");
-        };
-        let Some(after) = INDEX_HTML.get((start + replace.len())..) else {
-            unreachable!("the index.html included with the binary is invalid");
-        };
+/// Searches the text of every page for `?q=`, see [`Api::search`]
+async fn handle_search(
+    Query(query): Query<HashMap<String, String>>,
+    State(api): ApiState,
+) -> impl IntoResponse {
+    let hits = api.search(query.get("q").map_or("", String::as_str));
+    ([(CACHE_CONTROL, "no-cache")], Json(hits))
+}
+
+pub async fn handle_warnings(State(api): ApiState) -> impl IntoResponse {
+    let mut warnings = api.warnings.list();
+    warnings.retain(|warning| api.visible(&warning.key));
+    Json(warnings)
+}
 
-        let not_found = format!("{before}<h1>Error 404: Page not found</h1>{after}");
+pub async fn handle_clients(State(api): ApiState) -> impl IntoResponse {
+    let mut clients = api.clients.list();
+    clients.retain(|client| client.page.as_deref().is_none_or(|page| api.visible(page)));
+    ([(CACHE_CONTROL, "no-cache")], Json(clients))
+}
 
-        Self {
-            before,
-            after,
-            not_found,
-        }
-    }
+/// Sent when closing a socket whose client never subscribed
+const CLOSE_POLICY: u16 = 1008;
+/// Sent when closing a socket that reached its maximum lifetime
+const CLOSE_GOING_AWAY: u16 = 1001;
+
+pub async fn handle_ws(
+    ws: WebSocketUpgrade,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(api): ApiState,
+) -> impl IntoResponse {
+    // the socket outlives the request, subscriptions are checked on behalf of its viewer
+    let viewer = Viewer::from_headers(&headers);
+    ws.on_upgrade(move |socket| {
+        access::scope(viewer, async move {
+            println!("{BlueFg}refresh socket opened{Reset}");
+
+            // unregisters even if the connection dies mid-send
+            let client = api.clients.register(addr.map(|ConnectInfo(addr)| addr));
+            let mut socket = ClientSocket { socket, client };
+            // subscribed before the generation is sent, so no change falls in between
+            let mut changes = api.subscribe();
+            // lets the client catch up on updates missed while disconnected
+            let generation = refresh_message("generation", api.generation());
+            if socket.send_text(generation).await.is_ok() {
+                let _ = serve_socket(&api, &mut socket, &mut changes).await;
+            }
+
+            println!("{BlueFg}refresh socket closed{Reset}");
+        })
+    })
 }
 
-impl Template {
-    #[must_use]
-    pub fn html(&self, s: &str) -> String {
-        let capacity = self.before.len() + s.len() + self.after.len();
-        let mut html = String::with_capacity(capacity);
-        html.push_str(self.before);
-        html.push_str(s);
-        html.push_str(self.after);
-        html
+/// Waits on a refresh socket until there is something to tell its client
+///
+/// Changes only to stylesheets are passed on as they come, see
+/// [`ChangeEvent::styles_only`], any other change ends with a refresh.
+///
+/// Clients subscribe by sending any message, those that don't within
+/// [`SocketPolicy::grace`](settings::SocketPolicy::grace) are closed,
+/// as are sockets open for longer than the maximum lifetime.
+async fn serve_socket(
+    api: &Api,
+    socket: &mut ClientSocket<'_>,
+    changes: &mut broadcast::Receiver<ChangeEvent>,
+) -> Result<(), axum::Error> {
+    let policy = api.settings.sockets;
+    let idle = tokio::time::sleep(policy.grace());
+    let lifetime = async {
+        match policy.max_lifetime() {
+            Some(max) => tokio::time::sleep(max).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(idle, lifetime);
+    let mut subscribed = false;
+    // the last generation the client reloaded its styles for
+    let mut styled = 0;
+
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let close = tokio::select! {
+            biased;
+            () = api.server_closed.notified() => {
+                // lets the client tell a stopped server apart from a network problem
+                let farewell = refresh_message("shutdown", api.generation());
+                let _ = socket.send_text(farewell).await;
+                return socket.socket.send(Message::Close(None)).await;
+            }
+            message = socket.socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return Ok(()),
+                Some(Ok(message)) => match subscribed_page(&message) {
+                    Some(page) if !api.visible(clean_url(&page, &api.settings.extensions)) => {
+                        (CLOSE_POLICY, "the page is not shared")
+                    }
+                    page => {
+                        subscribed = true;
+                        socket.client.subscribe(page);
+                        continue;
+                    }
+                },
+            },
+            change = changes.recv() => match change {
+                // the events of a generation share it, tell the client once
+                Ok(event) if event.styles_only => {
+                    if event.generation > styled {
+                        styled = event.generation;
+                        let css = refresh_message("css", event.generation);
+                        socket.send_text(css).await?;
+                    }
+                    continue;
+                }
+                // lagging behind still means something changed
+                Ok(_) | Err(RecvError::Lagged(_)) => {
+                    let refresh = refresh_message("refresh", api.generation());
+                    return socket.send_text(refresh).await;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            () = &mut idle, if !subscribed => (CLOSE_POLICY, "no subscription"),
+            () = &mut lifetime => (CLOSE_GOING_AWAY, "maximum lifetime reached"),
+        };
+        let (code, reason) = close;
+        let frame = CloseFrame {
+            code,
+            reason: reason.into(),
+        };
+        return socket.socket.send(Message::Close(Some(frame))).await;
+    }
+}
+
+/// A refresh websocket along with its entry in [`Api::clients`]
+struct ClientSocket<'a> {
+    socket: WebSocket,
+    client: ClientGuard<'a>,
+}
+
+impl ClientSocket<'_> {
+    /// Sends a text message, counting it towards [`clients::Client::sent`]
+    async fn send_text(&mut self, text: String) -> Result<(), axum::Error> {
+        self.socket.send(text.into()).await?;
+        self.client.sent();
+        Ok(())
+    }
+}
+
+/// The page named by a subscribe message, such as the ones `index.js` sends
+fn subscribed_page(message: &Message) -> Option<String> {
+    let Message::Text(text) = message else {
+        return None;
+    };
+    let message: serde_json::Value = serde_json::from_str(text).ok()?;
+    message["page"].as_str().map(str::to_owned)
+}
+
+/// Streams changes to the served content as server-sent events, for editors and other tools
+///
+/// Each `change` event holds a [`ChangeEvent`] as json, with its generation as the event's
+/// id. `?prefix=docs/` only sends changes to keys starting with `docs/`, and `?since=` first
+/// replays the changes made after a generation, as does the `Last-Event-ID` header browsers
+/// send when reconnecting. A `lagged` event means some changes were missed and everything
+/// should be reloaded.
+///
+/// Streams are listed with the refresh websockets, share their maximum lifetime, and end
+/// with a `shutdown` event. Changes to pages the viewer may not see are left out.
+async fn handle_events(
+    Query(query): Query<HashMap<String, String>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(api): ApiState,
+) -> Response {
+    let last_id = headers.get("last-event-id").and_then(|v| v.to_str().ok());
+    let since = match query.get("since").map(String::as_str).or(last_id) {
+        None => None,
+        Some(since) => {
+            let Ok(since) = since.parse() else {
+                let body = "expected a generation, as in /api/events?since=42";
+                return (StatusCode::BAD_REQUEST, body).into_response();
+            };
+            Some(since)
+        }
+    };
+    // subscribed before responding, so no change made once the stream is open is missed
+    let subscription = since.map_or_else(
+        || (Some(Vec::new()), api.subscribe()),
+        |since| api.subscribe_since(since),
+    );
+    let stream = EventStream {
+        addr: addr.map(|ConnectInfo(addr)| addr),
+        prefix: query.get("prefix").cloned().unwrap_or_default(),
+    };
+    let viewer = Viewer::from_headers(&headers);
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(access::scope(
+        viewer,
+        stream_events(api, stream, subscription, tx),
+    ));
+    Sse::new(ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// What an event stream was opened with, see [`handle_events`]
+struct EventStream {
+    addr: Option<SocketAddr>,
+    /// Only changes to keys starting with this are sent
+    prefix: String,
+}
+
+/// Sends changes to an event stream until its client leaves, see [`handle_events`]
+///
+/// `missed` are replayed first, `None` if some are missing, see [`Api::subscribe_since`].
+async fn stream_events(
+    api: Arc<Api>,
+    stream: EventStream,
+    (missed, mut changes): (Option<Vec<ChangeEvent>>, broadcast::Receiver<ChangeEvent>),
+    tx: mpsc::Sender<Result<Event, Infallible>>,
+) {
+    let client = api.clients.register(stream.addr);
+    let wanted =
+        |event: &ChangeEvent| event.key.starts_with(&stream.prefix) && api.visible(&event.key);
+    let replay = missed.map_or_else(
+        || vec![notice_event("lagged", api.generation())],
+        |missed| {
+            missed
+                .iter()
+                .filter(|e| wanted(e))
+                .map(change_event)
+                .collect()
+        },
+    );
+    for event in replay {
+        if tx.send(Ok(event)).await.is_err() {
+            return;
+        }
+        client.sent();
+    }
+
+    let mut closing = api.closing.subscribe();
+    let closing = async move {
+        let _ = closing.wait_for(|closing| *closing).await;
+    };
+    let lifetime = async {
+        match api.settings.sockets.max_lifetime() {
+            Some(max) => tokio::time::sleep(max).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(closing, lifetime);
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let event = tokio::select! {
+            biased;
+            () = &mut closing => {
+                let _ = tx.send(Ok(notice_event("shutdown", api.generation()))).await;
+                return;
+            }
+            () = tx.closed() => return,
+            () = &mut lifetime => return,
+            change = changes.recv() => match change {
+                Ok(event) if wanted(&event) => change_event(&event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => notice_event("lagged", api.generation()),
+                Err(RecvError::Closed) => return,
+            },
+        };
+        if tx.send(Ok(event)).await.is_err() {
+            return;
+        }
+        client.sent();
+    }
+}
+
+/// A `change` event of [`handle_events`]
+fn change_event(event: &ChangeEvent) -> Event {
+    // serializing plain data can't fail
+    let data = serde_json::to_string(event).unwrap_or_default();
+    Event::default()
+        .event("change")
+        .id(event.generation.to_string())
+        .data(data)
+}
+
+/// An event of [`handle_events`] about the stream itself, shaped like a refresh message
+fn notice_event(kind: &str, generation: u64) -> Event {
+    Event::default()
+        .event(kind)
+        .data(refresh_message(kind, generation))
+}
+
+/// The first generation of a server
+///
+/// Starting from the current time keeps generations increasing across restarts,
+/// so clients that reconnect to a restarted server know to reload.
+#[must_use]
+pub fn start_generation() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// Formats a message sent over the refresh websocket
+#[must_use]
+pub fn refresh_message(kind: &str, generation: u64) -> String {
+    format!(r#"{{"type":"{kind}","generation":{generation}}}"#)
+}
+
+/// a collection of paths to parsed markdown files
+pub type MdFiles = Arc<DashMap<String, Page>>;
+/// a collection of alternate keys, each pointing to a key within [`MdFiles`]
+pub type Aliases = DashMap<String, String>;
+/// a [`ChangeEvent`] waiting for its generation
+type PendingChange = (String, ChangeKind, Option<u64>);
+
+const INDEX_HTML: &str = include_str!("../client/index.html");
+const INDEX_CSS: &str = include_str!("../client/index.css");
+const THEME_LIGHT_CSS: &str = include_str!("../client/theme-light.css");
+const THEME_DARK_CSS: &str = include_str!("../client/theme-dark.css");
+const INDEX_JS: &str = include_str!("../client/index.js");
+const FAVICON: &[u8] = include_bytes!("../client/favicon.ico");
+/// Loaded by `index.js` for pages with math, so it renders without the network
+const KATEX_JS: &str = include_str!("../client/vendor/katex.min.js");
+/// Loaded by `index.js` for pages with mermaid fences
+const MERMAID_JS: &str = include_str!("../client/vendor/mermaid.min.js");
+/// Shown in place of pages that don't exist, unless there is a custom 404 page
+const NOT_FOUND_HTML: &str = "<h1>Error 404: Page not found</h1>";
+/// The page of [`Api::compare`], which is not a key in [`MdFiles`]
+const COMPARE_PAGE: &str = "compare";
+/// Driven by `index.js`, styled by `index.css`
+const STATUS_HTML: &str =
+    r#"<div id="status" class="status" data-state="connecting" role="status"></div>"#;
+
+/// The finishing of this future indicates a shutdown signal
+///
+/// # Panics
+///
+/// Panics if either the `ctrl_c` signal or `sigterm`
+/// signal for unix fails to be installed
+#[allow(clippy::cognitive_complexity)]
+pub async fn signal(
+    mut console_recv: mpsc::Receiver<()>,
+    wx_handle: JoinHandle<anyhow::Result<()>>,
+    scan_handle: JoinHandle<anyhow::Result<()>>,
+) {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    // only a failed scan stops the server
+    let scan_failed = async {
+        match scan_handle.await.context("Handle Error").and_then(|e| e) {
+            Ok(()) => std::future::pending().await,
+            Err(e) => eprintln!("{RedFg}scan failed: {e:#}{Reset}"),
+        }
+    };
+
+    #[allow(clippy::redundant_pub_crate)]
+    let () = tokio::select! {
+        () = scan_failed => {},
+        () = ctrl_c => {
+            println!("{BlueFg}Ctrl-C received, app shutdown commencing{Reset}");
+        },
+        () = terminate => {
+            println!("{BlueFg}SIGTERM received, app shutdown commencing{Reset}");
+        },
+        Some(()) = console_recv.recv() => {
+            println!("{BlueFg}Console exit recieved, app shutdown commencing{Reset}");
+        },
+        e = wx_handle => {
+            if let Err(e) = e.context("Handle Error").and_then(|e| e) {
+                eprintln!("{RedFg}{e:#}{Reset}");
+            }
+            println!("{BlueFg}Watchexec handle stopped{Reset}");
+        }
+    };
+}
+
+type ApiState = State<Arc<Api>>;
+
+#[derive(Debug)]
+pub struct Api {
+    /// resolved settings, `base`, `index` and `limits` live in their own fields
+    settings: Settings,
+    limits: Mutex<Limits>,
+    /// followed symlinks, empty unless following them
+    links: Mutex<Vec<Link>>,
+    /// parsed md files
+    md: MdFiles,
+    /// alternate names for keys in `md`
+    aliases: Aliases,
+    /// the served route and the default
+    base: Mutex<PathBuf>,
+    index: Mutex<String>,
+    /// the summary ordering pages, see [`Api::load_book`]
+    book: Mutex<Option<Arc<Book>>>,
+    /// see [`Settings::glossary`]
+    glossary: Mutex<Option<Arc<Glossary>>>,
+    /// html templating, see [`Settings::template`]
+    template: Mutex<Arc<Template>>,
+    /// the commit pages are read from, see [`Settings::git_ref`]
+    revision: Mutex<Option<Revision>>,
+    /// the health of the file watcher
+    watch: Mutex<WatchState>,
+    /// whether the initial scan finished
+    ready: AtomicBool,
+    /// how far the initial scan got
+    progress: Progress,
+    /// pages found once the scan budget ran out, rendered once requested, see [`Api::scan_within`]
+    lazy: DashMap<String, PathBuf>,
+    /// files that fail to render, see [`Api::failed_files`]
+    failed: DashMap<String, FailedFile>,
+    /// whether pages render as strict commonmark, see [`Api::set_commonmark`]
+    commonmark: AtomicBool,
+    /// whether every newline breaks lines, see [`Api::set_hard_breaks`]
+    hard_breaks: AtomicBool,
+    /// processing added by embedders
+    hooks: Hooks,
+    /// problems worth showing to the user
+    pub warnings: Warnings,
+    /// recently changed pages
+    pub changes: ChangeLog,
+    /// the previous source of changed pages
+    archive: Archive,
+    /// The open refresh websockets and event streams
+    pub clients: Clients,
+    /// The results of checking links to other sites, see [`Api::check_external_links`]
+    pub external: ExternalLinks,
+    /// Changes reported by the watcher, see [`Api::process_updates`]
+    pub updates: UpdateQueue,
+    /// Monotonically increasing, bumped once per update
+    generation: AtomicU64,
+    /// See [`Api::render_generation`]
+    render_generation: AtomicU64,
+    /// Every change, see [`Api::subscribe`]
+    events: broadcast::Sender<ChangeEvent>,
+    /// The last changes sent, see [`Api::subscribe_since`]
+    event_log: Mutex<EventLog>,
+    /// Set once the server starts shutting down, see [`Api::close`]
+    closing: tokio::sync::watch::Sender<bool>,
+    server_closed: Notify,
+}
+
+impl Api {
+    pub fn new(args: &cli::Args) -> anyhow::Result<Self> {
+        Self::with_hooks(args, Hooks::default())
+    }
+
+    /// Starts configuring an [`Api`], see [`ApiBuilder`]
+    #[must_use]
+    pub fn builder(args: cli::Args) -> ApiBuilder {
+        ApiBuilder {
+            args,
+            hooks: Hooks::default(),
+        }
+    }
+
+    fn with_hooks(args: &cli::Args, hooks: Hooks) -> anyhow::Result<Self> {
+        let settings = Settings::new(args)?;
+        let base = settings.base.clone();
+        let warnings = Warnings::default();
+        let md = MdFiles::default();
+        let links = if settings.follow_symlinks {
+            symlinks::find(&base)
+        } else {
+            Vec::new()
+        };
+        let aliases = Aliases::default();
+
+        for mount in &settings.mounts {
+            check_mount(mount, &base, &settings.extensions)?;
+        }
+
+        let glossary = settings
+            .glossary
+            .as_deref()
+            .map(|path| read_glossary(path, settings.glossary_case_sensitive))
+            .transpose()
+            .context("unable to read the glossary")?;
+
+        let template = read_template(&settings, &warnings)?;
+
+        // resolved now so bad refs fail at startup, rather than in the scan
+        let revision = settings
+            .git_ref
+            .as_deref()
+            .map(|rev| Revision::resolve(&base, rev))
+            .transpose()?;
+
+        let index = settings.index.clone();
+        if base.is_file() {
+            let key = clean_url(&index, &settings.extensions);
+            // urls are typed in lowercase, so `CHANGELOG.md` is served at `/changelog` too
+            for alias in ["index".to_owned(), key.to_lowercase()] {
+                if alias != key {
+                    insert_alias(&md, &aliases, &warnings, &alias, key);
+                }
+            }
+        }
+
+        let generation = start_generation();
+        Ok(Self {
+            md,
+            aliases,
+            base: base.into(),
+            index: index.into(),
+            book: None.into(),
+            glossary: glossary.map(Arc::new).into(),
+            clients: Clients::default(),
+            external: ExternalLinks::default(),
+            updates: UpdateQueue::default(),
+            generation: generation.into(),
+            render_generation: AtomicU64::default(),
+            template: Mutex::new(Arc::new(template)),
+            revision: revision.into(),
+            limits: settings.limits.into(),
+            links: links.into(),
+            commonmark: settings.strict_commonmark.into(),
+            hard_breaks: settings.render.hard_breaks.into(),
+            settings,
+            watch: WatchState::Watching.into(),
+            ready: false.into(),
+            progress: Progress::default(),
+            lazy: DashMap::default(),
+            failed: DashMap::default(),
+            hooks,
+            warnings,
+            changes: ChangeLog::default(),
+            archive: Archive::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            event_log: EventLog::new(generation).into(),
+            closing: tokio::sync::watch::channel(false).0,
+            server_closed: Notify::default(),
+        })
+    }
+
+    /// The settings currently in effect
+    #[must_use]
+    pub fn settings(&self) -> Settings {
+        Settings {
+            base: self.base.unlock().clone(),
+            index: self.index.unlock().clone(),
+            limits: self.limits(),
+            render: self.render_config(),
+            strict_commonmark: self.is_commonmark(),
+            theme: self.theme(),
+            ..self.settings.clone()
+        }
+    }
+
+    /// How pages are rendered right now, see [`Api::set_commonmark`] and
+    /// [`Api::set_hard_breaks`]
+    #[must_use]
+    pub fn render_config(&self) -> render::Config {
+        let config = render::Config {
+            hard_breaks: self.hard_breaks.load(Ordering::Acquire),
+            ..self.settings.render
+        };
+        if self.is_commonmark() {
+            config.strict_commonmark()
+        } else {
+            config
+        }
+    }
+
+    /// Whether pages render as strict commonmark, see [`Settings::strict_commonmark`]
+    #[must_use]
+    pub fn is_commonmark(&self) -> bool {
+        self.commonmark.load(Ordering::Acquire)
+    }
+
+    /// Switches between rendering strict commonmark and rendering with extensions,
+    /// rerendering every page
+    pub fn set_commonmark(&self, commonmark: bool) -> anyhow::Result<()> {
+        self.commonmark.store(commonmark, Ordering::Release);
+        self.rescan()
+    }
+
+    /// Whether every newline in a paragraph breaks the line, see [`render::Config::hard_breaks`]
+    #[must_use]
+    pub fn is_hard_breaks(&self) -> bool {
+        self.hard_breaks.load(Ordering::Acquire)
+    }
+
+    /// Switches between breaking lines at every newline and only where markdown says to,
+    /// rerendering every page
+    pub fn set_hard_breaks(&self, hard_breaks: bool) -> anyhow::Result<()> {
+        self.hard_breaks.store(hard_breaks, Ordering::Release);
+        self.rescan()
+    }
+
+    /// The colors of pages, see [`Settings::theme`]
+    #[must_use]
+    pub fn theme(&self) -> Theme {
+        self.template().theme()
+    }
+
+    /// Switches the colors of pages, refreshing every open one
+    pub fn set_theme(&self, theme: Theme) {
+        let mut template = self.template.unlock();
+        if template.theme() == theme {
+            return;
+        }
+        *template = Arc::new(template.as_ref().clone().with_theme(theme));
+        drop(template);
+        self.publish(self.every_page(&[]));
+    }
+
+    #[must_use]
+    pub fn get_md(&self, url: &str) -> Option<String> {
+        self.templated(url, None)
+    }
+
+    /// The page at `url` with `term` marked wherever it occurs, see [`highlight::apply`]
+    #[must_use]
+    pub fn get_md_highlighted(&self, url: &str, term: &str) -> Option<String> {
+        self.templated(url, Some(term))
+    }
+
+    /// The weak `ETag` of the page at `url` as [`Api::get_md`] sends it, `None` for
+    /// streamed pages
+    ///
+    /// Every page embeds the generation, so the tag changes with it as well as with
+    /// the page's own [`Page::etag`].
+    #[must_use]
+    pub fn etag(&self, url: &str) -> Option<String> {
+        let generation = self.generation();
+        let (hash, streamed) = self.get_etag_hash(url)?;
+        (!streamed).then(|| page_etag(hash, generation))
+    }
+
+    /// The [`Page::etag`] of the page at `url`, and whether it is streamed
+    #[must_use]
+    pub fn get_etag_hash(&self, url: &str) -> Option<(u64, bool)> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.refresh_stale(&key);
+        self.md.get(&key).map(|page| (page.etag, page.streamed))
+    }
+
+    /// The page at `url` within the template, with `highlight` marked if given
+    fn templated(&self, url: &str, highlight: Option<&str>) -> Option<String> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.refresh_stale(&key);
+        let generation = self.generation();
+        self.md.get(&key).map(|r| {
+            let md = highlight.map_or(Cow::Borrowed(r.html.as_str()), |term| {
+                highlight::apply(&r.html, term)
+            });
+            self.template().html(&Slots {
+                md: &md,
+                title: &self.title(&r.title),
+                page: &key,
+                generation,
+                description: &r.description,
+                image: r.image(),
+                banner: &r.banner(),
+                toc: &r.toc,
+                class: &r.class(),
+                words: r.words,
+                modified: self.page_modified(&r),
+            })
+        })
+    }
+
+    /// The markdown source of the page at `url`, read again rather than kept around
+    #[must_use]
+    pub fn get_source(&self, url: &str) -> Option<anyhow::Result<String>> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        let path = self.md.get(&key)?.path.clone();
+        Some(self.read_source(&path))
+    }
+
+    /// The page at `url` compressed with gzip, `None` if its html is under [`compress::MIN_SIZE`]
+    ///
+    /// The compressed page is kept with the page until it is rerendered or the generation changes.
+    #[must_use]
+    pub fn get_md_gzip(&self, url: &str) -> Option<Bytes> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.refresh_stale(&key);
+        let generation = self.generation();
+        let cache = {
+            let page = self
+                .md
+                .get(&key)
+                .filter(|p| p.html.len() >= compress::MIN_SIZE)?;
+            if let Some(gzip) = page.gzip.as_ref().filter(|g| g.generation == generation) {
+                return Some(gzip.bytes.clone());
+            }
+            page.cache
+        };
+        let bytes = Bytes::from(compress::gzip(self.get_md(&key)?.as_bytes()));
+        // the page may have been replaced while compressing
+        if let Some(mut page) = self.md.get_mut(&key).filter(|p| p.cache == cache) {
+            page.gzip = Some(compress::Gzipped {
+                generation,
+                bytes: bytes.clone(),
+            });
+        }
+        Some(bytes)
+    }
+
+    /// The cache key a page rendered from `text` right now would have
+    #[must_use]
+    pub fn cache_key(&self, text: &str) -> CacheKey {
+        CacheKey {
+            source: hash_source(text),
+            options: self.render_config().fingerprint(),
+            generation: self.render_generation(),
+        }
+    }
+
+    /// Whether a page was rendered with outdated settings
+    ///
+    /// Changes to the source are picked up by the watcher, so they aren't checked here.
+    fn is_stale(&self, cache: &CacheKey) -> bool {
+        cache.options != self.render_config().fingerprint()
+            || cache.generation != self.render_generation()
+    }
+
+    /// Rerenders the page at `key` if it is stale, keeping the old html on failure
+    fn refresh_stale(&self, key: &str) {
+        let Some(path) = self
+            .md
+            .get(key)
+            .filter(|page| self.is_stale(&page.cache))
+            .map(|page| page.path.clone())
+        else {
+            return;
+        };
+        self.rerender(key, &path);
+    }
+
+    /// How pages are rendered right now
+    fn renderer(&self) -> Renderer<'_> {
+        Renderer {
+            config: self.render_config(),
+            generation: self.render_generation(),
+            targets: self,
+            hooks: &self.hooks,
+            strict: self.settings.strict,
+        }
+    }
+
+    /// Rerenders the page at `key` from `path`, keeping the old html on failure
+    fn rerender(&self, key: &str, path: &Path) {
+        let renderer = self.renderer();
+        if let Some(reason) = self.skip_reason(path) {
+            let page = Page::skipped(path, &reason, &renderer.config, renderer.generation);
+            self.md.insert(key.to_owned(), page);
+            return;
+        }
+        let mut page = Page::default();
+        let result = self
+            .read_source(path)
+            .and_then(|text| write_md(&mut page, key, path, &text, &renderer));
+        match result {
+            Ok(()) => {
+                self.warnings.resolve(Category::Render, key);
+                self.failed.remove(key);
+                page.report(key, &self.warnings);
+                self.md.insert(key.to_owned(), page);
+            }
+            Err(e) => self.render_failed(key, path, &e),
+        }
+    }
+
+    /// Rechecks the links of every page linking into `target`, see [`Page::anchor_links`]
+    ///
+    /// Returns the keys of the rerendered pages.
+    fn revalidate_referrers(&self, target: &str) -> Vec<String> {
+        let referrers: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| page.anchor_links.contains(target))
+            .map(|page| (page.key().clone(), page.path.clone()))
+            .collect();
+        referrers
+            .into_iter()
+            .map(|(key, path)| {
+                self.rerender(&key, &path);
+                key
+            })
+            .collect()
+    }
+
+    /// Rechecks the links of every page linking into another, once all of them are rendered
+    fn revalidate_links(&self) {
+        let referrers: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| !page.anchor_links.is_empty())
+            .map(|page| (page.key().clone(), page.path.clone()))
+            .collect();
+        for (key, path) in referrers {
+            self.rerender(&key, &path);
+        }
+    }
+
+    /// Every link to another site, deduplicated and sorted
+    #[must_use]
+    pub fn external_urls(&self) -> Vec<String> {
+        let urls: BTreeSet<_> = self
+            .md
+            .iter()
+            .flat_map(|page| page.links.clone())
+            .filter(|url| external::is_external(url))
+            .collect();
+        urls.into_iter().collect()
+    }
+
+    /// Checks every link to another site, rerendering the pages of links whose result changed
+    ///
+    /// Links checked recently are skipped unless `force` is set, see [`external::RESULT_TTL`].
+    /// Returns the links whose result changed.
+    pub async fn check_external_links(&self, force: bool) -> Vec<String> {
+        let changed = self.external.check(self.external_urls(), force).await;
+        if changed.is_empty() {
+            return changed;
+        }
+        let pages: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| page.links.iter().any(|url| changed.contains(url)))
+            .map(|page| (page.key().clone(), page.path.clone()))
+            .collect();
+        let mut updates = Vec::new();
+        for (key, path) in pages {
+            self.rerender(&key, &path);
+            if let Some(page) = self.md.get(&key) {
+                page.report(&key, &self.warnings);
+                updates.push((key, ChangeKind::Modified, Some(page.cache.source)));
+            }
+        }
+        self.publish(updates);
+        changed
+    }
+
+    /// Runs [`Api::check_external_links`] whenever one is requested, see [`ExternalLinks::request`]
+    ///
+    /// Never finishes, dropping the future cancels the running check.
+    pub async fn serve_link_checks(&self) {
+        loop {
+            let force = self.external.requested().await;
+            let changed = self.check_external_links(force).await;
+            let results = self.external.results();
+            let dead = results.iter().filter(|r| r.dead.is_some()).count();
+            if dead > 0 {
+                println!(
+                    "{YellowFg}{dead} of {} external links are unreachable, \
+                    enter {BlueFg}links{YellowFg} to show them{Reset}",
+                    results.len(),
+                );
+            } else if !changed.is_empty() || force {
+                println!(
+                    "{GreenFg}all {} external links are reachable{Reset}",
+                    results.len()
+                );
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> Limits {
+        *self.limits.unlock()
+    }
+
+    /// Why the file at `path` is over the limits, if it is
+    fn skip_reason(&self, path: &Path) -> Option<String> {
+        let base = self.base.unlock().clone();
+        self.revision().map_or_else(
+            || self.limits().check(&base, path),
+            |revision| {
+                let size = revision.size(path).unwrap_or(0);
+                self.limits().check_len(&base, path, size)
+            },
+        )
+    }
+
+    /// The commit being served, `None` unless serving a git ref, see [`Settings::git_ref`]
+    #[must_use]
+    pub fn revision(&self) -> Option<Revision> {
+        self.revision.unlock().clone()
+    }
+
+    /// Applies `edit` to the file of the page at `url`, see [`tasks::apply`]
+    ///
+    /// The page is rendered again like after any other change to its file.
+    pub fn edit_task(&self, url: &str, edit: TaskEdit) -> Result<(), TaskError> {
+        if self.revision().is_some() {
+            return Err(TaskError::ReadOnly);
+        }
+        let key = self
+            .resolve_key(clean_url(url, &self.settings.extensions))
+            .ok_or(TaskError::NoPage)?;
+        let path = self.md.get(&key).ok_or(TaskError::NoPage)?.path.clone();
+        // followed symlinks may lead anywhere
+        let path = path.canonicalize()?;
+        if !path.starts_with(&*self.base.unlock()) {
+            return Err(TaskError::OutsideBase(path));
+        }
+        let text = fs::read_to_string(&path)?;
+        let text = tasks::apply(&text, self.render_config().options, edit)
+            .ok_or(TaskError::NoTask(edit.index))?;
+        tasks::write_atomic(&path, &text)?;
+        Ok(())
+    }
+
+    /// Reads the markdown at `path`, from the served commit when serving a git ref
+    fn read_source(&self, path: &Path) -> anyhow::Result<String> {
+        match self.revision() {
+            Some(revision) => revision.read(path),
+            None => Ok(fs::read_to_string(path)?),
+        }
+    }
+
+    /// Resolves the served ref again, see [`Settings::git_ref`]
+    fn resolve_revision(&self) -> anyhow::Result<Option<Revision>> {
+        let Some(rev) = &self.settings.git_ref else {
+            return Ok(None);
+        };
+        let base = self.base.unlock().clone();
+        let revision = Revision::resolve(&base, rev)?;
+        *self.revision.unlock() = Some(revision.clone());
+        Ok(Some(revision))
+    }
+
+    /// Rescans if the served ref moved to another commit, see [`Settings::git_ref`]
+    ///
+    /// Returns whether it moved.
+    pub fn follow_git_ref(&self) -> anyhow::Result<bool> {
+        let (Some(rev), Some(current)) = (&self.settings.git_ref, self.revision()) else {
+            return Ok(false);
+        };
+        let base = self.base.unlock().clone();
+        let latest = Revision::resolve(&base, rev)?;
+        if latest.commit == current.commit {
+            return Ok(false);
+        }
+        println!(
+            "{GreenFg}{rev} moved to {BlueFg}{}{GreenFg}, rescanning{Reset}",
+            latest.short()
+        );
+        self.rescan()?;
+        Ok(true)
+    }
+
+    /// Changes the limits, files are only checked against them once changed or rescanned
+    pub fn set_limits(&self, limits: Limits) {
+        *self.limits.unlock() = limits;
+    }
+
+    /// Bumped on every runtime change that affects rendered output
+    #[must_use]
+    pub fn render_generation(&self) -> u64 {
+        self.render_generation.load(Ordering::Acquire)
+    }
+
+    /// Marks every page as stale, each is rerendered once next requested
+    pub fn invalidate_renders(&self) {
+        self.render_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Formats a page title using the title template
+    #[must_use]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    pub fn title(&self, title: &str) -> String {
+        self.settings.title_template.replace("{title}", title)
+    }
+
+    /// The current update generation, bumped by every [`Api::file_update`]
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// The number of open refresh websockets and event streams
+    #[must_use]
+    pub fn sockets(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Resolves `url` to the key of a page too large to cache, see [`Page::streamed`]
+    #[must_use]
+    pub fn streamed_key(&self, url: &str) -> Option<String> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.refresh_stale(&key);
+        let streamed = self.md.get(&key).is_some_and(|page| page.streamed);
+        streamed.then_some(key)
+    }
+
+    #[must_use]
+    pub fn get_hash(&self, url: &str) -> Option<u64> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.md.get(&key).map(|r| r.cache.source)
+    }
+
+    #[must_use]
+    pub fn get_info(&self, url: &str) -> Option<PageInfo> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        self.md.get(&key).map(|r| PageInfo {
+            title: r.title.clone(),
+            path: r.path.clone(),
+            hash: format!("{:016x}", r.cache.source),
+            render_micros: r.render_time.as_micros(),
+            render_kind: r.render_kind,
+            key,
+        })
+    }
+
+    /// Describes the page at `url` for other tools, see [`Meta`]
+    #[must_use]
+    pub fn meta(&self, url: &str) -> Option<Meta> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        let page = self.md.get(&key)?;
+        let (size, modified) = self.source_stats(&page.path);
+        Some(Meta {
+            title: page.title.clone(),
+            path: page.path.clone(),
+            size,
+            modified,
+            headings: page.outline.clone(),
+            words: page.words,
+            reading_minutes: render::reading_minutes(page.words),
+            front_matter: page.front_matter.clone(),
+            key,
+        })
+    }
+
+    /// When the source of `page` was last modified, see [`Page::modified`]
+    ///
+    /// Pages served from a git revision have no modification time, as the work tree says
+    /// nothing about the revision.
+    fn page_modified(&self, page: &Page) -> Option<time::Timestamp> {
+        page.modified.filter(|_| self.revision().is_none())
+    }
+
+    /// The size and modification time of the source file at `path`, `None` when it
+    /// can't be read
+    ///
+    /// Pages served from a git revision have no modification time.
+    fn source_stats(&self, path: &Path) -> (Option<u64>, Option<time::Timestamp>) {
+        // the work tree says nothing about a git revision
+        let revision = self.revision();
+        let meta = fs::metadata(path).ok().filter(|_| revision.is_none());
+        let size = meta
+            .as_ref()
+            .map(fs::Metadata::len)
+            .or_else(|| revision?.size(path).ok());
+        let modified = meta
+            .and_then(|m| m.modified().ok())
+            .map(time::Timestamp::from_system);
+        (size, modified)
+    }
+
+    /// Gathers everything known about the page at `url`, see [`Inspection`]
+    #[must_use]
+    pub fn inspect(&self, url: &str) -> Option<Inspection> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        let referrers = self
+            .md
+            .iter()
+            .filter(|page| page.anchor_links.contains(&key))
+            .map(|page| page.key().clone());
+        let mut referrers: Vec<_> = referrers.collect();
+        referrers.sort_unstable();
+        let mut aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .filter(|alias| *alias.value() == key)
+            .map(|alias| alias.key().clone())
+            .collect();
+        aliases.sort_unstable();
+        let warnings = self.warnings.list().into_iter();
+        let warnings = warnings.filter(|w| w.key == key).collect();
+
+        let page = self.md.get(&key)?;
+        let (size, modified) = self.source_stats(&page.path);
+        let links = page.links.iter().map(|url| InspectedLink {
+            broken: page.broken_anchors.contains(url),
+            url: url.clone(),
+        });
+        Some(Inspection {
+            title: page.title.clone(),
+            path: page.path.clone(),
+            size,
+            modified,
+            hash: format!("{:016x}", page.cache.source),
+            html_size: page.html.len(),
+            render_micros: page.render_time.as_micros(),
+            render_kind: page.render_kind,
+            render_generation: page.cache.generation,
+            stale: self.is_stale(&page.cache),
+            headings: page.headings,
+            links: links.collect(),
+            referrers,
+            aliases,
+            features: page.features.clone(),
+            front_matter: page.front_matter.clone(),
+            error: page.error.clone(),
+            streamed: page.streamed,
+            warnings,
+            key,
+        })
+    }
+
+    /// Aggregates the render durations of every page
+    #[must_use]
+    pub fn render_stats(&self, slowest: usize) -> RenderStats {
+        let stale = self.md.iter().filter(|r| self.is_stale(&r.cache)).count();
+        let compressed: Vec<_> = self
+            .md
+            .iter()
+            .filter_map(|r| r.gzip.as_ref().map(|g| g.bytes.len()))
+            .collect();
+        let mut times: Vec<_> = self
+            .md
+            .iter()
+            .map(|r| (r.key().clone(), r.render_time, r.render_kind))
+            .collect();
+        let Some(last) = times.len().checked_sub(1) else {
+            return RenderStats::default();
+        };
+        times.sort_unstable_by_key(|t| Reverse(t.1));
+        let percentile = |p: usize| times[last - last * p / 100].1;
+
+        RenderStats {
+            pages: times.len(),
+            stale,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: times[0].1,
+            slowest: times.into_iter().take(slowest).collect(),
+            compressed: compressed.len(),
+            compressed_bytes: compressed.iter().sum(),
+        }
+    }
+
+    /// Resolves a key or alias to a key within [`MdFiles`]
+    ///
+    /// Real keys always take precedence over aliases, and aliases over the index page of
+    /// a directory, see [`DIRECTORY_INDEXES`].
+    #[must_use]
+    pub fn resolve_key(&self, key: &str) -> Option<String> {
+        if !self.is_ready() {
+            self.load_missing(key);
+        }
+        let key = if self.md.contains_key(key) {
+            key.to_owned()
+        } else if let Some(alias) = self
+            .aliases
+            .get(key)
+            .map(|r| r.value().clone())
+            .filter(|k| self.md.contains_key(k))
+        {
+            alias
+        } else {
+            self.directory_index(key)?
+        };
+        self.visible(&key).then_some(key)
+    }
+
+    /// The key of the page served for the directory `dir`, see [`DIRECTORY_INDEXES`]
+    fn directory_index(&self, dir: &str) -> Option<String> {
+        if dir.is_empty() {
+            return None;
+        }
+        DIRECTORY_INDEXES.iter().find_map(|name| {
+            let key = format!("{dir}/{name}");
+            if !self.is_ready() {
+                self.load_missing(&key);
+            }
+            self.md.contains_key(&key).then_some(key)
+        })
+    }
+
+    /// Whether the request being handled may see the page at `key`, see [`access::Access`]
+    ///
+    /// Pages that aren't visible are left out of everything that lists or resolves keys.
+    #[must_use]
+    pub fn visible(&self, key: &str) -> bool {
+        self.settings.access.permits(key)
+    }
+
+    /// Renders the file at `key` ahead of the initial scan, if it exists
+    ///
+    /// Only the key the scan would give the file is loaded, so urls spelling a path
+    /// differently, such as `docs//setup`, never add a second page.
+    fn load_missing(&self, key: &str) {
+        if self.render_lazy(key) {
+            return;
+        }
+        // keys come from urls, they must stay within base
+        let contained = Path::new(key)
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+        if self.md.contains_key(key) || !contained || self.settings.git_ref.is_some() {
+            return;
+        }
+        let path = {
+            let base = self.base.unlock();
+            let extensions = &self.settings.extensions;
+            let Some(path) = extensions
+                .paths(&base, key)
+                .find(|path| path.is_file())
+                .filter(|_| !base.is_file())
+                .map(|path| path.components().collect::<PathBuf>())
+            else {
+                return;
+            };
+            if extensions.key(&base, &path) != Some(key) {
+                return;
+            }
+            path
+        };
+        let settings = self.settings();
+        match load_page(&settings, key, &path, &self.renderer(), &self.warnings) {
+            Ok(page) => {
+                self.failed.remove(key);
+                self.md.entry(key.to_owned()).or_insert(page);
+            }
+            Err(e) => self.render_failed(key, &path, &e),
+        }
+    }
+
+    /// Renders every file in base, serving each page as soon as it is rendered
+    ///
+    /// Until this finishes, pages are rendered on demand.
+    /// With a scan budget, see [`Settings::scan_budget`], base is walked by [`Api::scan_within`].
+    pub fn scan(&self) -> anyhow::Result<()> {
+        let revision = self.resolve_revision()?;
+        self.load_book();
+        let settings = self.settings();
+        if let Some(budget) = settings.scan_budget {
+            if revision.is_none() && !settings.base.is_file() {
+                let disk = Disk {
+                    follow_symlinks: settings.follow_symlinks,
+                };
+                return self.scan_within(&disk, budget);
+            }
+        }
+        self.progress.start(false);
+        let failed = initialize_md(
+            &settings,
+            self.render_generation(),
+            &self.hooks,
+            &self.warnings,
+            self,
+            revision.as_ref(),
+            &self.md,
+        )?;
+        for file in failed {
+            self.failed.insert(file.key.clone(), file);
+        }
+        self.progress.walked(Some(self.md.len()));
+        self.finish_scan();
+        Ok(())
+    }
+
+    /// Scans base like [`Api::scan`], but only renders pages until `budget` runs out
+    ///
+    /// The index is rendered first, then base is walked in the order of [`scan::prioritized`].
+    /// Pages found once the budget ran out are listed right away, but only rendered once
+    /// requested or once the walk is done.
+    pub fn scan_within(&self, walk: &dyn Walk, budget: Duration) -> anyhow::Result<()> {
+        let start = Instant::now();
+        self.load_book();
+        let settings = self.settings();
+        self.progress.start(true);
+        let index = clean_url(&settings.index, &settings.extensions).to_owned();
+        self.load_missing(&index);
+        if self.md.contains_key(&index) {
+            self.progress.found();
+            self.progress.rendered();
+        }
+
+        let renderer = self.renderer();
+        let mut spent = false;
+        scan::prioritized(walk, &settings.base, &self.warnings, |path| {
+            let Some(key) = settings.extensions.key(&settings.base, path) else {
+                return;
+            };
+            if left_out(&settings.extensions, path, key, &self.warnings) {
+                return;
+            }
+            // the index, or a page a change rendered first
+            if self.md.contains_key(key) {
+                return;
+            }
+            self.progress.found();
+            if start.elapsed() < budget {
+                match load_page(&settings, key, path, &renderer, &self.warnings) {
+                    Ok(page) => {
+                        self.md.entry(key.to_owned()).or_insert(page);
+                        self.progress.rendered();
+                    }
+                    Err(e) => self.render_failed(key, path, &e),
+                }
+                return;
+            }
+            if !spent {
+                spent = true;
+                println!(
+                    "{YellowFg}scan budget of {budget:?} spent, \
+                    the remaining pages are rendered once requested{Reset}"
+                );
+            }
+            self.lazy.insert(key.to_owned(), path.to_owned());
+            self.md
+                .entry(key.to_owned())
+                .or_insert_with(|| Page::pending(path, &renderer.config, renderer.generation));
+        });
+        self.progress.walked(None);
+
+        let pending: Vec<_> = self.lazy.iter().map(|r| r.key().clone()).collect();
+        for key in pending {
+            self.render_lazy(&key);
+        }
+        self.finish_scan();
+        Ok(())
+    }
+
+    /// Renders the page at `key` if the scan only listed it, returning whether it did
+    fn render_lazy(&self, key: &str) -> bool {
+        let Some((_, path)) = self.lazy.remove(key) else {
+            return false;
+        };
+        // removed since it was found
+        if !self.md.contains_key(key) {
+            return true;
+        }
+        let settings = self.settings();
+        match load_page(&settings, key, &path, &self.renderer(), &self.warnings) {
+            Ok(page) => {
+                self.failed.remove(key);
+                self.md.insert(key.to_owned(), page);
+            }
+            Err(e) => self.render_failed(key, &path, &e),
+        }
+        self.progress.rendered();
+        true
+    }
+
+    /// Records that the file at `path` failed to render, see [`Api::failed_files`]
+    fn render_failed(&self, key: &str, path: &Path, error: &dyn fmt::Display) {
+        let failed = FailedFile::report(key, path, error, &self.warnings);
+        self.failed.insert(key.to_owned(), failed);
+    }
+
+    /// The files that currently fail to render, sorted by key
+    ///
+    /// Files failing from the start are left out of the pages, while files failing after a
+    /// change keep their last successful render.
+    #[must_use]
+    pub fn failed_files(&self) -> Vec<FailedFile> {
+        let mut failed: Vec<_> = self.failed.iter().map(|f| f.value().clone()).collect();
+        failed.sort_by(|a, b| a.key.cmp(&b.key));
+        failed
+    }
+
+    /// How far the initial scan got, see [`Api::scan`]
+    #[must_use]
+    pub fn scan_status(&self) -> ScanStatus {
+        self.progress.status()
+    }
+
+    /// Marks the initial scan as done
+    fn finish_scan(&self) {
+        if self.md.contains_key(RECENT_KEY) {
+            Special::Recent.changed(RECENT_KEY, ChangeKind::Added, &self.warnings);
+        }
+        // pages rendered early couldn't see the headings of later ones
+        self.revalidate_links();
+        self.check_book();
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// Whether the initial scan finished, see [`Api::scan`]
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Returns the keys of every page under `prefix`, in the order listings show them
+    ///
+    /// An empty prefix lists every page, see [`ordering::compare`]. Pages the summary
+    /// lists come first, in its order, see [`Api::book`].
+    #[must_use]
+    pub fn ordered_keys(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.trim_matches('/');
+        let mut keys: Vec<_> = self
+            .md
+            .iter()
+            .map(|page| page.key().clone())
+            .filter(|key| self.visible(key))
+            .filter(|key| {
+                prefix.is_empty()
+                    || key
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .collect();
+        let overrides = self.order_overrides(&keys);
+        keys.sort_unstable_by(|a, b| ordering::compare(a, b, &overrides));
+        if let Some(book) = self.book() {
+            keys.sort_by_key(|key| book.position(key).unwrap_or(usize::MAX));
+        }
+        keys
+    }
+
+    /// Every page whose key starts with `prefix`, sorted by key
+    ///
+    /// Unlike [`Api::ordered_keys`], the order never depends on the summary or on
+    /// ordering overrides, so the list is stable for scripts.
+    #[must_use]
+    pub fn pages(&self, prefix: &str) -> Vec<ListedPage> {
+        let prefix = prefix.trim_start_matches('/');
+        let mut pages: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| page.key().starts_with(prefix) && self.visible(page.key()))
+            .map(|page| ListedPage {
+                key: page.key().clone(),
+                title: page.title.clone(),
+                path: page.path.clone(),
+            })
+            .collect();
+        pages.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+        pages
+    }
+
+    /// The pages whose text or title contains `query` in any case, at most
+    /// [`search::MAX_HITS`] of them
+    ///
+    /// Pages whose title matches come first, then pages are sorted by key. Blank queries
+    /// and queries over [`highlight::MAX_TERM_LEN`] characters match nothing.
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<search::Hit> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() > highlight::MAX_TERM_LEN {
+            return Vec::new();
+        }
+        let mut hits: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| self.visible(page.key()))
+            .filter_map(|page| {
+                let in_title = search::find(&page.title, query).is_some();
+                let snippet = match search::find(&page.text, query) {
+                    Some(found) => search::snippet(&page.text, found, query),
+                    None if in_title => {
+                        let mut snippet = String::new();
+                        let _ = escape_html(&mut snippet, &page.description);
+                        snippet
+                    }
+                    None => return None,
+                };
+                let hit = search::Hit {
+                    key: page.key().clone(),
+                    title: page.title.clone(),
+                    snippet,
+                };
+                Some((!in_title, hit))
+            })
+            .collect();
+        hits.sort_unstable_by(|(a_later, a), (b_later, b)| {
+            a_later.cmp(b_later).then_with(|| a.key.cmp(&b.key))
+        });
+        hits.into_iter()
+            .take(search::MAX_HITS)
+            .map(|(_, hit)| hit)
+            .collect()
+    }
+
+    /// Lists every page and asset for the service worker, see [`offline::Manifest`]
+    ///
+    /// Pages are in listing order, which is the order they are prefetched in.
+    /// Streamed pages are left out, they are too large to keep around.
+    #[must_use]
+    pub fn manifest(&self) -> offline::Manifest {
+        let pages = self
+            .ordered_keys("")
+            .into_iter()
+            .filter_map(|key| {
+                let page = self.md.get(&key).filter(|page| !page.streamed)?;
+                let url = format!("/{key}");
+                Some(offline::Entry::new(url, page.cache, page.html.len()))
+            })
+            .collect();
+        let mut assets = offline::assets();
+        if let Some(path) = &self.settings.css {
+            let css = fs::read_to_string(path).unwrap_or_default();
+            let url = format!("/{CUSTOM_CSS}");
+            assets.push(offline::Entry::new(url, &css, css.len()));
+        }
+        offline::Manifest {
+            generation: self.generation(),
+            prefetch: self.settings.offline_prefetch,
+            pages,
+            assets,
+        }
+    }
+
+    /// Finds the pages around the one at `url` in listing order, see [`Nav`]
+    #[must_use]
+    pub fn nav(&self, url: &str) -> Option<Nav> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        let keys = self.ordered_keys("");
+        let i = keys.iter().position(|k| *k == key)?;
+        let link = |key: &str| {
+            self.md.get(key).map(|page| NavLink {
+                key: key.to_owned(),
+                title: page.title.clone(),
+            })
+        };
+
+        // listed pages nest the way the summary does
+        let nested = |k: Option<&str>| k.filter(|k| self.exists(k)).map(str::to_owned);
+        let (parent, first_child) = self.book().filter(|book| book.lists(&key)).map_or_else(
+            || self.dir_nav(&key, &keys),
+            |book| (nested(book.parent(&key)), nested(book.first_child(&key))),
+        );
+
+        Some(Nav {
+            prev: i.checked_sub(1).and_then(|i| link(&keys[i])),
+            next: keys.get(i + 1).and_then(|k| link(k)),
+            parent: parent.and_then(|k| link(&k)),
+            first_child: first_child.and_then(|k| link(&k)),
+            key,
+        })
+    }
+
+    /// Finds the parent and first child of the page at `key` by its directory, see [`Nav`]
+    fn dir_nav(&self, key: &str, keys: &[String]) -> (Option<String>, Option<String>) {
+        let root = clean_url(&self.index.unlock(), &self.settings.extensions).to_owned();
+        let dir_index = |dir: &str| {
+            if dir.is_empty() {
+                root.clone()
+            } else {
+                format!("{dir}/index")
+            }
+        };
+        // the directories whose index could be the parent, nearest first
+        let mut dirs = Vec::new();
+        let mut rest = key;
+        while let Some((dir, _)) = rest.rsplit_once('/') {
+            dirs.push(dir);
+            rest = dir;
+        }
+        dirs.push("");
+        let parent = dirs
+            .into_iter()
+            .map(dir_index)
+            .find(|index| *index != key && self.exists(index));
+
+        let is_index = key == root || key.ends_with("/index");
+        let dir = key.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let first_child = keys.iter().filter(|_| is_index).find(|k| {
+            **k != key
+                && (dir.is_empty() || k.strip_prefix(dir).is_some_and(|r| r.starts_with('/')))
+        });
+        (parent, first_child.cloned())
+    }
+
+    /// Whether there is a page at `key` the request being handled may see
+    fn exists(&self, key: &str) -> bool {
+        self.md.contains_key(key) && self.visible(key)
+    }
+
+    /// Collects the explicit order of every directory holding one of `keys`
+    ///
+    /// The `order` field of a directory's index page is preferred over its
+    /// [`ordering::ORDER_FILE`].
+    fn order_overrides(&self, keys: &[String]) -> ordering::Overrides {
+        let base = self.base.unlock().clone();
+        let dirs: HashSet<_> = keys
+            .iter()
+            .map(|key| key.rsplit_once('/').map_or("", |(dir, _)| dir))
+            .collect();
+        let mut overrides = ordering::Overrides::default();
+        for dir in dirs {
+            let index = if dir.is_empty() {
+                "index".to_owned()
+            } else {
+                format!("{dir}/index")
+            };
+            let names = self
+                .md
+                .get(&index)
+                .and_then(|page| page.front_matter.as_ref()?.order.clone())
+                .or_else(|| {
+                    let file = base.join(dir).join(ordering::ORDER_FILE);
+                    let text = self.read_source(&file).ok()?;
+                    Some(ordering::parse_order_file(&text))
+                });
+            if let Some(names) = names {
+                overrides.insert(dir, names);
+            }
+        }
+        overrides
+    }
+
+    /// The summary ordering pages, if there is one
+    #[must_use]
+    pub fn book(&self) -> Option<Arc<Book>> {
+        self.book.unlock().clone()
+    }
+
+    /// The path of the summary in effect and its key, even if it doesn't exist
+    fn summary_path(&self) -> Option<(PathBuf, String)> {
+        let base = self.base.unlock().clone();
+        if base.is_file() {
+            return None;
+        }
+        let path = base.join(self.settings.summary.as_deref().unwrap_or(SUMMARY_FILE));
+        let key = self.settings.extensions.key(&base, &path)?.to_owned();
+        Some((path, key))
+    }
+
+    /// Reads the summary again, see [`Settings::summary`]
+    ///
+    /// Unless an index was given, its first chapter becomes the index.
+    pub fn load_book(&self) {
+        let book = self.summary_path().and_then(|(path, key)| {
+            match self.read_source(&path) {
+                Ok(text) => {
+                    self.warnings.resolve(Category::Summary, &key);
+                    Some(Book::parse(&key, &normalize_source(&text)))
+                }
+                // only a summary that was asked for is missed
+                Err(e) if self.settings.summary.is_some() => {
+                    let message = format!("unable to read the summary: {e}");
+                    self.warnings.push(Category::Summary, &key, message);
+                    None
+                }
+                Err(_) => None,
+            }
+        });
+        if let Some(first) = book.as_ref().and_then(|book| book.first()) {
+            if !self.settings.index_given {
+                *self.index.unlock() = format!("{first}.md");
+            }
+        }
+        let old = std::mem::replace(&mut *self.book.unlock(), book.map(Arc::new));
+        for chapter in old.iter().flat_map(|book| book.chapters()) {
+            if let Some(key) = &chapter.key {
+                self.warnings.resolve(Category::Summary, key);
+            }
+        }
+    }
+
+    /// Warns about chapters of the summary whose page doesn't exist
+    fn check_book(&self) {
+        let Some(book) = self.book() else {
+            return;
+        };
+        for key in book.chapters().filter_map(|chapter| chapter.key.as_deref()) {
+            if self.md.contains_key(key) {
+                self.warnings.resolve(Category::Summary, key);
+            } else {
+                self.warnings.push(
+                    Category::Summary,
+                    key,
+                    "listed in the summary, but there is no such page",
+                );
+            }
+        }
+    }
+
+    /// Returns every served key, in listing order with aliases last, along with the key
+    /// each alias points to
+    #[must_use]
+    pub fn list(&self) -> Vec<(String, Option<String>)> {
+        let mut aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .filter(|r| !self.md.contains_key(r.key()) && self.visible(r.value()))
+            .map(|r| (r.key().clone(), Some(r.value().clone())))
+            .collect();
+        let overrides = ordering::Overrides::default();
+        aliases.sort_unstable_by(|(a, _), (b, _)| ordering::compare(a, b, &overrides));
+        let pages = self.ordered_keys("").into_iter().map(|key| (key, None));
+        pages.chain(aliases).collect()
+    }
+
+    /// Receives an event for every change to the served content, the way browser clients do
+    ///
+    /// Events are sent once the pages they describe are updated. A receiver that falls more
+    /// than [`EVENT_CAPACITY`] events behind skips to the oldest one still buffered, with
+    /// [`broadcast::error::RecvError::Lagged`] saying how many were missed; the refresh
+    /// websocket treats that as a change like any other.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Like [`Api::subscribe`], along with the events made after `generation`
+    ///
+    /// Only the last [`EVENT_CAPACITY`] events are kept, the events are `None` if some
+    /// made after `generation` were dropped already.
+    #[must_use]
+    pub fn subscribe_since(
+        &self,
+        generation: u64,
+    ) -> (Option<Vec<ChangeEvent>>, broadcast::Receiver<ChangeEvent>) {
+        let log = self.event_log.unlock();
+        (log.since(generation), self.events.subscribe())
+    }
+
+    /// Ends event streams, so shutting down isn't held up by them, see [`handle_events`]
+    pub fn close(&self) {
+        self.closing.send_replace(true);
+    }
+
+    /// Queues file updates made by [`watchexec`] for [`Api::process_updates`]
+    ///
+    /// Kept quick, so the watcher stays responsive during floods of changes.
+    pub fn file_update(&self, h: &ActionHandler) {
+        for (path, _) in h.paths() {
+            self.updates.push(path.to_owned());
+        }
+    }
+
+    /// Renders the changes queued by [`Api::file_update`]
+    ///
+    /// Changes are handled as one batch once they settle, see [`updates::Debounce`], each
+    /// path only once. Returns right away if another task already processes updates.
+    pub async fn process_updates(self: Arc<Self>) {
+        let Some(mut rx) = self.updates.take_receiver() else {
+            return;
+        };
+        let mut queued = Vec::new();
+        while self.settings.debounce.recv(&mut rx, &mut queued).await > 0 {
+            let oldest = queued.iter().map(|q| q.at).min();
+            let total = queued.len();
+            let mut seen = HashSet::new();
+            let paths: Vec<_> = std::mem::take(&mut queued)
+                .into_iter()
+                .map(|q| q.path)
+                .filter(|path| seen.insert(path.clone()))
+                .collect();
+            let coalesced = total - paths.len();
+            // dropped changes can only be caught up on by reading everything
+            let overflowed = self.updates.take_overflow();
+
+            let api = self.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                if overflowed {
+                    api.rescan()
+                } else {
+                    api.update_paths(paths.iter().map(PathBuf::as_path))
+                }
+            })
+            .await;
+            match result {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => eprintln!("{RedFg}{e}{Reset}"),
+                Err(e) => eprintln!("{RedFg}rendering changes failed: {e}{Reset}"),
+            }
+
+            self.updates
+                .record(oldest.map_or(Duration::ZERO, |at| at.elapsed()), coalesced);
+            if overflowed {
+                eprintln!("{YellowFg}too many changes at once, rescanned instead{Reset}");
+            } else if coalesced > 0 {
+                eprintln!(
+                    "{YellowFg}{coalesced} repeated changes were coalesced, \
+                    only the latest state of each file was rendered{Reset}"
+                );
+            }
+        }
+    }
+
+    /// Picks up changes to `paths` as a single batch, see [`Api::file_update`]
+    pub fn update_paths<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> anyhow::Result<()> {
+        // don't read files twice
+        let mut files = HashSet::new();
+        let mut changes = Vec::new();
+
+        for path in paths {
+            if !files.insert(path) {
+                continue;
+            }
+
+            if let Some(change) = self.mount_change(path).or_else(|| self.css_change(path)) {
+                changes.push(change);
+                continue;
+            }
+
+            let base = self.base.unlock().clone();
+            let Some(path) = self.within_base(&base, path) else {
+                continue;
+            };
+            let path = path.as_path();
+            changes.extend(self.rerender_includers(path));
+            let Some((key, path)) = self.changed_page(&base, path) else {
+                continue;
+            };
+            let path = path.as_ref();
+
+            let mut title = None;
+            let anchors = self.md.get(key).and_then(|page| page.anchors.clone());
+            let kind = if !path.exists() {
+                let Some(removed) = self.remove_page(key) else {
+                    continue;
+                };
+                title = Some(removed);
+                ChangeKind::Removed
+            } else if let Some(reason) = path.is_file().then(|| self.skip_reason(path)).flatten() {
+                self.warnings.push(Category::Skipped, key, reason.as_str());
+                let render = self.render_config();
+                let page = Page::skipped(path, &reason, &render, self.render_generation());
+                match self.md.insert(key.to_owned(), page) {
+                    Some(_) => ChangeKind::Modified,
+                    None => ChangeKind::Added,
+                }
+            } else if path.is_file() {
+                self.warnings.resolve(Category::Skipped, key);
+                let text = match fs::read_to_string(path) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        self.render_failed(key, path, &e);
+                        continue;
+                    }
+                };
+                let cache = self.cache_key(&text);
+                if self.md.get(key).is_some_and(|page| page.cache == cache) {
+                    // the file was touched without changing
+                    continue;
+                }
+                let mut page = Page {
+                    render_kind: RenderKind::Watch,
+                    ..Page::default()
+                };
+                if let Err(e) = write_md(&mut page, key, path, &text, &self.renderer()) {
+                    self.render_failed(key, path, &e);
+                    continue;
+                }
+                self.warnings.resolve(Category::Render, key);
+                self.failed.remove(key);
+                page.report(key, &self.warnings);
+                self.archive.record(key, &text);
+                match self.md.insert(key.to_owned(), page) {
+                    Some(_) => ChangeKind::Modified,
+                    None => ChangeKind::Added,
+                }
+            } else {
+                continue;
+            };
+
+            let page = self
+                .md
+                .get(key)
+                .map(|page| (page.title.clone(), page.cache.source));
+            let (title, hash) =
+                page.map_or((title, None), |(title, hash)| (Some(title), Some(hash)));
+            self.changes.push(key, title.unwrap_or_default(), kind);
+            changes.push((key.to_owned(), kind, hash));
+            if anchors != self.md.get(key).and_then(|page| page.anchors.clone()) {
+                for referrer in self.revalidate_referrers(key) {
+                    let hash = self.md.get(&referrer).map(|page| page.cache.source);
+                    changes.push((referrer, ChangeKind::Modified, hash));
+                }
+            }
+            if let Some(special) = self.special(key) {
+                special.changed(key, kind, &self.warnings);
+            }
+        }
+
+        self.refresh_book(&files, &changes);
+        self.refresh_template(&files, &mut changes);
+        let glossary = self.settings.glossary.as_deref();
+        if glossary.is_some_and(|path| files.contains(path)) {
+            self.load_glossary();
+            let pushed: HashSet<_> = changes.iter().map(|(key, ..)| key.clone()).collect();
+            let rerendered = self.rerender_all().into_iter();
+            changes.extend(rerendered.filter(|(key, ..)| !pushed.contains(key)));
+        }
+        self.publish(changes);
+        // only the git directory is watched, pages change when the ref moves
+        if self.settings.git_ref.is_some() {
+            self.follow_git_ref()?;
+        }
+        Ok(())
+    }
+
+    /// The path of a changed file within `base`, `None` if it isn't served
+    fn within_base(&self, base: &Path, path: &Path) -> Option<PathBuf> {
+        if self.settings.git_ref.is_some() {
+            // the work tree isn't served, see the ref check in update_paths
+            None
+        } else if path.starts_with(base) {
+            Some(path.to_owned())
+        } else {
+            // changes within link targets are reported by their canonical path
+            symlinks::to_base(&self.links.unlock(), path)
+        }
+    }
+
+    /// The key of the page a change to `path` affects and the file it is now rendered
+    /// from, `None` for files that aren't pages or are left out for another file
+    ///
+    /// Files sharing the key take over from removed ones, see [`Extensions::fallback`].
+    fn changed_page<'p>(&self, base: &Path, path: &'p Path) -> Option<(&'p str, Cow<'p, Path>)> {
+        let extensions = &self.settings.extensions;
+        let key = extensions.key(base, path)?;
+        if path.exists() {
+            if left_out(extensions, path, key, &self.warnings) {
+                return None;
+            }
+            if let Some(other) = extensions.fallback(path) {
+                left_out(extensions, &other, key, &self.warnings);
+            }
+            return Some((key, Cow::Borrowed(path)));
+        }
+        self.warnings.resolve(Category::Collision, key);
+        let Some(served) = extensions.fallback(path) else {
+            return Some((key, Cow::Borrowed(path)));
+        };
+        if let Some(other) = extensions.fallback(&served) {
+            left_out(extensions, &other, key, &self.warnings);
+        }
+        // the page was already rendered from a file preferred over the removed one
+        if extensions.preferred_over(path).any(|p| p == served) {
+            return None;
+        }
+        Some((key, Cow::Owned(served)))
+    }
+
+    /// Rerenders every page that includes the file at `path`, see [`Page::includes`]
+    fn rerender_includers(&self, path: &Path) -> Vec<PendingChange> {
+        let includers: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| page.includes.contains(path))
+            .map(|page| (page.key().clone(), page.path.clone()))
+            .collect();
+        includers
+            .into_iter()
+            .map(|(key, page_path)| {
+                self.rerender(&key, &page_path);
+                let hash = self.md.get(&key).map(|page| page.cache.source);
+                (key, ChangeKind::Modified, hash)
+            })
+            .collect()
+    }
+
+    /// Drops the page at `key` and its warnings, returning its title if there was one
+    fn remove_page(&self, key: &str) -> Option<String> {
+        self.warnings.resolve(Category::Render, key);
+        self.failed.remove(key);
+        self.warnings.resolve(Category::Skipped, key);
+        self.warnings.resolve(Category::Anchor, key);
+        self.warnings.resolve(Category::Problem, key);
+        self.archive.remove(key);
+        self.md.remove(key).map(|(_, page)| page.title)
+    }
+
+    /// Reads the glossary again, keeping the previous one if it can't be read
+    fn load_glossary(&self) {
+        let Some(path) = &self.settings.glossary else {
+            return;
+        };
+        let key = path.display().to_string();
+        match read_glossary(path, self.settings.glossary_case_sensitive) {
+            Ok(glossary) => {
+                self.warnings.resolve(Category::Glossary, &key);
+                *self.glossary.unlock() = Some(Arc::new(glossary));
+            }
+            Err(e) => {
+                let message = format!("unable to read the glossary, the previous one is kept: {e}");
+                self.warnings.push(Category::Glossary, &key, message);
+            }
+        }
+    }
+
+    /// The template pages are served in
+    #[must_use]
+    pub fn template(&self) -> Arc<Template> {
+        self.template.unlock().clone()
+    }
+
+    /// Reads the template again if it is among `files`, adding every page to `changes` as
+    /// they are all served differently, see [`Settings::template`]
+    ///
+    /// The previous template is kept if the new one can't be used.
+    fn refresh_template(&self, files: &HashSet<&Path>, changes: &mut Vec<PendingChange>) {
+        let Some(path) = self.settings.template.as_deref() else {
+            return;
+        };
+        if !files.contains(path) {
+            return;
+        }
+        let key = path.display().to_string();
+        match read_template(&self.settings(), &self.warnings) {
+            Ok(template) => {
+                self.warnings.resolve(Category::Template, &key);
+                *self.template.unlock() = Arc::new(template);
+            }
+            Err(e) => {
+                let message =
+                    format!("unable to use the template, the previous one is kept: {e:#}");
+                self.warnings.push(Category::Template, &key, message);
+                return;
+            }
+        }
+        let pages = self.every_page(changes);
+        changes.extend(pages);
+    }
+
+    /// Every page not among `changes` as modified, for changes to how all pages are served
+    fn every_page(&self, changes: &[PendingChange]) -> Vec<PendingChange> {
+        let pushed: HashSet<_> = changes.iter().map(|(key, ..)| key.as_str()).collect();
+        self.md
+            .iter()
+            .filter(|page| !pushed.contains(page.key().as_str()))
+            .map(|page| {
+                (
+                    page.key().clone(),
+                    ChangeKind::Modified,
+                    Some(page.cache.source),
+                )
+            })
+            .collect()
+    }
+
+    /// Rerenders every page, as when the glossary changed
+    fn rerender_all(&self) -> Vec<PendingChange> {
+        let pages: Vec<_> = self
+            .md
+            .iter()
+            .map(|page| (page.key().clone(), page.path.clone()))
+            .collect();
+        pages
+            .into_iter()
+            .map(|(key, path)| {
+                self.rerender(&key, &path);
+                let hash = self.md.get(&key).map(|page| page.cache.source);
+                (key, ChangeKind::Modified, hash)
+            })
+            .collect()
+    }
+
+    /// Reads the summary again if it is among `files`, checking it again if it or the
+    /// pages that exist changed
+    fn refresh_book(&self, files: &HashSet<&Path>, changes: &[PendingChange]) {
+        let summary = self.summary_path();
+        let summary_changed = summary.is_some_and(|(path, _)| files.contains(path.as_path()));
+        if summary_changed {
+            self.load_book();
+        }
+        let added_or_removed = changes
+            .iter()
+            .any(|(_, kind, _)| matches!(kind, ChangeKind::Added | ChangeKind::Removed));
+        if summary_changed || added_or_removed {
+            self.check_book();
+        }
+    }
+
+    /// Describes a change to a file within a watched mount, see [`Settings::watch_mounts`]
+    fn mount_change(&self, path: &Path) -> Option<PendingChange> {
+        let mount = self
+            .settings
+            .mounts
+            .iter()
+            .find(|m| self.settings.watch_mounts && path.starts_with(&m.dir))?;
+        let file = path.strip_prefix(&mount.dir).unwrap_or(path);
+        let url = format!("/{}/{}", mount.prefix, file.display());
+        let kind = if path.exists() {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Removed
+        };
+        Some((url, kind, None))
+    }
+
+    /// Describes a change to the stylesheet given with `--css`, see [`Settings::css`]
+    fn css_change(&self, path: &Path) -> Option<PendingChange> {
+        self.settings.css.as_deref().filter(|&css| css == path)?;
+        let kind = if path.exists() {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Removed
+        };
+        Some((format!("/{CUSTOM_CSS}"), kind, None))
+    }
+
+    /// Bumps the generation once for all of `changes` and sends them to subscribers
+    fn publish(&self, changes: Vec<PendingChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        // mixed batches count as content
+        let styles_only = changes.iter().all(|(key, ..)| is_mounted_stylesheet(key));
+        let timestamp = time::Timestamp::now();
+        // held while sending, so subscribers catching up see each event once
+        let mut log = self.event_log.unlock();
+        for (key, kind, hash) in changes {
+            let event = ChangeEvent {
+                key,
+                kind,
+                hash,
+                generation,
+                timestamp,
+                styles_only,
+            };
+            log.push(event.clone());
+            // only fails without subscribers
+            let _ = self.events.send(event);
+        }
+        drop(log);
+    }
+
+    /// Rereads every file in base, replacing the current pages
+    pub fn rescan(&self) -> anyhow::Result<()> {
+        let fresh = MdFiles::default();
+        let generation = self.render_generation();
+        let revision = self.resolve_revision()?;
+        self.load_book();
+        let settings = self.settings();
+        let failed = initialize_md(
+            &settings,
+            generation,
+            &self.hooks,
+            &self.warnings,
+            self,
+            revision.as_ref(),
+            &fresh,
+        )?;
+        self.failed.clear();
+        for file in failed {
+            self.failed.insert(file.key.clone(), file);
+        }
+        if settings.follow_symlinks {
+            *self.links.unlock() = symlinks::find(&settings.base);
+        }
+        let mut changes: Vec<_> = self
+            .md
+            .iter()
+            .filter(|page| !fresh.contains_key(page.key()))
+            .map(|page| (page.key().clone(), ChangeKind::Removed, None))
+            .collect();
+        for page in fresh.iter() {
+            let hash = page.cache.source;
+            let kind = match self.md.get(page.key()).map(|old| old.cache) {
+                None => ChangeKind::Added,
+                // rendered with other settings, as after switching to strict commonmark
+                Some(old) if old.source != hash || old.options != page.cache.options => {
+                    ChangeKind::Modified
+                }
+                Some(_) => continue,
+            };
+            changes.push((page.key().clone(), kind, Some(hash)));
+        }
+
+        self.md.retain(|key, _| fresh.contains_key(key));
+        for page in fresh.iter() {
+            self.md.insert(page.key().clone(), page.value().clone());
+        }
+        self.revalidate_links();
+        self.check_book();
+        self.publish(changes);
+        Ok(())
+    }
+
+    /// Compares the keys a rescan would find with the current ones, rendering nothing
+    ///
+    /// Scans `base` instead of the current base if given, see [`ScanPreview`].
+    pub fn preview_scan(&self, base: Option<PathBuf>) -> anyhow::Result<ScanPreview> {
+        let mut settings = self.settings();
+        if let Some(base) = base {
+            settings.base = base;
+        }
+        let mut found = HashSet::new();
+        if let Some(rev) = &settings.git_ref {
+            Revision::resolve(&settings.base, rev)?.find_md(
+                &settings.extensions,
+                |_| (),
+                |key, _, _| {
+                    found.insert(key.to_owned());
+                    AnyOk(())
+                },
+            )?;
+        } else {
+            // warnings are for what is served, not what might be
+            find_md(&settings, &Warnings::default(), |key, _| {
+                found.insert(key.to_owned());
+                AnyOk(())
+            })?;
+        }
+
+        let mut preview = ScanPreview::default();
+        for page in self.md.iter() {
+            if found.remove(page.key()) {
+                preview.kept += 1;
+            } else {
+                preview.removed.push(page.key().clone());
+            }
+        }
+        preview.added = found.into_iter().collect();
+        preview.added.sort_unstable();
+        preview.removed.sort_unstable();
+        Ok(preview)
+    }
+
+    #[must_use]
+    pub fn watch_state(&self) -> WatchState {
+        self.watch.unlock().clone()
+    }
+
+    /// Updates the watcher's health, logging any transition
+    pub fn set_watch_state(&self, state: WatchState) {
+        let mut current = self.watch.unlock();
+        if *current == state {
+            return;
+        }
+        match &state {
+            WatchState::Watching => {
+                println!("{GreenFg}watching for changes again{Reset}");
+            }
+            WatchState::Degraded(reason) => {
+                eprintln!("{YellowFg}watcher degraded, serving cached content: {reason}{Reset}");
+            }
+            WatchState::Failed(reason) => {
+                eprintln!("{RedFg}watcher gave up, serving cached content: {reason}{Reset}");
+            }
+        }
+        *current = state;
+    }
+
+    /// The paths the watcher should cover
+    ///
+    /// When serving a git ref, its git directory takes the place of base.
+    #[must_use]
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        let mounts = self
+            .settings
+            .mounts
+            .iter()
+            .filter(|_| self.settings.watch_mounts);
+        let links = self.links.unlock();
+        let watched = self
+            .revision()
+            .map_or_else(|| self.base.unlock().clone(), |r| r.git_dir);
+        let files = self
+            .settings
+            .glossary
+            .iter()
+            .chain(&self.settings.css)
+            .chain(&self.settings.template)
+            .filter(|file| !file.starts_with(&watched));
+        std::iter::once(watched.clone())
+            .chain(files.cloned())
+            .chain(mounts.map(|m| m.dir.clone()))
+            .chain(links.iter().map(|l| l.target.clone()))
+            .collect()
+    }
+
+    /// Returns the special page `key` refers to, if any
+    #[must_use]
+    pub fn special(&self, key: &str) -> Option<Special> {
+        if key == clean_url(&self.index.unlock(), &self.settings.extensions) {
+            Some(Special::Index)
+        } else if key == NOT_FOUND_KEY {
+            Some(Special::NotFound)
+        } else if key == RECENT_KEY {
+            Some(Special::Recent)
+        } else {
+            None
+        }
+    }
+
+    /// Renders the changes made to a page by its last update, see [`history::render_diff`]
+    #[must_use]
+    pub fn diff(&self, url: &str) -> Option<String> {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions))?;
+        let title = self.md.get(&key)?.title.clone();
+        let mut md = String::from("<h1>Changes to ");
+        let _ = escape_html(&mut md, &title);
+        md.push_str("</h1>\n");
+        let diff = self.archive.versions(&key).map_or_else(
+            || "<p>No previous version, the page has not changed since startup.</p>".to_owned(),
+            |(previous, current)| history::render_diff(&previous, &current),
+        );
+        md.push_str(&diff);
+        Some(self.template().html(&Slots {
+            md: &md,
+            title: &self.title(&format!("Changes to {title}")),
+            page: &key,
+            generation: self.generation(),
+            ..Slots::default()
+        }))
+    }
+
+    /// Renders the pages at `left` and `right` side by side
+    ///
+    /// Each column follows its own page, see `index.js`. Pages that don't exist show the
+    /// 404 page in their column.
+    #[must_use]
+    pub fn compare(&self, left: &str, right: &str) -> String {
+        let mut md = String::from(r#"<div class="compare">"#);
+        for url in [left, right] {
+            self.compare_column(&mut md, url);
+        }
+        md.push_str("</div>");
+        self.template().html(&Slots {
+            md: &md,
+            title: &self.title(&format!("Comparing {left} and {right}")),
+            page: COMPARE_PAGE,
+            generation: self.generation(),
+            ..Slots::default()
+        })
+    }
+
+    /// Writes the column of [`Api::compare`] showing the page at `url`
+    fn compare_column(&self, md: &mut String, url: &str) {
+        let key = self.resolve_key(clean_url(url, &self.settings.extensions));
+        if let Some(key) = &key {
+            self.refresh_stale(key);
+        }
+        let page = key.as_ref().and_then(|key| {
+            let page = self.md.get(key)?;
+            Some((format!("{:016x}", page.etag), page.html.clone()))
+        });
+        let key = key
+            .as_deref()
+            .unwrap_or_else(|| clean_url(url, &self.settings.extensions));
+        let (hash, html) = page.unwrap_or_else(|| {
+            let custom = self.md.get(NOT_FOUND_KEY).map(|r| r.html.clone());
+            (
+                String::new(),
+                custom.unwrap_or_else(|| NOT_FOUND_HTML.to_owned()),
+            )
+        });
+        md.push_str(r#"<section class="compare-column" data-page=""#);
+        let _ = escape_html(&mut *md, key);
+        md.push_str(r#"" data-hash=""#);
+        md.push_str(&hash);
+        md.push_str(r#""><a class="compare-key" href="/"#);
+        let _ = escape_href(&mut *md, key);
+        md.push_str(r#"">"#);
+        let _ = escape_html(&mut *md, key);
+        md.push_str(r#"</a><div class="compare-body">"#);
+        md.push_str(&html);
+        md.push_str("</div></section>");
+    }
+
+    /// Responds to `url` matching no page, according to [`Settings::fallback`]
+    ///
+    /// Falls back to the 404 page when there is nothing else to serve.
+    pub fn fallback(&self, url: &str) -> Response {
+        let html = match self.settings.fallback {
+            Fallback::NotFound => None,
+            Fallback::Index => {
+                let index = self.index.unlock().clone();
+                self.get_md(&index)
+            }
+            Fallback::Listing => self.listing(url),
+        };
+        html.map_or_else(
+            || (StatusCode::NOT_FOUND, Html(self.not_found())).into_response(),
+            |html| Html(html).into_response(),
+        )
+    }
+
+    /// Lists the pages within the nearest directory of `url` that has any
+    ///
+    /// `url` is treated as a directory first, then each of its parents. Returns `None`
+    /// if there are no pages at all.
+    #[must_use]
+    pub fn listing(&self, url: &str) -> Option<String> {
+        let requested = clean_url(url, &self.settings.extensions).trim_matches('/');
+        let mut dir = requested;
+        let keys = loop {
+            let keys = self.ordered_keys(dir);
+            if !keys.is_empty() {
+                break keys;
+            }
+            if dir.is_empty() {
+                return None;
+            }
+            dir = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+        };
+
+        let heading = if dir.is_empty() {
+            "All pages".to_owned()
+        } else {
+            format!("Pages in {dir}")
+        };
+        let mut md = String::from("<h1>");
+        let _ = escape_html(&mut md, &heading);
+        md.push_str("</h1>\n<ul class=\"listing\">\n");
+        let book = self.book();
+        for key in keys {
+            self.list_page(&mut md, &key, book.as_deref());
+        }
+        md.push_str("</ul>\n");
+        Some(self.template().html(&Slots {
+            md: &md,
+            title: &self.title(&heading),
+            page: requested,
+            generation: self.generation(),
+            ..Slots::default()
+        }))
+    }
+
+    /// Lists the pages and subdirectories directly within the directory at `url`, `None`
+    /// if it has no pages
+    ///
+    /// Unlike [`Api::listing`], nested pages are reached through their directory.
+    /// Directories with an index page serve that instead, see [`Api::resolve_key`].
+    #[must_use]
+    pub fn directory(&self, url: &str) -> Option<String> {
+        let dir = clean_url(url, &self.settings.extensions);
+        if dir.is_empty() {
+            return None;
+        }
+        let keys = self.ordered_keys(dir);
+        let mut subdirs: Vec<&str> = Vec::new();
+        let mut pages: Vec<&str> = Vec::new();
+        for key in &keys {
+            let Some(rest) = key
+                .strip_prefix(dir)
+                .and_then(|rest| rest.strip_prefix('/'))
+            else {
+                continue;
+            };
+            match rest.split_once('/') {
+                Some((subdir, _)) if !subdirs.contains(&subdir) => subdirs.push(subdir),
+                Some(_) => (),
+                None => pages.push(key),
+            }
+        }
+        if subdirs.is_empty() && pages.is_empty() {
+            return None;
+        }
+
+        let heading = format!("Contents of {dir}");
+        let mut md = String::from("<h1>");
+        let _ = escape_html(&mut md, &heading);
+        md.push_str("</h1>\n<ul class=\"listing directory\">\n");
+        for subdir in subdirs {
+            md.push_str("<li class=\"subdirectory\"><a href=\"/");
+            let _ = escape_href(&mut md, &format!("{dir}/{subdir}/"));
+            md.push_str("\">");
+            let _ = escape_html(&mut md, subdir);
+            md.push_str("/</a></li>\n");
+        }
+        let book = self.book();
+        for key in pages {
+            self.list_page(&mut md, key, book.as_deref());
+        }
+        md.push_str("</ul>\n");
+        Some(self.template().html(&Slots {
+            md: &md,
+            title: &self.title(&heading),
+            page: dir,
+            generation: self.generation(),
+            ..Slots::default()
+        }))
+    }
+
+    /// Writes a listing's item linking to the page at `key`, by its title
+    ///
+    /// Pages the summary leaves out are marked unlisted.
+    fn list_page(&self, md: &mut String, key: &str, book: Option<&Book>) {
+        let title = self.md.get(key).map(|page| page.title.clone());
+        let unlisted = book.is_some_and(|book| !book.lists(key));
+        md.push_str(if unlisted {
+            "<li class=\"unlisted\"><a href=\"/"
+        } else {
+            "<li><a href=\"/"
+        });
+        let _ = escape_href(&mut *md, key);
+        md.push_str("\">");
+        let _ = escape_html(&mut *md, title.as_deref().unwrap_or(key));
+        md.push_str("</a>");
+        if unlisted {
+            md.push_str(" <small>unlisted</small>");
+        }
+        md.push_str("</li>\n");
+    }
+
+    /// Renders the recent changes page, see [`changes::render_recent`]
+    #[must_use]
+    pub fn recent(&self) -> String {
+        let mut changes = self.changes.recent(changes::RECENT_PAGE_LEN);
+        changes.retain(|change| self.visible(&change.key));
+        self.template().html(&Slots {
+            md: &changes::render_recent(&changes, time::Timestamp::now()),
+            title: &self.title("Recent changes"),
+            page: RECENT_KEY,
+            generation: self.generation(),
+            ..Slots::default()
+        })
+    }
+
+    /// Renders the 404 page, preferring a custom one if it exists
+    #[must_use]
+    pub fn not_found(&self) -> String {
+        let custom = self.md.get(NOT_FOUND_KEY);
+        self.template()
+            .not_found(self.generation(), custom.as_ref().map(|r| r.html.as_str()))
+    }
+
+    /// Creates a watcher that feeds [`Api::file_update`], it must be started by the caller
+    ///
+    /// Changes are only rendered while [`Api::process_updates`] runs.
+    pub fn watcher(self: &Arc<Self>) -> anyhow::Result<Watchexec> {
+        let wx_api = self.clone();
+        let config = Config::default();
+
+        // changes are grouped by process_updates, see Settings::debounce
+        config.throttle(Duration::from_millis(20));
+        config.pathset(self.watch_paths());
+        config.on_action(move |h| {
+            wx_api.file_update(&h);
+            h
+        });
+
+        Ok(Watchexec::with_config(config)?)
+    }
+}
+
+/// Whether the key of a [`ChangeEvent`] is the url path of a stylesheet, within a mount or
+/// given with `--css`
+fn is_mounted_stylesheet(key: &str) -> bool {
+    key.starts_with('/') && assets::content_type(Path::new(key)).starts_with("text/css")
+}
+
+/// The names of the pages served for their directory, in order of preference, such as
+/// `docs/index.md` for `/docs/`
+pub const DIRECTORY_INDEXES: &[&str] = &["index", "README"];
+
+/// Turns the decoded path of a page's url into its key, such as `/docs/setup.md` into
+/// `docs/setup`
+///
+/// Keys never start or end with a slash, see [`Extensions::key`].
+#[must_use]
+pub fn clean_url<'a>(url: &'a str, extensions: &Extensions) -> &'a str {
+    extensions.strip(url.trim_matches('/'))
+}
+
+/// Whether the page at `path` is left out for a file sharing its key, warning about it
+/// if so, see [`Extensions::shadowed_by`]
+fn left_out(extensions: &Extensions, path: &Path, key: &str, warnings: &Warnings) -> bool {
+    let Some(served) = extensions.shadowed_by(path) else {
+        return false;
+    };
+    let ext = |path: &Path| extensions.extension(path).unwrap_or_default().to_owned();
+    let message = format!("{key}.{} is left out for {key}.{}", ext(path), ext(&served));
+    warnings.push(Category::Collision, key, message);
+    true
+}
+
+/// Points `alias` at `key`, unless `alias` is a real key
+///
+/// Real files are always preferred over aliases.
+pub fn insert_alias(md: &MdFiles, aliases: &Aliases, warnings: &Warnings, alias: &str, key: &str) {
+    if md.contains_key(alias) {
+        let message = format!("a real file is served instead of the alias to \"{key}\"");
+        warnings.push(Category::Collision, alias, message);
+    }
+    aliases.insert(alias.to_owned(), key.to_owned());
+}
+
+/// Names used by built-in routes, which mounts may not use
+pub const RESERVED_PREFIXES: &[&str] = &[
+    "api",
+    ASSET_PREFIX,
+    "diff",
+    "raw",
+    "compare",
+    "healthz",
+    "refresh-ws",
+    "index.css",
+    "theme-light.css",
+    "theme-dark.css",
+    "index.js",
+    "vendor",
+    "favicon.ico",
+    CUSTOM_CSS,
+    "manifest.json",
+    "sw.js",
+];
+
+/// Ensures a mount doesn't shadow any page within `base`
+///
+/// Only the files under the mount's prefix are looked at, so this runs before the scan.
+pub fn check_mount(mount: &Mount, base: &Path, extensions: &Extensions) -> anyhow::Result<()> {
+    if base.is_file() {
+        return Ok(());
+    }
+    let page = extensions
+        .paths(base, &mount.prefix)
+        .find(|page| page.is_file())
+        .or_else(|| {
+            WalkDir::new(base.join(&mount.prefix))
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(walkdir::DirEntry::into_path)
+                .find(|path| extensions.key(base, path).is_some())
+        });
+    let shadowed = page.as_deref().and_then(|page| extensions.key(base, page));
+    if let Some(key) = shadowed {
+        bail!("mount \"{}\" would shadow the page \"{key}\"", mount.prefix);
+    }
+    Ok(())
+}
+
+/// Renders every markdown file in base into `md`, or in `revision` if given
+///
+/// Pages already within `md` are kept, as they were rendered by a newer change.
+/// Files that fail to render are reported and left out, and returned. Fails only if
+/// there were files to render and every one of them failed.
+pub fn initialize_md(
+    settings: &Settings,
+    generation: u64,
+    hooks: &Hooks,
+    warnings: &Warnings,
+    live: &dyn Targets,
+    revision: Option<&Revision>,
+    md: &MdFiles,
+) -> anyhow::Result<Vec<FailedFile>> {
+    let targets = PageTargets {
+        md,
+        live,
+        base: &settings.base,
+        extensions: &settings.extensions,
+        revision,
+    };
+    let renderer = Renderer {
+        config: settings.render,
+        generation,
+        targets: &targets,
+        hooks,
+        strict: settings.strict,
+    };
+    let mut loaded = 0_usize;
+    let mut failed = Vec::new();
+    let mut add = |key: &str, path: &Path, page: anyhow::Result<Page>| match page {
+        Ok(page) => {
+            md.entry(key.to_owned()).or_insert(page);
+            loaded += 1;
+        }
+        Err(e) => failed.push(FailedFile::report(key, path, &e, warnings)),
+    };
+    if let Some(revision) = revision {
+        let submodule = |path: &Path| {
+            let key = path.strip_prefix(&settings.base).unwrap_or(path).display();
+            let message = format!("submodule, {} can't be read from it", revision.rev);
+            warnings.push(Category::Skipped, &key.to_string(), message);
+        };
+        revision.find_md(&settings.extensions, submodule, |key, path, text| {
+            if !md.contains_key(key) {
+                add(
+                    key,
+                    path,
+                    load_blob(settings, key, path, text, &renderer, warnings),
+                );
+            }
+            AnyOk(())
+        })?;
+    } else {
+        find_md(settings, warnings, |key, path| {
+            if !md.contains_key(key) {
+                add(
+                    key,
+                    path,
+                    load_page(settings, key, path, &renderer, warnings),
+                );
+            }
+            AnyOk(())
+        })?;
+    }
+    if loaded == 0 && !failed.is_empty() {
+        bail!(
+            "none of the {} markdown files could be rendered",
+            failed.len()
+        );
+    }
+    Ok(failed)
+}
+
+/// A markdown file that failed to render, see [`Api::failed_files`]
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedFile {
+    pub key: String,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl FailedFile {
+    /// Logs the failure, with the file's path, and warns about it
+    fn report(key: &str, path: &Path, error: &dyn fmt::Display, warnings: &Warnings) -> Self {
+        let error = error.to_string();
+        eprintln!("{RedFg}failed to render {}: {error}{Reset}", path.display());
+        warnings.push(Category::Render, key, error.as_str());
+        Self {
+            key: key.to_owned(),
+            path: path.to_owned(),
+            error,
+        }
+    }
+}
+
+/// Calls `found` with the key and path of every markdown file within base, without reading them
+pub fn find_md(
+    settings: &Settings,
+    warnings: &Warnings,
+    mut found: impl FnMut(&str, &Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let Settings {
+        base, extensions, ..
+    } = settings;
+    if base.is_file() {
+        let key = extensions
+            .key(base, base)
+            .context("only utf8 paths allowed")?;
+        return found(key, base);
+    }
+
+    let walk = WalkDir::new(base).follow_links(settings.follow_symlinks);
+    for entry in walk {
+        let file = match entry {
+            Ok(file) => file,
+            Err(e) => {
+                // only reachable through symlinks, anything else is unreadable
+                if let Some(path) = e.path().filter(|_| settings.follow_symlinks) {
+                    let key = path.strip_prefix(base).unwrap_or(path).display();
+                    let message = if e.loop_ancestor().is_some() {
+                        "symlink loops back to a parent directory".to_owned()
+                    } else {
+                        format!("unable to follow symlink: {e}")
+                    };
+                    warnings.push(Category::Symlink, &key.to_string(), message);
+                }
+                continue;
+            }
+        };
+        if !file.file_type().is_file() {
+            continue;
+        }
+        let Some(key) = extensions.key(base, file.path()) else {
+            continue;
+        };
+        if left_out(extensions, file.path(), key, warnings) {
+            continue;
+        }
+        found(key, file.path())?;
+    }
+
+    Ok(())
+}
+
+/// Renders a single file
+///
+/// Files over the limits are replaced by placeholders, see [`Page::skipped`].
+pub fn load_page(
+    settings: &Settings,
+    key: &str,
+    path: &Path,
+    renderer: &Renderer,
+    warnings: &Warnings,
+) -> anyhow::Result<Page> {
+    if let Some(reason) = settings.limits.check(&settings.base, path) {
+        warnings.push(Category::Skipped, key, reason.as_str());
+        let generation = renderer.generation;
+        return Ok(Page::skipped(path, &reason, &renderer.config, generation));
+    }
+    warnings.resolve(Category::Skipped, key);
+    let mut page = Page::default();
+    write_md_from_file(&mut page, key, path, renderer)?;
+    page.report(key, warnings);
+    Ok(page)
+}
+
+/// Renders a single file read from a git revision, see [`load_page`]
+pub fn load_blob(
+    settings: &Settings,
+    key: &str,
+    path: &Path,
+    text: &str,
+    renderer: &Renderer,
+    warnings: &Warnings,
+) -> anyhow::Result<Page> {
+    let size = text.len() as u64;
+    if let Some(reason) = settings.limits.check_len(&settings.base, path, size) {
+        warnings.push(Category::Skipped, key, reason.as_str());
+        let generation = renderer.generation;
+        return Ok(Page::skipped(path, &reason, &renderer.config, generation));
+    }
+    warnings.resolve(Category::Skipped, key);
+    let mut page = Page::default();
+    write_md(&mut page, key, path, text, renderer)?;
+    page.report(key, warnings);
+    Ok(page)
+}
+
+/// Everything rendering a page needs besides its source
+#[derive(Clone, Copy)]
+pub struct Renderer<'a> {
+    pub config: render::Config,
+    /// See [`Api::render_generation`]
+    pub generation: u64,
+    /// The pages links are checked against, see [`render::render_page`]
+    pub targets: &'a dyn Targets,
+    pub hooks: &'a Hooks,
+    /// See [`Settings::strict`]
+    pub strict: bool,
+}
+
+pub fn write_md_from_file(
+    out: &mut Page,
+    key: &str,
+    path: &Path,
+    renderer: &Renderer,
+) -> anyhow::Result<()> {
+    let text = fs::read_to_string(path)?;
+    write_md(out, key, path, &text, renderer)
+}
+
+/// Renders `text`, read from `path`, into `out`
+///
+/// A failing post render hook leaves an explanation in place of the html, see [`Page::error`].
+pub fn write_md(
+    out: &mut Page,
+    key: &str,
+    path: &Path,
+    text: &str,
+    renderer: &Renderer,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let Renderer {
+        config,
+        generation,
+        targets,
+        hooks,
+        strict,
+    } = *renderer;
+    let mut html = std::mem::take(&mut out.html);
+    let source = normalize_source(text);
+    let summary = if extensions::is_text(path) {
+        render_text(&mut html, &source)
+    } else if extensions::is_csv(path) {
+        render_csv(&mut html, &source, config.max_csv_rows)?
+    } else {
+        render_page(&mut html, &source, &config, key, targets, hooks)?
+    };
+    out.streamed = summary.oversized;
+    out.features = features(&summary, out.streamed, hooks);
+    // too large to hold on to, let alone pass to hooks
+    let hooked = if out.streamed {
+        Ok(html)
+    } else {
+        hooks.apply(key, html)
+    };
+    match hooked {
+        Ok(html) => {
+            out.html = html;
+            out.error = None;
+        }
+        Err(e) => {
+            out.html = String::from("<p><strong>This page failed to render:</strong> ");
+            let _ = escape_html(&mut out.html, &format!("{e:#}"));
+            out.html.push_str("</p>");
+            out.error = Some(format!("post render hook failed: {e:#}"));
+        }
+    }
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    out.title = summary.title(stem);
+    out.description = summary.excerpt;
+    out.front_matter = summary.front_matter;
+    out.headings = summary.headings;
+    out.outline = summary.outline;
+    out.words = summary.words;
+    out.modified = fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .map(time::Timestamp::from_system);
+    out.links = summary.links;
+    out.anchors = Some(summary.anchors);
+    out.anchor_links = summary.anchor_links;
+    out.includes = summary.includes;
+    out.broken_anchors = summary.broken_anchors;
+    out.dead_links = summary.dead_links;
+    out.problems = summary.problems;
+    out.toc = summary.toc.unwrap_or_default();
+    out.text = summary.text;
+    if strict {
+        out.fail_strict();
+    }
+    out.cache = CacheKey {
+        source: hash_source(text),
+        options: config.fingerprint(),
+        generation,
+    };
+    out.etag = hash_page(out.cache.source, &out.html);
+    path.clone_into(&mut out.path);
+    out.render_time = start.elapsed();
+    Ok(())
+}
+
+/// Names what changed a page's html beyond plain markdown, see [`Page::features`]
+fn features(summary: &render::Summary, streamed: bool, hooks: &Hooks) -> Vec<String> {
+    let mut features = Vec::new();
+    if summary.front_matter.is_some() {
+        features.push("front matter".to_owned());
+    }
+    if summary.math {
+        features.push("math".to_owned());
+    }
+    features.extend(summary.fences.iter().map(|lang| format!("{lang} fence")));
+    if streamed {
+        features.push("streamed".to_owned());
+    } else if hooks.has_post_render() {
+        features.push("post render hooks".to_owned());
+    }
+    features
+}
+
+/// Hashes markdown source, see [`Page::hash`]
+#[must_use]
+pub fn hash_source(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The weak `ETag` of a page with the given [`Page::etag`], see [`Api::etag`]
+fn page_etag(hash: u64, generation: u64) -> String {
+    format!("W/\"{hash:016x}-{generation:x}\"")
+}
+
+/// Hashes what a page shows, see [`Page::etag`]
+fn hash_page(source: u64, html: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    html.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A rendered markdown file
+#[derive(Debug, Default, Clone)]
+pub struct Page {
+    /// The rendered html
+    pub html: String,
+    /// What the html was rendered from
+    pub cache: CacheKey,
+    /// The hash of the html and its source, computed whenever the page is rendered
+    ///
+    /// The source covers what the page shows outside of the html, such as front matter.
+    /// See [`Api::etag`].
+    pub etag: u64,
+    /// The markdown source file
+    pub path: PathBuf,
+    /// The page title, see [`render::Summary::title`]
+    pub title: String,
+    /// A plain text excerpt, see [`render::Summary::excerpt`]
+    pub description: String,
+    /// See [`render::Summary::front_matter`]
+    pub front_matter: Option<FrontMatter>,
+    /// See [`render::Summary::headings`]
+    pub headings: usize,
+    /// See [`render::Summary::outline`]
+    pub outline: Vec<render::Heading>,
+    /// See [`render::Summary::words`]
+    pub words: usize,
+    /// When the source file was last modified as it was rendered, `None` if that
+    /// couldn't be read
+    pub modified: Option<time::Timestamp>,
+    /// See [`render::Summary::links`]
+    pub links: Vec<String>,
+    /// What changed the html beyond plain markdown, such as front matter or fence handlers
+    pub features: Vec<String>,
+    /// The ids links can point to, `None` until the page is rendered
+    pub anchors: Option<HashSet<String>>,
+    /// The other pages this one links to with a fragment
+    pub anchor_links: HashSet<String>,
+    /// See [`render::Summary::includes`]
+    pub includes: HashSet<PathBuf>,
+    /// Links whose fragment matches no id on their target, see [`render::Summary`]
+    pub broken_anchors: Vec<String>,
+    /// See [`render::Summary::dead_links`]
+    pub dead_links: Vec<String>,
+    /// See [`render::Summary::problems`]
+    pub problems: Vec<String>,
+    /// Why the html is an error message instead, see [`hooks::Hooks::post_render`]
+    /// and [`Settings::strict`]
+    pub error: Option<String>,
+    /// Whether the html went over [`render::Config::max_html_size`]
+    ///
+    /// The html is then left empty and rendered again for every request, see [`stream_md`].
+    pub streamed: bool,
+    /// The templated page compressed with gzip once requested, see [`Api::get_md_gzip`]
+    pub gzip: Option<compress::Gzipped>,
+    /// How long the last [`write_md`] took
+    pub render_time: Duration,
+    /// What caused the last render
+    pub render_kind: RenderKind,
+    /// The table of contents filled into `{{toc}}`, see [`render::Summary::toc`]
+    pub toc: String,
+    /// See [`render::Summary::text`]
+    pub text: String,
+}
+
+impl Page {
+    /// A placeholder for a file that wasn't rendered, explaining why
+    #[must_use]
+    pub fn skipped(path: &Path, reason: &str, render: &render::Config, generation: u64) -> Self {
+        let mut html = String::from("<p><strong>This file was skipped:</strong> it is ");
+        let _ = escape_html(&mut html, reason);
+        html.push_str(".</p><p>Raise the limit and rescan to render it.</p>");
+        Self::placeholder(path, html, render, generation)
+    }
+
+    /// Lists a file the initial scan hasn't rendered yet, see [`Api::scan_within`]
+    #[must_use]
+    pub fn pending(path: &Path, render: &render::Config, generation: u64) -> Self {
+        let html = "<p>This page hasn't been rendered yet.</p>".to_owned();
+        Self::placeholder(path, html, render, generation)
+    }
+
+    /// A page titled after the file name, showing `html` instead of the file
+    fn placeholder(path: &Path, html: String, render: &render::Config, generation: u64) -> Self {
+        let title = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(render::prettify_stem)
+            .unwrap_or_default();
+        Self {
+            etag: hash_page(0, &html),
+            html,
+            cache: CacheKey {
+                source: 0,
+                options: render.fingerprint(),
+                generation,
+            },
+            path: path.to_owned(),
+            title,
+            ..Self::default()
+        }
+    }
+}
+
+impl Page {
+    /// The image shown when the page is shared, from the front matter
+    #[must_use]
+    pub fn image(&self) -> Option<&str> {
+        self.front_matter.as_ref()?.image.as_deref()
+    }
+
+    /// The classes its front matter gives the page, see [`FrontMatter::classes`]
+    #[must_use]
+    pub fn class(&self) -> String {
+        self.front_matter
+            .as_ref()
+            .map(FrontMatter::classes)
+            .unwrap_or_default()
+    }
+
+    /// The partial problems listed at the top of the page, empty if it failed outright
+    #[must_use]
+    pub fn banner(&self) -> Vec<String> {
+        if self.error.is_some() {
+            return Vec::new();
+        }
+        let links = self
+            .broken_anchors
+            .iter()
+            .map(|url| format!("link to a missing heading: {url}"));
+        self.problems.iter().cloned().chain(links).collect()
+    }
+
+    /// Replaces the html with a list of the page's partial problems, if it has any
+    fn fail_strict(&mut self) {
+        let problems = self.banner();
+        if problems.is_empty() {
+            return;
+        }
+        self.html = String::from("<p><strong>This page failed to render:</strong></p>\n<ul>\n");
+        for problem in &problems {
+            self.html.push_str("<li>");
+            let _ = escape_html(&mut self.html, problem);
+            self.html.push_str("</li>\n");
+        }
+        self.html.push_str("</ul>\n");
+        // the explanation is small enough to cache
+        self.streamed = false;
+        self.error = Some(format!("failed in strict mode: {}", problems.join("; ")));
+    }
+
+    /// Records warnings for the page at `key` if it failed or links to missing headings
+    pub fn report(&self, key: &str, warnings: &Warnings) {
+        if let Some(error) = &self.error {
+            warnings.push(Category::Render, key, error.as_str());
+        }
+        if self.problems.is_empty() {
+            warnings.resolve(Category::Problem, key);
+        } else {
+            warnings.push(Category::Problem, key, self.problems.join("; "));
+        }
+        if self.streamed {
+            let message = "the rendered html is over the size limit, it is streamed instead";
+            warnings.push(Category::Oversized, key, message);
+        } else {
+            warnings.resolve(Category::Oversized, key);
+        }
+        if self.broken_anchors.is_empty() {
+            warnings.resolve(Category::Anchor, key);
+        } else {
+            let links = self.broken_anchors.join(", ");
+            warnings.push(
+                Category::Anchor,
+                key,
+                format!("links to missing headings: {links}"),
+            );
+        }
+        if self.dead_links.is_empty() {
+            warnings.resolve(Category::DeadLink, key);
+        } else {
+            let links = self.dead_links.join(", ");
+            warnings.push(
+                Category::DeadLink,
+                key,
+                format!("links to unreachable sites: {links}"),
+            );
+        }
+    }
+}
+
+impl Targets for DashMap<String, Page> {
+    fn has_anchor(&self, key: &str, id: &str) -> Option<bool> {
+        let page = self.get(key)?;
+        page.anchors.as_ref().map(|anchors| anchors.contains(id))
+    }
+}
+
+/// Pages along with the results of checking links to other sites, and the files of base
+struct PageTargets<'a> {
+    md: &'a DashMap<String, Page>,
+    /// the server, which knows about dead links and the glossary
+    live: &'a dyn Targets,
+    base: &'a Path,
+    extensions: &'a Extensions,
+    revision: Option<&'a Revision>,
+}
+
+impl Targets for PageTargets<'_> {
+    fn has_anchor(&self, key: &str, id: &str) -> Option<bool> {
+        self.md.has_anchor(key, id)
+    }
+
+    fn dead_link(&self, url: &str) -> Option<String> {
+        self.live.dead_link(url)
+    }
+
+    fn resolve_include(&self, key: &str, path: &str) -> anyhow::Result<PathBuf> {
+        resolve_include(self.base, key, path)
+    }
+
+    fn read_include(&self, path: &Path) -> anyhow::Result<String> {
+        match self.revision {
+            Some(revision) => revision.read(path),
+            None => Ok(fs::read_to_string(path)?),
+        }
+    }
+
+    fn glossary(&self) -> Option<Arc<Glossary>> {
+        self.live.glossary()
+    }
+
+    fn extensions(&self) -> Cow<'_, Extensions> {
+        Cow::Borrowed(self.extensions)
+    }
+}
+
+impl Targets for Api {
+    fn has_anchor(&self, key: &str, id: &str) -> Option<bool> {
+        self.md.has_anchor(key, id)
+    }
+
+    fn dead_link(&self, url: &str) -> Option<String> {
+        self.external.dead(url)
+    }
+
+    fn resolve_include(&self, key: &str, path: &str) -> anyhow::Result<PathBuf> {
+        resolve_include(&self.base.unlock(), key, path)
+    }
+
+    fn read_include(&self, path: &Path) -> anyhow::Result<String> {
+        self.read_source(path)
+    }
+
+    fn glossary(&self) -> Option<Arc<Glossary>> {
+        self.glossary.unlock().clone()
+    }
+
+    fn extensions(&self) -> Cow<'_, Extensions> {
+        Cow::Borrowed(&self.settings.extensions)
+    }
+}
+
+/// Reads the template in effect, warning about its mismatches, see [`Template::check`]
+///
+/// Fails on mismatches with [`Settings::strict_template`].
+fn read_template(settings: &Settings, warnings: &Warnings) -> anyhow::Result<Template> {
+    let template = match &settings.template {
+        Some(path) => {
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("unable to read {}", path.display()))?;
+            Template::parse(&source, settings.status_indicator)
+                .with_context(|| format!("invalid template {}", path.display()))?
+        }
+        None => Template::new(settings.status_indicator),
+    };
+    let template = template
+        .with_theme(settings.theme)
+        .with_custom_css(settings.css.is_some());
+    let mismatches = template.check(settings);
+    if let Some(mismatch) = mismatches.first().filter(|_| settings.strict_template) {
+        bail!("template mismatch: {mismatch}");
+    }
+    warnings.resolve(Category::Template, "template");
+    for mismatch in mismatches {
+        warnings.push(Category::Template, "template", mismatch.to_string());
+    }
+    Ok(template)
+}
+
+/// Reads the glossary at `path`, see [`Settings::glossary`]
+fn read_glossary(path: &Path, case_sensitive: bool) -> anyhow::Result<Glossary> {
+    let text = fs::read_to_string(path)?;
+    Ok(Glossary::parse(&normalize_source(&text), case_sensitive))
+}
+
+/// Resolves `path`, relative to the page at `key`, into a file within `base`
+///
+/// Paths starting with `/` are relative to base. When base is a single file, its
+/// directory is used instead.
+pub fn resolve_include(base: &Path, key: &str, path: &str) -> anyhow::Result<PathBuf> {
+    let root = if base.is_file() {
+        base.parent().unwrap_or(base)
+    } else {
+        base
+    };
+    let segments =
+        render::resolve_path(key, path).with_context(|| format!("{path} is outside of base"))?;
+    let file = root.join(segments.join("/"));
+    // symlinks may still lead out of base
+    match file.canonicalize() {
+        Ok(canonical) if !canonical.starts_with(root) => bail!("{path} is outside of base"),
+        Ok(canonical) => Ok(canonical),
+        Err(_) => Ok(file),
+    }
+}
+
+/// Everything a [`Page`]'s html depends on
+///
+/// A page whose key differs from [`Api::cache_key`] is stale and must be rerendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The hash of the markdown source, see [`hash_source`]
+    ///
+    /// Used to cheaply detect stale content, see [`handle_hash`].
+    pub source: u64,
+    /// See [`render::Config::fingerprint`]
+    pub options: u64,
+    /// See [`Api::render_generation`]
+    pub generation: u64,
+}
+
+/// How a page changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Modified => "modified",
+            Self::Removed => "removed",
+        }
+    }
+}
+
+/// What caused a [`Page`] to be rendered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderKind {
+    /// The initial scan of base
+    #[default]
+    Scan,
+    /// A file change seen by the watcher
+    Watch,
+}
+
+impl RenderKind {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Scan => "scan",
+            Self::Watch => "watch",
+        }
+    }
+}
+
+/// Information about a single [`Page`], see [`handle_file`]
+#[derive(Debug, Clone, Serialize)]
+pub struct PageInfo {
+    pub key: String,
+    pub title: String,
+    pub path: PathBuf,
+    pub hash: String,
+    pub render_micros: u128,
+    pub render_kind: RenderKind,
+}
+
+/// Everything known about a single [`Page`], see [`Api::inspect`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Inspection {
+    pub key: String,
+    pub title: String,
+    pub path: PathBuf,
+    /// The size of the source file in bytes, `None` if it can no longer be read
+    pub size: Option<u64>,
+    /// When the source file was last modified
+    pub modified: Option<time::Timestamp>,
+    pub hash: String,
+    /// The length of the cached html, zero for streamed pages
+    pub html_size: usize,
+    pub render_micros: u128,
+    pub render_kind: RenderKind,
+    /// See [`CacheKey::generation`]
+    pub render_generation: u64,
+    /// Whether the page will be rerendered when next requested, see [`CacheKey`]
+    pub stale: bool,
+    pub headings: usize,
+    pub links: Vec<InspectedLink>,
+    /// The pages linking to this one with a fragment
+    pub referrers: Vec<String>,
+    /// The aliases pointing to this page
+    pub aliases: Vec<String>,
+    /// See [`Page::features`]
+    pub features: Vec<String>,
+    pub front_matter: Option<FrontMatter>,
+    pub error: Option<String>,
+    pub streamed: bool,
+    pub warnings: Vec<warnings::Warning>,
+}
+
+/// A page as [`Api::pages`] lists it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListedPage {
+    pub key: String,
+    pub title: String,
+    /// The markdown source file
+    pub path: PathBuf,
+}
+
+/// What [`handle_meta`] tells about a page
+#[derive(Debug, Clone, Serialize)]
+pub struct Meta {
+    pub key: String,
+    pub path: PathBuf,
+    /// The size of the source file in bytes, `None` if it can no longer be read
+    pub size: Option<u64>,
+    /// When the source file was last modified
+    pub modified: Option<time::Timestamp>,
+    pub title: String,
+    /// See [`render::Summary::outline`]
+    pub headings: Vec<render::Heading>,
+    /// See [`render::Summary::words`]
+    pub words: usize,
+    /// See [`render::reading_minutes`]
+    pub reading_minutes: usize,
+    pub front_matter: Option<FrontMatter>,
+}
+
+/// A link found on an inspected page
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectedLink {
+    pub url: String,
+    /// Whether the link points to a heading that doesn't exist
+    pub broken: bool,
+}
+
+/// The pages around another in listing order, see [`Api::nav`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Nav {
+    pub key: String,
+    pub prev: Option<NavLink>,
+    pub next: Option<NavLink>,
+    /// The index page of the nearest directory above, or the chapter the summary nests
+    /// the page within
+    pub parent: Option<NavLink>,
+    /// The first page within the directory, for index pages, or the first chapter the
+    /// summary nests within the page
+    pub first_child: Option<NavLink>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NavLink {
+    pub key: String,
+    pub title: String,
+}
+
+/// How a rescan would change the served keys, see [`Api::preview_scan`]
+#[derive(Debug, Default, Clone)]
+pub struct ScanPreview {
+    /// Keys that would be added, sorted
+    pub added: Vec<String>,
+    /// Keys that would be removed, sorted
+    pub removed: Vec<String>,
+    /// The number of keys that would stay
+    pub kept: usize,
+}
+
+/// Render durations aggregated over every page
+#[derive(Debug, Default, Clone)]
+pub struct RenderStats {
+    pub pages: usize,
+    /// Pages rendered with outdated settings, see [`CacheKey`]
+    pub stale: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// The slowest pages, slowest first
+    pub slowest: Vec<(String, Duration, RenderKind)>,
+    /// Pages with a compressed copy, see [`Page::gzip`]
+    pub compressed: usize,
+    /// The size of every compressed copy, in bytes
+    pub compressed_bytes: usize,
+}
+
+/// A named slot within a [`Template`], written as `{{name}}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Placeholder {
+    /// The rendered markdown
+    Md,
+    /// The html escaped page title
+    Title,
+    /// The update generation the page was rendered at
+    Generation,
+    /// The connection status indicator, unless disabled
+    Status,
+    /// The html escaped key of the page, stable across reloads
+    Page,
+    /// Description and open graph tags for link previews
+    Meta,
+    /// A dismissible list of the page's partial problems, see [`Page::banner`]
+    Banner,
+    /// The page's table of contents, see [`render::Config::toc`]
+    Toc,
+    /// The html escaped classes the page's front matter gives it, see [`Page::class`]
+    Class,
+    /// The number of words on the page, see [`Page::words`]
+    WordCount,
+    /// About how long reading the page takes, as in `3 min read`
+    ReadingTime,
+    /// A footer telling when the source file was last modified, see [`Page::modified`]
+    Modified,
+    /// Links to the stylesheets given on the command line, see [`Settings::css`]
+    Styles,
+}
+
+impl Placeholder {
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        use Placeholder::*;
+        match name {
+            "md" => Some(Md),
+            "title" => Some(Title),
+            "generation" => Some(Generation),
+            "status" => Some(Status),
+            "page" => Some(Page),
+            "meta" => Some(Meta),
+            "banner" => Some(Banner),
+            "toc" => Some(Toc),
+            "class" => Some(Class),
+            "word_count" => Some(WordCount),
+            "reading_time" => Some(ReadingTime),
+            "modified" => Some(Modified),
+            "styles" => Some(Styles),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        use Placeholder::*;
+        match self {
+            Md => "md",
+            Title => "title",
+            Generation => "generation",
+            Status => "status",
+            Page => "page",
+            Meta => "meta",
+            Banner => "banner",
+            Toc => "toc",
+            Class => "class",
+            WordCount => "word_count",
+            ReadingTime => "reading_time",
+            Modified => "modified",
+            Styles => "styles",
+        }
+    }
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{{{}}}}}", self.name())
+    }
+}
+
+/// A placeholder that disagrees with the enabled features, see [`Template::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateMismatch {
+    /// The placeholder's feature is disabled, so it always renders empty, and why
+    AlwaysEmpty(Placeholder, &'static str),
+    /// The named feature is enabled, but its placeholder is missing
+    Unplaced(Placeholder, &'static str),
+    /// Not a placeholder mdflc knows, left in pages as is
+    Unknown(String),
+}
+
+impl fmt::Display for TemplateMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlwaysEmpty(slot, reason) => write!(f, "{slot} always renders empty, {reason}"),
+            Self::Unplaced(slot, feature) => {
+                write!(
+                    f,
+                    "{feature} is enabled, but there is no {slot} to put it in"
+                )
+            }
+            Self::Unknown(name) => write!(f, "{{{{{name}}}}} is unknown and is left as is"),
+        }
+    }
+}
+
+/// The per-page values filled into a [`Template`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Slots<'a> {
+    pub md: &'a str,
+    pub title: &'a str,
+    pub page: &'a str,
+    pub generation: u64,
+    /// Shown in link previews, left out when empty
+    pub description: &'a str,
+    pub image: Option<&'a str>,
+    /// Left out when empty
+    pub banner: &'a [String],
+    /// See [`Page::toc`]
+    pub toc: &'a str,
+    /// See [`Page::class`]
+    pub class: &'a str,
+    /// See [`Page::words`], left out when zero
+    pub words: usize,
+    /// See [`Page::modified`]
+    pub modified: Option<time::Timestamp>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Slot(Placeholder),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+    /// `{{name}}`s that aren't placeholders, see [`TemplateMismatch::Unknown`]
+    unknown: Vec<String>,
+    /// whether [`Placeholder::Status`] is filled in
+    status_indicator: bool,
+    /// whether [`Placeholder::Styles`] links [`CUSTOM_CSS`]
+    custom_css: bool,
+    /// the bundled stylesheet [`Placeholder::Styles`] links
+    theme: Theme,
+    /// whether this is the index.html included with the binary
+    embedded: bool,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Template {
+    /// The index.html included with the binary
+    #[must_use]
+    pub fn new(status_indicator: bool) -> Self {
+        let Ok(template) = Self::parse(INDEX_HTML, status_indicator) else {
+            unreachable!("the index.html included with the binary is invalid");
+        };
+        Self {
+            embedded: true,
+            ..template
+        }
+    }
+
+    /// Splits `source` at its placeholders, failing if it has no `{{md}}`
+    pub fn parse(source: &str, status_indicator: bool) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut unknown: Vec<String> = Vec::new();
+        let mut rest = source;
+        let push_text = |parts: &mut Vec<Part>, text: &str| match parts.last_mut() {
+            Some(Part::Text(last)) => last.push_str(text),
+            _ => parts.push(Part::Text(text.to_owned())),
+        };
+
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+            let name = &rest[start + 2..start + len];
+            let Some(slot) = Placeholder::parse(name) else {
+                if !name.contains("{{") && !unknown.iter().any(|u| u == name) {
+                    unknown.push(name.to_owned());
+                }
+                push_text(&mut parts, &rest[..start + 2]);
+                rest = &rest[start + 2..];
+                continue;
+            };
+            push_text(&mut parts, &rest[..start]);
+            parts.push(Part::Slot(slot));
+            rest = &rest[start + len + 2..];
+        }
+        push_text(&mut parts, rest);
+
+        let template = Self {
+            parts,
+            unknown,
+            status_indicator,
+            custom_css: false,
+            theme: Theme::default(),
+            embedded: false,
+        };
+        ensure!(
+            template.placeholders().contains(&Placeholder::Md),
+            "the template has no {} placeholder for the page's content",
+            Placeholder::Md,
+        );
+        Ok(template)
+    }
+
+    /// Links [`CUSTOM_CSS`] from every page, see [`Settings::css`]
+    #[must_use]
+    pub const fn with_custom_css(mut self, custom_css: bool) -> Self {
+        self.custom_css = custom_css;
+        self
+    }
+
+    /// Links the bundled stylesheet of `theme` from every page, see [`Settings::theme`]
+    #[must_use]
+    pub const fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    #[must_use]
+    pub const fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Every placeholder found in the template, in order of first appearance
+    #[must_use]
+    pub fn placeholders(&self) -> Vec<Placeholder> {
+        let mut found = Vec::new();
+        for part in &self.parts {
+            if let Part::Slot(slot) = part {
+                if !found.contains(slot) {
+                    found.push(*slot);
+                }
+            }
+        }
+        found
+    }
+
+    /// Finds placeholders that always render empty under `settings`, enabled features
+    /// without a placeholder, and unknown placeholders
+    ///
+    /// The embedded template is made to work with every feature, so its placeholders of
+    /// disabled features aren't reported.
+    #[must_use]
+    pub fn check(&self, settings: &Settings) -> Vec<TemplateMismatch> {
+        use Placeholder::*;
+        let features = [
+            (Title, true, "the page title", ""),
+            (Generation, true, "live reload", ""),
+            (Page, true, "live reload", ""),
+            (Meta, true, "link previews", ""),
+            (
+                Status,
+                settings.status_indicator,
+                "the live reload status indicator",
+                "--no-status-indicator is set",
+            ),
+            (
+                Banner,
+                !settings.strict,
+                "the partial problem banner",
+                "--strict fails such pages instead",
+            ),
+        ];
+        let found = self.placeholders();
+        let mut mismatches = Vec::new();
+        for (slot, enabled, feature, reason) in features {
+            match (found.contains(&slot), enabled) {
+                (true, false) if !self.embedded => {
+                    mismatches.push(TemplateMismatch::AlwaysEmpty(slot, reason));
+                }
+                (false, true) => mismatches.push(TemplateMismatch::Unplaced(slot, feature)),
+                _ => (),
+            }
+        }
+        // pages can turn on their own table of contents, so it is only ever unplaced
+        if settings.render.toc && !found.contains(&Toc) {
+            mismatches.push(TemplateMismatch::Unplaced(Toc, "the table of contents"));
+        }
+        // index.css is light enough on its own, so a theme is only ever unplaced
+        let styled = settings.css.is_some() || settings.theme != Theme::Light;
+        if styled && !found.contains(&Styles) {
+            let feature = "the theme or custom stylesheet";
+            mismatches.push(TemplateMismatch::Unplaced(Styles, feature));
+        }
+        let unknown = self.unknown.iter().cloned();
+        mismatches.extend(unknown.map(TemplateMismatch::Unknown));
+        mismatches
+    }
+
+    #[must_use]
+    pub fn html(&self, slots: &Slots) -> String {
+        let mut html = String::with_capacity(INDEX_HTML.len() + slots.md.len());
+        self.write_parts(&mut html, &self.parts, slots);
+        html
+    }
+
+    /// Fills in everything before and after the markdown, for html sent in pieces
+    #[must_use]
+    pub fn split(&self, slots: &Slots) -> (String, String) {
+        let md = self
+            .parts
+            .iter()
+            .position(|p| matches!(p, Part::Slot(Placeholder::Md)))
+            .unwrap_or(self.parts.len());
+        let (mut head, mut tail) = (String::new(), String::new());
+        self.write_parts(&mut head, &self.parts[..md], slots);
+        self.write_parts(
+            &mut tail,
+            self.parts.get(md + 1..).unwrap_or_default(),
+            slots,
+        );
+        (head, tail)
+    }
+
+    fn write_parts(&self, html: &mut String, parts: &[Part], slots: &Slots) {
+        for part in parts {
+            match part {
+                Part::Text(s) => html.push_str(s),
+                Part::Slot(Placeholder::Md) => html.push_str(slots.md),
+                Part::Slot(Placeholder::Title) => {
+                    let _ = escape_html(&mut *html, slots.title);
+                }
+                Part::Slot(Placeholder::Generation) => {
+                    html.push_str(&slots.generation.to_string());
+                }
+                Part::Slot(Placeholder::Status) if self.status_indicator => {
+                    html.push_str(STATUS_HTML);
+                }
+                Part::Slot(Placeholder::Styles) => self.write_styles(html),
+                Part::Slot(Placeholder::Status) => (),
+                Part::Slot(Placeholder::Page) => {
+                    let _ = escape_html(&mut *html, slots.page);
+                }
+                Part::Slot(Placeholder::Meta) => write_meta(html, slots),
+                Part::Slot(Placeholder::Banner) => write_banner(html, slots.banner),
+                Part::Slot(Placeholder::Toc) => html.push_str(slots.toc),
+                Part::Slot(Placeholder::Class) => {
+                    let _ = escape_html(&mut *html, slots.class);
+                }
+                Part::Slot(Placeholder::WordCount | Placeholder::ReadingTime)
+                    if slots.words == 0 => {}
+                Part::Slot(Placeholder::WordCount) => html.push_str(&slots.words.to_string()),
+                Part::Slot(Placeholder::ReadingTime) => {
+                    html.push_str(&render::reading_minutes(slots.words).to_string());
+                    html.push_str(" min read");
+                }
+                Part::Slot(Placeholder::Modified) => write_modified(html, slots.modified),
+            }
+        }
+    }
+
+    /// Links the theme's stylesheet, both with media queries for [`Theme::Auto`], then
+    /// the custom one
+    fn write_styles(&self, html: &mut String) {
+        let link = |html: &mut String, href: &str, media: Option<&str>| {
+            html.push_str("<link href=\"/");
+            html.push_str(href);
+            html.push_str("\" rel=\"stylesheet\"");
+            if let Some(media) = media {
+                html.push_str(" media=\"");
+                html.push_str(media);
+                html.push('"');
+            }
+            html.push_str(" />");
+        };
+        match self.theme {
+            Theme::Light => link(html, "theme-light.css", None),
+            Theme::Dark => link(html, "theme-dark.css", None),
+            Theme::Auto => {
+                link(
+                    html,
+                    "theme-light.css",
+                    Some("(prefers-color-scheme: light)"),
+                );
+                link(html, "theme-dark.css", Some("(prefers-color-scheme: dark)"));
+            }
+        }
+        if self.custom_css {
+            link(html, CUSTOM_CSS, None);
+        }
+    }
+
+    #[must_use]
+    pub fn not_found(&self, generation: u64, custom: Option<&str>) -> String {
+        self.html(&Slots {
+            md: custom.unwrap_or(NOT_FOUND_HTML),
+            title: "Not Found",
+            page: NOT_FOUND_KEY,
+            generation,
+            ..Slots::default()
+        })
+    }
+}
+
+/// Writes when a page's source was last modified, unless that is unknown
+///
+/// Pages are cached and revalidated, so the time relative to now is left for the
+/// client to fill in.
+fn write_modified(html: &mut String, modified: Option<time::Timestamp>) {
+    let Some(modified) = modified else {
+        return;
+    };
+    let exact = modified.rfc3339();
+    html.push_str("<footer class=\"modified\">Last modified <time datetime=\"");
+    html.push_str(&exact);
+    html.push_str("\">");
+    html.push_str(&exact);
+    html.push_str("</time></footer>");
+}
+
+/// Writes the link preview tags for a page, escaping every value
+fn write_meta(html: &mut String, slots: &Slots) {
+    let mut sep = "";
+    let mut tag = |attr: &str, name: &str, content: &str| {
+        html.push_str(sep);
+        sep = "\n    ";
+        html.push_str("<meta ");
+        html.push_str(attr);
+        html.push_str("=\"");
+        html.push_str(name);
+        html.push_str("\" content=\"");
+        let _ = escape_html(&mut *html, content);
+        html.push_str("\" />");
+    };
+    tag("property", "og:title", slots.title);
+    if !slots.description.is_empty() {
+        tag("name", "description", slots.description);
+        tag("property", "og:description", slots.description);
+    }
+    if let Some(image) = slots.image {
+        tag("property", "og:image", image);
+    }
+}
+
+/// Writes the banner listing a page's partial problems, if it has any
+fn write_banner(html: &mut String, problems: &[String]) {
+    if problems.is_empty() {
+        return;
+    }
+    html.push_str(
+        "<aside class=\"problems\" role=\"alert\">\
+        <button type=\"button\" class=\"problems-dismiss\" aria-label=\"Dismiss\">&times;</button>\
+        <strong>Parts of this page failed to render:</strong><ul>",
+    );
+    for problem in problems {
+        html.push_str("<li>");
+        let _ = escape_html(&mut *html, problem);
+        html.push_str("</li>");
     }
+    html.push_str("</ul></aside>");
 }
 
 pub trait MutexExt<'a, T: ?Sized> {
@@ -0,0 +1,53 @@
+use axum::http::{header::ACCEPT, HeaderMap};
+
+/// How a page is sent, see [`format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The rendered page, for browsers
+    Html,
+    /// The markdown source, for tools like `curl`
+    Markdown,
+}
+
+/// Media types answered with the markdown source
+const MARKDOWN_TYPES: [&str; 3] = ["text/markdown", "text/x-markdown", "text/plain"];
+/// Media types answered with html, wildcards included as browsers send them
+const HTML_TYPES: [&str; 4] = ["text/html", "application/xhtml+xml", "text/*", "*/*"];
+
+/// Picks the format asked for by the `format` query parameter, or else by `Accept`
+///
+/// The markdown source is only sent when `Accept` prefers one of [`MARKDOWN_TYPES`] over
+/// html, so browsers and requests without the header get html.
+/// Returns the parameter back if it is neither `md` nor `html`.
+pub fn format<'a>(param: Option<&'a str>, headers: &HeaderMap) -> Result<Format, &'a str> {
+    match param {
+        Some("md" | "markdown") => return Ok(Format::Markdown),
+        Some("html") => return Ok(Format::Html),
+        Some(other) => return Err(other),
+        None => {}
+    }
+    let (mut markdown, mut html) = (0.0, 0.0);
+    let ranges = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for range in ranges {
+        let mut parts = range.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+            .unwrap_or(0.0);
+        if MARKDOWN_TYPES.contains(&name.as_str()) {
+            markdown = f32::max(markdown, quality);
+        } else if HTML_TYPES.contains(&name.as_str()) {
+            html = f32::max(html, quality);
+        }
+    }
+    Ok(if markdown > html {
+        Format::Markdown
+    } else {
+        Format::Html
+    })
+}
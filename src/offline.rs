@@ -0,0 +1,60 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use serde::Serialize;
+
+use crate::{FAVICON, INDEX_CSS, INDEX_JS, THEME_DARK_CSS, THEME_LIGHT_CSS};
+
+/// The service worker `index.js` registers with `--offline`
+pub const SERVICE_WORKER: &str = include_str!("../client/sw.js");
+
+/// Everything the service worker may cache, see [`crate::Api::manifest`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    /// The update generation the manifest was made at
+    pub generation: u64,
+    /// How many bytes of pages to fetch ahead of time, `None` to only cache visited pages
+    pub prefetch: Option<u64>,
+    pub pages: Vec<Entry>,
+    pub assets: Vec<Entry>,
+}
+
+/// A url the service worker caches, with a hash that changes along with its content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub url: String,
+    pub hash: String,
+    /// The size of the body in bytes, pages are templated so theirs is approximate
+    pub size: usize,
+}
+
+impl Entry {
+    pub fn new(url: String, content: impl Hash, size: usize) -> Self {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            url,
+            hash: format!("{:016x}", hasher.finish()),
+            size,
+        }
+    }
+}
+
+/// The files served along with every page
+#[must_use]
+pub fn assets() -> Vec<Entry> {
+    vec![
+        Entry::new("/index.css".to_owned(), INDEX_CSS, INDEX_CSS.len()),
+        Entry::new(
+            "/theme-light.css".to_owned(),
+            THEME_LIGHT_CSS,
+            THEME_LIGHT_CSS.len(),
+        ),
+        Entry::new(
+            "/theme-dark.css".to_owned(),
+            THEME_DARK_CSS,
+            THEME_DARK_CSS.len(),
+        ),
+        Entry::new("/index.js".to_owned(), INDEX_JS, INDEX_JS.len()),
+        Entry::new("/favicon.ico".to_owned(), FAVICON, FAVICON.len()),
+    ]
+}
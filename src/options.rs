@@ -0,0 +1,65 @@
+use pulldown_cmark::Options;
+use serde::Serializer;
+
+/// The names of every configurable markdown extension
+pub const MD_OPTIONS: &[(&str, Options)] = &[
+    ("tables", Options::ENABLE_TABLES),
+    ("footnotes", Options::ENABLE_FOOTNOTES),
+    ("strikethrough", Options::ENABLE_STRIKETHROUGH),
+    ("tasklists", Options::ENABLE_TASKLISTS),
+    ("smart_punctuation", Options::ENABLE_SMART_PUNCTUATION),
+    ("heading_attributes", Options::ENABLE_HEADING_ATTRIBUTES),
+    ("yaml_metadata", Options::ENABLE_YAML_STYLE_METADATA_BLOCKS),
+    (
+        "pluses_metadata",
+        Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+    ),
+    ("old_footnotes", Options::ENABLE_OLD_FOOTNOTES),
+    ("math", Options::ENABLE_MATH),
+    ("gfm", Options::ENABLE_GFM),
+];
+
+/// Finds the option with the given name
+#[must_use]
+pub fn parse_option(name: &str) -> Option<Options> {
+    MD_OPTIONS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, option)| *option)
+}
+
+/// Returns every option name, comma separated
+#[must_use]
+pub fn option_names() -> String {
+    MD_OPTIONS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Applies a list of overrides such as `-smart_punctuation,+tables`
+///
+/// Options without a sign are enabled. On failure, the unknown name is returned.
+pub fn apply_overrides(mut options: Options, overrides: &str) -> Result<Options, String> {
+    for o in overrides
+        .split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+    {
+        let (enable, name) = match o.as_bytes()[0] {
+            b'-' => (false, &o[1..]),
+            b'+' => (true, &o[1..]),
+            _ => (true, o),
+        };
+        let option = parse_option(name).ok_or_else(|| name.to_owned())?;
+        options.set(option, enable);
+    }
+    Ok(options)
+}
+
+/// Serializes the names of every enabled option
+pub fn serialize<S: Serializer>(options: &Options, serializer: S) -> Result<S::Ok, S::Error> {
+    let enabled = MD_OPTIONS.iter().filter(|(_, o)| options.contains(*o));
+    serializer.collect_seq(enabled.map(|(name, _)| name))
+}
@@ -0,0 +1,103 @@
+use std::{cmp::Ordering, collections::HashMap, iter::Peekable, str::Chars};
+
+/// The file listing a directory's order, one name per line
+pub const ORDER_FILE: &str = "_order.txt";
+
+/// Explicit orders of directories, keyed like pages with `""` for base
+///
+/// Names listed for a directory come first, in the order listed, followed by the rest.
+#[derive(Debug, Default, Clone)]
+pub struct Overrides(HashMap<String, Vec<String>>);
+
+impl Overrides {
+    /// Sets the order of `dir`, names may be of pages or directories, with or without `.md`
+    pub fn insert(&mut self, dir: &str, names: Vec<String>) {
+        let names = names
+            .into_iter()
+            .map(|name| {
+                let name = name.trim().trim_end_matches('/');
+                name.strip_suffix(".md").unwrap_or(name).to_owned()
+            })
+            .collect();
+        self.0.insert(dir.to_owned(), names);
+    }
+
+    fn position(&self, dir: &str, name: &str) -> Option<usize> {
+        self.0.get(dir)?.iter().position(|n| n == name)
+    }
+}
+
+/// Reads the names listed in an [`ORDER_FILE`], skipping blank lines and `#` comments
+#[must_use]
+pub fn parse_order_file(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Orders two page keys the way every listing should
+///
+/// Keys are compared segment by segment. Within a directory, names listed in its
+/// [`Overrides`] come first, then directories before pages, then [`natural_cmp`].
+#[must_use]
+pub fn compare(a: &str, b: &str, overrides: &Overrides) -> Ordering {
+    let a_segments: Vec<_> = a.split('/').collect();
+    let b_segments: Vec<_> = b.split('/').collect();
+    for (i, (sa, sb)) in a_segments.iter().zip(&b_segments).enumerate() {
+        let a_dir = i + 1 < a_segments.len();
+        let b_dir = i + 1 < b_segments.len();
+        if sa == sb && a_dir == b_dir {
+            continue;
+        }
+        let dir = a_segments[..i].join("/");
+        let explicit = match (overrides.position(&dir, sa), overrides.position(&dir, sb)) {
+            (Some(pa), Some(pb)) => pa.cmp(&pb),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        return explicit
+            .then(b_dir.cmp(&a_dir))
+            .then_with(|| natural_cmp(sa, sb));
+    }
+    a_segments.len().cmp(&b_segments.len())
+}
+
+/// Compares strings case-insensitively, with runs of digits compared by their value
+///
+/// `2-foo` comes before `10-bar`. Strings equal apart from case or leading zeros
+/// are ordered by their bytes, so the order is total.
+#[must_use]
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let (na, nb) = (digits(&mut a_chars), digits(&mut b_chars));
+                let (na, nb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                na.len().cmp(&nb.len()).then_with(|| na.cmp(nb))
+            }
+            (Some(&ca), Some(&cb)) => {
+                a_chars.next();
+                b_chars.next();
+                ca.to_lowercase().cmp(cb.to_lowercase())
+            }
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
+fn digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
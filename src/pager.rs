@@ -0,0 +1,110 @@
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{
+        self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use easy_sgr::{Color::*, Style::*};
+
+/// Shows `lines`, paging them if they don't fit on screen
+///
+/// Falls back to printing when paging is disabled or stdout isn't a terminal.
+pub fn page(lines: &[String], enabled: bool) -> io::Result<()> {
+    let rows = terminal::size().map_or(0, |(_, rows)| usize::from(rows));
+    if !enabled || !io::stdout().is_terminal() || lines.len() < rows || rows == 0 {
+        for line in lines {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    let _guard = ScreenGuard::enter()?;
+    Pager { lines, top: 0 }.run()
+}
+
+/// Restores the terminal when dropped, including while panicking
+struct ScreenGuard;
+
+impl ScreenGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // from here on the guard is responsible for cleanup
+        let guard = Self;
+        execute!(io::stdout(), EnterAlternateScreen, DisableLineWrap, Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, EnableLineWrap, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Pager<'a> {
+    lines: &'a [String],
+    top: usize,
+}
+
+impl Pager<'_> {
+    fn run(&mut self) -> io::Result<()> {
+        loop {
+            let height = Self::height()?;
+            self.draw(height)?;
+
+            let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            }) = event::read()?
+            else {
+                continue;
+            };
+
+            let bottom = self.lines.len().saturating_sub(height);
+            self.top = match code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Up | KeyCode::Char('k') => self.top.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => self.top + 1,
+                KeyCode::PageUp | KeyCode::Char('b') => self.top.saturating_sub(height),
+                KeyCode::PageDown | KeyCode::Char(' ') => self.top + height,
+                KeyCode::Home | KeyCode::Char('g') => 0,
+                KeyCode::End | KeyCode::Char('G') => bottom,
+                _ => self.top,
+            }
+            .min(bottom);
+        }
+        Ok(())
+    }
+
+    /// The number of rows available for lines, leaving one for the status line
+    fn height() -> io::Result<usize> {
+        let (_, rows) = terminal::size()?;
+        Ok(usize::from(rows.saturating_sub(1)).max(1))
+    }
+
+    fn draw(&self, height: usize) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        queue!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        for line in self.lines.iter().skip(self.top).take(height) {
+            queue!(stdout, Print(line), Print(Reset), Print("\r\n"))?;
+        }
+        let end = (self.top + height).min(self.lines.len());
+        let status = format!(
+            "{BlueFg}lines {}-{end} of {}, arrows/pgup/pgdn to move, q to quit{Reset}",
+            self.top + 1,
+            self.lines.len(),
+        );
+        queue!(stdout, Print(status))?;
+        stdout.flush()
+    }
+}
@@ -0,0 +1,1444 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::{self, Write as _},
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, ensure, Context};
+
+use pulldown_cmark::{
+    html::write_html_fmt, BlockQuoteKind, CodeBlockKind, CowStr, Event, HeadingLevel, Options,
+    Parser, Tag, TagEnd, TextMergeStream,
+};
+use pulldown_cmark_escape::{escape_href, escape_html};
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::{
+    assets, autolink, containers, csv, definitions,
+    emoji::Emoji,
+    extensions::Extensions,
+    glossary::Glossary,
+    hooks::Hooks,
+    includes::{self, Expanded},
+    options, sanitize,
+};
+
+/// Information collected from markdown while rendering it
+#[derive(Debug, Default, Clone)]
+pub struct Summary {
+    /// The text of the first level 1 heading
+    pub heading: Option<String>,
+    /// The yaml the page starts with, see [`split_front_matter`]
+    pub front_matter: Option<FrontMatter>,
+    /// Whether the page has math rendered by the client, see [`Config::math`]
+    pub math: bool,
+    /// The plain text of the first paragraph, see [`EXCERPT_LEN`]
+    pub excerpt: String,
+    /// The number of headings of any level
+    pub headings: usize,
+    /// Every heading in order, see [`Heading`]
+    pub outline: Vec<Heading>,
+    /// The number of words outside of code blocks and the front matter
+    pub words: usize,
+    /// The text of the page without markup, code blocks included, see [`crate::search`]
+    pub text: String,
+    /// The destination of every link, in order of appearance
+    pub links: Vec<String>,
+    /// The languages of code blocks rendered by a fence handler, see [`Hooks::fence`]
+    pub fences: Vec<String>,
+    /// The ids links can point to
+    pub anchors: HashSet<String>,
+    /// The other pages linked to with a fragment, see [`Targets`]
+    pub anchor_links: HashSet<String>,
+    /// Links whose fragment matches no id on the page they point to
+    pub broken_anchors: Vec<String>,
+    /// External links that failed their last check, see [`Targets::dead_link`]
+    pub dead_links: Vec<String>,
+    /// Problems that left part of the page degraded, such as a failing fence handler
+    ///
+    /// The rest of the page renders normally.
+    pub problems: Vec<String>,
+    /// Whether the html went over [`Config::max_html_size`] and was dropped
+    pub oversized: bool,
+    /// A nested list of links to every heading, see [`Config::toc`]
+    pub toc: Option<String>,
+    /// The files read into the page, including those that couldn't be read,
+    /// see [`Targets::resolve_include`]
+    pub includes: HashSet<PathBuf>,
+}
+
+impl Summary {
+    /// Picks the title of a page
+    ///
+    /// The front matter title is preferred, then the first level 1 heading,
+    /// then the prettified file `stem`.
+    #[must_use]
+    pub fn title(&self, stem: &str) -> String {
+        self.front_matter
+            .as_ref()
+            .and_then(|front| front.title.as_deref())
+            .or(self.heading.as_deref())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map_or_else(|| prettify_stem(stem), String::from)
+    }
+}
+
+/// How many words a minute [`reading_minutes`] assumes are read
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// About how many minutes reading `words` words takes, rounded up
+#[must_use]
+pub const fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// A heading on a page, see [`Summary::outline`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heading {
+    /// From 1 for `<h1>` to 6
+    pub level: u8,
+    /// The id links point to
+    pub id: String,
+    /// The plain text of the heading, without its number
+    pub text: String,
+}
+
+/// The fields of a page's front matter, see [`split_front_matter`]
+///
+/// Fields besides these are allowed and ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct FrontMatter {
+    /// Preferred over the first heading, see [`Summary::title`]
+    pub title: Option<String>,
+    /// Shown in link previews
+    pub image: Option<String>,
+    /// How the pages next to this one are listed, see [`crate::ordering::Overrides`]
+    pub order: Option<Vec<String>>,
+    pub tags: Vec<String>,
+    /// Overrides [`Config::toc`] for this page
+    pub toc: Option<bool>,
+    /// Overrides [`Config::number_headings`] for this page
+    pub number_headings: Option<bool>,
+    /// Classes for the page's content, see [`FrontMatter::classes`]
+    pub class: Option<String>,
+    /// Also a class for the page's content, as in `layout: wide`
+    pub layout: Option<String>,
+}
+
+impl FrontMatter {
+    /// Parses the yaml between the `---` lines
+    ///
+    /// Scalars are accepted where lists are expected, split at commas.
+    pub fn parse(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        let fields = match serde_yaml::from_str(yaml)? {
+            Value::Mapping(fields) => fields,
+            Value::Null => return Ok(Self::default()),
+            _ => {
+                use serde::de::Error;
+                return Err(serde_yaml::Error::custom("expected a mapping of fields"));
+            }
+        };
+        let field = |name: &str| fields.get(name);
+        Ok(Self {
+            title: field("title").and_then(yaml_scalar),
+            image: field("image").and_then(yaml_scalar),
+            order: field("order").and_then(yaml_list),
+            tags: field("tags").and_then(yaml_list).unwrap_or_default(),
+            toc: field("toc").and_then(Value::as_bool),
+            number_headings: field("number_headings").and_then(Value::as_bool),
+            class: field("class").and_then(yaml_string),
+            layout: field("layout").and_then(yaml_string),
+        })
+    }
+
+    /// The classes the page's content is given in the template's `{{class}}`,
+    /// space separated
+    #[must_use]
+    pub fn classes(&self) -> String {
+        let classes: Vec<_> = [&self.class, &self.layout]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        classes.join(" ")
+    }
+}
+
+/// The text of a string, `None` if it is blank or anything else
+fn yaml_string(value: &Value) -> Option<String> {
+    let text = value.as_str()?.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// The text of a string, number or boolean, `None` if it is blank or anything else
+fn yaml_scalar(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_owned(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn yaml_list(value: &Value) -> Option<Vec<String>> {
+    let list: Vec<_> = match value {
+        Value::Sequence(items) => items.iter().filter_map(yaml_scalar).collect(),
+        value => yaml_scalar(value)?
+            .split(',')
+            .map(|item| item.trim().to_owned())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    };
+    (!list.is_empty()).then_some(list)
+}
+
+/// Splits the front matter from the start of `text`, returning its yaml and the body
+///
+/// Front matter sits between a `---` line at the very start and the next `---` or `...`
+/// line. Like the parser's metadata blocks, it can't start with a blank line, which
+/// leaves a leading thematic break alone.
+#[must_use]
+pub fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let Some((first, rest)) = text.split_once('\n') else {
+        return (None, text);
+    };
+    if first.trim_end() != "---" || rest.starts_with('\n') {
+        return (None, text);
+    }
+    let mut end = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return (Some(&rest[..end]), &rest[end + line.len()..]);
+        }
+        end += line.len();
+    }
+    (None, text)
+}
+
+/// The longest [`Summary::excerpt`], in characters
+pub const EXCERPT_LEN: usize = 200;
+
+#[derive(Debug, Default)]
+struct Collector {
+    summary: Summary,
+    in_h1: bool,
+    in_code_block: bool,
+    excerpt: Excerpt,
+}
+
+/// Progress through the first paragraph
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Excerpt {
+    #[default]
+    Before,
+    Within,
+    After,
+}
+
+impl Collector {
+    fn observe(&mut self, event: &Event) {
+        self.collect_text(event);
+        match event {
+            Event::Start(Tag::Heading { .. }) => self.summary.headings += 1,
+            Event::Text(text) | Event::Code(text) if !self.in_code_block => {
+                self.summary.words += text.split_whitespace().count();
+            }
+            _ => (),
+        }
+        match event {
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) if self.summary.heading.is_none() => {
+                self.in_h1 = true;
+                self.summary.heading = Some(String::new());
+            }
+            Event::End(TagEnd::Heading(HeadingLevel::H1)) => self.in_h1 = false,
+            Event::Start(Tag::CodeBlock(_)) => self.in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => self.in_code_block = false,
+            Event::Start(Tag::Paragraph) if self.excerpt == Excerpt::Before => {
+                self.excerpt = Excerpt::Within;
+            }
+            Event::End(TagEnd::Paragraph) if self.excerpt == Excerpt::Within => {
+                self.excerpt = Excerpt::After;
+                self.summary.excerpt = truncate(self.summary.excerpt.trim(), EXCERPT_LEN);
+            }
+            Event::Text(text) | Event::Code(text) if self.excerpt == Excerpt::Within => {
+                self.summary.excerpt.push_str(text);
+            }
+            Event::SoftBreak | Event::HardBreak if self.excerpt == Excerpt::Within => {
+                self.summary.excerpt.push(' ');
+            }
+            Event::Text(text) | Event::Code(text) if self.in_h1 => {
+                if let Some(heading) = &mut self.summary.heading {
+                    heading.push_str(text);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Collector {
+    /// Adds the text of `event` to [`Summary::text`], separating blocks with a space
+    fn collect_text(&mut self, event: &Event) {
+        match event {
+            Event::Text(text) | Event::Code(text) => self.summary.text.push_str(text),
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::TableCell
+                | TagEnd::CodeBlock,
+            ) => self.summary.text.push(' '),
+            _ => (),
+        }
+    }
+}
+
+/// The default [`Config::max_csv_rows`]
+pub const DEFAULT_MAX_CSV_ROWS: usize = 1000;
+
+/// Settings that change how markdown is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    /// The markdown extensions to enable
+    #[serde(serialize_with = "options::serialize")]
+    pub options: Options,
+    /// What to do with raw html found in markdown
+    pub raw_html: RawHtml,
+    /// The most html a page may render to before it is left to [`stream_page`]
+    pub max_html_size: usize,
+    /// Wrap `$...$` and `$$...$$` in elements the client renders as math with katex
+    pub math: bool,
+    /// End every heading with a `¶` link to itself
+    pub permalinks: bool,
+    /// Replace `:shortcode:`s outside of code with their emoji, see [`crate::emoji`]
+    pub emoji: bool,
+    /// Collect a table of contents for the template's `{{toc}}`, see [`Summary::toc`]
+    ///
+    /// Pages can override this with `toc` in their front matter.
+    pub toc: bool,
+    /// Clean the rendered html down to an allowlist, see [`crate::sanitize`]
+    pub sanitize: bool,
+    /// Render task list checkboxes enabled and numbered, see [`crate::tasks`]
+    pub edit_tasks: bool,
+    /// The most rows a [`CSV_INCLUDE`] fence shows before noting how many were left out
+    pub max_csv_rows: usize,
+    /// Wrap every line of fenced code blocks in a numbered span, see [`number_lines`]
+    pub code_line_numbers: bool,
+    /// Open links to other sites in a new tab, see [`external_blank`]
+    pub external_blank: bool,
+    /// Link bare `http://` and `https://` urls outside of code, see [`crate::autolink`]
+    pub autolink: bool,
+    /// Leave out this crate's own syntax, see [`Config::strict_commonmark`]
+    pub commonmark: bool,
+    /// Break lines at every newline within paragraphs, not only where markdown says to
+    pub hard_breaks: bool,
+    /// Start headings with numbers like `1.2`, see [`number_headings`]
+    ///
+    /// Pages can override this with `number_headings` in their front matter.
+    pub number_headings: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            options: Options::all(),
+            raw_html: RawHtml::default(),
+            max_html_size: usize::MAX,
+            math: false,
+            permalinks: false,
+            toc: false,
+            emoji: false,
+            sanitize: false,
+            edit_tasks: false,
+            max_csv_rows: DEFAULT_MAX_CSV_ROWS,
+            code_line_numbers: false,
+            external_blank: true,
+            autolink: false,
+            commonmark: false,
+            hard_breaks: false,
+            number_headings: false,
+        }
+    }
+}
+
+impl Config {
+    /// These settings, rendering nothing but commonmark
+    ///
+    /// Every markdown extension is disabled, along with the syntax this crate adds, such
+    /// as definition lists, containers, math, emoji and bare links.
+    #[must_use]
+    pub const fn strict_commonmark(self) -> Self {
+        Self {
+            options: Options::empty(),
+            math: false,
+            emoji: false,
+            autolink: false,
+            commonmark: true,
+            ..self
+        }
+    }
+
+    /// Identifies the output these settings produce, see [`crate::CacheKey`]
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// What to do with raw html found in markdown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawHtml {
+    /// Pass it through untouched
+    #[default]
+    Keep,
+    /// Drop it
+    Strip,
+    /// Show it escaped, as inline code
+    Show,
+}
+
+impl RawHtml {
+    fn apply(self, event: Event) -> Option<Event> {
+        match (self, event) {
+            (Self::Strip, Event::Html(_) | Event::InlineHtml(_)) => None,
+            (Self::Show, Event::Html(html) | Event::InlineHtml(html)) => Some(Event::Code(html)),
+            (_, event) => Some(event),
+        }
+    }
+}
+
+/// Renders task lists, footnotes and `> [!NOTE]` callouts the way GitHub does
+#[derive(Debug, Default)]
+struct Gfm {
+    /// footnote labels, numbered in order of first appearance,
+    /// along with how many times each was referenced
+    footnotes: HashMap<String, (usize, usize)>,
+    /// the footnote definition being rendered
+    definition: Option<String>,
+    /// whether task list checkboxes are enabled, see [`Config::edit_tasks`]
+    edit_tasks: bool,
+    /// the number of task list items so far
+    tasks: usize,
+    /// the open blockquotes, innermost last, and whether each is a callout
+    quotes: Vec<bool>,
+}
+
+impl Gfm {
+    fn footnote(&mut self, label: &str) -> &mut (usize, usize) {
+        let next = self.footnotes.len() + 1;
+        self.footnotes.entry(label.to_owned()).or_insert((next, 0))
+    }
+
+    fn map<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        let html = match event {
+            Event::TaskListMarker(checked) => {
+                let mut html =
+                    "<input type=\"checkbox\" class=\"task-list-item-checkbox\"".to_owned();
+                if self.edit_tasks {
+                    let _ = write!(html, " data-task=\"{}\"", self.tasks);
+                } else {
+                    html.push_str(" disabled");
+                }
+                if checked {
+                    html.push_str(" checked");
+                }
+                self.tasks += 1;
+                html.push_str(" /> ");
+                html
+            }
+            Event::FootnoteReference(label) => {
+                let (n, refs) = self.footnote(&label);
+                *refs += 1;
+                let (n, refs) = (*n, *refs);
+                let id = escape(&label);
+                // only the first reference is linked back to
+                let ref_id = if refs == 1 {
+                    format!("fnref-{id}")
+                } else {
+                    format!("fnref-{id}-{refs}")
+                };
+                format!(
+                    "<sup class=\"footnote-reference\" id=\"{ref_id}\">\
+                    <a href=\"#fn-{id}\">{n}</a></sup>"
+                )
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                let n = self.footnote(&label).0;
+                let id = escape(&label);
+                self.definition = Some(label.into_string());
+                format!(
+                    "<div class=\"footnote-definition\" id=\"fn-{id}\">\
+                    <sup class=\"footnote-definition-label\">{n}</sup>\n"
+                )
+            }
+            Event::Start(Tag::BlockQuote(kind)) => {
+                self.quotes.push(kind.is_some());
+                let Some(kind) = kind else {
+                    return Event::Start(Tag::BlockQuote(None));
+                };
+                let (class, title) = callout(kind);
+                format!(
+                    "<div class=\"callout callout-{class}\">\n\
+                    <p class=\"callout-title\">{title}</p>\n"
+                )
+            }
+            Event::End(TagEnd::BlockQuote) => {
+                if !self.quotes.pop().unwrap_or_default() {
+                    return Event::End(TagEnd::BlockQuote);
+                }
+                "</div>\n".to_owned()
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                let id = escape(&self.definition.take().unwrap_or_default());
+                format!(
+                    "<a href=\"#fnref-{id}\" class=\"footnote-backref\" \
+                    aria-label=\"Back to reference\">↩</a></div>\n"
+                )
+            }
+            event => return event,
+        };
+        Event::Html(CowStr::from(html))
+    }
+}
+
+/// The class suffix and title of a GitHub style `> [!NOTE]` callout
+const fn callout(kind: BlockQuoteKind) -> (&'static str, &'static str) {
+    match kind {
+        BlockQuoteKind::Note => ("note", "Note"),
+        BlockQuoteKind::Tip => ("tip", "Tip"),
+        BlockQuoteKind::Important => ("important", "Important"),
+        BlockQuoteKind::Warning => ("warning", "Warning"),
+        BlockQuoteKind::Caution => ("caution", "Caution"),
+    }
+}
+
+/// Gives every heading an id, derived from its text unless one was set explicitly
+#[derive(Debug, Default)]
+struct Anchors {
+    /// ids already taken, along with how many times each was derived
+    used: HashMap<String, usize>,
+}
+
+impl Anchors {
+    fn assign(&mut self, events: &mut [Event]) {
+        // explicit ids are claimed first, so derived ones never collide with them
+        for event in &*events {
+            if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+                self.used.insert(id.to_string(), 0);
+            }
+        }
+        for i in 0..events.len() {
+            if !matches!(events[i], Event::Start(Tag::Heading { id: None, .. })) {
+                continue;
+            }
+            let text: String = events[i + 1..]
+                .iter()
+                .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+                .filter_map(|e| match e {
+                    Event::Text(text) | Event::Code(text) => Some(&**text),
+                    _ => None,
+                })
+                .collect();
+            let slug = self.unique(slugify(&text));
+            if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+                *id = Some(slug.into());
+            }
+        }
+    }
+
+    /// Suffixes repeated slugs with `-1`, `-2` and so on
+    fn unique(&mut self, slug: String) -> String {
+        let Some(count) = self.used.get_mut(&slug) else {
+            self.used.insert(slug.clone(), 0);
+            return slug;
+        };
+        *count += 1;
+        let mut n = *count;
+        loop {
+            let candidate = format!("{slug}-{n}");
+            if !self.used.contains_key(&candidate) {
+                self.used.insert(candidate.clone(), 0);
+                self.used.insert(slug, n);
+                return candidate;
+            }
+            n += 1;
+        }
+    }
+}
+
+/// Turns heading text into an id the way GitHub does, `Hello, World!` becomes `hello-world`
+#[must_use]
+pub fn slugify(text: &str) -> String {
+    let slug: String = text
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '_' || c == '-' => Some(c.to_lowercase()),
+            c if c.is_whitespace() => Some('-'.to_lowercase()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    if slug.is_empty() {
+        "section".to_owned()
+    } else {
+        slug
+    }
+}
+
+/// Lists every heading as a link to its id, nesting lower levels within higher ones
+///
+/// Pages without headings get an empty `nav`.
+fn toc_html(events: &[Event]) -> String {
+    let mut html = String::from("<nav class=\"toc\">");
+    // the levels of the lists still open
+    let mut open: Vec<HeadingLevel> = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        let Event::Start(Tag::Heading { level, id, .. }) = event else {
+            continue;
+        };
+        while open.last().is_some_and(|top| top > level) {
+            html.push_str("</li></ul>");
+            open.pop();
+        }
+        if open.last() == Some(level) {
+            html.push_str("</li><li>");
+        } else {
+            html.push_str("<ul><li>");
+            open.push(*level);
+        }
+        html.push_str("<a href=\"#");
+        let _ = escape_href(&mut html, id.as_deref().unwrap_or_default());
+        html.push_str("\">");
+        let _ = escape_html(&mut html, &heading_text(&events[i + 1..]));
+        html.push_str("</a>");
+    }
+    for _ in open {
+        html.push_str("</li></ul>");
+    }
+    html.push_str("</nav>");
+    html
+}
+
+/// Lists every heading, see [`Summary::outline`]
+fn outline(events: &[Event]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        if let Event::Start(Tag::Heading { level, id, .. }) = event {
+            headings.push(Heading {
+                level: *level as u8,
+                id: id.as_deref().unwrap_or_default().to_owned(),
+                text: heading_text(&events[i + 1..]),
+            });
+        }
+    }
+    headings
+}
+
+/// The text of the heading whose events start `events`
+fn heading_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events
+        .iter()
+        .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+    {
+        if let Event::Text(t) | Event::Code(t) = event {
+            text.push_str(t);
+        }
+    }
+    text
+}
+
+/// Starts every heading with its number within the page, as in `1.`, `1.1` and `1.1.1`
+///
+/// The shallowest heading level on the page is numbered `1.`, `2.` and so on, and each
+/// level below it adds a number. Added once ids are assigned, so they are left out of
+/// ids, but before the table of contents is collected, so they are in it.
+fn number_headings(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let top = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Start(Tag::Heading { level, .. }) => Some(*level as usize),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(1);
+    let mut counts = [0_usize; 6];
+    let mut out = Vec::with_capacity(events.len());
+    for event in events {
+        let Event::Start(Tag::Heading { level, .. }) = &event else {
+            out.push(event);
+            continue;
+        };
+        let depth = *level as usize - top;
+        counts[depth] += 1;
+        counts[depth + 1..].fill(0);
+        let numbers: Vec<_> = counts[..=depth].iter().map(ToString::to_string).collect();
+        let mut number = numbers.join(".");
+        if depth == 0 {
+            number.push('.');
+        }
+        out.push(event);
+        out.push(Event::InlineHtml(CowStr::Borrowed(
+            "<span class=\"heading-number\">",
+        )));
+        out.push(Event::Text(number.into()));
+        out.push(Event::InlineHtml(CowStr::Borrowed("</span>")));
+        out.push(Event::Text(CowStr::Borrowed(" ")));
+    }
+    out
+}
+
+/// Ends every heading with a `¶` link to its id, once ids are assigned
+///
+/// The link is added after the summary is collected, so it never shows up in titles.
+fn permalinks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut id = None;
+    for event in events {
+        match &event {
+            Event::Start(Tag::Heading { id: heading, .. }) => id.clone_from(heading),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(id) = id.take() {
+                    let mut html = String::from("<a class=\"permalink\" href=\"#");
+                    let _ = escape_href(&mut html, &id);
+                    html.push_str("\" aria-label=\"Permalink\">¶</a>");
+                    out.push(Event::InlineHtml(html.into()));
+                }
+            }
+            _ => {}
+        }
+        out.push(event);
+    }
+    out
+}
+
+/// The pages links can point into, used to find links to missing headings
+pub trait Targets {
+    /// Whether the page at `key` has an element with `id`, `None` if the page is unknown
+    fn has_anchor(&self, key: &str, id: &str) -> Option<bool>;
+
+    /// Why the external link `url` is dead, `None` if it isn't or wasn't checked
+    fn dead_link(&self, _url: &str) -> Option<String> {
+        None
+    }
+
+    /// Resolves `path`, relative to the page at `key`, into the file it names
+    ///
+    /// The file must be within base, but needn't exist.
+    fn resolve_include(&self, _key: &str, path: &str) -> anyhow::Result<PathBuf> {
+        bail!("{path} can't be included without a base directory")
+    }
+
+    /// Reads a file found by [`Targets::resolve_include`]
+    fn read_include(&self, path: &Path) -> anyhow::Result<String> {
+        Ok(fs::read_to_string(path)?)
+    }
+
+    /// The terms explained where pages use them, see [`Glossary::apply`]
+    fn glossary(&self) -> Option<Arc<Glossary>> {
+        None
+    }
+
+    /// The extensions stripped from links to find the pages they point to
+    fn extensions(&self) -> Cow<'_, Extensions> {
+        Cow::Owned(Extensions::default())
+    }
+}
+
+/// No other pages, only links within a page are checked
+impl Targets for () {
+    fn has_anchor(&self, _: &str, _: &str) -> Option<bool> {
+        None
+    }
+}
+
+/// Marks links whose fragment doesn't exist on their target with the `broken-anchor` class
+///
+/// Links without a fragment, or into unknown pages, are left alone.
+/// External links that failed their last check get the `dead-link` class instead.
+fn check_links(events: &mut [Event], key: &str, summary: &mut Summary, targets: &dyn Targets) {
+    for event in events {
+        let Event::Start(Tag::Link {
+            dest_url, title, ..
+        }) = event
+        else {
+            continue;
+        };
+        summary.links.push(dest_url.to_string());
+        if let Some(reason) = targets.dead_link(dest_url) {
+            summary.dead_links.push(dest_url.to_string());
+            let title = if title.is_empty() {
+                format!("unreachable: {reason}")
+            } else {
+                format!("{title} (unreachable: {reason})")
+            };
+            *event = Event::InlineHtml(marked_link(dest_url, &title, "dead-link").into());
+            continue;
+        }
+        let Some((target, id)) = link_target(key, dest_url, &targets.extensions()) else {
+            continue;
+        };
+        let found = if target == key {
+            Some(summary.anchors.contains(id))
+        } else {
+            summary.anchor_links.insert(target.clone());
+            targets.has_anchor(&target, id)
+        };
+        if found != Some(false) {
+            continue;
+        }
+        summary.broken_anchors.push(dest_url.to_string());
+        *event = Event::InlineHtml(marked_link(dest_url, title, "broken-anchor").into());
+    }
+}
+
+/// Points relative images at the files they show, see [`assets::asset_url`]
+fn asset_images(events: &mut [Event], key: &str) {
+    for event in events {
+        if let Event::Start(Tag::Image { dest_url, .. }) = event {
+            if let Some(url) = assets::asset_url(key, dest_url) {
+                *dest_url = url.into();
+            }
+        }
+    }
+}
+
+/// Opens `http://` and `https://` links in a new tab, without giving it this one
+///
+/// Relative, protocol relative and `mailto:` links, and links already marked by
+/// [`check_links`], are left alone.
+fn external_blank(events: &mut [Event]) {
+    for event in events {
+        let Event::Start(Tag::Link {
+            dest_url, title, ..
+        }) = event
+        else {
+            continue;
+        };
+        let scheme = dest_url.split_once("://").map(|(scheme, _)| scheme);
+        if !scheme
+            .is_some_and(|s| s.eq_ignore_ascii_case("http") || s.eq_ignore_ascii_case("https"))
+        {
+            continue;
+        }
+        let mut html = String::from("<a href=\"");
+        let _ = escape_href(&mut html, dest_url);
+        if !title.is_empty() {
+            html.push_str("\" title=\"");
+            let _ = escape_html(&mut html, title);
+        }
+        html.push_str("\" target=\"_blank\" rel=\"noopener noreferrer\">");
+        *event = Event::InlineHtml(html.into());
+    }
+}
+
+/// The opening tag of a link to `dest` with `class`
+fn marked_link(dest: &str, title: &str, class: &str) -> String {
+    let mut html = String::from("<a href=\"");
+    let _ = escape_href(&mut html, dest);
+    if !title.is_empty() {
+        html.push_str("\" title=\"");
+        let _ = escape_html(&mut html, title);
+    }
+    html.push_str("\" class=\"");
+    html.push_str(class);
+    html.push_str("\">");
+    html
+}
+
+/// Resolves a link on the page at `key` into the key and fragment it points to
+///
+/// Only relative and root relative links with a fragment are resolved, the extension of
+/// the page they name is stripped, see [`Extensions::strip`].
+#[must_use]
+pub fn link_target<'a>(
+    key: &str,
+    dest: &'a str,
+    extensions: &Extensions,
+) -> Option<(String, &'a str)> {
+    let (path, id) = dest.split_once('#')?;
+    let path = path.split('?').next().unwrap_or_default();
+    let scheme = path.split_once(':').map(|(scheme, _)| scheme);
+    if id.is_empty() || scheme.is_some_and(|s| !s.contains('/')) || path.starts_with("//") {
+        return None;
+    }
+    if path.is_empty() {
+        return Some((key.to_owned(), id));
+    }
+    let target = resolve_path(key, path)?.join("/");
+    Some((extensions.strip(&target).to_owned(), id))
+}
+
+/// Resolves `path`, relative to the page at `key`, into the segments of the path from
+/// base it points to
+///
+/// Paths starting with `/` are relative to base. Returns `None` for paths escaping base.
+#[must_use]
+pub fn resolve_path<'a>(key: &'a str, path: &'a str) -> Option<Vec<&'a str>> {
+    let mut segments: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        key.split('/').collect()
+    };
+    // the page itself isn't a directory
+    segments.pop();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments)
+}
+
+/// Replaces fenced code blocks that have a handler with its output, see [`Hooks::fence`]
+///
+/// The languages of the collapsed blocks are added to [`Summary::fences`], once each,
+/// and failing handlers to [`Summary::problems`].
+fn handle_fences<'a>(
+    events: Vec<Event<'a>>,
+    hooks: &Hooks,
+    summary: &mut Summary,
+) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut fence: Option<(CowStr, String)> = None;
+    for event in events {
+        match (event, &mut fence) {
+            (Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))), None) => {
+                fence = Some((info, String::new()));
+            }
+            (Event::Text(text), Some((_, body))) => body.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                let Some((info, body)) = fence.take() else {
+                    continue;
+                };
+                match hooks.fence(&info, &body) {
+                    Some(result) => {
+                        let lang = info.split_whitespace().next().unwrap_or_default();
+                        if !summary.fences.iter().any(|l| l == lang) {
+                            summary.fences.push(lang.to_owned());
+                        }
+                        let html = result.unwrap_or_else(|e| {
+                            summary.problems.push(format!("{lang} block failed: {e:#}"));
+                            fence_error(lang, &e)
+                        });
+                        out.push(Event::Html(html.into()));
+                    }
+                    None => out.extend([
+                        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))),
+                        Event::Text(body.into()),
+                        Event::End(TagEnd::CodeBlock),
+                    ]),
+                }
+            }
+            (event, _) => out.push(event),
+        }
+    }
+    out
+}
+
+/// The fence whose info string names a csv file to show as a table instead
+///
+/// ```` ```csv-include data/prices.csv ````, the path is relative to the page.
+pub const CSV_INCLUDE: &str = "csv-include";
+
+/// Replaces [`CSV_INCLUDE`] fences with a table of the file they name
+///
+/// The files are added to [`Summary::includes`], and files that can't be read or parsed
+/// to [`Summary::problems`], with an error shown in place of the table.
+fn include_csv<'a>(
+    events: Vec<Event<'a>>,
+    key: &str,
+    config: &Config,
+    targets: &dyn Targets,
+    summary: &mut Summary,
+) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut within = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split_whitespace().next() == Some(CSV_INCLUDE) =>
+            {
+                within = true;
+                let path = info.trim_start()[CSV_INCLUDE.len()..].trim();
+                let html = csv_table(path, key, config, targets, summary).unwrap_or_else(|e| {
+                    let problem = format!("{CSV_INCLUDE} block failed: {e:#}");
+                    summary.problems.push(problem);
+                    fence_error(CSV_INCLUDE, &e)
+                });
+                if !summary.fences.iter().any(|l| l == CSV_INCLUDE) {
+                    summary.fences.push(CSV_INCLUDE.to_owned());
+                }
+                out.push(Event::Html(html.into()));
+            }
+            Event::End(TagEnd::CodeBlock) if within => within = false,
+            _ if within => (),
+            event => out.push(event),
+        }
+    }
+    out
+}
+
+/// Shows why the `failed` include directives failed in place of them, adding them to
+/// [`Summary::problems`]
+///
+/// Directives are found as lines of text, so those within code are left alone.
+fn include_errors<'a>(
+    events: Vec<Event<'a>>,
+    failed: &[(String, String)],
+    summary: &mut Summary,
+) -> Vec<Event<'a>> {
+    for (directive, e) in failed {
+        summary.problems.push(format!("{directive} failed: {e}"));
+    }
+    let mut out = Vec::with_capacity(events.len());
+    let mut within = false;
+    for event in events {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => within = true,
+            Event::End(TagEnd::CodeBlock) => within = false,
+            Event::Text(text) if !within => {
+                if let Some((_, e)) = failed.iter().find(|(directive, _)| directive == &**text) {
+                    let mut html = String::from("<span class=\"include-error\"><strong>");
+                    let _ = escape_html(&mut html, text);
+                    html.push_str(" failed:</strong> ");
+                    let _ = escape_html(&mut html, e);
+                    html.push_str("</span>");
+                    out.push(Event::InlineHtml(html.into()));
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        out.push(event);
+    }
+    out
+}
+
+/// Renders the csv file at `path` as a table, see [`include_csv`]
+fn csv_table(
+    path: &str,
+    key: &str,
+    config: &Config,
+    targets: &dyn Targets,
+    summary: &mut Summary,
+) -> anyhow::Result<String> {
+    ensure!(!path.is_empty(), "no file was named");
+    let file = targets.resolve_include(key, path)?;
+    summary.includes.insert(file.clone());
+    let text = targets
+        .read_include(&file)
+        .with_context(|| format!("unable to read {path}"))?;
+    let records = csv::parse(&text).with_context(|| format!("{path} isn't valid csv"))?;
+    Ok(csv::table_html(&records, config.max_csv_rows))
+}
+
+/// Writes fenced code blocks with every line in a `code-line` span, labelled with
+/// their language
+///
+/// The stylesheet numbers the spans with a counter, keeping the numbers out of copied text.
+fn number_lines(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut block: Option<(CowStr, String)> = None;
+    for event in events {
+        match (event, &mut block) {
+            (Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))), None) => {
+                block = Some((info, String::new()));
+            }
+            (Event::Text(text), Some((_, body))) => body.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                let Some((info, body)) = block.take() else {
+                    continue;
+                };
+                out.push(Event::Html(numbered_block(&info, &body).into()));
+            }
+            (event, _) => out.push(event),
+        }
+    }
+    out
+}
+
+/// The html of one code block, see [`number_lines`]
+fn numbered_block(info: &str, body: &str) -> String {
+    let lang = info.split_whitespace().next().unwrap_or_default();
+    let mut html = String::with_capacity(body.len() * 2);
+    html.push_str("<div class=\"code-block\">");
+    if lang.is_empty() {
+        html.push_str("<pre><code>");
+    } else {
+        html.push_str("<span class=\"code-lang\">");
+        let _ = escape_html(&mut html, lang);
+        html.push_str("</span><pre><code class=\"language-");
+        let _ = escape_html(&mut html, lang);
+        html.push_str("\">");
+    }
+    let lines = body.strip_suffix('\n').unwrap_or(body).split('\n');
+    for line in lines.filter(|_| !body.is_empty()) {
+        html.push_str("<span class=\"code-line\">");
+        let _ = escape_html(&mut html, line);
+        html.push_str("</span>\n");
+    }
+    html.push_str("</code></pre></div>\n");
+    html
+}
+
+/// Shown in place of a fenced code block whose handler failed
+fn fence_error(lang: &str, e: &anyhow::Error) -> String {
+    let mut html = String::from("<div class=\"fence-error\"><strong>");
+    let _ = escape_html(&mut html, lang);
+    html.push_str(" block failed:</strong> ");
+    let _ = escape_html(&mut html, &format!("{e:#}"));
+    html.push_str("</div>\n");
+    html
+}
+
+/// Collects the `id` and `name` attributes of raw html, which links may also point to
+fn html_ids(html: &str, out: &mut HashSet<String>) {
+    for attr in [" id=", " name="] {
+        for (i, _) in html.match_indices(attr) {
+            let rest = &html[i + attr.len()..];
+            let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+                continue;
+            };
+            if let Some((id, _)) = rest[1..].split_once(quote) {
+                out.insert(id.to_owned());
+            }
+        }
+    }
+}
+
+/// Escapes text for use within html
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let _ = escape_html(&mut escaped, s);
+    escaped
+}
+
+/// Renders markdown into `out`, replacing its contents
+///
+/// Only links within the page are checked and only built-in fence handlers are used,
+/// see [`render_page`].
+pub fn render_md(out: &mut String, text: &str, config: &Config) -> anyhow::Result<Summary> {
+    render_page(out, text, config, "", &(), &Hooks::default())
+}
+
+/// Renders the page at `key` into `out`, checking its links against `targets`
+///
+/// Fenced code blocks are passed to the fence handlers within `hooks`. Html over
+/// [`Config::max_html_size`] is dropped as soon as the limit is hit, leaving `out` empty,
+/// see [`Summary::oversized`].
+pub fn render_page(
+    out: &mut String,
+    text: &str,
+    config: &Config,
+    key: &str,
+    targets: &dyn Targets,
+    hooks: &Hooks,
+) -> anyhow::Result<Summary> {
+    let expanded = expand(text, config, key, targets);
+    let (events, mut summary) = prepare(&expanded, config, key, targets, hooks);
+    let additional = out.capacity().saturating_sub(text.len());
+
+    out.reserve(additional);
+    out.clear();
+    let mut capped = Capped {
+        out,
+        cap: config.max_html_size,
+        over: false,
+    };
+    match write_events(&mut capped, events, config) {
+        Err(_) if capped.over => {
+            out.clear();
+            out.shrink_to_fit();
+            summary.oversized = true;
+        }
+        result => result?,
+    }
+    Ok(summary)
+}
+
+/// Renders the page at `key` into `out` like [`render_page`], without a size limit
+///
+/// Used for pages over the limit, writing as it goes instead of holding all of the html.
+pub fn stream_page(
+    out: &mut dyn fmt::Write,
+    text: &str,
+    config: &Config,
+    key: &str,
+    targets: &dyn Targets,
+    hooks: &Hooks,
+) -> anyhow::Result<Summary> {
+    let expanded = expand(text, config, key, targets);
+    let (events, summary) = prepare(&expanded, config, key, targets, hooks);
+    write_events(out, events, config)?;
+    Ok(summary)
+}
+
+/// Writes `events` out as html, sanitized if [`Config::sanitize`] is set
+fn write_events(out: &mut dyn fmt::Write, events: Vec<Event>, config: &Config) -> fmt::Result {
+    if !config.sanitize {
+        return write_html_fmt(out, events.into_iter());
+    }
+    let mut html = String::new();
+    write_html_fmt(&mut html, events.into_iter())?;
+    out.write_str(&sanitize::clean(&html))
+}
+
+/// Fails writes that would take `out` over `cap` bytes
+struct Capped<'a> {
+    out: &'a mut String,
+    cap: usize,
+    over: bool,
+}
+
+impl fmt::Write for Capped<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.out.len().saturating_add(s.len()) > self.cap {
+            self.over = true;
+            return Err(fmt::Error);
+        }
+        self.out.push_str(s);
+        Ok(())
+    }
+}
+
+/// Includes the files named by the page's `{{include path}}` lines, unless rendering
+/// strict commonmark, see [`includes::expand`]
+fn expand<'a>(text: &'a str, config: &Config, key: &str, targets: &dyn Targets) -> Expanded<'a> {
+    if config.commonmark {
+        return Expanded {
+            text: Cow::Borrowed(text),
+            ..Expanded::default()
+        };
+    }
+    includes::expand(text, key, targets)
+}
+
+/// Turns markdown into the events written out as html, summarizing it along the way
+fn prepare<'a>(
+    expanded: &'a Expanded,
+    config: &Config,
+    key: &str,
+    targets: &dyn Targets,
+    hooks: &Hooks,
+) -> (Vec<Event<'a>>, Summary) {
+    let text = &expanded.text;
+    let mut collector = Collector::default();
+    let (yaml, body) = split_front_matter(text);
+    collector.summary.front_matter = yaml.map(|yaml| {
+        FrontMatter::parse(yaml).unwrap_or_else(|e| {
+            let problem = format!("the front matter isn't valid yaml, it was ignored: {e}");
+            collector.summary.problems.push(problem);
+            FrontMatter::default()
+        })
+    });
+    let mut gfm = Gfm {
+        edit_tasks: config.edit_tasks,
+        ..Gfm::default()
+    };
+    let mut math = false;
+    let parsed = Parser::new_ext(body, config.options)
+        .into_offset_iter()
+        .filter_map(|(e, range)| Some((config.raw_html.apply(e)?, range)));
+    let parsed = if config.commonmark {
+        parsed.map(|(e, _)| e).collect()
+    } else {
+        // after raw html is dealt with, which would take their tags with it
+        definitions::apply(body, containers::apply(body, parsed))
+    };
+    let events: Vec<_> = TextMergeStream::new(parsed.into_iter())
+        .inspect(|e| collector.observe(e))
+        .map(|e| gfm.map(e))
+        // after definition lists and containers, which split paragraphs at soft breaks
+        .map(|e| match e {
+            Event::SoftBreak if config.hard_breaks => Event::HardBreak,
+            e => e,
+        })
+        .map(|e| {
+            if !config.math {
+                return e;
+            }
+            let (e, found) = math_html(e);
+            math |= found;
+            e
+        })
+        .collect();
+    let mut summary = collector.summary;
+    summary.math = math;
+    let events = handle_fences(events, hooks, &mut summary);
+    let mut events = include_csv(events, key, config, targets, &mut summary);
+    if !expanded.failed.is_empty() {
+        events = include_errors(events, &expanded.failed, &mut summary);
+    }
+    summary.includes.extend(expanded.files.iter().cloned());
+    if config.code_line_numbers {
+        events = number_lines(events);
+    }
+    let mut anchors = Anchors::default();
+    anchors.assign(&mut events);
+    // after assigning ids, which keep the shortcode's name like GitHub's do
+    if config.emoji {
+        events = Emoji::apply(events, &mut summary);
+    }
+    // before headings are numbered
+    summary.outline = outline(&events);
+    let front = summary.front_matter.as_ref();
+    if front
+        .and_then(|front| front.number_headings)
+        .unwrap_or(config.number_headings)
+    {
+        events = number_headings(events);
+    }
+    let toc = front.and_then(|front| front.toc);
+    if toc.unwrap_or(config.toc) {
+        summary.toc = Some(toc_html(&events));
+    }
+    if config.permalinks {
+        events = permalinks(events);
+    }
+
+    summary.anchors = anchors.used.into_keys().collect();
+    for event in &events {
+        if let Event::Html(html) | Event::InlineHtml(html) = event {
+            html_ids(html, &mut summary.anchors);
+        }
+    }
+    if let Some(glossary) = targets.glossary() {
+        events = glossary.apply(events);
+    }
+    // before links are checked and marked, like the ones written out
+    if config.autolink {
+        events = autolink::apply(events);
+    }
+    asset_images(&mut events, key);
+    check_links(&mut events, key, &mut summary, targets);
+    if config.external_blank {
+        external_blank(&mut events);
+    }
+    (events, summary)
+}
+
+/// Wraps math in an element marked with `data-math` for the client to render
+///
+/// The parser never emits math within code, so code is left alone.
+/// Returns whether `event` was math.
+fn math_html(event: Event<'_>) -> (Event<'_>, bool) {
+    let (mode, tex) = match &event {
+        Event::InlineMath(tex) => ("inline", tex),
+        Event::DisplayMath(tex) => ("display", tex),
+        _ => return (event, false),
+    };
+    let mut html = format!("<span class=\"math math-{mode}\" data-math=\"{mode}\">");
+    let _ = escape_html(&mut html, tex);
+    html.push_str("</span>");
+    (Event::InlineHtml(html.into()), true)
+}
+
+/// Strips a leading byte order mark and turns CRLF and lone CR line endings into LF
+///
+/// Files exported from Windows tools often have both, which otherwise leak into the output.
+#[must_use]
+pub fn normalize_source(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Renders plain text into `out` as a preformatted page, summarized like markdown
+///
+/// The excerpt is the first paragraph, and nothing is ever left to [`stream_page`].
+pub fn render_text(out: &mut String, text: &str) -> Summary {
+    out.clear();
+    out.push_str("<pre class=\"plain-text\">");
+    let _ = escape_html(&mut *out, text);
+    out.push_str("</pre>");
+    let first = text.split("\n\n").map(str::trim).find(|p| !p.is_empty());
+    let first: Vec<_> = first.unwrap_or_default().split_whitespace().collect();
+    Summary {
+        excerpt: truncate(&first.join(" "), EXCERPT_LEN),
+        words: text.split_whitespace().count(),
+        text: text.to_owned(),
+        ..Summary::default()
+    }
+}
+
+/// Renders a csv file into `out` as a table, leaving out rows past `max_rows`
+///
+/// The excerpt names the columns, see [`csv::table_html`].
+pub fn render_csv(out: &mut String, text: &str, max_rows: usize) -> anyhow::Result<Summary> {
+    let records = csv::parse(text).context("invalid csv")?;
+    out.clear();
+    out.push_str(&csv::table_html(&records, max_rows));
+    // parsing fails without a header, so there is always one
+    let header = records.first().map(|header| header.join(", "));
+    let rows = records.len().saturating_sub(1);
+    let excerpt = format!("{rows} rows of {}", header.unwrap_or_default());
+    let cells: Vec<_> = records.iter().flatten().map(String::as_str).collect();
+    Ok(Summary {
+        excerpt: truncate(&excerpt, EXCERPT_LEN),
+        text: cells.join(" "),
+        words: records
+            .iter()
+            .flatten()
+            .map(|cell| cell.split_whitespace().count())
+            .sum(),
+        ..Summary::default()
+    })
+}
+
+/// Cuts `text` down to at most `len` characters at a word boundary, marking the cut
+#[must_use]
+pub fn truncate(text: &str, len: usize) -> String {
+    let Some((end, _)) = text.char_indices().nth(len) else {
+        return text.to_owned();
+    };
+    let cut = &text[..end];
+    let cut = cut.rfind(char::is_whitespace).map_or(cut, |i| &cut[..i]);
+    format!("{}…", cut.trim_end())
+}
+
+/// Turns a file stem such as `getting-started_guide` into `Getting Started Guide`
+#[must_use]
+pub fn prettify_stem(stem: &str) -> String {
+    let title = stem
+        .split(['-', '_', ' '])
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars
+                .next()
+                .map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    if title.is_empty() {
+        "Untitled".to_owned()
+    } else {
+        title
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use ammonia::Builder;
+
+/// The elements kept by [`builder`], along with the attributes each may have
+/// besides [`GLOBAL_ATTRIBUTES`]
+///
+/// Everything markdown renders to is here, other elements are dropped while their
+/// content is kept, apart from [`DROPPED_WITH_CONTENT`].
+pub const ALLOWED_TAGS: &[(&str, &[&str])] = &[
+    ("a", &["href", "name", "rel", "target"]),
+    ("abbr", &[]),
+    ("aside", &[]),
+    ("b", &[]),
+    ("blockquote", &["cite"]),
+    ("br", &[]),
+    ("caption", &[]),
+    ("cite", &[]),
+    ("code", &[]),
+    ("dd", &[]),
+    ("del", &["cite", "datetime"]),
+    ("details", &["open"]),
+    ("dfn", &[]),
+    ("div", &[]),
+    ("dl", &[]),
+    ("dt", &[]),
+    ("em", &[]),
+    ("figcaption", &[]),
+    ("figure", &[]),
+    ("h1", &[]),
+    ("h2", &[]),
+    ("h3", &[]),
+    ("h4", &[]),
+    ("h5", &[]),
+    ("h6", &[]),
+    ("hr", &[]),
+    ("i", &[]),
+    ("img", &["src", "alt", "width", "height", "loading"]),
+    // always a checkbox, see `builder`
+    ("input", &["checked", "disabled"]),
+    ("ins", &["cite", "datetime"]),
+    ("kbd", &[]),
+    ("li", &["value"]),
+    ("mark", &[]),
+    ("nav", &[]),
+    ("ol", &["start", "reversed", "type"]),
+    ("p", &[]),
+    ("pre", &[]),
+    ("q", &["cite"]),
+    ("s", &[]),
+    ("samp", &[]),
+    ("section", &[]),
+    ("small", &[]),
+    ("span", &[]),
+    ("strong", &[]),
+    ("sub", &[]),
+    ("summary", &[]),
+    ("sup", &[]),
+    ("table", &[]),
+    ("tbody", &[]),
+    ("td", &["align", "colspan", "rowspan", "style"]),
+    ("tfoot", &[]),
+    ("th", &["align", "colspan", "rowspan", "style"]),
+    ("thead", &[]),
+    ("time", &["datetime"]),
+    ("tr", &[]),
+    ("u", &[]),
+    ("ul", &[]),
+    ("var", &[]),
+];
+
+/// Attributes any allowed element may have, along with [`GLOBAL_PREFIXES`] ones
+pub const GLOBAL_ATTRIBUTES: &[&str] = &["class", "dir", "id", "lang", "role", "title"];
+
+/// Prefixes of the attributes any allowed element may have
+pub const GLOBAL_PREFIXES: &[&str] = &["aria-", "data-"];
+
+/// The only css `style` attributes may set, tables are aligned with it
+pub const STYLE_PROPERTIES: &[&str] = &["text-align"];
+
+/// Elements dropped along with everything within them
+pub const DROPPED_WITH_CONTENT: &[&str] = &[
+    "embed", "frame", "frameset", "iframe", "noembed", "noframes", "noscript", "object", "script",
+    "style", "template", "textarea",
+];
+
+/// The schemes urls in `href`, `src` and `cite` may have, relative urls are always kept
+pub const URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// The sanitizer configured from [`ALLOWED_TAGS`] and the other lists here
+///
+/// Inputs are only kept as the checkboxes of task lists.
+#[must_use]
+pub fn builder() -> Builder<'static> {
+    let mut builder = Builder::empty();
+    builder
+        .tags(ALLOWED_TAGS.iter().map(|(tag, _)| *tag).collect())
+        .tag_attributes(
+            ALLOWED_TAGS
+                .iter()
+                .map(|(tag, attributes)| (*tag, attributes.iter().copied().collect()))
+                .collect::<HashMap<_, HashSet<_>>>(),
+        )
+        .generic_attributes(GLOBAL_ATTRIBUTES.iter().copied().collect())
+        .generic_attribute_prefixes(GLOBAL_PREFIXES.iter().copied().collect())
+        .clean_content_tags(DROPPED_WITH_CONTENT.iter().copied().collect())
+        .url_schemes(URL_SCHEMES.iter().copied().collect())
+        .filter_style_properties(STYLE_PROPERTIES.iter().copied().collect())
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .link_rel(None);
+    builder
+}
+
+/// Cleans `html` down to [`ALLOWED_TAGS`], see [`builder`]
+#[must_use]
+pub fn clean(html: &str) -> String {
+    static BUILDER: LazyLock<Builder<'static>> = LazyLock::new(builder);
+    BUILDER.clean(html).to_string()
+}
@@ -0,0 +1,212 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+
+use crate::warnings::{Category, Warnings};
+
+/// A file or directory found while walking base, see [`Walk`]
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub is_file: bool,
+    /// Whether the entry is a symlink, `is_dir` and `is_file` describe its target if followed
+    pub is_symlink: bool,
+    pub modified: Option<SystemTime>,
+}
+
+/// How a budgeted scan reads directories, see [`crate::Api::scan_within`]
+pub trait Walk {
+    /// The entries of `dir`, in no particular order
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>>;
+}
+
+/// Reads directories from disk
+#[derive(Debug, Clone, Copy)]
+pub struct Disk {
+    pub follow_symlinks: bool,
+}
+
+impl Walk for Disk {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let is_symlink = entry.file_type()?.is_symlink();
+            let path = entry.path();
+            let metadata = if is_symlink && self.follow_symlinks {
+                fs::metadata(&path)
+            } else {
+                entry.metadata()
+            };
+            // dangling symlinks are left to the symlink warnings
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            entries.push(Entry {
+                path,
+                is_dir: metadata.is_dir(),
+                is_file: metadata.is_file(),
+                is_symlink,
+                modified: metadata.modified().ok(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Walks `base` breadth first, calling `found` with every file
+///
+/// Shallow files come before deeper ones. Within each depth, recently modified directories
+/// come first, as adding or removing a file changes its directory's mtime, then recently
+/// modified files. Unreadable directories are skipped, like [`crate::find_md`] does.
+pub fn prioritized(
+    walk: &dyn Walk,
+    base: &Path,
+    warnings: &Warnings,
+    mut found: impl FnMut(&Path),
+) {
+    let mut followed = HashSet::new();
+    let mut level = vec![base.to_owned()];
+    while !level.is_empty() {
+        let mut next = Vec::new();
+        for dir in level {
+            let Ok(mut entries) = walk.read_dir(&dir) else {
+                continue;
+            };
+            entries.sort_by_key(|entry| Reverse(entry.modified));
+            for entry in entries {
+                if entry.is_file {
+                    found(&entry.path);
+                } else if entry.is_dir && follow(&entry, base, &mut followed, warnings) {
+                    next.push(entry);
+                }
+            }
+        }
+        next.sort_by_key(|entry| Reverse(entry.modified));
+        level = next.into_iter().map(|entry| entry.path).collect();
+    }
+}
+
+/// Whether to walk into the directory `entry`, which symlinks only are if they lead somewhere new
+fn follow(
+    entry: &Entry,
+    base: &Path,
+    followed: &mut HashSet<PathBuf>,
+    warnings: &Warnings,
+) -> bool {
+    if !entry.is_symlink {
+        return true;
+    }
+    let key = entry
+        .path
+        .strip_prefix(base)
+        .unwrap_or(&entry.path)
+        .display();
+    let (Ok(target), Some(Ok(parent))) = (
+        entry.path.canonicalize(),
+        entry.path.parent().map(Path::canonicalize),
+    ) else {
+        return false;
+    };
+    if parent.starts_with(&target) {
+        let message = "symlink loops back to a parent directory";
+        warnings.push(Category::Symlink, &key.to_string(), message);
+        return false;
+    }
+    followed.insert(target)
+}
+
+/// How far the initial scan got, see [`crate::Api::scan`]
+#[derive(Debug)]
+pub struct Progress {
+    found: AtomicUsize,
+    rendered: AtomicUsize,
+    walking: AtomicBool,
+    /// Whether `found` counts every file found so far, rather than only once the walk is done
+    counted: AtomicBool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            found: AtomicUsize::default(),
+            rendered: AtomicUsize::default(),
+            // the scan starts along with the server
+            walking: true.into(),
+            counted: false.into(),
+        }
+    }
+}
+
+impl Progress {
+    /// Resets the counts for a new scan, `counted` if files are counted as they are found
+    pub fn start(&self, counted: bool) {
+        self.found.store(0, Ordering::Relaxed);
+        self.rendered.store(0, Ordering::Relaxed);
+        self.counted.store(counted, Ordering::Relaxed);
+        self.walking.store(true, Ordering::Release);
+    }
+
+    pub fn found(&self) {
+        self.found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rendered(&self) {
+        self.rendered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks the walk as done, having found `total` pages if they weren't counted
+    pub fn walked(&self, total: Option<usize>) {
+        if let Some(total) = total {
+            self.found.store(total, Ordering::Relaxed);
+            self.rendered.store(total, Ordering::Relaxed);
+        }
+        self.walking.store(false, Ordering::Release);
+    }
+
+    #[must_use]
+    pub fn status(&self) -> ScanStatus {
+        let walking = self.walking.load(Ordering::Acquire);
+        let found = self.found.load(Ordering::Relaxed);
+        let rendered = self.rendered.load(Ordering::Relaxed).min(found);
+        let percent = if found == 0 {
+            (!walking).then_some(100)
+        } else if walking && !self.counted.load(Ordering::Relaxed) {
+            None
+        } else {
+            let percent = rendered * 100 / found;
+            // files the walk has yet to find aren't counted
+            let percent = if walking { percent.min(99) } else { percent };
+            u8::try_from(percent).ok()
+        };
+        ScanStatus {
+            found,
+            rendered,
+            walking,
+            percent,
+        }
+    }
+}
+
+/// A snapshot of [`Progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScanStatus {
+    /// Pages found so far
+    pub found: usize,
+    /// Pages rendered so far, the rest are rendered once requested or the walk is done
+    pub rendered: usize,
+    /// Whether base is still being walked
+    pub walking: bool,
+    /// How many of the pages found so far are rendered, `None` if unknown
+    ///
+    /// Without a scan budget, pages are only counted once the walk is done.
+    pub percent: Option<u8>,
+}
@@ -0,0 +1,74 @@
+use std::ops::Range;
+
+use pulldown_cmark_escape::escape_html;
+use serde::Serialize;
+
+use crate::highlight;
+
+/// The most pages a search returns
+pub const MAX_HITS: usize = 50;
+
+/// How many characters of text a snippet shows on either side of the match
+pub const CONTEXT: usize = 60;
+
+/// A page matching a search, see [`crate::Api::search`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hit {
+    pub key: String,
+    pub title: String,
+    /// Escaped html of the text around the first match, with matches in `<mark>`
+    pub snippet: String,
+}
+
+/// Where `query` first occurs in `text`, in any case
+#[must_use]
+pub fn find(text: &str, query: &str) -> Option<Range<usize>> {
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    if query.is_empty() {
+        return None;
+    }
+    text.char_indices().find_map(|(start, _)| {
+        let mut chars = text[start..].char_indices();
+        for q in query.chars() {
+            let (_, c) = chars.next()?;
+            if !same(c, q) {
+                return None;
+            }
+        }
+        let end = chars.next().map_or(text.len(), |(i, _)| start + i);
+        Some(start..end)
+    })
+}
+
+/// The text around `found` with whitespace collapsed, as html marking every occurrence
+/// of `query`, see [`highlight::apply`]
+///
+/// Text cut off on either side is marked with an ellipsis.
+#[must_use]
+pub fn snippet(text: &str, found: Range<usize>, query: &str) -> String {
+    let start = text[..found.start]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[found.end..]
+        .char_indices()
+        .nth(CONTEXT)
+        .map_or(text.len(), |(i, _)| found.end + i);
+    let mut plain = String::new();
+    if start > 0 {
+        plain.push('…');
+    }
+    plain.push_str(
+        &text[start..end]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    if end < text.len() {
+        plain.push('…');
+    }
+    let mut html = String::new();
+    let _ = escape_html(&mut html, &plain);
+    highlight::apply(&html, query).into_owned()
+}
@@ -0,0 +1,422 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{ensure, Context};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    access::{Access, Login},
+    assets::Mount,
+    cli::Args,
+    extensions::Extensions,
+    render,
+    tls::Tls,
+    updates::Debounce,
+    watch::WatchPolicy,
+    RESERVED_PREFIXES,
+};
+
+/// Every setting the server runs with, after resolving the cli arguments
+///
+/// Only [`Settings::base`] and [`Settings::index`] change at runtime, see
+/// [`crate::Api::settings`] for the live values.
+#[derive(Debug, Clone, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Settings {
+    /// server url
+    pub url: String,
+    pub addr: SocketAddr,
+    /// the certificate pages are served over https with, plain http without one
+    pub tls: Option<Tls>,
+    /// the served path
+    pub base: PathBuf,
+    /// the default page, relative to base
+    pub index: String,
+    /// whether the index was given, otherwise the summary's first chapter is used
+    pub index_given: bool,
+    /// the mdBook summary given, relative to base, see [`crate::book`]
+    ///
+    /// Without one, [`crate::book::SUMMARY_FILE`] is used if it exists.
+    pub summary: Option<String>,
+    /// the file of terms explained where pages use them, see [`crate::glossary`]
+    pub glossary: Option<PathBuf>,
+    /// whether glossary terms only match in the same case
+    pub glossary_case_sensitive: bool,
+    /// the stylesheet served at [`crate::CUSTOM_CSS`], linked from every page
+    pub css: Option<PathBuf>,
+    /// the template used instead of the built-in one, see [`crate::Template::parse`]
+    pub template: Option<PathBuf>,
+    /// the colors of pages, can change at runtime, see [`crate::Api::set_theme`]
+    pub theme: Theme,
+    /// formats page titles, `{title}` is replaced by the page's title
+    pub title_template: String,
+    /// allow per-request markdown option overrides
+    pub debug_render: bool,
+    /// how markdown is rendered, before [`Settings::strict_commonmark`]
+    pub render: render::Config,
+    /// render strict commonmark, see [`render::Config::strict_commonmark`]
+    pub strict_commonmark: bool,
+    /// fail pages with partial problems instead of listing them in a banner
+    pub strict: bool,
+    /// check links to other sites in the background once scanned
+    pub check_external_links: bool,
+    /// page long console output
+    pub pager: bool,
+    /// directories served read-only
+    pub mounts: Vec<Mount>,
+    /// whether changes within mounts are pushed to clients
+    pub watch_mounts: bool,
+    /// how watcher failures are handled
+    pub watch: WatchPolicy,
+    /// files skipped instead of rendered
+    pub limits: Limits,
+    /// which files are pages, and which is served when several share a key
+    pub extensions: Extensions,
+    /// whether symlinks within base are followed
+    pub follow_symlinks: bool,
+    /// whether pages show the live reload connection status
+    pub status_indicator: bool,
+    /// fail to start when the template disagrees with the enabled features
+    pub strict_template: bool,
+    /// serve the markdown of this git revision instead of the files in base
+    pub git_ref: Option<String>,
+    /// what urls matching no page get
+    pub fallback: Fallback,
+    /// whether pages are kept readable offline by a service worker
+    pub offline: bool,
+    /// how many bytes of pages the service worker fetches ahead of time
+    pub offline_prefetch: Option<u64>,
+    /// how long changes wait for more before they are rendered
+    pub debounce: Debounce,
+    /// how long the initial scan renders pages before leaving the rest for later
+    pub scan_budget: Option<Duration>,
+    /// when refresh websockets are closed
+    pub sockets: SocketPolicy,
+    /// who may see pages under protected prefixes
+    pub access: Access,
+    /// the login every request but health checks must give, see [`crate::authenticate`]
+    pub auth: Option<Login>,
+}
+
+impl Settings {
+    /// Resolves `args`, checking that every path exists
+    pub fn new(args: &Args) -> anyhow::Result<Self> {
+        let base = args.base.canonicalize().context("invalid base path")?;
+        let extensions = Extensions::new(&args.extensions)
+            .context("invalid --extensions")?
+            .with_csv(args.csv);
+        // the index is linked to like other pages, by its key and `.md`
+        let index = if base.is_file() {
+            // a single file is its own index
+            let key = extensions
+                .key(&base, &base)
+                .context("only utf8 paths allowed")?;
+            format!("{key}.md")
+        } else if let Some(index) = &args.index {
+            let index =
+                within_base(&base, index, args.git_ref.is_some()).context("invalid index path")?;
+            let key = extensions.key(Path::new(""), Path::new(&index));
+            key.map(|key| format!("{key}.md")).unwrap_or(index)
+        } else {
+            "index.md".to_owned()
+        };
+        let summary = args
+            .summary
+            .as_ref()
+            .filter(|_| !base.is_file())
+            .map(|summary| {
+                within_base(&base, &base.join(summary), args.git_ref.is_some())
+                    .context("invalid summary path")
+            })
+            .transpose()?;
+        let glossary = args
+            .glossary
+            .as_ref()
+            .map(|path| path.canonicalize().context("invalid glossary path"))
+            .transpose()?;
+        let css = args
+            .css
+            .as_ref()
+            .map(|path| path.canonicalize().context("invalid css path"))
+            .transpose()?;
+        let template = args
+            .template
+            .as_ref()
+            .map(|path| path.canonicalize().context("invalid template path"))
+            .transpose()?;
+        let tls = Tls::from_args(args);
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let mounts = args
+            .mount
+            .iter()
+            .map(resolve_mount)
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            url: format!("{scheme}://localhost:{}/", args.addr.port()),
+            addr: args.addr,
+            tls,
+            base,
+            index,
+            index_given: args.index.is_some(),
+            summary,
+            glossary,
+            glossary_case_sensitive: args.glossary_case_sensitive,
+            css,
+            template,
+            theme: args.theme,
+            title_template: args.title_template.clone(),
+            debug_render: args.debug_render,
+            render: args.render_config(),
+            strict_commonmark: args.strict_commonmark,
+            strict: args.strict,
+            check_external_links: args.check_external_links,
+            pager: !args.no_pager,
+            mounts,
+            watch_mounts: args.watch_mounts,
+            watch: WatchPolicy {
+                exit_on_failure: args.exit_on_watch_failure,
+                retry_for: Duration::from_secs(args.watch_retry),
+            },
+            limits: Limits {
+                max_file_size: args.max_file_size,
+                max_depth: args.max_depth,
+            },
+            extensions,
+            follow_symlinks: args.follow_symlinks,
+            status_indicator: !args.no_status_indicator,
+            strict_template: args.strict_template,
+            git_ref: args.git_ref.clone(),
+            fallback: args.fallback,
+            offline: args.offline,
+            offline_prefetch: args.offline_prefetch,
+            debounce: Debounce {
+                quiet: Duration::from_millis(args.debounce),
+                max: Duration::from_millis(args.max_debounce.max(args.debounce)),
+            },
+            scan_budget: args.scan_budget.map(Duration::from_secs),
+            sockets: SocketPolicy {
+                grace_secs: args.ws_grace,
+                max_lifetime_secs: args.ws_max_lifetime,
+            },
+            access: Access::new(args.protect.clone()),
+            auth: args.auth.clone(),
+        })
+    }
+
+    /// Lists every setting as `name: value`, nested settings joined by dots
+    #[must_use]
+    pub fn lines(&self) -> Vec<(String, String)> {
+        let mut lines = Vec::new();
+        // serializing a struct of plain data can't fail
+        let value = serde_json::to_value(self).unwrap_or_default();
+        flatten(String::new(), value, &mut lines);
+        lines
+    }
+}
+
+/// Resolves `path` into a path relative to `base`, it must exist unless `git_ref` is set
+fn within_base(base: &Path, path: &Path, git_ref: bool) -> anyhow::Result<String> {
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        // a git revision's files needn't exist in the work tree
+        Err(_) if git_ref => std::path::absolute(path)?,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(path
+        .strip_prefix(base)
+        .context("must be a path within base")?
+        .to_str()
+        .context("only utf8 paths allowed")?
+        .to_owned())
+}
+
+/// What is served for urls that match no page, see [`crate::Api::fallback`]
+///
+/// Routes under `/api/` always respond with a plain 404.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    /// The 404 page
+    #[default]
+    #[serde(rename = "404")]
+    #[value(name = "404")]
+    NotFound,
+    /// The index page, as single page apps expect
+    Index,
+    /// A listing of the nearest directory with pages
+    Listing,
+}
+
+/// Which bundled stylesheet colors pages, `theme-light.css` or `theme-dark.css`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Dark text on a light background
+    #[default]
+    Light,
+    /// Light text on a dark background
+    Dark,
+    /// Whichever the reader's system prefers
+    Auto,
+}
+
+impl Theme {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+/// When refresh websockets are closed by the server
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SocketPolicy {
+    /// How long a socket may stay open before its client subscribes
+    pub grace_secs: u64,
+    /// How long a socket may stay open at all, unlimited if `None`
+    pub max_lifetime_secs: Option<u64>,
+}
+
+impl SocketPolicy {
+    #[must_use]
+    pub const fn grace(&self) -> Duration {
+        Duration::from_secs(self.grace_secs)
+    }
+
+    #[must_use]
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime_secs.map(Duration::from_secs)
+    }
+}
+
+fn flatten(name: String, value: Value, out: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if name.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{name}.{key}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(join(&key), value, out);
+            }
+        }
+        Value::Array(list) if list.iter().any(Value::is_object) => {
+            for (i, value) in list.into_iter().enumerate() {
+                flatten(join(&i.to_string()), value, out);
+            }
+        }
+        Value::Array(list) => {
+            let list: Vec<_> = list.iter().map(display).collect();
+            out.push((name, list.join(", ")));
+        }
+        value => out.push((name, display(&value))),
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Canonicalizes a mount's directory, rejecting prefixes used by other routes
+pub fn resolve_mount(mount: &Mount) -> anyhow::Result<Mount> {
+    let prefix = &mount.prefix;
+    ensure!(
+        !RESERVED_PREFIXES.contains(&prefix.as_str()),
+        "the mount prefix \"{prefix}\" is reserved"
+    );
+    let dir = mount
+        .dir
+        .canonicalize()
+        .with_context(|| format!("invalid mount directory {}", mount.dir.display()))?;
+    ensure!(dir.is_dir(), "mount {} is not a directory", dir.display());
+    Ok(Mount {
+        prefix: prefix.clone(),
+        dir,
+    })
+}
+
+/// Bounds on which files are rendered, anything past them is skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Limits {
+    /// in bytes
+    pub max_file_size: u64,
+    /// The number of directories a file may be nested in below base
+    pub max_depth: usize,
+}
+
+impl Limits {
+    /// Returns why the file at `path` should be skipped, if it should be
+    #[must_use]
+    pub fn check(&self, base: &Path, path: &Path) -> Option<String> {
+        let size = fs::metadata(path).map_or(0, |m| m.len());
+        self.check_len(base, path, size)
+    }
+
+    /// Like [`Limits::check`], for a file of `size` bytes that isn't on disk
+    #[must_use]
+    pub fn check_len(&self, base: &Path, path: &Path, size: u64) -> Option<String> {
+        let depth = path
+            .strip_prefix(base)
+            .map_or(0, |p| p.components().count().saturating_sub(1));
+        if depth > self.max_depth {
+            return Some(format!(
+                "nested {depth} directories deep, over the limit of {}",
+                self.max_depth
+            ));
+        }
+        (size > self.max_file_size).then(|| {
+            format!(
+                "{}, over the limit of {}",
+                format_size(size),
+                format_size(self.max_file_size)
+            )
+        })
+    }
+}
+
+const SIZE_UNITS: &[(&str, u64)] = &[("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+
+/// Parses a size in bytes, optionally suffixed by `K`, `M` or `G`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let upper = s.to_ascii_uppercase();
+    let (number, unit) = SIZE_UNITS
+        .iter()
+        .find_map(|(suffix, unit)| Some((upper.strip_suffix(suffix)?, *unit)))
+        .unwrap_or((&upper, 1));
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size \"{s}\", expected bytes such as 512K or 4M"))
+}
+
+/// Formats a size in bytes using the largest fitting unit
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn format_size(size: u64) -> String {
+    SIZE_UNITS
+        .iter()
+        .find(|(_, unit)| size >= *unit)
+        .map_or_else(
+            || format!("{size}B"),
+            |(suffix, unit)| format!("{:.1}{suffix}", size as f64 / *unit as f64),
+        )
+}
@@ -0,0 +1,51 @@
+use easy_sgr::{Color::*, Style::*};
+
+use crate::{
+    warnings::{Category, Warnings},
+    ChangeKind,
+};
+
+/// The key of the page served in place of the built-in 404 page
+pub const NOT_FOUND_KEY: &str = "404";
+
+/// A page with behavior beyond being served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    /// The page `/` redirects to
+    Index,
+    /// Replaces the built-in 404 page
+    NotFound,
+    /// Shadows the built-in recent changes page
+    Recent,
+}
+
+impl Special {
+    /// Reacts to a special page being added, modified or removed
+    pub fn changed(self, key: &str, kind: ChangeKind, warnings: &Warnings) {
+        use ChangeKind::*;
+        match (self, kind) {
+            (_, Modified) => (),
+            (Self::Index, Added) => {
+                warnings.resolve(Category::Special, key);
+                println!("{GreenFg}index page {BlueFg}{key}{GreenFg} restored{Reset}");
+            }
+            (Self::Index, Removed) => {
+                warnings.push(Category::Special, key, "the index page was removed");
+            }
+            (Self::NotFound, Added) => {
+                println!("{GreenFg}now using {BlueFg}{key}{GreenFg} as the 404 page{Reset}");
+            }
+            (Self::NotFound, Removed) => {
+                println!("{BlueFg}{key}{Reset} removed, using the built-in 404 page");
+            }
+            (Self::Recent, Added) => {
+                let message = "a real file is served instead of the recent changes page";
+                warnings.push(Category::Collision, key, message);
+            }
+            (Self::Recent, Removed) => {
+                warnings.resolve(Category::Collision, key);
+                println!("{BlueFg}{key}{Reset} removed, serving recent changes again");
+            }
+        }
+    }
+}
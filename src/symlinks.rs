@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+/// A symlink within base, see [`find`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Where the link is, as seen under base
+    pub path: PathBuf,
+    /// The canonical path the link points to
+    pub target: PathBuf,
+}
+
+/// Finds every followable symlink within `base`, including ones within linked directories
+///
+/// Broken and looping links are left out, they are reported while scanning.
+#[must_use]
+pub fn find(base: &Path) -> Vec<Link> {
+    WalkDir::new(base)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(walkdir::DirEntry::path_is_symlink)
+        .filter_map(|entry| {
+            let target = entry.path().canonicalize().ok()?;
+            Some(Link {
+                path: entry.into_path(),
+                target,
+            })
+        })
+        .collect()
+}
+
+/// Maps a path within a link's target back to where it is seen under base
+///
+/// The innermost matching link is used, so nested links map correctly.
+#[must_use]
+pub fn to_base(links: &[Link], path: &Path) -> Option<PathBuf> {
+    links
+        .iter()
+        .filter_map(|link| Some((link, path.strip_prefix(&link.target).ok()?)))
+        .max_by_key(|(link, _)| link.target.components().count())
+        .map(|(link, rest)| {
+            if rest.as_os_str().is_empty() {
+                link.path.clone()
+            } else {
+                link.path.join(rest)
+            }
+        })
+}
@@ -0,0 +1,105 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use axum::http::StatusCode;
+use pulldown_cmark::{Event, Options, Parser};
+use serde::Deserialize;
+
+use crate::render::split_front_matter;
+
+/// The body of a request to `/api/task/*md`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TaskEdit {
+    /// The task list item, counting from zero in the order the page shows them
+    pub index: usize,
+    /// Whether the item should be checked, `None` flips it
+    pub checked: Option<bool>,
+}
+
+/// Why a task list item could not be changed
+#[derive(Debug)]
+pub enum TaskError {
+    /// There is no page at the url
+    NoPage,
+    /// The page has fewer task list items
+    NoTask(usize),
+    /// The page's file is outside of base, as followed symlinks may be
+    OutsideBase(PathBuf),
+    /// Pages are served from a git revision, see [`crate::settings::Settings::git_ref`]
+    ReadOnly,
+    Io(io::Error),
+}
+
+impl TaskError {
+    #[must_use]
+    pub const fn status(&self) -> StatusCode {
+        match self {
+            Self::NoPage => StatusCode::NOT_FOUND,
+            Self::NoTask(_) => StatusCode::BAD_REQUEST,
+            Self::OutsideBase(_) => StatusCode::FORBIDDEN,
+            Self::ReadOnly => StatusCode::CONFLICT,
+            Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoPage => write!(f, "no page at this url"),
+            Self::NoTask(index) => write!(f, "the page has no task list item {index}"),
+            Self::OutsideBase(path) => write!(f, "{} is outside of base", path.display()),
+            Self::ReadOnly => write!(f, "pages served from a git revision can't be edited"),
+            Self::Io(e) => write!(f, "unable to write the page: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for TaskError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Applies `edit` to the markdown `text`, returning `None` if the item doesn't exist
+///
+/// Items are counted the way [`crate::render`] numbers them. Everything but the item's
+/// `[ ]` or `[x]` is left as it was, line endings included.
+#[must_use]
+pub fn apply(text: &str, options: Options, edit: TaskEdit) -> Option<String> {
+    let (_, body) = split_front_matter(text.strip_prefix('\u{feff}').unwrap_or(text));
+    let offset = text.len() - body.len();
+    let (checked, range) = Parser::new_ext(body, options)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::TaskListMarker(checked) => Some((checked, range)),
+            _ => None,
+        })
+        .nth(edit.index)?;
+    let mark = offset + range.start + body[range].find('[')? + 1;
+    let mark_char = if edit.checked.unwrap_or(!checked) {
+        "x"
+    } else {
+        " "
+    };
+    let mut text = text.to_owned();
+    text.replace_range(mark..=mark, mark_char);
+    Some(text)
+}
+
+/// Replaces the file at `path` with `text` in one step, so nothing sees it half written
+///
+/// The text is written to a hidden file next to it first, which is then renamed over it.
+pub fn write_atomic(path: &Path, text: &str) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{name}.mdflc-{}", std::process::id()));
+    let result = fs::write(&temp, text)
+        .and_then(|()| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
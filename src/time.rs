@@ -0,0 +1,84 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Serialize, Serializer};
+
+/// A point in time, in whole seconds since the unix epoch
+///
+/// Serialized as RFC 3339, shown to people relative to now, see [`Timestamp::ago`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    #[must_use]
+    pub fn now() -> Self {
+        Self::from_system(SystemTime::now())
+    }
+
+    /// Converts a [`SystemTime`], times before the epoch become the epoch
+    #[must_use]
+    pub fn from_system(time: SystemTime) -> Self {
+        Self(time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+    }
+
+    /// Formats the time as RFC 3339 in UTC, such as `2024-05-01T13:45:00Z`
+    #[must_use]
+    pub fn rfc3339(self) -> String {
+        let (days, secs) = (self.0 / 86400, self.0 % 86400);
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+
+    /// Describes how long before `now` this was, such as `3m ago`
+    ///
+    /// Times in the future, such as the mtimes of files from a skewed clock, are `just now`.
+    #[must_use]
+    pub fn ago(self, now: Self) -> String {
+        const UNITS: &[(&str, u64)] = &[("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+        let elapsed = now.0.saturating_sub(self.0);
+        if elapsed < 5 {
+            return "just now".to_owned();
+        }
+        UNITS
+            .iter()
+            .find(|(_, secs)| elapsed >= *secs)
+            .map(|(unit, secs)| format!("{}{unit} ago", elapsed / secs))
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rfc3339())
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Turns days since the unix epoch into a year, month and day
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+const fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // shifted so eras start on the 1st of March, 0000
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,65 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{ensure, Context};
+use rustls::{crypto::ring, ServerConfig};
+use serde::Serialize;
+
+use crate::cli::Args;
+
+/// The certificate and key pages are served over https with, see [`Settings::tls`]
+///
+/// [`Settings::tls`]: crate::settings::Settings::tls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tls {
+    /// A pem file of the certificate chain, the server's own certificate first
+    pub cert: PathBuf,
+    /// A pem file of the certificate's private key
+    pub key: PathBuf,
+}
+
+impl Tls {
+    /// The certificate and key given with `--tls-cert` and `--tls-key`, if both were
+    #[must_use]
+    pub fn from_args(args: &Args) -> Option<Self> {
+        let (cert, key) = args.tls_cert.clone().zip(args.tls_key.clone())?;
+        Some(Self { cert, key })
+    }
+
+    /// Reads the certificate chain and key, failing when either can't be read or they
+    /// don't belong together
+    pub fn server_config(&self) -> anyhow::Result<Arc<ServerConfig>> {
+        let certs = rustls_pemfile::certs(&mut &*read(&self.cert)?)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("invalid certificate {}", self.cert.display()))?;
+        ensure!(
+            !certs.is_empty(),
+            "{} has no pem encoded certificate",
+            self.cert.display()
+        );
+        let key = rustls_pemfile::private_key(&mut &*read(&self.key)?)
+            .with_context(|| format!("invalid private key {}", self.key.display()))?
+            .with_context(|| format!("{} has no pem encoded private key", self.key.display()))?;
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .with_context(|| {
+                format!(
+                    "the key {} doesn't match the certificate {}",
+                    self.key.display(),
+                    self.cert.display()
+                )
+            })?;
+        // websockets are only upgraded from http/1.1
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("unable to read {}", path.display()))
+}
@@ -0,0 +1,160 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+use tokio::{
+    sync::mpsc::{self, Receiver, Sender},
+    time,
+};
+
+use crate::MutexExt;
+
+/// The most changed paths left waiting for the render task, past this a rescan is queued instead
+pub const QUEUE_CAPACITY: usize = 4096;
+
+/// A changed path waiting to be rendered, see [`UpdateQueue`]
+#[derive(Debug)]
+pub struct Queued {
+    pub path: PathBuf,
+    /// When the watcher reported the change
+    pub at: Instant,
+}
+
+/// Changed paths passed from the watcher to the render task, see [`crate::Api::process_updates`]
+#[derive(Debug)]
+pub struct UpdateQueue {
+    tx: Sender<Queued>,
+    /// Taken by the render task once it starts
+    rx: Mutex<Option<Receiver<Queued>>>,
+    /// Whether changes were dropped since the last batch, which calls for a rescan
+    overflowed: AtomicBool,
+    /// How long the last batch waited, in microseconds
+    lag_micros: AtomicU64,
+    coalesced: AtomicU64,
+    overflows: AtomicU64,
+}
+
+impl Default for UpdateQueue {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        Self {
+            tx,
+            rx: Some(rx).into(),
+            overflowed: false.into(),
+            lag_micros: AtomicU64::default(),
+            coalesced: AtomicU64::default(),
+            overflows: AtomicU64::default(),
+        }
+    }
+}
+
+impl UpdateQueue {
+    /// Queues `path` without waiting, returning false if the queue is full
+    ///
+    /// Dropped changes are caught up on with a rescan, see [`UpdateQueue::take_overflow`].
+    pub fn push(&self, path: PathBuf) -> bool {
+        let queued = Queued {
+            path,
+            at: Instant::now(),
+        };
+        if self.tx.try_send(queued).is_ok() {
+            return true;
+        }
+        if !self.overflowed.swap(true, Ordering::AcqRel) {
+            self.overflows.fetch_add(1, Ordering::Relaxed);
+        }
+        false
+    }
+
+    /// The receiving end, `None` once a render task took it
+    pub fn take_receiver(&self) -> Option<Receiver<Queued>> {
+        self.rx.unlock().take()
+    }
+
+    /// Whether changes were dropped since this was last called
+    pub fn take_overflow(&self) -> bool {
+        self.overflowed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Records a processed batch that waited `lag` and had `coalesced` repeated paths
+    pub fn record(&self, lag: Duration, coalesced: usize) {
+        let lag = u64::try_from(lag.as_micros()).unwrap_or(u64::MAX);
+        self.lag_micros.store(lag, Ordering::Relaxed);
+        self.coalesced
+            .fetch_add(coalesced as u64, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> QueueStats {
+        QueueStats {
+            depth: self.tx.max_capacity() - self.tx.capacity(),
+            capacity: self.tx.max_capacity(),
+            lag_micros: self.lag_micros.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The health of the [`UpdateQueue`]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct QueueStats {
+    /// The paths waiting right now
+    pub depth: usize,
+    pub capacity: usize,
+    /// How long the last batch waited before it was processed
+    pub lag_micros: u64,
+    /// Repeated paths rendered once, skipping their intermediate states
+    pub coalesced: u64,
+    /// How often the queue filled up, each followed by a rescan
+    pub overflows: u64,
+}
+
+/// How long changes wait for more before they are rendered and sent to clients
+///
+/// A single save is sent once things are quiet for a moment, while a generator that keeps
+/// writing still has its changes sent every so often.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Debounce {
+    /// Changes wait until none came for this long
+    #[serde(rename = "quiet_ms", serialize_with = "serialize_millis")]
+    pub quiet: Duration,
+    /// But never for longer than this after the first of them
+    #[serde(rename = "max_ms", serialize_with = "serialize_millis")]
+    pub max: Duration,
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+impl Debounce {
+    /// Waits for the next batch of items from `rx`, adding them to `batch`
+    ///
+    /// Returns how many were added, zero once `rx` is closed and empty.
+    pub async fn recv<T>(&self, rx: &mut Receiver<T>, batch: &mut Vec<T>) -> usize {
+        let mut received = rx.recv_many(batch, QUEUE_CAPACITY).await;
+        if received == 0 {
+            return 0;
+        }
+        let deadline = time::Instant::now() + self.max;
+        loop {
+            let wake = deadline.min(time::Instant::now() + self.quiet);
+            #[allow(clippy::redundant_pub_crate)]
+            let more = tokio::select! {
+                () = time::sleep_until(wake) => return received,
+                more = rx.recv_many(batch, QUEUE_CAPACITY) => more,
+            };
+            if more == 0 {
+                return received;
+            }
+            received += more;
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use easy_sgr::{Color::*, Style::*};
+use serde::Serialize;
+
+use crate::{time::Timestamp, MutexExt};
+
+/// The maximum number of warnings kept, older warnings are dropped first
+pub const MAX_WARNINGS: usize = 256;
+
+/// What caused a [`Warning`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// A file could not be read or rendered
+    Render,
+    /// Two sources claim the same key
+    Collision,
+    /// A special page is missing
+    Special,
+    /// A file is over the size or depth limits
+    Skipped,
+    /// A symlink is broken or loops
+    Symlink,
+    /// A link points to a heading that doesn't exist
+    Anchor,
+    /// A page renders to too much html to cache
+    Oversized,
+    /// Part of a page failed to render, see [`crate::render::Summary::problems`]
+    Problem,
+    /// A link to another site failed its last check, see [`crate::external`]
+    DeadLink,
+    /// The template disagrees with the enabled features, see [`crate::Template::check`]
+    Template,
+    /// The summary lists a page that doesn't exist, see [`crate::book`]
+    Summary,
+    /// The glossary couldn't be read again, see [`crate::glossary`]
+    Glossary,
+}
+
+impl Category {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        use Category::*;
+        match self {
+            Render => "render",
+            Collision => "collision",
+            Special => "special",
+            Skipped => "skipped",
+            Symlink => "symlink",
+            Anchor => "anchor",
+            Oversized => "oversized",
+            Problem => "problem",
+            DeadLink => "dead_link",
+            Template => "template",
+            Summary => "summary",
+            Glossary => "glossary",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    pub category: Category,
+    /// The key or path the warning is about
+    pub key: String,
+    pub message: String,
+    pub timestamp: Timestamp,
+}
+
+/// A bounded list of [`Warning`]s, at most one per category and key
+#[derive(Debug, Default)]
+pub struct Warnings {
+    list: Mutex<VecDeque<Warning>>,
+}
+
+impl Warnings {
+    /// Records a warning, replacing any previous one for the same category and key
+    pub fn push(&self, category: Category, key: &str, message: impl Into<String>) {
+        let message = message.into();
+        eprintln!("{YellowFg}{}: {key}: {message}{Reset}", category.name());
+
+        let timestamp = Timestamp::now();
+        let mut list = self.list.unlock();
+        list.retain(|w| w.category != category || w.key != key);
+        if list.len() == MAX_WARNINGS {
+            list.pop_front();
+        }
+        list.push_back(Warning {
+            category,
+            key: key.to_owned(),
+            message,
+            timestamp,
+        });
+    }
+
+    /// Drops the warning for the given category and key, once its cause is resolved
+    pub fn resolve(&self, category: Category, key: &str) {
+        self.list
+            .unlock()
+            .retain(|w| w.category != category || w.key != key);
+    }
+
+    /// Returns every warning, oldest first
+    #[must_use]
+    pub fn list(&self) -> Vec<Warning> {
+        self.list.unlock().iter().cloned().collect()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.list.unlock().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.list.unlock().is_empty()
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    future::pending,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use easy_sgr::{Color::*, Style::*};
+use serde::{Serialize, Serializer};
+use tokio::{task::JoinHandle, time::sleep};
+use watchexec::{error::CriticalError, Config, Watchexec};
+
+use crate::Api;
+
+/// The longest wait between attempts to restart the watcher
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// The health of the file watcher
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum WatchState {
+    /// Changes are being picked up
+    Watching,
+    /// The watcher failed, cached content is still served while it is retried
+    Degraded(String),
+    /// The watcher could not be restarted, cached content is still served
+    Failed(String),
+}
+
+impl WatchState {
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Watching => "watching",
+            Self::Degraded(_) => "degraded",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+/// How to react to the watcher failing
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WatchPolicy {
+    /// Shut the server down instead of retrying
+    pub exit_on_failure: bool,
+    /// How long to keep retrying before giving up
+    #[serde(rename = "retry_secs", serialize_with = "serialize_secs")]
+    pub retry_for: Duration,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+/// Runs the watcher, restarting it after failures according to `policy`
+///
+/// Only finishes when the server should shut down.
+pub async fn supervise(
+    api: Arc<Api>,
+    config: Config,
+    mut handle: JoinHandle<Result<(), CriticalError>>,
+    policy: WatchPolicy,
+) -> anyhow::Result<()> {
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let mut degraded_since = None;
+
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let error = tokio::select! {
+            e = &mut handle => match e {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => e.to_string(),
+                Err(e) => e.to_string(),
+            },
+            _ = tick.tick() => {
+                // a vanished base doesn't always stop the watcher, check on it directly
+                let exists = api.base.lock().is_ok_and(|b| b.exists());
+                match (exists, degraded_since) {
+                    (false, None) => {
+                        degraded_since = Some(Instant::now());
+                        api.set_watch_state(WatchState::Degraded("base path disappeared".into()));
+                    }
+                    (false, Some(since)) if since.elapsed() > policy.retry_for => {
+                        api.set_watch_state(WatchState::Failed("base path disappeared".into()));
+                        return pending().await;
+                    }
+                    (true, Some(_)) => {
+                        degraded_since = None;
+                        // resetting the pathset makes watchexec watch the base again
+                        config.pathset(api.watch_paths());
+                        recover(&api);
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+        };
+
+        if policy.exit_on_failure {
+            bail!("watcher failed: {error}");
+        }
+        api.set_watch_state(WatchState::Degraded(error.clone()));
+
+        let start = Instant::now();
+        let mut delay = Duration::from_secs(1);
+        // keep the instance alive alongside its handle
+        let _wx;
+        (_wx, handle) = loop {
+            if start.elapsed() > policy.retry_for {
+                api.set_watch_state(WatchState::Failed(error));
+                return pending().await;
+            }
+            sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+
+            if !api.base.lock().is_ok_and(|b| b.exists()) {
+                continue;
+            }
+            match Watchexec::with_config(config.clone()) {
+                Ok(wx) => {
+                    let handle = wx.main();
+                    break (wx, handle);
+                }
+                Err(e) => eprintln!("{YellowFg}unable to restart watcher: {e}{Reset}"),
+            }
+        };
+        degraded_since = None;
+        recover(&api);
+    }
+}
+
+/// Catches up on changes missed while the watcher was down
+fn recover(api: &Api) {
+    match api.rescan() {
+        Ok(()) => api.set_watch_state(WatchState::Watching),
+        Err(e) => api.set_watch_state(WatchState::Degraded(format!("rescan failed: {e}"))),
+    }
+}
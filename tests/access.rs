@@ -0,0 +1,150 @@
+mod common;
+
+use std::{fs, time::Duration};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, protocol::frame::coding::CloseCode, Message},
+};
+
+/// `alice:secret` as a basic `Authorization` header
+const ALICE: &str = "Authorization: Basic YWxpY2U6c2VjcmV0";
+
+/// A base with a page under `private/` along with public ones
+fn base() -> tempfile::TempDir {
+    let base = common::base();
+    fs::write(base.path().join("a.md"), "# A\n").unwrap();
+    fs::create_dir(base.path().join("private")).unwrap();
+    fs::write(base.path().join("private/notes.md"), "# Notes\n").unwrap();
+    fs::write(base.path().join("z.md"), "# Z\n").unwrap();
+    base
+}
+
+#[tokio::test]
+async fn protected_pages_need_credentials() {
+    let base = base();
+    let extra = ["--protect", "private:alice:secret", "--fallback", "listing"];
+    let (_api, addr) = common::serve(base.path(), &extra).await;
+
+    let (head, _) = common::get_with(addr, "/private/notes", &[]).await;
+    assert!(head.starts_with("http/1.1 401"), "{head}");
+    assert!(
+        head.contains(r#"www-authenticate: basic realm="/private""#),
+        "{head}"
+    );
+    let (head, _) = common::get_with(addr, "/api/file/private/notes", &[]).await;
+    assert!(head.starts_with("http/1.1 401"), "{head}");
+    let wrong = "Authorization: Basic YWxpY2U6d3Jvbmc=";
+    let (head, _) = common::get_with(addr, "/private/notes", &[wrong]).await;
+    assert!(head.starts_with("http/1.1 401"), "{head}");
+
+    let (head, body) = common::get_with(addr, "/private/notes", &[ALICE]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(body.contains("Notes"), "{body}");
+
+    // the listing and navigation skip the page for everyone else
+    let (_, listing) = common::get(addr, "/missing").await;
+    assert!(
+        listing.contains("/z") && !listing.contains("private"),
+        "{listing}"
+    );
+    let (_, listing) = common::get_with(addr, "/missing", &[ALICE]).await;
+    assert!(listing.contains("/private/notes"), "{listing}");
+    let mut linked = false;
+    for page in ["index", "a", "z"] {
+        let (_, nav) = common::get(addr, &format!("/api/nav/{page}")).await;
+        assert!(!nav.contains("private"), "{nav}");
+        let (_, nav) = common::get_with(addr, &format!("/api/nav/{page}"), &[ALICE]).await;
+        linked |= nav.contains("private/notes");
+    }
+    assert!(linked, "no page links to private/notes");
+}
+
+#[tokio::test]
+async fn prefixes_without_credentials_are_denied() {
+    let base = base();
+    let (api, addr) = common::serve(base.path(), &["--protect", "/private/"]).await;
+
+    let (status, _) = common::get(addr, "/private/notes").await;
+    assert_eq!(status, 403);
+    let (head, _) = common::get_with(addr, "/private/notes", &[ALICE]).await;
+    assert!(head.starts_with("http/1.1 403"), "{head}");
+    let (status, _) = common::get(addr, "/api/hash/private%2Fnotes").await;
+    assert_eq!(status, 403);
+
+    let notes = base.path().join("private/notes.md");
+    fs::write(&notes, "# Changed notes\n").unwrap();
+    let a = base.path().join("a.md");
+    fs::write(&a, "# Changed a\n").unwrap();
+    api.update_paths([notes.as_path(), a.as_path()]).unwrap();
+    let (_, recent) = common::get(addr, "/recent").await;
+    assert!(
+        recent.contains("Changed a") && !recent.contains("notes"),
+        "{recent}"
+    );
+    // work outside of requests still sees every page
+    assert!(api.get_md("private/notes").is_some());
+}
+
+#[tokio::test]
+async fn subscribing_to_protected_pages_is_checked() {
+    let base = base();
+    let (_api, addr) = common::serve(base.path(), &["--protect", "private:alice:secret"]).await;
+    let url = format!("ws://{addr}/refresh-ws");
+    let subscribe = || Message::Text(r#"{"type":"subscribe","page":"private/notes"}"#.into());
+
+    let (mut socket, _) = connect_async(&url).await.unwrap();
+    socket.send(subscribe()).await.unwrap();
+    let code = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Close(frame) = message {
+                return frame.map(|f| f.code);
+            }
+        }
+        None
+    });
+    assert_eq!(code.await.unwrap(), Some(CloseCode::Policy));
+
+    let mut request = url.into_client_request().unwrap();
+    let (name, value) = ALICE.split_once(": ").unwrap();
+    request.headers_mut().insert(name, value.parse().unwrap());
+    let (mut socket, _) = connect_async(request).await.unwrap();
+    socket.send(subscribe()).await.unwrap();
+    let next = tokio::time::timeout(Duration::from_millis(500), async {
+        // the generation is sent before anything else
+        socket.next().await;
+        socket.next().await
+    })
+    .await;
+    assert!(next.is_err(), "{next:?}");
+}
+
+#[tokio::test]
+async fn logins_guard_every_route() {
+    let base = base();
+    let (_api, addr) = common::serve(base.path(), &["--auth", "alice:secret"]).await;
+
+    for path in ["/a", "/index.css", "/api/config", "/missing"] {
+        let (head, _) = common::get_with(addr, path, &[]).await;
+        assert!(head.starts_with("http/1.1 401"), "{path}: {head}");
+        assert!(
+            head.contains(r#"www-authenticate: basic realm="mdflc""#),
+            "{head}"
+        );
+        let (head, _) = common::get_with(addr, path, &[ALICE]).await;
+        assert!(!head.starts_with("http/1.1 401"), "{path}: {head}");
+    }
+    let wrong = "Authorization: Basic YWxpY2U6d3Jvbmc=";
+    let (head, _) = common::get_with(addr, "/a", &[wrong]).await;
+    assert!(head.starts_with("http/1.1 401"), "{head}");
+    let (status, _) = common::get(addr, "/healthz").await;
+    assert_eq!(status, 200);
+
+    let url = format!("ws://{addr}/refresh-ws");
+    assert!(connect_async(&url).await.is_err());
+    let mut request = url.into_client_request().unwrap();
+    let (name, value) = ALICE.split_once(": ").unwrap();
+    request.headers_mut().insert(name, value.parse().unwrap());
+    assert!(connect_async(request).await.is_ok());
+}
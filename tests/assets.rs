@@ -0,0 +1,235 @@
+mod common;
+
+use std::fs;
+
+use mdflc::assets::asset_url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// A one pixel png
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0x7a, 0x5e, 0xab, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+/// Requests `path` as is, without a client normalizing it first
+async fn raw_get(addr: std::net::SocketAddr, path: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .expect("malformed response");
+    let head = String::from_utf8_lossy(&response[..split]).to_lowercase();
+    (head, response[split + 4..].to_vec())
+}
+
+#[test]
+fn relative_urls_resolve_against_the_page() {
+    assert_eq!(
+        asset_url("notes/today", "./img/diagram.png").as_deref(),
+        Some("/assets/notes/img/diagram.png")
+    );
+    assert_eq!(
+        asset_url("notes/today", "../shared/a%20b.png?v=2").as_deref(),
+        Some("/assets/shared/a%20b.png?v=2")
+    );
+    assert_eq!(asset_url("index", "../../escape.png"), None);
+    assert_eq!(asset_url("index", "https://example.com/a.png"), None);
+    assert_eq!(asset_url("index", "/already/rooted.png"), None);
+    assert_eq!(asset_url("index", "data:image/png;base64,AAAA"), None);
+}
+
+#[tokio::test]
+async fn pages_show_their_sibling_images() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir_all(dir.join("notes/img")).unwrap();
+    fs::write(dir.join("notes/img/diagram.png"), PNG).unwrap();
+    fs::write(
+        dir.join("notes/today.md"),
+        "# Today\n\n![diagram](./img/diagram.png)\n",
+    )
+    .unwrap();
+    fs::create_dir(dir.join(".git")).unwrap();
+    fs::write(dir.join(".git/config"), "[core]\n").unwrap();
+    let (api, addr) = common::serve(dir, &[]).await;
+
+    let html = api.get_md("notes/today").unwrap();
+    assert!(
+        html.contains(r#"<img src="/assets/notes/img/diagram.png" alt="diagram" />"#),
+        "{html}"
+    );
+    let (head, body) = raw_get(addr, "/assets/notes/img/diagram.png").await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(head.contains("content-type: image/png"), "{head}");
+    assert_eq!(body, PNG);
+
+    for path in [
+        "/assets/../../etc/passwd",
+        "/assets/%2e%2e/%2e%2e/etc/passwd",
+        "/assets/notes/..%2F..%2F..%2Fetc%2Fpasswd",
+        "/assets/.git/config",
+        "/assets/notes/img/missing.png",
+    ] {
+        let (head, _) = raw_get(addr, path).await;
+        assert!(head.starts_with("http/1.1 404"), "{path}: {head}");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinks_out_of_base_are_refused() {
+    let base = common::base();
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("secret.png"), PNG).unwrap();
+    std::os::unix::fs::symlink(
+        outside.path().join("secret.png"),
+        base.path().join("linked.png"),
+    )
+    .unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+    let (head, _) = raw_get(addr, "/assets/linked.png").await;
+    assert!(head.starts_with("http/1.1 404"), "{head}");
+}
+
+#[tokio::test]
+async fn custom_css_is_linked_and_follows_its_file() {
+    let base = common::base();
+    let styles = tempfile::tempdir().unwrap();
+    let css = styles.path().join("style.css");
+    fs::write(&css, "body { color: teal; }\n").unwrap();
+    let css_arg = css.to_str().unwrap();
+    let (api, addr) = common::serve(base.path(), &["--css", css_arg]).await;
+    let css = css.canonicalize().unwrap();
+    assert!(api.watch_paths().contains(&css));
+
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(
+        body.contains("<link href=\"/custom.css\" rel=\"stylesheet\" />"),
+        "{body}"
+    );
+    let (head, body) = raw_get(addr, "/custom.css").await;
+    assert!(head.contains("content-type: text/css"), "{head}");
+    assert_eq!(body, b"body { color: teal; }\n");
+
+    let mut changes = api.subscribe();
+    fs::write(&css, "body { color: navy; }\n").unwrap();
+    api.update_paths([css.as_path()]).unwrap();
+    let change = changes.try_recv().unwrap();
+    assert_eq!(change.key, "/custom.css");
+    assert!(change.styles_only);
+
+    // a missing stylesheet is served empty, so pages aren't left unstyled
+    fs::remove_file(&css).unwrap();
+    let (head, body) = raw_get(addr, "/custom.css").await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn pages_link_no_custom_css_by_default() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(!body.contains("custom.css"), "{body}");
+    let (status, _) = common::get(addr, "/custom.css").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn mounts_serve_only_their_own_files() {
+    let base = common::base();
+    let shared = tempfile::tempdir().unwrap();
+    let static_dir = shared.path().join("static");
+    fs::create_dir(&static_dir).unwrap();
+    fs::write(static_dir.join("logo.png"), PNG).unwrap();
+    fs::write(shared.path().join("secret.txt"), "hidden").unwrap();
+    let mount = format!("static={}", static_dir.display());
+    let (_, addr) = common::serve(base.path(), &["--mount", &mount]).await;
+
+    let (head, body) = raw_get(addr, "/static/logo.png").await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(head.contains("content-type: image/png"), "{head}");
+    assert!(head.contains("cache-control: no-cache"), "{head}");
+    assert_eq!(body, PNG);
+    for path in [
+        "/static/../secret.txt",
+        "/static/%2e%2e/secret.txt",
+        "/static/..%2Fsecret.txt",
+    ] {
+        let (head, _) = raw_get(addr, path).await;
+        assert!(head.starts_with("http/1.1 404"), "{path}: {head}");
+    }
+}
+
+#[test]
+fn mounts_may_not_shadow_pages() {
+    let base = common::base();
+    fs::create_dir(base.path().join("static")).unwrap();
+    fs::write(base.path().join("static/page.md"), "# Page\n").unwrap();
+    let shared = tempfile::tempdir().unwrap();
+    let mount = format!("static={}", shared.path().display());
+    let e = mdflc::Api::new(&common::args(base.path(), &["--mount", &mount])).unwrap_err();
+    assert!(
+        e.to_string()
+            .contains("mount \"static\" would shadow the page \"static/page\""),
+        "{e}"
+    );
+}
+
+#[tokio::test]
+async fn single_files_only_share_their_own_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let page = dir.path().join("CHANGELOG.md");
+    fs::write(&page, "# Changes\n").unwrap();
+    fs::write(dir.path().join("logo.png"), PNG).unwrap();
+    fs::create_dir(dir.path().join("private")).unwrap();
+    fs::write(dir.path().join("private/secret.png"), PNG).unwrap();
+    let (_, addr) = common::serve(&page, &[]).await;
+
+    let (head, body) = raw_get(addr, "/assets/logo.png").await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert_eq!(body, PNG);
+    for path in ["/assets/private/secret.png", "/assets/private%2Fsecret.png"] {
+        let (head, _) = raw_get(addr, path).await;
+        assert!(head.starts_with("http/1.1 404"), "{path}: {head}");
+    }
+}
+
+#[tokio::test]
+async fn active_files_are_sandboxed() {
+    let base = common::base();
+    let dir = base.path();
+    fs::write(dir.join("page.html"), "<script>alert(1)</script>").unwrap();
+    fs::write(
+        dir.join("drawing.svg"),
+        "<svg><script>alert(1)</script></svg>",
+    )
+    .unwrap();
+    fs::write(dir.join("image.png"), PNG).unwrap();
+    let (_, addr) = common::serve(dir, &[]).await;
+
+    for path in ["/assets/page.html", "/assets/drawing.svg"] {
+        let (head, _) = raw_get(addr, path).await;
+        assert!(
+            head.contains("content-security-policy: sandbox"),
+            "{path}: {head}"
+        );
+        assert!(
+            head.contains("x-content-type-options: nosniff"),
+            "{path}: {head}"
+        );
+    }
+    let (head, _) = raw_get(addr, "/assets/image.png").await;
+    assert!(!head.contains("content-security-policy"), "{head}");
+    assert!(head.contains("x-content-type-options: nosniff"), "{head}");
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use std::{fs, sync::Arc};
+
+use clap::Parser;
+use mdflc::{
+    book::{Book, Chapter, Item},
+    cli::Args,
+    warnings::Category,
+    Api,
+};
+
+const SUMMARY: &str = "\
+# Summary
+
+[Introduction](README.md)
+
+# Guide
+
+- [Getting started](guide/start.md)
+    - [Installing](./guide/install.md#linux)
+    - [Unwritten]()
+        - [Deep](guide/deep%20dive.md)
+- [Usage](guide/usage.md)
+
+---
+
+- [Reference](../outside/reference.md)
+
+[Contributors](contributors.md)
+";
+
+fn chapter(title: &str, key: Option<&str>, depth: usize) -> Item {
+    Item::Chapter(Chapter {
+        title: title.to_owned(),
+        key: key.map(str::to_owned),
+        depth,
+    })
+}
+
+#[test]
+fn summaries_parse_like_mdbook() {
+    let book = Book::parse("book/SUMMARY", SUMMARY);
+    assert_eq!(
+        book.items,
+        [
+            chapter("Introduction", Some("book/README"), 0),
+            Item::PartTitle("Guide".to_owned()),
+            chapter("Getting started", Some("book/guide/start"), 1),
+            chapter("Installing", Some("book/guide/install"), 2),
+            chapter("Unwritten", None, 2),
+            chapter("Deep", Some("book/guide/deep dive"), 3),
+            chapter("Usage", Some("book/guide/usage"), 1),
+            Item::Separator,
+            chapter("Reference", Some("outside/reference"), 1),
+            chapter("Contributors", Some("book/contributors"), 0),
+        ]
+    );
+    assert_eq!(book.first(), Some("book/README"));
+    assert_eq!(book.parent("book/guide/install"), Some("book/guide/start"));
+    // drafts are skipped over
+    assert_eq!(
+        book.parent("book/guide/deep dive"),
+        Some("book/guide/start")
+    );
+    assert_eq!(book.parent("book/guide/start"), None);
+    assert_eq!(book.parent("book/README"), None);
+    assert_eq!(
+        book.first_child("book/guide/start"),
+        Some("book/guide/install")
+    );
+    assert_eq!(book.first_child("book/guide/usage"), None);
+    assert!(book.position("book/guide/usage") < book.position("book/contributors"));
+    assert!(!book.lists("book/SUMMARY"));
+}
+
+#[tokio::test]
+async fn summaries_drive_the_order_and_index() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir(dir.join("guide")).unwrap();
+    fs::write(dir.join("SUMMARY.md"), SUMMARY).unwrap();
+    fs::write(dir.join("README.md"), "# Intro\n").unwrap();
+    fs::write(dir.join("guide/start.md"), "# Start\n").unwrap();
+    fs::write(dir.join("guide/install.md"), "# Install\n").unwrap();
+    fs::write(dir.join("guide/usage.md"), "# Usage\n").unwrap();
+    fs::write(dir.join("aaa.md"), "# Unlisted\n").unwrap();
+
+    let args = Args::parse_from(["mdflc".as_ref(), dir.as_os_str()]);
+    let api = Arc::new(Api::new(&args).unwrap());
+    api.scan().unwrap();
+    assert_eq!(api.settings().index, "README.md");
+    let keys = api.ordered_keys("");
+    assert_eq!(
+        keys[..4],
+        ["README", "guide/start", "guide/install", "guide/usage"]
+    );
+    assert_eq!(keys[4..], ["aaa", "index", "SUMMARY"]);
+
+    let nav = api.nav("/guide/install").unwrap();
+    assert_eq!(nav.prev.unwrap().key, "guide/start");
+    assert_eq!(nav.next.unwrap().key, "guide/usage");
+    assert_eq!(nav.parent.unwrap().key, "guide/start");
+    let nav = api.nav("/guide/start").unwrap();
+    assert_eq!(nav.first_child.unwrap().key, "guide/install");
+    assert!(nav.parent.is_none());
+
+    let listing = api.listing("/").unwrap();
+    assert!(
+        listing.contains(r#"<li class="unlisted"><a href="/aaa">"#),
+        "{listing}"
+    );
+    assert!(
+        listing.contains(r#"<li><a href="/guide/start">"#),
+        "{listing}"
+    );
+
+    let missing = |key: &str| {
+        api.warnings
+            .list()
+            .iter()
+            .any(|w| w.category == Category::Summary && w.key == key)
+    };
+    assert!(missing("guide/deep dive") && missing("contributors"));
+    assert!(!missing("guide/start"));
+
+    // the summary is read again once it changes
+    let summary = dir.join("SUMMARY.md");
+    fs::write(
+        &summary,
+        "- [Usage](guide/usage.md)\n- [Start](guide/start.md)\n",
+    )
+    .unwrap();
+    api.update_paths([summary.as_path()]).unwrap();
+    assert_eq!(
+        api.ordered_keys("guide"),
+        ["guide/usage", "guide/start", "guide/install"]
+    );
+    assert_eq!(api.settings().index, "guide/usage.md");
+    assert!(!missing("contributors"));
+
+    let deep = dir.join("guide/deep dive.md");
+    fs::write(&summary, "- [Deep](guide/deep%20dive.md)\n").unwrap();
+    api.update_paths([summary.as_path()]).unwrap();
+    assert!(missing("guide/deep dive"));
+    fs::write(&deep, "# Deep\n").unwrap();
+    api.update_paths([deep.as_path()]).unwrap();
+    assert!(!missing("guide/deep dive"));
+}
+
+#[tokio::test]
+async fn given_indexes_win_over_the_summary() {
+    let base = common::base();
+    fs::write(base.path().join("SUMMARY.md"), "- [First](first.md)\n").unwrap();
+    fs::write(base.path().join("first.md"), "# First\n").unwrap();
+    let api = Api::new(&common::args(base.path(), &[])).unwrap();
+    api.scan().unwrap();
+    assert_eq!(api.settings().index, "index.md");
+    assert_eq!(api.ordered_keys("")[0], "first");
+}
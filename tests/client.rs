@@ -0,0 +1,38 @@
+use std::{io::ErrorKind, path::Path, process::Command};
+
+/// Runs the node script `fixture` with `client/index.js`, skipped when node is missing
+fn run_client(fixture: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new("node")
+        .arg(root.join("tests/fixtures/client").join(fixture))
+        .arg(root.join("client/index.js"))
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("node not found, skipping {fixture}");
+            return;
+        }
+        Err(e) => panic!("unable to run node: {e}"),
+    };
+    assert!(
+        output.status.success(),
+        "{fixture} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn refresh_sockets_subscribe_once_open() {
+    run_client("subscribe.js");
+}
+
+#[test]
+fn math_uses_the_bundled_katex() {
+    run_client("math.js");
+}
+
+#[test]
+fn diagram_failures_are_shown() {
+    run_client("mermaid.js");
+}
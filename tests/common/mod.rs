@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+
+use std::{ffi::OsStr, fs, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use clap::Parser;
+use mdflc::{cli::Args, router, Api};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A temporary base directory containing an `index.md`
+pub fn base() -> TempDir {
+    let dir = tempfile::tempdir().expect("unable to create temp dir");
+    fs::write(dir.path().join("index.md"), "# Index\n").expect("unable to write index");
+    dir
+}
+
+/// Parses the arguments for serving `base` on a random local port
+pub fn args(base: &Path, extra: &[&str]) -> Args {
+    let index = base.join("index.md");
+    let mut args = vec![
+        "mdflc".as_ref(),
+        base.as_os_str(),
+        "-i".as_ref(),
+        index.as_os_str(),
+        "-a".as_ref(),
+        "127.0.0.1:0".as_ref(),
+    ];
+    args.extend(extra.iter().map(OsStr::new));
+    Args::parse_from(args)
+}
+
+/// Starts a server for `base` on a random local port
+pub async fn serve(base: &Path, extra: &[&str]) -> (Arc<Api>, SocketAddr) {
+    let api = Arc::new(Api::new(&args(base, extra)).expect("unable to create api"));
+    api.scan().expect("unable to scan base");
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(api.clone()).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (api, addr)
+}
+
+/// Requests `path` from the server at `addr`, returning the status code and body
+pub async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split(' ')
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .expect("malformed status line");
+    (status, body.to_owned())
+}
+
+/// Requests `path` with extra `headers`, each a `Name: value` line, returning the
+/// lowercased response head and the body
+pub async fn get_with(addr: SocketAddr, path: &str, headers: &[&str]) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n");
+    for header in headers {
+        request.push_str(header);
+        request.push_str("\r\n");
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    (head.to_lowercase(), body.to_owned())
+}
+
+/// Posts `body` as json to `path`, returning the status code and body
+pub async fn post(addr: SocketAddr, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+        Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split(' ')
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .expect("malformed status line");
+    (status, body.to_owned())
+}
+
+/// Polls `f` until it returns true, panicking after a few seconds
+pub async fn wait_for(mut f: impl FnMut() -> bool) {
+    for _ in 0..200 {
+        if f() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("condition not met in time");
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::fs;
+
+#[tokio::test]
+async fn pages_are_shown_side_by_side() {
+    let base = common::base();
+    let docs = base.path().join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("old.md"), "# Old\n").unwrap();
+    fs::write(docs.join("new.md"), "# New\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/compare?left=docs/old&right=/docs/new.md").await;
+    assert_eq!(status, 200);
+    let old = body
+        .find(r#"data-page="docs/old""#)
+        .expect("no left column");
+    let new = body
+        .find(r#"data-page="docs/new""#)
+        .expect("no right column");
+    assert!(old < new);
+    assert!(body.contains(r#"<h1 id="old">Old</h1>"#), "{body}");
+    assert!(body.contains(r#"<h1 id="new">New</h1>"#), "{body}");
+    let hash = format!(
+        r#"data-hash="{:016x}""#,
+        api.get_etag_hash("docs/new").unwrap().0
+    );
+    assert!(body.contains(&hash), "{body}");
+
+    // the view is not a page of its own
+    assert!(api.list().iter().all(|(key, _)| key != "compare"));
+    let (status, _) = common::get(addr, "/api/nav/compare").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn unknown_pages_only_fail_their_column() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/compare?left=index&right=missing").await;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#"<h1 id="index">Index</h1>"#), "{body}");
+    assert!(
+        body.contains(r#"data-page="missing" data-hash="""#),
+        "{body}"
+    );
+    assert!(body.contains("Error 404"), "{body}");
+
+    let (status, _) = common::get(addr, "/compare?left=index").await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::{fs, io::Read, net::SocketAddr};
+
+use flate2::read::GzDecoder;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+fn gunzip(bytes: &[u8]) -> String {
+    let mut html = String::new();
+    GzDecoder::new(bytes).read_to_string(&mut html).unwrap();
+    html
+}
+
+/// Markdown rendering to well over [`mdflc::compress::MIN_SIZE`]
+fn large_page() -> String {
+    "A paragraph repeated to make the page large.\n\n".repeat(2000)
+}
+
+/// Requests `path` with `accept_encoding`, returning the response head and raw body
+async fn get_encoded(addr: SocketAddr, path: &str, accept_encoding: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {addr}\r\nAccept-Encoding: {accept_encoding}\r\n\
+        Connection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8_lossy(&response[..end]).to_lowercase();
+    (head, response[end + 4..].to_vec())
+}
+
+#[tokio::test]
+async fn large_pages_are_served_precompressed() {
+    let base = common::base();
+    fs::write(base.path().join("large.md"), large_page()).unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, body) = get_encoded(addr, "/large", "br;q=1.0, gzip;q=0.8").await;
+    assert!(head.contains("content-encoding: gzip"), "{head}");
+    assert!(head.contains("vary: accept-encoding"), "{head}");
+    assert_eq!(gunzip(&body), api.get_md("large").unwrap());
+
+    let (head, body) = get_encoded(addr, "/large", "gzip;q=0").await;
+    assert!(!head.contains("content-encoding"), "{head}");
+    assert_eq!(
+        String::from_utf8(body).unwrap(),
+        api.get_md("large").unwrap()
+    );
+
+    // small pages are left alone
+    let (head, _) = get_encoded(addr, "/index", "gzip").await;
+    assert!(!head.contains("content-encoding"), "{head}");
+    assert!(api.get_md_gzip("index").is_none());
+}
+
+#[tokio::test]
+async fn other_responses_are_compressed() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, body) = get_encoded(addr, "/index.css", "gzip").await;
+    assert!(head.contains("content-encoding: gzip"), "{head}");
+    assert!(head.contains("etag: w/\""), "{head}");
+    assert!(gunzip(&body).contains('{'));
+    let (head, body) = get_encoded(addr, "/api/config", "gzip").await;
+    assert!(head.contains("content-encoding: gzip"), "{head}");
+    assert!(gunzip(&body).starts_with('{'));
+
+    // tiny responses aren't worth it
+    let (head, _) = get_encoded(addr, "/api/ping", "gzip").await;
+    assert!(!head.contains("content-encoding"), "{head}");
+    let (head, _) = get_encoded(addr, "/index.css", "identity").await;
+    assert!(!head.contains("content-encoding"), "{head}");
+}
+
+#[tokio::test]
+async fn compressed_pages_are_cached_until_changed() {
+    let base = common::base();
+    let path = base.path().join("large.md");
+    fs::write(&path, large_page()).unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    assert_eq!(api.render_stats(0).compressed, 0);
+
+    let first = api.get_md_gzip("large").unwrap();
+    // the same copy is handed out again
+    assert_eq!(first.as_ptr(), api.get_md_gzip("large").unwrap().as_ptr());
+    let stats = api.render_stats(0);
+    assert_eq!(stats.compressed, 1);
+    assert_eq!(stats.compressed_bytes, first.len());
+
+    fs::write(&path, large_page() + "# Changed\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert_eq!(api.render_stats(0).compressed, 0);
+    let second = api.get_md_gzip("large").unwrap();
+    assert!(gunzip(&second).contains("Changed"));
+}
@@ -0,0 +1,128 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, sync::Arc};
+
+use mdflc::{
+    control::{self, ControlFiles, END_OF_OUTPUT},
+    Api,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::mpsc,
+};
+
+/// Sends `command` over the control socket, returning its output without colors
+async fn run(stream: &mut BufReader<UnixStream>, command: &str) -> String {
+    stream
+        .get_mut()
+        .write_all(format!("{command}\n").as_bytes())
+        .await
+        .unwrap();
+    let mut output = String::new();
+    loop {
+        let mut line = String::new();
+        assert_ne!(
+            stream.read_line(&mut line).await.unwrap(),
+            0,
+            "closed early"
+        );
+        if line.trim_end_matches('\n') == END_OF_OUTPUT {
+            break;
+        }
+        output.push_str(&line);
+    }
+    strip_ansi(&output)
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[tokio::test]
+async fn consoles_attach_to_a_running_server() {
+    let base = common::base();
+    let api = Arc::new(Api::new(&common::args(base.path(), &[])).unwrap());
+    api.scan().unwrap();
+    let wx = Arc::new(api.watcher().unwrap());
+    let runtime = tempfile::tempdir().unwrap();
+    let dir = runtime.path().join("mdflc");
+
+    let (listener, files) = control::listen(&dir, 4000).unwrap();
+    let mode = fs::metadata(&files.socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let mode = fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+    let pid = fs::read_to_string(&files.pid_file).unwrap();
+    assert_eq!(
+        pid,
+        format!("{}\n{}\n", std::process::id(), files.socket.display())
+    );
+    assert!(control::listen(&dir, 4000).is_err());
+
+    let (quit_send, mut quit_recv) = mpsc::channel(1);
+    tokio::spawn(control::serve(api.clone(), wx, listener, quit_send));
+    assert_eq!(control::find(&dir, None).unwrap(), files.socket);
+    assert_eq!(control::find(&dir, Some(4000)).unwrap(), files.socket);
+    assert!(control::find(&dir, Some(4001)).is_err());
+
+    let mut first = BufReader::new(UnixStream::connect(&files.socket).await.unwrap());
+    let mut second = BufReader::new(UnixStream::connect(&files.socket).await.unwrap());
+    let path = run(&mut second, "path").await;
+    let name = base.path().file_name().unwrap().to_str().unwrap();
+    assert!(path.trim().ends_with(name), "{path}");
+    let url = run(&mut first, "url").await;
+    assert_eq!(url.trim(), api.settings().url);
+    let unknown = run(&mut first, "nonsense").await;
+    assert!(unknown.contains("unknown input"), "{unknown}");
+    assert!(run(&mut second, "").await.is_empty());
+
+    run(&mut second, "quit").await;
+    quit_recv.recv().await.unwrap();
+
+    let (socket, pid_file) = (files.socket.clone(), files.pid_file.clone());
+    drop(files);
+    assert!(!socket.exists() && !pid_file.exists());
+}
+
+#[tokio::test]
+async fn stale_sockets_are_replaced() {
+    let runtime = tempfile::tempdir().unwrap();
+    let dir = runtime.path().join("mdflc");
+    let (listener, files) = control::listen(&dir, 4000).unwrap();
+    drop(listener);
+    // a server that was killed leaves its files behind
+    let socket = files.socket.clone();
+    std::mem::forget(files);
+    assert!(socket.exists());
+    assert!(control::find(&dir, None).is_err());
+
+    let (_listener, files) = control::listen(&dir, 4000).unwrap();
+    assert_eq!(files.socket, socket);
+    assert_eq!(
+        control::find(&dir, None).unwrap(),
+        ControlFiles::socket_path(&dir, 4000)
+    );
+}
+
+#[test]
+fn directories_others_may_enter_are_refused() {
+    let runtime = tempfile::tempdir().unwrap();
+    let dir = runtime.path().join("mdflc");
+    fs::create_dir(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+    let e = control::listen(&dir, 4000).unwrap_err();
+    assert!(e.to_string().contains("other users"), "{e}");
+    assert!(!ControlFiles::socket_path(&dir, 4000).exists());
+}
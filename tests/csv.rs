@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+
+use mdflc::{
+    csv::{parse, table_html},
+    Api,
+};
+
+#[test]
+fn quoted_fields_may_hold_anything() {
+    let records = parse("name,note\r\n\"Smith, J\",\"said \"\"hi\"\"\ntwice\"\n\nDoe,\n").unwrap();
+    assert_eq!(
+        records,
+        [
+            vec!["name", "note"],
+            vec!["Smith, J", "said \"hi\"\ntwice"],
+            vec!["Doe", ""],
+        ]
+    );
+    assert_eq!(parse("a,b").unwrap(), [vec!["a", "b"]]);
+}
+
+#[test]
+fn malformed_files_say_where() {
+    let error = |text| parse(text).unwrap_err().to_string();
+    assert_eq!(
+        error("a,b\n1,2\n3\n"),
+        "line 3: 1 fields where the header has 2"
+    );
+    assert_eq!(error("a\n\"1\nx"), "line 2: a quoted field is never closed");
+    assert_eq!(error("a\n1\"2\n"), "line 2: quote within an unquoted field");
+    assert_eq!(
+        error("a\n\"1\"2\n"),
+        "line 2: unexpected text after a quoted field"
+    );
+    assert_eq!(error("\n\n"), "line 1: there is no header row");
+}
+
+#[test]
+fn tables_escape_and_truncate() {
+    let records = parse("<b>,x\n1 & 2,<i>\n3,4\n5,6\n").unwrap();
+    assert_eq!(
+        table_html(&records, 2),
+        "<table class=\"csv\"><thead><tr><th>&lt;b&gt;</th><th>x</th></tr></thead><tbody>\n\
+        <tr><td>1 &amp; 2</td><td>&lt;i&gt;</td></tr>\n\
+        <tr><td>3</td><td>4</td></tr>\n\
+        </tbody></table>\n\
+        <p class=\"csv-truncated\">Showing the first 2 of 3 rows.</p>\n"
+    );
+}
+
+#[tokio::test]
+async fn includes_render_and_follow_their_files() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir(dir.join("data")).unwrap();
+    let csv = dir.join("data/prices.csv");
+    fs::write(&csv, "item,price\napple,1\npear,2\n").unwrap();
+    fs::write(
+        dir.join("data/report.md"),
+        "# Report\n\n```csv-include prices.csv\n```\n\n```csv-include ../../secret.csv\n```\n",
+    )
+    .unwrap();
+    let api = Api::new(&common::args(dir, &["--max-csv-rows", "1"])).unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("data/report").unwrap();
+    assert!(
+        html.contains("<td>apple</td>") && !html.contains("pear"),
+        "{html}"
+    );
+    assert!(html.contains("Showing the first 1 of 2 rows."), "{html}");
+    assert!(html.contains("class=\"fence-error\""), "{html}");
+    let problems = api.warnings.list();
+    assert!(
+        problems
+            .iter()
+            .any(|w| w.key == "data/report" && w.message.contains("secret.csv is outside of base")),
+        "{problems:?}"
+    );
+
+    fs::write(&csv, "item,price\n\"banana,1\n").unwrap();
+    api.update_paths([csv.as_path()]).unwrap();
+    let html = api.get_md("data/report").unwrap();
+    assert!(html.contains("valid csv: line 2"), "{html}");
+    assert!(!html.contains("apple"), "{html}");
+
+    fs::write(&csv, "item,price\ncherry,3\n").unwrap();
+    api.update_paths([csv.as_path()]).unwrap();
+    let html = api.get_md("data/report").unwrap();
+    assert!(html.contains("<td>cherry</td>"), "{html}");
+}
+
+#[tokio::test]
+async fn csv_files_are_pages_when_enabled() {
+    let base = common::base();
+    let csv = base.path().join("sales.csv");
+    fs::write(&csv, "region,total\n\"North, east\",<5>\nSouth,7\nWest,9\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &["--csv", "--max-csv-rows", "2"]).await;
+
+    let (status, body) = common::get(addr, "/sales").await;
+    assert_eq!(status, 200);
+    assert!(
+        body.contains("<td>North, east</td><td>&lt;5&gt;</td>"),
+        "{body}"
+    );
+    assert!(body.contains("Showing the first 2 of 3 rows."), "{body}");
+    assert!(body.contains("<title>Sales"), "{body}");
+
+    let mut changes = api.subscribe();
+    fs::write(&csv, "region,total\nEast,1\n").unwrap();
+    api.update_paths([csv.as_path()]).unwrap();
+    assert!(api.get_md("sales").unwrap().contains("<td>East</td>"));
+    assert_eq!(changes.try_recv().unwrap().key, "sales");
+
+    let plain = common::base();
+    fs::write(plain.path().join("sales.csv"), "a\n1\n").unwrap();
+    let (api, _) = common::serve(plain.path(), &[]).await;
+    assert!(api.get_md("sales").is_none());
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use mdflc::updates::Debounce;
+use tokio::{
+    sync::mpsc::{self, Sender},
+    time::{self, Instant},
+};
+
+const POLICY: Debounce = Debounce {
+    quiet: Duration::from_millis(150),
+    max: Duration::from_secs(1),
+};
+
+/// Sends `count` items `gap` apart, then closes the channel
+fn burst(count: usize, gap: Duration) -> mpsc::Receiver<usize> {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(send(tx, count, gap));
+    rx
+}
+
+async fn send(tx: Sender<usize>, count: usize, gap: Duration) {
+    for i in 0..count {
+        if i > 0 {
+            time::sleep(gap).await;
+        }
+        tx.send(i).await.unwrap();
+    }
+    // stays open until the receiver is done, like the update queue
+    tx.closed().await;
+}
+
+/// Collects every batch `count` items make, with how long after the start each was sent
+async fn batches(count: usize, gap: Duration) -> Vec<(usize, Duration)> {
+    let start = Instant::now();
+    let mut rx = burst(count, gap);
+    let mut batches = Vec::new();
+    let mut total = 0;
+    while total < count {
+        let mut batch = Vec::new();
+        let received = POLICY.recv(&mut rx, &mut batch).await;
+        assert_eq!(received, batch.len());
+        total += received;
+        batches.push((received, start.elapsed()));
+    }
+    batches
+}
+
+#[tokio::test(start_paused = true)]
+async fn single_saves_wait_for_the_quiet_period() {
+    assert_eq!(batches(1, Duration::ZERO).await, [(1, POLICY.quiet)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn bursts_are_sent_once_they_settle() {
+    let gap = Duration::from_millis(50);
+    let batches = batches(5, gap).await;
+    assert_eq!(batches, [(5, gap * 4 + POLICY.quiet)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn sustained_activity_is_sent_at_least_every_max() {
+    // three seconds of changes, never quiet for long enough
+    let batches = batches(31, Duration::from_millis(100)).await;
+    assert_eq!(batches.len(), 4, "{batches:?}");
+    let mut previous = Duration::ZERO;
+    for (i, (_, at)) in batches.iter().enumerate() {
+        let bound = if i == batches.len() - 1 {
+            POLICY.quiet
+        } else {
+            POLICY.max
+        };
+        assert!(
+            *at - previous <= bound + Duration::from_millis(100),
+            "{batches:?}"
+        );
+        previous = *at;
+    }
+    assert_eq!(batches.iter().map(|(n, _)| n).sum::<usize>(), 31);
+}
+
+#[tokio::test(start_paused = true)]
+async fn closed_channels_end_the_wait() {
+    let (tx, mut rx) = mpsc::channel(4);
+    tx.send(1).await.unwrap();
+    drop(tx);
+    let mut batch = Vec::new();
+    assert_eq!(POLICY.recv(&mut rx, &mut batch).await, 1);
+    assert_eq!(POLICY.recv(&mut rx, &mut batch).await, 0);
+}
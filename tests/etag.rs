@@ -0,0 +1,89 @@
+mod common;
+
+use std::fs;
+
+/// The `ETag` in a head lowercased by [`common::get_with`]
+fn etag(head: &str) -> String {
+    let etag = head
+        .lines()
+        .find_map(|line| line.strip_prefix("etag: "))
+        .unwrap_or_else(|| panic!("no etag in {head}"));
+    etag.replacen("w/", "W/", 1)
+}
+
+#[tokio::test]
+async fn unchanged_pages_are_not_sent_again() {
+    let base = common::base();
+    let path = base.path().join("page.md");
+    fs::write(&path, "# Page\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, _) = common::get_with(addr, "/page", &[]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    let first = etag(&head);
+    let matching = format!("If-None-Match: {first}");
+    let (head, body) = common::get_with(addr, "/page", &[&matching]).await;
+    assert!(head.starts_with("http/1.1 304"), "{head}");
+    assert!(body.is_empty(), "{body}");
+
+    fs::write(&path, "# Page\n\nedited\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    let (head, body) = common::get_with(addr, "/page", &[&matching]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(body.contains("edited"), "{body}");
+    assert_ne!(etag(&head), first);
+}
+
+#[tokio::test]
+async fn built_in_files_have_stable_tags() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    for path in [
+        "/index.css",
+        "/index.js",
+        "/theme-dark.css",
+        "/vendor/katex.min.js",
+        "/vendor/mermaid.min.js",
+    ] {
+        let (head, _) = common::get_with(addr, path, &[]).await;
+        let tag = etag(&head);
+        let (again, _) = common::get_with(addr, path, &[]).await;
+        assert_eq!(etag(&again), tag);
+        let (head, _) =
+            common::get_with(addr, path, &[&format!("If-None-Match: \"other\", {tag}")]).await;
+        assert!(head.starts_with("http/1.1 304"), "{path}: {head}");
+    }
+}
+
+#[tokio::test]
+async fn hash_probes_give_the_page_tag() {
+    let base = common::base();
+    fs::write(base.path().join("page.md"), "# Page\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (_, probe) = common::get(addr, "/api/hash/page").await;
+    let probe: serde_json::Value = serde_json::from_str(&probe).unwrap();
+    let tag = probe["etag"].as_str().unwrap();
+    assert!(tag.contains(probe["hash"].as_str().unwrap()), "{probe}");
+    let (head, _) = common::get_with(addr, "/page", &[]).await;
+    assert_eq!(etag(&head), tag);
+    let (head, _) = common::get_with(addr, "/page", &[&format!("If-None-Match: {tag}")]).await;
+    assert!(head.starts_with("http/1.1 304"), "{head}");
+}
+
+#[tokio::test]
+async fn files_in_base_are_revalidated() {
+    let base = common::base();
+    fs::write(base.path().join("image.png"), b"not really a png").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, _) = common::get_with(addr, "/assets/image.png", &[]).await;
+    let tag = etag(&head);
+    let strong = tag.trim_start_matches("W/");
+    let listed = format!("If-None-Match: \"other\", {strong}");
+    let (head, body) = common::get_with(addr, "/assets/image.png", &[&listed]).await;
+    assert!(head.starts_with("http/1.1 304"), "{head}");
+    assert!(body.is_empty(), "{body}");
+    assert_eq!(etag(&head), tag);
+}
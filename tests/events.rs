@@ -0,0 +1,136 @@
+mod common;
+
+use std::{fs, net::SocketAddr, time::Duration};
+
+use tokio::time::timeout;
+
+/// An open `/api/events` stream
+struct Events {
+    response: reqwest::Response,
+    buf: String,
+}
+
+impl Events {
+    async fn open(addr: SocketAddr, query: &str) -> Self {
+        let response = reqwest::get(format!("http://{addr}/api/events{query}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        Self {
+            response,
+            buf: String::new(),
+        }
+    }
+
+    /// The name and data of the next event, `None` once the stream ends
+    async fn next(&mut self) -> Option<(String, String)> {
+        loop {
+            while let Some((event, rest)) = self.buf.split_once("\n\n") {
+                let (event, rest) = (event.to_owned(), rest.to_owned());
+                self.buf = rest;
+                let (mut name, mut data) = (String::new(), String::new());
+                for line in event.lines() {
+                    if let Some(value) = line.strip_prefix("event: ") {
+                        value.clone_into(&mut name);
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        value.clone_into(&mut data);
+                    }
+                }
+                // keep-alive comments have no name
+                if !name.is_empty() {
+                    return Some((name, data));
+                }
+            }
+            let chunk = timeout(Duration::from_secs(5), self.response.chunk())
+                .await
+                .expect("no event in time")
+                .unwrap()?;
+            self.buf.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    /// The data of the next event, which must be a change
+    async fn change(&mut self) -> serde_json::Value {
+        let (name, data) = self.next().await.expect("the stream ended");
+        assert_eq!(name, "change", "{data}");
+        serde_json::from_str(&data).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn streams_replay_then_follow_changes() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir(dir.join("docs")).unwrap();
+    let (api, addr) = common::serve(dir, &[]).await;
+    let since = api.generation();
+
+    let (first, notes) = (dir.join("docs/first.md"), dir.join("notes.md"));
+    fs::write(&first, "# First\n").unwrap();
+    fs::write(&notes, "# Notes\n").unwrap();
+    api.update_paths([first.as_path(), notes.as_path()])
+        .unwrap();
+
+    let mut events = Events::open(addr, &format!("?prefix=docs/&since={since}")).await;
+    let replayed = events.change().await;
+    assert_eq!(replayed["key"], "docs/first");
+    assert_eq!(replayed["kind"], "added");
+    assert_eq!(replayed["generation"], since + 1);
+    assert_eq!(replayed["hash"], api.get_hash("docs/first").unwrap());
+    assert!(replayed["timestamp"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(api.sockets(), 1);
+
+    fs::write(&notes, "# Notes again\n").unwrap();
+    api.update_paths([notes.as_path()]).unwrap();
+    fs::write(&first, "# First again\n").unwrap();
+    api.update_paths([first.as_path()]).unwrap();
+    let live = events.change().await;
+    assert_eq!(live["key"], "docs/first");
+    assert_eq!(live["kind"], "modified");
+    assert_eq!(live["generation"], api.generation());
+
+    drop(events);
+    common::wait_for(|| api.sockets() == 0).await;
+}
+
+#[tokio::test]
+async fn streams_say_when_changes_were_missed() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    let (status, _) = common::get(addr, "/api/events?since=soon").await;
+    assert_eq!(status, 400);
+
+    // generations from before the server started can't be replayed
+    let mut events = Events::open(addr, "?since=0").await;
+    let (name, data) = events.next().await.unwrap();
+    assert_eq!(name, "lagged");
+    assert!(data.contains(&api.generation().to_string()), "{data}");
+
+    // nothing was missed since the current generation
+    let mut events = Events::open(addr, &format!("?since={}", api.generation())).await;
+    let index = base.path().join("index.md");
+    fs::write(&index, "# Changed\n").unwrap();
+    api.update_paths([index.as_path()]).unwrap();
+    assert_eq!(events.change().await["key"], "index");
+
+    api.close();
+    let (name, _) = events.next().await.unwrap();
+    assert_eq!(name, "shutdown");
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn streams_leave_out_protected_pages() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir(dir.join("secret")).unwrap();
+    let (api, addr) = common::serve(dir, &["--protect", "secret:me:pw"]).await;
+    let mut events = Events::open(addr, "").await;
+
+    let (secret, index) = (dir.join("secret/plans.md"), dir.join("index.md"));
+    fs::write(&secret, "# Plans\n").unwrap();
+    api.update_paths([secret.as_path()]).unwrap();
+    fs::write(&index, "# Changed\n").unwrap();
+    api.update_paths([index.as_path()]).unwrap();
+    assert_eq!(events.change().await["key"], "index");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::fs;
+
+use mdflc::{extensions::Extensions, warnings::Category};
+
+#[tokio::test]
+async fn other_markdown_extensions_are_served() {
+    let base = common::base();
+    fs::write(base.path().join("long.markdown"), "# Long\n").unwrap();
+    fs::create_dir(base.path().join("guide")).unwrap();
+    fs::write(base.path().join("guide/short.mdown"), "# Short\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/long").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("<h1"), "{body}");
+    assert!(api.get_md("guide/short").unwrap().contains("Short"));
+
+    let path = base.path().join("added.markdown");
+    fs::write(&path, "# Added\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert!(api.get_md("added").unwrap().contains("Added"));
+}
+
+#[tokio::test]
+async fn colliding_extensions_are_reported() {
+    let base = common::base();
+    let md = base.path().join("both.md");
+    let markdown = base.path().join("both.markdown");
+    fs::write(&md, "# From md\n").unwrap();
+    fs::write(&markdown, "# From markdown\n").unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+
+    assert!(api.get_md("both").unwrap().contains("From md"));
+    let warnings = api.warnings.list();
+    assert!(
+        warnings.iter().any(|w| w.category == Category::Collision
+            && w.message == "both.markdown is left out for both.md"),
+        "{warnings:?}"
+    );
+
+    // the file left out never overwrites the one served
+    fs::write(&markdown, "# Still markdown\n").unwrap();
+    api.update_paths([markdown.as_path()]).unwrap();
+    assert!(api.get_md("both").unwrap().contains("From md"));
+
+    fs::remove_file(&md).unwrap();
+    api.update_paths([md.as_path()]).unwrap();
+    assert!(api.get_md("both").unwrap().contains("Still markdown"));
+    assert!(api
+        .warnings
+        .list()
+        .iter()
+        .all(|w| w.category != Category::Collision));
+}
+
+#[tokio::test]
+async fn links_to_other_extensions_find_their_page() {
+    let base = common::base();
+    fs::write(base.path().join("notes.markdown"), "# Notes\n\n## Part\n").unwrap();
+    fs::write(
+        base.path().join("links.md"),
+        "[a](notes.markdown#part) [b](notes.markdown#missing)\n",
+    )
+    .unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+
+    let anchors: Vec<_> = api
+        .warnings
+        .list()
+        .into_iter()
+        .filter(|w| w.category == Category::Anchor)
+        .collect();
+    assert_eq!(anchors.len(), 1, "{anchors:?}");
+    assert!(anchors[0].message.contains("missing"), "{anchors:?}");
+}
+
+#[tokio::test]
+async fn extensions_can_be_chosen() {
+    let base = common::base();
+    fs::write(base.path().join("notes.markdown"), "# Notes\n").unwrap();
+    fs::write(base.path().join("page.mkd"), "# Page\n").unwrap();
+    let (api, _) = common::serve(base.path(), &["--extensions", "md,.mkd"]).await;
+
+    assert!(api.get_md("page").is_some());
+    assert!(api.get_md("notes").is_none());
+
+    let (_, addr) = common::serve(base.path(), &[]).await;
+    let (status, body) = common::get(addr, "/notes.markdown").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("Notes"), "{body}");
+
+    let err = Extensions::new(&["txt".to_owned()]).unwrap_err();
+    assert_eq!(err.to_string(), "txt files are always plain text");
+    assert!(Extensions::new(&[String::new()]).is_err());
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::{fs, net::SocketAddr};
+
+use axum::{
+    http::{Method, StatusCode},
+    routing::{any, get},
+    Router,
+};
+use mdflc::warnings::Category;
+use tokio::net::TcpListener;
+
+/// Serves `/ok`, `/gone` and `/no-head`, which refuses `HEAD` but answers `GET`
+async fn remote() -> SocketAddr {
+    let app = Router::new()
+        .route("/ok", get(|| async { "ok" }))
+        .route("/gone", get(|| async { StatusCode::NOT_FOUND }))
+        .route(
+            "/no-head",
+            any(|method: Method| async move {
+                if method == Method::HEAD {
+                    StatusCode::METHOD_NOT_ALLOWED
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    addr
+}
+
+/// An address nothing listens on
+async fn closed() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn dead_links_are_marked_after_a_check() {
+    let remote = remote().await;
+    let closed = closed().await;
+    let base = common::base();
+    fs::write(
+        base.path().join("links.md"),
+        format!(
+            "[ok](http://{remote}/ok) [gone](http://{remote}/gone \"Gone\") \
+            [no head](http://{remote}/no-head) [closed](http://{closed}/)\n\
+            [local](index.md)\n"
+        ),
+    )
+    .unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    assert_eq!(api.external_urls().len(), 4);
+    // nothing is checked while rendering
+    assert!(!api.get_md("links").unwrap().contains("dead-link"));
+
+    let changed = api.check_external_links(false).await;
+    let gone = format!("http://{remote}/gone");
+    let refused = format!("http://{closed}/");
+    let mut dead = [refused, gone.clone()];
+    dead.sort_unstable();
+    assert_eq!(changed, dead);
+    assert!(api
+        .external
+        .dead(&format!("http://{remote}/no-head"))
+        .is_none());
+    let reason = api.external.dead(&gone).unwrap();
+    assert!(reason.contains("404"), "{reason}");
+
+    let html = api.get_md("links").unwrap();
+    assert_eq!(html.matches("class=\"dead-link\"").count(), 2);
+    assert!(html.contains("title=\"Gone (unreachable: responded with 404 Not Found)\""));
+    let warnings = api.warnings.list();
+    let warning = warnings
+        .iter()
+        .find(|w| w.category == Category::DeadLink)
+        .expect("no dead link warning");
+    assert_eq!(warning.key, "links");
+
+    // recent results are reused, forcing checks again without changing anything
+    assert!(api.check_external_links(false).await.is_empty());
+    assert!(api.check_external_links(true).await.is_empty());
+    assert_eq!(api.external.results().len(), 4);
+    assert!(api.external.results()[0].dead.is_some());
+}
+
+#[tokio::test]
+async fn revived_links_resolve_their_warning() {
+    let closed = closed().await;
+    let base = common::base();
+    fs::write(base.path().join("a.md"), format!("[a](http://{closed}/)\n")).unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    let mut events = api.subscribe();
+    assert_eq!(api.check_external_links(false).await.len(), 1);
+    let event = events.recv().await.unwrap();
+    assert_eq!(event.key, "a");
+    assert!(api
+        .warnings
+        .list()
+        .iter()
+        .any(|w| w.category == Category::DeadLink));
+
+    // the same port now answers
+    let listener = TcpListener::bind(closed).await.unwrap();
+    let app = Router::new().route("/", get(|| async { "back" }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    assert_eq!(api.check_external_links(true).await.len(), 1);
+    assert!(!api.get_md("a").unwrap().contains("dead-link"));
+    assert!(!api
+        .warnings
+        .list()
+        .iter()
+        .any(|w| w.category == Category::DeadLink));
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use std::{fs, net::SocketAddr};
+
+use tempfile::TempDir;
+
+/// A base with `index.md` and two pages in `docs`, served with `--fallback mode`
+async fn serve(mode: &str) -> (TempDir, SocketAddr) {
+    let base = common::base();
+    fs::create_dir(base.path().join("docs")).unwrap();
+    fs::write(base.path().join("docs/setup.md"), "# Setup\n").unwrap();
+    fs::write(base.path().join("docs/usage.md"), "# Usage\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &["--fallback", mode]).await;
+    (base, addr)
+}
+
+/// Api routes never fall back, whatever the mode
+async fn check_api_misses(addr: SocketAddr) {
+    for path in ["/api/missing", "/api/hash/missing", "/api/nav/docs/missing"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 404, "{path}");
+        assert!(!body.contains("<html"), "{path}: {body}");
+    }
+}
+
+#[tokio::test]
+async fn not_found_is_the_default() {
+    let (_base, addr) = serve("404").await;
+    for path in ["/missing", "/docs/missing", "/docs/missing/deeper"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 404, "{path}");
+        assert!(body.contains("Error 404"), "{path}: {body}");
+    }
+    check_api_misses(addr).await;
+}
+
+#[tokio::test]
+async fn index_is_served_for_misses() {
+    let (_base, addr) = serve("index").await;
+    for path in ["/missing", "/docs/missing", "/docs/missing/deeper"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+        assert!(
+            body.contains(r#"<h1 id="index">Index</h1>"#),
+            "{path}: {body}"
+        );
+    }
+    check_api_misses(addr).await;
+}
+
+#[tokio::test]
+async fn the_nearest_directory_is_listed() {
+    let (_base, addr) = serve("listing").await;
+    // directories themselves are listed without falling back, see
+    // directories_list_their_contents
+    for path in ["/docs/missing", "/docs/missing/deeper"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+        assert!(body.contains("<h1>Pages in docs</h1>"), "{path}: {body}");
+        let setup = body.find(r#"<a href="/docs/setup">Setup</a>"#).unwrap();
+        let usage = body.find(r#"<a href="/docs/usage">Usage</a>"#).unwrap();
+        assert!(setup < usage);
+        assert!(!body.contains(r#"href="/index""#), "{path}: {body}");
+    }
+
+    let (status, body) = common::get(addr, "/missing").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("<h1>All pages</h1>"), "{body}");
+    assert!(body.contains(r#"<a href="/index">Index</a>"#), "{body}");
+    check_api_misses(addr).await;
+}
+
+#[tokio::test]
+async fn nested_pages_are_reachable() {
+    let base = common::base();
+    fs::create_dir_all(base.path().join("docs/deep")).unwrap();
+    fs::write(base.path().join("docs/deep/setup.md"), "# Deep setup\n").unwrap();
+    fs::write(base.path().join("docs/my notes.md"), "# Spaced\n").unwrap();
+    fs::write(base.path().join("docs/café.md"), "# Unicode\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    for (path, heading) in [
+        ("/docs/deep/setup", "Deep setup"),
+        ("/docs/deep/setup.md", "Deep setup"),
+        ("/docs/deep/setup/", "Deep setup"),
+        ("/docs/my%20notes", "Spaced"),
+        ("/docs/caf%C3%A9", "Unicode"),
+    ] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+        assert!(body.contains(heading), "{path}: {body}");
+    }
+    let (status, _) = common::get(addr, "/assets/docs/deep/setup.md").await;
+    assert_eq!(status, 200);
+    check_api_misses(addr).await;
+}
+
+#[tokio::test]
+async fn directories_list_their_contents() {
+    let base = common::base();
+    fs::create_dir_all(base.path().join("docs/guides")).unwrap();
+    fs::write(base.path().join("docs/setup.md"), "# Setting up\n").unwrap();
+    fs::write(base.path().join("docs/guides/first.md"), "# First\n").unwrap();
+    fs::create_dir(base.path().join("notes")).unwrap();
+    fs::write(base.path().join("notes/README.md"), "# About notes\n").unwrap();
+    fs::write(base.path().join("notes/today.md"), "# Today\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    for path in ["/docs/", "/docs"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+        assert!(body.contains("Contents of docs"), "{body}");
+        assert!(
+            body.contains("<a href=\"/docs/guides/\">guides/</a>"),
+            "{body}"
+        );
+        assert!(
+            body.contains("<a href=\"/docs/setup\">Setting up</a>"),
+            "{body}"
+        );
+        assert!(!body.contains("First"), "{body}");
+    }
+
+    let (status, body) = common::get(addr, "/notes/").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("About notes"), "{body}");
+    assert!(!body.contains("Contents of"), "{body}");
+
+    let added = base.path().join("docs/later.md");
+    fs::write(&added, "# Later\n").unwrap();
+    api.update_paths([added.as_path()]).unwrap();
+    let (_, body) = common::get(addr, "/docs/").await;
+    assert!(body.contains("<a href=\"/docs/later\">Later</a>"), "{body}");
+
+    let (status, _) = common::get(addr, "/missing/").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn custom_not_found_pages_come_and_go() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    let custom = base.path().join("404.md");
+
+    fs::write(&custom, "# Lost\n\nnothing here\n").unwrap();
+    api.update_paths([custom.as_path()]).unwrap();
+    let (status, body) = common::get(addr, "/missing").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("nothing here"), "{body}");
+    assert!(!body.contains("Error 404"), "{body}");
+
+    fs::remove_file(&custom).unwrap();
+    api.update_paths([custom.as_path()]).unwrap();
+    let (status, body) = common::get(addr, "/missing").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("Error 404"), "{body}");
+}
+
+#[tokio::test]
+async fn single_files_are_served_under_their_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let page = dir.path().join("CHANGELOG.md");
+    fs::write(&page, "# Changes\n").unwrap();
+    let (api, addr) = common::serve(&page, &[]).await;
+
+    for path in ["/index", "/CHANGELOG", "/changelog"] {
+        let (status, body) = common::get(addr, path).await;
+        assert_eq!(status, 200, "{path}");
+        assert!(
+            body.contains(r#"<h1 id="changes">Changes</h1>"#),
+            "{path}: {body}"
+        );
+    }
+    let keys: Vec<_> = api.list().into_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, ["CHANGELOG", "changelog", "index"]);
+}
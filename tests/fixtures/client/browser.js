@@ -0,0 +1,61 @@
+// A minimal browser stand-in for running index.js under node, see tests/client.rs
+
+const fs = require("fs");
+const vm = require("vm");
+
+function element(props) {
+  return Object.assign(
+    {
+      dataset: {},
+      querySelector() { return null; },
+      querySelectorAll() { return []; },
+      addEventListener() {},
+      appendChild() {},
+      setAttribute(name, value) { this[name] = value; },
+      before() {},
+      after() {},
+    },
+    props,
+  );
+}
+
+// runs the index.js given as the only argument with `root` as the page's #root, `head`
+// and `status` stand for document.head and the status indicator
+function run({ root, head = element({}), status = element({}), WebSocket = class {} }) {
+  const window = {
+    document: {
+      getElementById(id) {
+        return { root: root, status: status }[id] || null;
+      },
+      querySelector() { return null; },
+      querySelectorAll() { return []; },
+      addEventListener() {},
+      createElement() { return element({}); },
+      head: head,
+    },
+    location: { protocol: "http:", host: "localhost", pathname: "/docs/page" },
+    navigator: {},
+    sessionStorage: { getItem() { return null; }, setItem() {}, removeItem() {} },
+    history: {},
+    fetch() { return new Promise(function () {}); },
+    setTimeout() {},
+    setInterval() {},
+    scrollY: 0,
+    scrollTo() {},
+    WebSocket: WebSocket,
+    URL: URL,
+    console: console,
+  };
+  // browsers keep window.status as a string, whatever is assigned to it
+  var statusText = "";
+  Object.defineProperty(window, "status", {
+    get() { return statusText; },
+    set(value) { statusText = String(value); },
+  });
+  window.window = window;
+  vm.createContext(window);
+  vm.runInContext(fs.readFileSync(process.argv[2], "utf8"), window);
+  return window;
+}
+
+module.exports = { element, run };
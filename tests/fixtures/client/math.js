@@ -0,0 +1,36 @@
+// Checks that math is drawn by the bundled katex, see tests/client.rs
+
+const assert = require("assert");
+const { element, run } = require("./browser");
+
+const math = element({ textContent: "x^2", dataset: { math: "inline" } });
+const root = element({
+  dataset: { page: "docs/page", generation: "3" },
+  querySelectorAll(selector) {
+    return selector === "[data-math]" ? [math] : [];
+  },
+});
+const loaded = [];
+const head = element({
+  appendChild(child) {
+    loaded.push(child);
+  },
+});
+
+const window = run({ root: root, head: head });
+
+assert.deepStrictEqual(
+  loaded.map(function (child) { return child.src || child.href; }),
+  ["/vendor/katex.min.js"],
+);
+const rendered = [];
+window.katex = {
+  render(text, target, options) {
+    rendered.push([text, target, options]);
+  },
+};
+loaded[0].onload();
+assert.strictEqual(rendered.length, 1);
+assert.strictEqual(rendered[0][0], "x^2");
+assert.strictEqual(rendered[0][1], math);
+assert.strictEqual(rendered[0][2].output, "mathml");
@@ -0,0 +1,57 @@
+// Checks that diagrams are drawn by the bundled mermaid, and that failures show on the
+// page, see tests/client.rs
+
+const assert = require("assert");
+const { element, run } = require("./browser");
+
+// runs index.js on a page with one diagram, giving the diagram, the scripts it loads and
+// the window
+function page() {
+  const notes = [];
+  const diagram = element({
+    before(note) {
+      notes.push(note);
+    },
+  });
+  const root = element({
+    dataset: { page: "docs/page", generation: "3" },
+    querySelectorAll(selector) {
+      return selector === "pre.mermaid" ? [diagram] : [];
+    },
+  });
+  const loaded = [];
+  const head = element({
+    appendChild(child) {
+      loaded.push(child);
+    },
+  });
+  const window = run({ root: root, head: head });
+  assert.deepStrictEqual(
+    loaded.map(function (child) { return child.src; }),
+    ["/vendor/mermaid.min.js"],
+  );
+  return { notes: notes, script: loaded[0], window: window };
+}
+
+(async function () {
+  const unloaded = page();
+  unloaded.script.onerror();
+  assert.strictEqual(unloaded.notes.length, 1);
+  assert.strictEqual(unloaded.notes[0].className, "fence-error");
+  assert.strictEqual(unloaded.notes[0].textContent, "mermaid: unable to load mermaid");
+
+  const broken = page();
+  broken.window.mermaid = {
+    initialize() {},
+    run() {
+      return Promise.reject(new Error("Parse error on line 1"));
+    },
+  };
+  broken.script.onload();
+  await new Promise(setImmediate);
+  assert.strictEqual(broken.notes.length, 1);
+  assert.strictEqual(broken.notes[0].textContent, "mermaid: Parse error on line 1");
+})().catch(function (e) {
+  console.error(e);
+  process.exit(1);
+});
@@ -0,0 +1,27 @@
+// Checks that refresh sockets subscribe once open, see tests/client.rs
+
+const assert = require("assert");
+const { element, run } = require("./browser");
+
+const root = element({ dataset: { page: "docs/page", generation: "3" } });
+const indicator = element({});
+const sockets = [];
+class WebSocket {
+  constructor(url) {
+    this.url = url;
+    this.sent = [];
+    sockets.push(this);
+  }
+  send(message) {
+    this.sent.push(JSON.parse(message));
+  }
+}
+
+run({ root: root, status: indicator, WebSocket: WebSocket });
+
+assert.strictEqual(sockets.length, 1);
+sockets[0].onopen();
+assert.deepStrictEqual(sockets[0].sent, [
+  { type: "subscribe", page: "docs/page", generation: 3 },
+]);
+assert.strictEqual(indicator.dataset.state, "connected");
@@ -0,0 +1,120 @@
+mod common;
+
+use std::fs;
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+use mdflc::{warnings::Category, Api};
+
+/// Commits everything in the work tree of `repo` to `HEAD`
+fn commit(repo: &Repository, message: &str) -> Oid {
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("mdflc", "mdflc@example.com").unwrap();
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn pages_come_from_the_commit() {
+    let base = common::base();
+    let repo = Repository::init(base.path()).unwrap();
+    fs::create_dir(base.path().join("guide")).unwrap();
+    fs::write(base.path().join("guide/setup.md"), "# Setup\n").unwrap();
+    fs::write(base.path().join("notes.txt"), "plain text").unwrap();
+    fs::write(base.path().join("data.csv"), "not a page").unwrap();
+    let id = commit(&repo, "Add the guide");
+
+    // the work tree is ignored
+    fs::write(base.path().join("index.md"), "# Uncommitted\n").unwrap();
+    fs::write(base.path().join("draft.md"), "# Draft\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &["--git-ref", "HEAD"]).await;
+
+    let mut keys: Vec<_> = api.list().into_iter().map(|(key, _)| key).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["guide/setup", "index", "notes"]);
+    assert!(api.get_md("index").unwrap().contains("Index"));
+    let (status, _) = common::get(addr, "/draft").await;
+    assert_eq!(status, 404);
+
+    let revision = api.revision().unwrap();
+    assert_eq!(revision.commit, id.to_string());
+    assert_eq!(revision.summary, "Add the guide");
+    assert_eq!(api.watch_paths()[0], repo.path().canonicalize().unwrap());
+}
+
+#[tokio::test]
+async fn moving_the_ref_rescans() {
+    let base = common::base();
+    let repo = Repository::init(base.path()).unwrap();
+    commit(&repo, "First");
+    let (api, _) = common::serve(base.path(), &["--git-ref", "HEAD"]).await;
+    assert!(!api.follow_git_ref().unwrap());
+
+    fs::write(base.path().join("index.md"), "# Moved\n").unwrap();
+    let mut events = api.subscribe();
+    // only watched paths within the git directory are reported
+    api.update_paths([repo.path().join("HEAD").as_path()])
+        .unwrap();
+    assert!(api.get_md("index").unwrap().contains("Index"));
+
+    let id = commit(&repo, "Second");
+    api.update_paths([repo.path().join("refs/heads").as_path()])
+        .unwrap();
+    assert_eq!(api.revision().unwrap().commit, id.to_string());
+    assert!(api.get_md("index").unwrap().contains("Moved"));
+    assert_eq!(events.recv().await.unwrap().key, "index");
+}
+
+#[tokio::test]
+async fn keys_are_relative_to_base() {
+    let root = common::base();
+    let repo = Repository::init(root.path()).unwrap();
+    let docs = root.path().join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("index.md"), "# Docs\n").unwrap();
+    fs::write(docs.join("api.md"), "# Api\n").unwrap();
+    commit(&repo, "Add docs");
+
+    let (api, _) = common::serve(&docs, &["--git-ref", "HEAD"]).await;
+    let mut keys: Vec<_> = api.list().into_iter().map(|(key, _)| key).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["api", "index"]);
+    let inspection = api.inspect("api").unwrap();
+    assert_eq!(inspection.size, Some(6));
+    assert!(api
+        .warnings
+        .list()
+        .iter()
+        .all(|w| w.category != Category::Skipped));
+}
+
+#[test]
+fn bad_refs_fail_to_start() {
+    let base = common::base();
+    let error = Api::new(&common::args(base.path(), &["--git-ref", "HEAD"])).unwrap_err();
+    assert!(
+        error.to_string().contains("is not within a git repository"),
+        "{error:#}"
+    );
+
+    let repo = Repository::init(base.path()).unwrap();
+    commit(&repo, "First");
+    let error = Api::new(&common::args(base.path(), &["--git-ref", "missing"])).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("no ref or commit named \"missing\""),
+        "{error:#}"
+    );
+}
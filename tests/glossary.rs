@@ -0,0 +1,69 @@
+mod common;
+
+use std::fs;
+
+use mdflc::glossary::Glossary;
+
+#[test]
+fn glossaries_read_lines_and_definition_lists() {
+    let text = "# Glossary\n\nAPI: Application programming interface\n\n\
+        **Pull request**\nPR\n: A proposed change\n: Ignored\n\nNot a term\n";
+    let glossary = Glossary::parse(text, false);
+    let terms: Vec<_> = glossary
+        .terms()
+        .iter()
+        .map(|t| (t.name.as_str(), t.definition.as_str()))
+        .collect();
+    assert_eq!(
+        terms,
+        [
+            ("Pull request", "A proposed change"),
+            ("API", "Application programming interface"),
+            ("PR", "A proposed change"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn first_uses_of_terms_are_explained() {
+    let base = common::base();
+    let dir = base.path();
+    let glossary = dir.join("glossary.txt");
+    fs::write(&glossary, "API: Application \"programming\" interface\n").unwrap();
+    let page = "# API\n\nThe `API` and [API](x.md) aren't, the api is. APIs, rapid, \
+        and the API again aren't.\n";
+    fs::write(dir.join("a.md"), page).unwrap();
+    let path = glossary.to_str().unwrap();
+    let (api, _) = common::serve(dir, &["--glossary", path]).await;
+
+    let html = api.get_md("a").unwrap();
+    let abbr = r#"<abbr title="Application &quot;programming&quot; interface">"#;
+    assert!(html.contains(&format!("the {abbr}api</abbr> is")), "{html}");
+    assert_eq!(html.matches("<abbr").count(), 1, "{html}");
+
+    // edits rerender every page
+    fs::write(&glossary, "rapid: Quick\n").unwrap();
+    api.update_paths([glossary.as_path()]).unwrap();
+    let html = api.get_md("a").unwrap();
+    assert!(
+        html.contains(r#"<abbr title="Quick">rapid</abbr>"#),
+        "{html}"
+    );
+    assert!(!html.contains("Application"), "{html}");
+}
+
+#[tokio::test]
+async fn case_sensitive_glossaries_match_exactly() {
+    let base = common::base();
+    let dir = base.path();
+    let glossary = dir.join("glossary.txt");
+    fs::write(&glossary, "Go: A language\n").unwrap();
+    fs::write(dir.join("a.md"), "We go, in Go.\n").unwrap();
+    let path = glossary.to_str().unwrap();
+    let (api, _) = common::serve(dir, &["--glossary", path, "--glossary-case-sensitive"]).await;
+    let html = api.get_md("a").unwrap();
+    assert!(
+        html.contains(r#"We go, in <abbr title="A language">Go</abbr>."#),
+        "{html}"
+    );
+}
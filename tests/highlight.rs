@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use mdflc::highlight::{apply, MAX_MARKS};
+
+#[test]
+fn only_text_is_marked() {
+    let html = r#"<p class="rust">Rust and <a href="/rust" title="RUST">rust</a> &amp; <code>rusty</code></p><script>let rust;</script><!-- rust -->"#;
+    assert_eq!(
+        apply(html, "rust"),
+        r#"<p class="rust"><mark>Rust</mark> and <a href="/rust" title="RUST"><mark>rust</mark></a> &amp; <code><mark>rust</mark>y</code></p><script>let rust;</script><!-- rust -->"#
+    );
+}
+
+#[test]
+fn character_references_are_matched_whole() {
+    let html = "<p>fish &amp; chips, amp &lt;b&gt;</p>";
+    assert_eq!(
+        apply(html, "& chips"),
+        "<p>fish <mark>&amp; chips</mark>, amp &lt;b&gt;</p>"
+    );
+    assert_eq!(
+        apply(html, "amp"),
+        "<p>fish &amp; chips, <mark>amp</mark> &lt;b&gt;</p>"
+    );
+    assert_eq!(
+        apply(html, "<b>"),
+        "<p>fish &amp; chips, amp <mark>&lt;b&gt;</mark></p>"
+    );
+    assert_eq!(
+        apply("<p>ÉCOLE école</p>", "école")
+            .matches("<mark>")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn marks_are_capped() {
+    let html = format!("<p>{}</p>", "a ".repeat(MAX_MARKS * 2));
+    assert_eq!(apply(&html, "a").matches("<mark>").count(), MAX_MARKS);
+    assert_eq!(apply(&html, "  "), html);
+    assert_eq!(apply(&html, &"a".repeat(1000)), html);
+}
+
+#[tokio::test]
+async fn pages_highlight_the_term_asked_for() {
+    let base = common::base();
+    fs::write(
+        base.path().join("page.md"),
+        "# Search\n\nFind the *needle* here.\n",
+    )
+    .unwrap();
+    let (_api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/page?highlight=Needle").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("<em><mark>needle</mark></em>"), "{body}");
+    let (_, body) = common::get(addr, "/page").await;
+    assert!(!body.contains("<mark>"), "{body}");
+}
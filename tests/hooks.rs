@@ -0,0 +1,108 @@
+mod common;
+
+use anyhow::bail;
+use mdflc::{warnings::Category, Api};
+
+#[test]
+fn post_render_hooks_run_in_order() {
+    let base = common::base();
+    let api = Api::builder(common::args(base.path(), &[]))
+        .post_render(|key, html| Ok(format!("{html}<p>{key}</p>")))
+        .post_render(|_, html| Ok(html.replace("<h1", "<h1 data-hooked")))
+        .build()
+        .unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("index").unwrap();
+    assert!(html.contains("<h1 data-hooked id=\"index\">Index</h1>\n<p>index</p>"));
+}
+
+#[test]
+fn failing_hooks_mark_the_page() {
+    let base = common::base();
+    let api = Api::builder(common::args(base.path(), &[]))
+        .post_render(|_, _| bail!("no <thanks>"))
+        .build()
+        .unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("index").unwrap();
+    assert!(html.contains("failed to render:</strong> no &lt;thanks&gt;"));
+    let warnings = api.warnings.list();
+    assert!(warnings
+        .iter()
+        .any(|w| w.category == Category::Render && w.key == "index"));
+}
+
+#[test]
+fn fence_handlers_replace_code_blocks() {
+    let base = common::base();
+    let text = "```graphviz big\ndigraph { a -> b }\n```\n\n```broken\n<x>\n```\n\n```mermaid\na --> b\n```\n\n```rust\nfn main() {}\n```\n";
+    std::fs::write(base.path().join("index.md"), text).unwrap();
+    let api = Api::builder(common::args(base.path(), &[]))
+        .fence_handler("graphviz", |info, body| {
+            Ok(format!("<svg data-info=\"{info}\">{body}</svg>"))
+        })
+        .fence_handler("broken", |_, _| bail!("bad <input>"))
+        .build()
+        .unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("index").unwrap();
+    assert!(html.contains("<svg data-info=\"graphviz big\">digraph { a -> b }\n</svg>"));
+    assert!(html.contains("<strong>broken block failed:</strong> bad &lt;input&gt;</div>"));
+    assert!(html.contains("<pre class=\"mermaid\">a --&gt; b\n</pre>"));
+    assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}\n</code></pre>"));
+}
+
+#[test]
+fn partial_problems_are_listed_in_a_banner() {
+    let base = common::base();
+    let text = "# Index\n\n```broken\n<x>\n```\n\n[gone](#nowhere)\n\nstill rendered\n";
+    std::fs::write(base.path().join("index.md"), text).unwrap();
+    let api = Api::builder(common::args(base.path(), &[]))
+        .fence_handler("broken", |_, _| bail!("bad <input>"))
+        .build()
+        .unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("index").unwrap();
+    let banner = html.find("<aside class=\"problems\"").expect("no banner");
+    assert!(banner < html.find("id=\"root\"").unwrap());
+    assert!(html.contains("<li>broken block failed: bad &lt;input&gt;</li>"));
+    assert!(html.contains("<li>link to a missing heading: #nowhere</li>"));
+    assert!(html.contains("<p>still rendered</p>"));
+    let warnings = api.warnings.list();
+    assert!(warnings.iter().any(
+        |w| w.category == Category::Problem && w.message == "broken block failed: bad <input>"
+    ));
+    assert!(!warnings.iter().any(|w| w.category == Category::Render));
+
+    std::fs::write(base.path().join("index.md"), "# Index\n\nfine\n").unwrap();
+    api.rescan().unwrap();
+    assert!(!api.get_md("index").unwrap().contains("class=\"problems\""));
+    assert!(api.warnings.is_empty(), "{:?}", api.warnings.list());
+}
+
+#[test]
+fn strict_mode_fails_pages_with_problems() {
+    let base = common::base();
+    let text = "# Index\n\n```broken\n<x>\n```\n\nnot rendered\n";
+    std::fs::write(base.path().join("index.md"), text).unwrap();
+    std::fs::write(base.path().join("fine.md"), "# Fine\n").unwrap();
+    let api = Api::builder(common::args(base.path(), &["--strict"]))
+        .fence_handler("broken", |_, _| bail!("bad <input>"))
+        .build()
+        .unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("index").unwrap();
+    assert!(html.contains("failed to render:</strong></p>\n<ul>\n<li>broken block failed"));
+    assert!(!html.contains("<p>not rendered</p>"));
+    assert!(!html.contains("class=\"problems\""));
+    let warnings = api.warnings.list();
+    assert!(warnings
+        .iter()
+        .any(|w| w.category == Category::Render && w.key == "index"));
+    assert!(!api.get_md("fine").unwrap().contains("failed"));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::fs;
+
+use mdflc::Api;
+
+#[tokio::test]
+async fn included_files_render_in_place() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir_all(dir.join("docs/parts")).unwrap();
+    fs::write(
+        dir.join("docs/guide.md"),
+        "# Guide\n\n{{include parts/intro.md}}\n\n> {{ include /quote.md }}\n\n\
+        ```\n{{include parts/intro.md}}\n```\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("docs/parts/intro.md"),
+        "---\ntitle: Intro\n---\nSee *this*.\n\n{{include steps.md}}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("docs/parts/steps.md"), "1. one\n2. two\n").unwrap();
+    fs::write(dir.join("quote.md"), "quoted\nlines\n").unwrap();
+    let api = Api::new(&common::args(dir, &[])).unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("docs/guide").unwrap();
+    assert!(html.contains("<p>See <em>this</em>.</p>"), "{html}");
+    assert!(html.contains("<li>two</li>"), "{html}");
+    assert!(!html.contains("title: Intro"), "{html}");
+    assert!(
+        html.contains("<blockquote>\n<p>quoted\nlines</p>\n</blockquote>"),
+        "{html}"
+    );
+    // code is left alone
+    assert!(
+        html.contains("<code>{{include parts/intro.md}}\n</code>"),
+        "{html}"
+    );
+    assert!(api.warnings.list().is_empty(), "{:?}", api.warnings.list());
+}
+
+#[tokio::test]
+async fn failed_includes_say_why() {
+    let base = common::base();
+    let dir = base.path();
+    fs::write(
+        dir.join("a.md"),
+        "{{include a.md}}\n\n{{include b.md}}\n\n{{include missing.md}}\n\n\
+        {{include ../outside.md}}\n\n{{include}}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("b.md"), "from b\n\n{{include a.md}}\n").unwrap();
+    let api = Api::new(&common::args(dir, &[])).unwrap();
+    api.scan().unwrap();
+
+    let html = api.get_md("a").unwrap();
+    assert_eq!(html.matches("from b").count(), 1, "{html}");
+    assert!(html.contains("class=\"include-error\""), "{html}");
+    for reason in [
+        "a.md is already being included",
+        "unable to read missing.md",
+        "../outside.md is outside of base",
+        "no file was named",
+    ] {
+        assert!(html.contains(reason), "{reason}: {html}");
+    }
+    let problems = api.warnings.list();
+    assert!(
+        problems
+            .iter()
+            .any(|w| w.key == "a" && w.message.contains("{{include missing.md}} failed")),
+        "{problems:?}"
+    );
+
+    // strict commonmark has no includes
+    api.set_commonmark(true).unwrap();
+    let html = api.get_md("a").unwrap();
+    assert!(!html.contains("from b"), "{html}");
+    assert!(!html.contains("include-error"), "{html}");
+}
+
+#[tokio::test]
+async fn pages_follow_the_files_they_include() {
+    let base = common::base();
+    let dir = base.path();
+    fs::create_dir(dir.join("parts")).unwrap();
+    fs::write(
+        dir.join("page.md"),
+        "{{include parts/a.md}}\n\n{{include later.md}}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("parts/a.md"), "{{include b.md}}\n").unwrap();
+    let b = dir.join("parts/b.md");
+    fs::write(&b, "first\n").unwrap();
+    let api = Api::new(&common::args(dir, &[])).unwrap();
+    api.scan().unwrap();
+    assert!(api.get_md("page").unwrap().contains("first"));
+
+    // files included by included files count too
+    fs::write(&b, "second\n").unwrap();
+    api.update_paths([b.as_path()]).unwrap();
+    let html = api.get_md("page").unwrap();
+    assert!(html.contains("second") && !html.contains("first"), "{html}");
+
+    // as do files that don't exist yet
+    let later = dir.join("later.md");
+    fs::write(&later, "third\n").unwrap();
+    api.update_paths([later.as_path()]).unwrap();
+    let html = api.get_md("page").unwrap();
+    assert!(
+        html.contains("third") && !html.contains("include-error"),
+        "{html}"
+    );
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::fs;
+
+#[tokio::test]
+async fn inspect_reports_page_state() {
+    let base = common::base();
+    fs::write(
+        base.path().join("index.md"),
+        "# Index\n\n## Part\n\n[ok](other#setup) [missing](other#nowhere) [web](https://example.com)\n",
+    )
+    .unwrap();
+    fs::write(
+        base.path().join("other.md"),
+        "---\ntitle: Other page\n---\n\n## Setup\n\n```mermaid\ngraph TD\n```\n",
+    )
+    .unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let index = api.inspect("index").unwrap();
+    assert_eq!(index.headings, 2);
+    let links: Vec<_> = index
+        .links
+        .iter()
+        .map(|l| (l.url.as_str(), l.broken))
+        .collect();
+    assert_eq!(
+        links,
+        [
+            ("other#setup", false),
+            ("other#nowhere", true),
+            ("https://example.com", false),
+        ]
+    );
+    assert_eq!(
+        index.size,
+        Some(fs::metadata(base.path().join("index.md")).unwrap().len())
+    );
+    assert!(index.modified.is_some());
+    assert!(!index.stale);
+    assert_eq!(index.warnings.len(), 1, "{:?}", index.warnings);
+
+    let other = api.inspect("other.md").unwrap();
+    assert_eq!(other.title, "Other page");
+    assert_eq!(other.referrers, ["index"]);
+    assert_eq!(other.features, ["front matter", "mermaid fence"]);
+    assert!(other.links.is_empty());
+
+    api.invalidate_renders();
+    assert!(api.inspect("other").unwrap().stale);
+    assert!(api.inspect("missing").is_none());
+
+    let (status, body) = common::get(addr, "/api/inspect/other").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["key"], "other");
+    assert_eq!(json["referrers"][0], "index");
+    let (status, _) = common::get(addr, "/api/inspect/missing").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn meta_describes_pages_as_json() {
+    let base = common::base();
+    fs::write(
+        base.path().join("guide.md"),
+        "---\ntitle: The guide\ntags: [a, b]\n---\n\n# Start `here`\n\n## Then\n",
+    )
+    .unwrap();
+    let (_api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/api/meta/guide").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["key"], "guide");
+    assert_eq!(json["title"], "The guide");
+    let size = fs::metadata(base.path().join("guide.md")).unwrap().len();
+    assert_eq!(json["size"], size);
+    assert!(json["modified"].is_string(), "{json}");
+    assert!(json["path"].as_str().unwrap().ends_with("guide.md"));
+    assert_eq!(
+        json["headings"],
+        serde_json::json!([
+            { "level": 1, "id": "start-here", "text": "Start here" },
+            { "level": 2, "id": "then", "text": "Then" },
+        ])
+    );
+    assert_eq!(json["front_matter"]["tags"], serde_json::json!(["a", "b"]));
+    assert_eq!(json["words"], 3);
+    assert_eq!(json["reading_minutes"], 1);
+
+    let (status, _) = common::get(addr, "/api/meta/missing").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn pages_are_listed_as_json() {
+    let base = common::base();
+    fs::create_dir(base.path().join("docs")).unwrap();
+    fs::write(base.path().join("docs/b.md"), "# Bee\n").unwrap();
+    fs::write(base.path().join("docs/a.md"), "# Ay\n").unwrap();
+    fs::write(base.path().join("zed.md"), "# Zed\n").unwrap();
+    let (_api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/api/pages").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let keys: Vec<_> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|page| page["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["docs/a", "docs/b", "index", "zed"]);
+    assert_eq!(json[0]["title"], "Ay");
+    assert!(json[0]["path"].as_str().unwrap().ends_with("a.md"));
+
+    let (_, body) = common::get(addr, "/api/pages?prefix=docs/").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    let (_, body) = common::get(addr, "/api/pages?prefix=nothing").await;
+    assert_eq!(body, "[]");
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use std::fs;
+
+fn keys(nav: &mdflc::Nav) -> [Option<&str>; 4] {
+    [&nav.prev, &nav.next, &nav.parent, &nav.first_child]
+        .map(|l| l.as_ref().map(|l| l.key.as_str()))
+}
+
+#[tokio::test]
+async fn nav_follows_listing_order() {
+    let base = common::base();
+    fs::write(base.path().join("b.md"), "# Bee\n").unwrap();
+    fs::write(base.path().join("a.md"), "# A\n").unwrap();
+    fs::create_dir(base.path().join("docs")).unwrap();
+    fs::write(base.path().join("docs/index.md"), "# Docs\n").unwrap();
+    fs::write(base.path().join("docs/setup 2.md"), "# Setup 2\n").unwrap();
+    fs::write(base.path().join("docs/setup 10.md"), "# Setup 10\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    // directories come first, then pages in natural order
+    assert_eq!(
+        api.ordered_keys(""),
+        [
+            "docs/index",
+            "docs/setup 2",
+            "docs/setup 10",
+            "a",
+            "b",
+            "index"
+        ]
+    );
+
+    let nav = api.nav("docs/index").unwrap();
+    assert_eq!(
+        keys(&nav),
+        [
+            None,
+            Some("docs/setup 2"),
+            Some("index"),
+            Some("docs/setup 2")
+        ]
+    );
+    let nav = api.nav("docs/setup 10").unwrap();
+    assert_eq!(
+        keys(&nav),
+        [Some("docs/setup 2"), Some("a"), Some("docs/index"), None]
+    );
+    assert_eq!(nav.next.unwrap().title, "A");
+    let nav = api.nav("index").unwrap();
+    assert_eq!(keys(&nav), [Some("b"), None, None, Some("docs/index")]);
+
+    // order changes are picked up without a restart
+    fs::write(base.path().join("_order.txt"), "b\n").unwrap();
+    let nav = api.nav("b").unwrap();
+    assert_eq!(keys(&nav), [None, Some("docs/index"), Some("index"), None]);
+
+    let (status, body) = common::get(addr, "/api/nav/a").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["prev"]["key"], "docs/setup 10");
+    assert_eq!(json["next"]["title"], "Index");
+    let (status, body) = common::get(addr, "/api/nav/missing").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("\"error\""), "{body}");
+}
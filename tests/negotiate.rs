@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs;
+
+use axum::http::{header::ACCEPT, HeaderMap, HeaderValue};
+use mdflc::negotiate::{format, Format};
+
+fn accept(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static(value));
+    headers
+}
+
+#[test]
+fn browsers_get_html() {
+    for value in [
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        "*/*",
+        "text/html, text/markdown;q=0.5",
+        "text/markdown;q=0, */*",
+    ] {
+        assert_eq!(format(None, &accept(value)), Ok(Format::Html), "{value}");
+    }
+    assert_eq!(format(None, &HeaderMap::new()), Ok(Format::Html));
+}
+
+#[test]
+fn tools_get_markdown() {
+    for value in ["text/markdown", "text/plain", "text/x-markdown, */*;q=0.1"] {
+        assert_eq!(
+            format(None, &accept(value)),
+            Ok(Format::Markdown),
+            "{value}"
+        );
+    }
+}
+
+#[test]
+fn the_query_wins_over_the_header() {
+    assert_eq!(
+        format(Some("md"), &accept("text/html")),
+        Ok(Format::Markdown)
+    );
+    assert_eq!(
+        format(Some("html"), &accept("text/markdown")),
+        Ok(Format::Html)
+    );
+    assert_eq!(format(Some("pdf"), &HeaderMap::new()), Err("pdf"));
+}
+
+#[tokio::test]
+async fn markdown_is_served_from_the_source() {
+    let base = common::base();
+    fs::write(base.path().join("page.md"), "# Page\n\nSome *text*.\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, body) = common::get_with(addr, "/page", &["Accept: text/markdown"]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(head.contains("content-type: text/markdown"), "{head}");
+    assert!(head.contains("vary: accept"), "{head}");
+    assert_eq!(body, "# Page\n\nSome *text*.\n");
+    let etag = head
+        .lines()
+        .find_map(|line| line.strip_prefix("etag: "))
+        .unwrap();
+    assert!(etag.starts_with("\"md-"), "{etag}");
+
+    let (head, _) = common::get_with(
+        addr,
+        "/page",
+        &["Accept: text/markdown", &format!("If-None-Match: {etag}")],
+    )
+    .await;
+    assert!(head.starts_with("http/1.1 304"), "{head}");
+    assert!(head.contains(&format!("etag: {etag}")), "{head}");
+
+    let (head, body) = common::get_with(addr, "/page?format=html", &["Accept: text/plain"]).await;
+    assert!(head.contains("vary: accept"), "{head}");
+    assert!(body.contains(r#"<h1 id="page">Page</h1>"#), "{body}");
+    let (_, body) = common::get_with(addr, "/page?format=md", &[]).await;
+    assert!(body.starts_with("# Page"), "{body}");
+
+    let (status, _) = common::get(addr, "/missing?format=md").await;
+    assert_eq!(status, 404);
+    let (status, _) = common::get(addr, "/page?format=pdf").await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn raw_urls_always_serve_markdown() {
+    let base = common::base();
+    let path = base.path().join("page.md");
+    fs::write(&path, "# Page\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+
+    let (head, body) = common::get_with(addr, "/raw/page", &["Accept: text/html"]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(
+        head.contains("content-type: text/markdown; charset=utf-8"),
+        "{head}"
+    );
+    assert_eq!(body, "# Page\n");
+
+    // read at request time, before the watcher sees the edit
+    fs::write(&path, "# Page\n\nedited\n").unwrap();
+    let (_, body) = common::get_with(addr, "/raw/page", &[]).await;
+    assert_eq!(body, "# Page\n\nedited\n");
+
+    let (status, _) = common::get(addr, "/raw/missing").await;
+    assert_eq!(status, 404);
+}
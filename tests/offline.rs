@@ -0,0 +1,73 @@
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+#[tokio::test]
+async fn the_manifest_follows_the_pages() {
+    let base = common::base();
+    fs::write(base.path().join("other.md"), "# Other\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &["--offline", "--offline-prefetch", "1K"]).await;
+
+    let manifest = |body: &str| serde_json::from_str::<Value>(body).unwrap();
+    let hash = |manifest: &Value, url: &str| {
+        let pages = manifest["pages"].as_array().unwrap();
+        let page = pages.iter().find(|page| page["url"] == url);
+        page.map(|page| page["hash"].as_str().unwrap().to_owned())
+    };
+
+    let (status, body) = common::get(addr, "/manifest.json").await;
+    assert_eq!(status, 200);
+    let before = manifest(&body);
+    assert_eq!(before["prefetch"], 1024);
+    let urls: Vec<_> = before["assets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|asset| asset["url"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        urls,
+        [
+            "/index.css",
+            "/theme-light.css",
+            "/theme-dark.css",
+            "/index.js",
+            "/favicon.ico"
+        ]
+    );
+    let other = hash(&before, "/other").expect("no entry for other");
+    assert!(hash(&before, "/index").is_some());
+
+    fs::write(base.path().join("other.md"), "# Changed\n").unwrap();
+    fs::remove_file(base.path().join("index.md")).unwrap();
+    api.rescan().unwrap();
+    let (_, body) = common::get(addr, "/manifest.json").await;
+    let after = manifest(&body);
+    assert_ne!(hash(&after, "/other").unwrap(), other);
+    assert_eq!(hash(&after, "/index"), None);
+}
+
+#[tokio::test]
+async fn the_service_worker_covers_every_page() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &["--offline"]).await;
+    let (head, body) = common::get_with(addr, "/sw.js", &[]).await;
+    assert!(head.starts_with("http/1.1 200"), "{head}");
+    assert!(head.contains("content-type: text/javascript"), "{head}");
+    assert!(head.contains("service-worker-allowed: /"), "{head}");
+    assert!(body.contains("addEventListener(\"fetch\""), "{body}");
+    let (_, body) = common::get(addr, "/manifest.json").await;
+    assert!(body.contains(r#""prefetch":null"#), "{body}");
+}
+
+#[tokio::test]
+async fn nothing_is_served_without_the_flag() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+    for path in ["/manifest.json", "/sw.js"] {
+        let (status, _) = common::get(addr, path).await;
+        assert_eq!(status, 404, "{path}");
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use clap::Parser;
+use mdflc::cli::Args;
+use pulldown_cmark::Options;
+
+#[test]
+fn md_options_pick_the_extensions() {
+    let base = common::base();
+    let args = common::args(base.path(), &["--md-options", "tables,footnotes"]);
+    assert_eq!(
+        args.render_config().options,
+        Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES
+    );
+
+    let args = common::args(
+        base.path(),
+        &["--md-options", "tables,math", "--disable-ext", "math"],
+    );
+    assert_eq!(args.render_config().options, Options::ENABLE_TABLES);
+
+    let args = common::args(base.path(), &[]);
+    assert_eq!(args.render_config().options, Options::all());
+}
+
+#[tokio::test]
+async fn pages_render_with_only_the_chosen_extensions() {
+    let base = common::base();
+    std::fs::write(base.path().join("a.md"), "\"Quoted\" -- ~~struck~~\n").unwrap();
+    let (api, _) = common::serve(base.path(), &["--md-options", "strikethrough"]).await;
+    let html = api.get_md("a").unwrap();
+    assert!(
+        html.contains("<p>\"Quoted\" -- <del>struck</del></p>"),
+        "{html}"
+    );
+}
+
+#[test]
+fn unknown_md_options_list_the_valid_ones() {
+    let e = Args::try_parse_from(["mdflc", "--md-options", "tables,smarts"]).unwrap_err();
+    let message = e.to_string();
+    assert!(message.contains("smarts"), "{message}");
+    assert!(message.contains("smart_punctuation"), "{message}");
+}
+
+#[test]
+fn external_links_open_in_a_new_tab_unless_asked_not_to() {
+    let base = common::base();
+    assert!(
+        common::args(base.path(), &[])
+            .render_config()
+            .external_blank
+    );
+    let args = common::args(base.path(), &["--no-external-blank"]);
+    assert!(!args.render_config().external_blank);
+}
+
+#[test]
+fn bare_urls_are_linked_only_when_asked() {
+    let base = common::base();
+    assert!(!common::args(base.path(), &[]).render_config().autolink);
+    let args = common::args(base.path(), &["--autolink"]);
+    assert!(args.render_config().autolink);
+}
+
+#[tokio::test]
+async fn strict_commonmark_can_be_switched_off_and_on() {
+    let base = common::base();
+    std::fs::write(base.path().join("a.md"), "| a |\n| - |\n| b |\n").unwrap();
+    let (api, _) = common::serve(base.path(), &["--strict-commonmark"]).await;
+    assert!(api.settings().strict_commonmark);
+    assert!(!api.get_md("a").unwrap().contains("<table>"));
+
+    let mut changes = api.subscribe();
+    api.set_commonmark(false).unwrap();
+    assert!(api.get_md("a").unwrap().contains("<table>"));
+    assert!(!api.settings().render.commonmark);
+    // pages look different, so clients are told to reload them
+    let change = changes.try_recv().unwrap();
+    assert!(["a", "index"].contains(&change.key.as_str()), "{change:?}");
+
+    api.set_commonmark(true).unwrap();
+    assert!(!api.get_md("a").unwrap().contains("<table>"));
+}
+
+#[tokio::test]
+async fn hard_breaks_can_be_switched_on_and_off() {
+    let base = common::base();
+    std::fs::write(base.path().join("a.md"), "one\ntwo\n").unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    assert!(!api.get_md("a").unwrap().contains("<br />"));
+
+    api.set_hard_breaks(true).unwrap();
+    assert!(api.get_md("a").unwrap().contains("one<br />"));
+    assert!(api.settings().render.hard_breaks);
+
+    api.set_hard_breaks(false).unwrap();
+    assert!(!api.get_md("a").unwrap().contains("<br />"));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::fs;
+
+use mdflc::{
+    ordering::{compare, natural_cmp, parse_order_file, Overrides},
+    Api,
+};
+
+fn sorted(keys: &[&str], overrides: &Overrides) -> Vec<String> {
+    let mut keys: Vec<_> = keys.iter().map(|&k| k.to_owned()).collect();
+    keys.sort_by(|a, b| compare(a, b, overrides));
+    keys
+}
+
+#[test]
+fn numbers_compare_by_value() {
+    let keys = ["10-bar", "2-foo", "1-baz", "02-qux", "v1.10", "v1.9"];
+    assert_eq!(
+        sorted(&keys, &Overrides::default()),
+        ["1-baz", "2-foo", "02-qux", "10-bar", "v1.9", "v1.10"]
+    );
+}
+
+#[test]
+fn case_and_unicode() {
+    let keys = ["ñu", "Zebra", "apple", "Ñandú", "Apple", "émile", "日本"];
+    assert_eq!(
+        sorted(&keys, &Overrides::default()),
+        ["Apple", "apple", "Zebra", "émile", "Ñandú", "ñu", "日本"]
+    );
+    assert!(natural_cmp("ÄPFEL", "äpfel").is_lt());
+}
+
+#[test]
+fn directories_come_first() {
+    let keys = ["b", "a/z", "c/d/e", "a", "c/a"];
+    assert_eq!(
+        sorted(&keys, &Overrides::default()),
+        ["a/z", "c/d/e", "c/a", "a", "b"]
+    );
+}
+
+#[test]
+fn explicit_orders_mix_with_unlisted_names() {
+    let mut overrides = Overrides::default();
+    overrides.insert("", vec!["usage.md".into(), "guide/".into(), "intro".into()]);
+    overrides.insert(
+        "guide",
+        parse_order_file("# setup first\nsetup\n\n  advanced  \n"),
+    );
+    let keys = [
+        "intro",
+        "usage",
+        "faq",
+        "10-changelog",
+        "2-about",
+        "guide/basics",
+        "guide/advanced",
+        "guide/setup",
+        "extras/more",
+    ];
+    assert_eq!(
+        sorted(&keys, &overrides),
+        [
+            "usage",
+            "guide/setup",
+            "guide/advanced",
+            "guide/basics",
+            "intro",
+            "extras/more",
+            "2-about",
+            "10-changelog",
+            "faq",
+        ]
+    );
+}
+
+#[test]
+fn pages_follow_front_matter_and_order_files() {
+    let base = common::base();
+    let dir = base.path();
+    fs::write(
+        dir.join("index.md"),
+        "---\norder:\n  - zeta\n  - docs\n---\n# Index\n",
+    )
+    .unwrap();
+    fs::create_dir(dir.join("docs")).unwrap();
+    fs::write(dir.join("docs/_order.txt"), "last.md\n").unwrap();
+    for page in ["zeta", "alpha", "docs/first", "docs/last"] {
+        fs::write(dir.join(format!("{page}.md")), "# Page\n").unwrap();
+    }
+    let api = Api::new(&common::args(dir, &[])).unwrap();
+    api.scan().unwrap();
+
+    assert_eq!(
+        api.ordered_keys(""),
+        ["zeta", "docs/last", "docs/first", "alpha", "index"]
+    );
+    assert_eq!(api.ordered_keys("/docs/"), ["docs/last", "docs/first"]);
+}
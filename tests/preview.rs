@@ -0,0 +1,36 @@
+mod common;
+
+use std::fs;
+
+use mdflc::Api;
+
+#[test]
+fn dry_runs_compare_keys_without_changing_pages() {
+    let base = common::base();
+    fs::write(base.path().join("a.md"), "# A\n").unwrap();
+    fs::create_dir(base.path().join("docs")).unwrap();
+    fs::write(base.path().join("docs/setup.md"), "# Setup\n").unwrap();
+    let api = Api::new(&common::args(base.path(), &[])).unwrap();
+    api.scan().unwrap();
+
+    fs::write(base.path().join("b.md"), "# B\n").unwrap();
+    fs::remove_file(base.path().join("a.md")).unwrap();
+    let preview = api.preview_scan(None).unwrap();
+    assert_eq!(preview.added, ["b"]);
+    assert_eq!(preview.removed, ["a"]);
+    assert_eq!(preview.kept, 2);
+    assert!(api.get_hash("a").is_some());
+    assert!(api.get_hash("b").is_none());
+
+    let other = common::base();
+    fs::write(other.path().join("docs.md"), "# Docs\n").unwrap();
+    let preview = api.preview_scan(Some(other.path().to_owned())).unwrap();
+    assert_eq!(preview.added, ["docs"]);
+    assert_eq!(preview.removed, ["a", "docs/setup"]);
+    assert_eq!(preview.kept, 1);
+
+    api.rescan().unwrap();
+    let preview = api.preview_scan(None).unwrap();
+    assert!(preview.added.is_empty() && preview.removed.is_empty());
+    assert_eq!(preview.kept, 3);
+}
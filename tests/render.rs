@@ -0,0 +1,490 @@
+use std::{
+    env, fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use mdflc::{
+    extensions::Extensions,
+    hooks::Hooks,
+    render::{
+        link_target, normalize_source, render_md, slugify, split_front_matter, stream_page,
+        truncate, Config, FrontMatter,
+    },
+    Slots, Template,
+};
+use pulldown_cmark::Options;
+
+/// Renders every `.md` fixture in `dir`, comparing it to the matching `.html`
+///
+/// Sources are normalized first, like pages read from disk.
+///
+/// Set `MDFLC_BLESS` to write the current output as the expected output.
+fn check_fixtures(dir: &str, config: &Config) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let bless = env::var_os("MDFLC_BLESS").is_some();
+    let mut checked = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        let mut html = String::new();
+        render_md(&mut html, &normalize_source(&text), config).unwrap();
+
+        let expected = path.with_extension("html");
+        if bless {
+            fs::write(&expected, &html).unwrap();
+        } else {
+            let expected = fs::read_to_string(&expected).unwrap();
+            assert_eq!(html, expected, "{} rendered differently", path.display());
+        }
+        checked += 1;
+    }
+    assert_ne!(checked, 0, "no fixtures in {}", dir.display());
+}
+
+#[test]
+fn gfm_fixtures() {
+    check_fixtures("tests/fixtures/gfm", &Config::default());
+}
+
+#[test]
+fn source_fixtures() {
+    check_fixtures("tests/fixtures/source", &Config::default());
+}
+
+#[test]
+fn anchor_fixtures() {
+    check_fixtures("tests/fixtures/anchors", &Config::default());
+}
+
+#[test]
+fn math_fixtures() {
+    let config = Config {
+        math: true,
+        ..Config::default()
+    };
+    check_fixtures("tests/fixtures/math", &config);
+}
+
+#[test]
+fn math_is_left_as_is_without_the_flag() {
+    let mut html = String::new();
+    let summary = render_md(&mut html, "$x^2$\n", &Config::default()).unwrap();
+    assert!(!summary.math);
+    assert!(!html.contains("data-math"), "{html}");
+}
+
+#[test]
+fn meta_tags_escape_the_excerpt() {
+    let text = "---\nimage: /cover.png?a=1&b=2\n---\n\nSays \"hi\" to `<b>` and <i>friends</i>\nover two lines.\n\nNot this one.\n";
+    let summary = render_md(
+        &mut String::new(),
+        text,
+        &Config {
+            options: Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+            ..Config::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        summary.excerpt,
+        "Says \"hi\" to <b> and friends over two lines."
+    );
+
+    let html = Template::default().html(&Slots {
+        title: "a \"quoted\" <title>",
+        description: &summary.excerpt,
+        image: summary
+            .front_matter
+            .as_ref()
+            .and_then(|front| front.image.as_deref()),
+        ..Slots::default()
+    });
+    assert!(html
+        .contains("<meta property=\"og:title\" content=\"a &quot;quoted&quot; &lt;title&gt;\" />"));
+    assert!(html.contains(
+        "<meta name=\"description\" content=\"Says &quot;hi&quot; to &lt;b&gt; and friends over two lines.\" />"
+    ));
+    assert!(html.contains("<meta property=\"og:image\" content=\"/cover.png?a=1&amp;b=2\" />"));
+}
+
+#[test]
+fn excerpts_are_truncated_at_a_word() {
+    assert_eq!(truncate("one two three", 9), "one two…");
+    assert_eq!(truncate("short", 9), "short");
+}
+
+#[test]
+fn link_targets_resolve_relative_to_the_page() {
+    let ext = Extensions::default();
+    assert_eq!(
+        link_target("a/b", "#x", &ext),
+        Some(("a/b".to_owned(), "x"))
+    );
+    assert_eq!(
+        link_target("a/b", "c.md#x", &ext),
+        Some(("a/c".to_owned(), "x"))
+    );
+    assert_eq!(
+        link_target("a/b", "../c#x", &ext),
+        Some(("c".to_owned(), "x"))
+    );
+    assert_eq!(
+        link_target("a/b", "/d/e.md?q=1#x", &ext),
+        Some(("d/e".to_owned(), "x"))
+    );
+    assert_eq!(link_target("a", "../c#x", &ext), None);
+    assert_eq!(link_target("a", "c.md", &ext), None);
+    assert_eq!(link_target("a", "https://example.com/c#x", &ext), None);
+    assert_eq!(link_target("a", "//example.com/c#x", &ext), None);
+    assert_eq!(
+        link_target("a/b", "c.markdown#x", &ext),
+        Some(("a/c".to_owned(), "x"))
+    );
+    let ext = Extensions::new(&["mkd".to_owned()]).unwrap();
+    assert_eq!(
+        link_target("a/b", "c.mkd#x", &ext),
+        Some(("a/c".to_owned(), "x"))
+    );
+    assert_eq!(
+        link_target("a/b", "c.md#x", &ext),
+        Some(("a/c.md".to_owned(), "x"))
+    );
+}
+
+#[test]
+fn oversized_pages_are_dropped_and_streamed() {
+    let text = "# Big\n\n".to_owned() + &"Some text.\n\n".repeat(100);
+    let config = Config {
+        max_html_size: 256,
+        ..Config::default()
+    };
+    let mut html = String::from("stale");
+    let summary = render_md(&mut html, &text, &config).unwrap();
+    assert!(summary.oversized);
+    assert!(html.is_empty());
+
+    let mut streamed = String::new();
+    let hooks = Hooks::default();
+    stream_page(&mut streamed, &text, &config, "big", &(), &hooks).unwrap();
+    let mut cached = String::new();
+    render_md(&mut cached, &text, &Config::default()).unwrap();
+    assert_eq!(streamed, cached);
+}
+
+#[test]
+fn front_matter_is_stripped_and_parsed() {
+    let text = "---\ntitle: Foo\ntags: [x, \"y\"]\nother: { a: 1 }\n---\n# Heading\n";
+    // whether or not the parser understands metadata blocks
+    for options in [Options::empty(), Options::ENABLE_YAML_STYLE_METADATA_BLOCKS] {
+        let mut html = String::new();
+        let config = Config {
+            options,
+            ..Config::default()
+        };
+        let summary = render_md(&mut html, text, &config).unwrap();
+        assert_eq!(html, "<h1 id=\"heading\">Heading</h1>\n");
+        assert_eq!(summary.title("stem"), "Foo");
+        let front = summary.front_matter.unwrap();
+        assert_eq!(front.tags, ["x", "y"]);
+        assert!(summary.problems.is_empty());
+    }
+
+    assert_eq!(
+        FrontMatter::parse("order: b, a\ntitle: 2024").unwrap(),
+        FrontMatter {
+            title: Some("2024".to_owned()),
+            order: Some(vec!["b".to_owned(), "a".to_owned()]),
+            ..FrontMatter::default()
+        }
+    );
+    assert_eq!(FrontMatter::parse("").unwrap(), FrontMatter::default());
+}
+
+#[test]
+fn pages_without_front_matter_are_untouched() {
+    for text in [
+        "# Title\n---\nx\n---\n",
+        "---\n\nafter a break\n---\n",
+        "---\nunclosed\n",
+    ] {
+        assert_eq!(split_front_matter(text), (None, text));
+    }
+    assert_eq!(
+        split_front_matter("---\na: 1\n...\nbody"),
+        (Some("a: 1\n"), "body")
+    );
+}
+
+#[test]
+fn malformed_front_matter_is_a_problem() {
+    let mut html = String::new();
+    let text = "---\ntitle: [unclosed\n---\nThe body.\n";
+    let summary = render_md(&mut html, text, &Config::default()).unwrap();
+    assert_eq!(html, "<p>The body.</p>\n");
+    assert_eq!(summary.front_matter, Some(FrontMatter::default()));
+    assert_eq!(summary.problems.len(), 1);
+    assert!(
+        summary.problems[0].contains("isn't valid yaml"),
+        "{:?}",
+        summary.problems
+    );
+
+    let summary = render_md(&mut html, "---\n- a list\n---\n", &Config::default()).unwrap();
+    assert!(
+        summary.problems[0].contains("expected a mapping"),
+        "{:?}",
+        summary.problems
+    );
+}
+
+#[test]
+fn slugs_match_github() {
+    assert_eq!(slugify("Hello, World!"), "hello-world");
+    assert_eq!(slugify("a  b"), "a--b");
+    assert_eq!(slugify("Größe & Ünïcode"), "größe--ünïcode");
+    assert_eq!(slugify("İstanbul"), "i̇stanbul");
+    assert_eq!(slugify("snake_case-and-kebab"), "snake_case-and-kebab");
+    assert_eq!(slugify("?!"), "section");
+}
+
+#[test]
+fn permalinks_follow_heading_ids() {
+    let config = Config {
+        permalinks: true,
+        ..Config::default()
+    };
+    let mut html = String::new();
+    let summary = render_md(
+        &mut html,
+        "# Intro\n\n## Intro\n\n# Café {#custom}\n",
+        &config,
+    )
+    .unwrap();
+    assert_eq!(
+        html,
+        "<h1 id=\"intro\">Intro<a class=\"permalink\" href=\"#intro\" aria-label=\"Permalink\">¶</a></h1>\n\
+         <h2 id=\"intro-1\">Intro<a class=\"permalink\" href=\"#intro-1\" aria-label=\"Permalink\">¶</a></h2>\n\
+         <h1 id=\"custom\">Café<a class=\"permalink\" href=\"#custom\" aria-label=\"Permalink\">¶</a></h1>\n"
+    );
+    assert_eq!(summary.title("stem"), "Intro");
+}
+
+#[test]
+fn tables_of_contents_nest_by_level() {
+    let config = Config {
+        toc: true,
+        ..Config::default()
+    };
+    let text = "# A & B\n\n## `one`\n\n### Deep\n\n## Two\n\n# C {#see}\n";
+    let summary = render_md(&mut String::new(), text, &config).unwrap();
+    assert_eq!(
+        summary.toc.unwrap(),
+        "<nav class=\"toc\"><ul><li><a href=\"#a--b\">A &amp; B</a>\
+         <ul><li><a href=\"#one\">one</a><ul><li><a href=\"#deep\">Deep</a></li></ul>\
+         </li><li><a href=\"#two\">Two</a></li></ul>\
+         </li><li><a href=\"#see\">C</a></li></ul></nav>"
+    );
+
+    let summary = render_md(&mut String::new(), "No headings.\n", &config).unwrap();
+    assert_eq!(summary.toc.unwrap(), "<nav class=\"toc\"></nav>");
+}
+
+#[test]
+fn front_matter_overrides_the_toc_flag() {
+    let off = "---\ntoc: false\n---\n# Heading\n";
+    let on = "---\ntoc: true\n---\n# Heading\n";
+    let enabled = Config {
+        toc: true,
+        ..Config::default()
+    };
+    let toc = |text, config: &Config| render_md(&mut String::new(), text, config).unwrap().toc;
+    assert_eq!(toc(off, &enabled), None);
+    assert!(toc(on, &Config::default()).is_some());
+    assert_eq!(toc("# Heading\n", &Config::default()), None);
+}
+
+#[test]
+fn emoji_fixtures() {
+    let config = Config {
+        emoji: true,
+        ..Config::default()
+    };
+    check_fixtures("tests/fixtures/emoji", &config);
+}
+
+#[test]
+fn sanitize_fixtures() {
+    let config = Config {
+        sanitize: true,
+        ..Config::default()
+    };
+    check_fixtures("tests/fixtures/sanitize", &config);
+}
+
+#[test]
+fn titles_get_emoji_but_ids_keep_shortcodes() {
+    let config = Config {
+        emoji: true,
+        ..Config::default()
+    };
+    let mut html = String::new();
+    let summary = render_md(&mut html, "# Done :tada:\n\nAll :+1:\n", &config).unwrap();
+    assert_eq!(summary.title("stem"), "Done 🎉");
+    assert_eq!(summary.excerpt, "All 👍");
+    assert!(html.starts_with("<h1 id=\"done-tada\">"), "{html}");
+}
+
+#[test]
+fn callout_fixtures() {
+    check_fixtures("tests/fixtures/callouts", &Config::default());
+}
+
+#[test]
+fn code_line_fixtures() {
+    let config = Config {
+        code_line_numbers: true,
+        ..Config::default()
+    };
+    check_fixtures("tests/fixtures/code_lines", &config);
+}
+
+#[test]
+fn long_code_blocks_number_quickly() {
+    let config = Config {
+        code_line_numbers: true,
+        ..Config::default()
+    };
+    let lines = 100_000;
+    let mut text = String::from("```rust\n");
+    for i in 0..lines {
+        text.push_str(&format!("let x{i} = {i} < {lines};\n"));
+    }
+    text.push_str("```\n");
+
+    let start = Instant::now();
+    let mut html = String::new();
+    render_md(&mut html, &text, &config).unwrap();
+    let elapsed = start.elapsed();
+    assert_eq!(html.matches("<span class=\"code-line\">").count(), lines);
+    assert!(html.contains("let x99999 = 99999 &lt; 100000;</span>\n</code>"));
+    // generous enough for unoptimized builds, numbering is linear in the block's size
+    assert!(elapsed < Duration::from_secs(5), "took {elapsed:?}");
+}
+
+#[test]
+fn definition_list_fixtures() {
+    check_fixtures("tests/fixtures/definitions", &Config::default());
+}
+
+#[test]
+fn container_fixtures() {
+    check_fixtures("tests/fixtures/containers", &Config::default());
+}
+
+#[test]
+fn autolink_fixtures() {
+    let config = Config {
+        autolink: true,
+        ..Config::default()
+    };
+    check_fixtures("tests/fixtures/autolink", &config);
+}
+
+#[test]
+fn only_links_to_other_sites_open_in_a_new_tab() {
+    let md = "[a](https://example.com/a \"Title\") [b](HTTP://example.com) \
+        [c](//example.com) [d](mailto:me@example.com) [e](#heading) [f](other.md)\n";
+    let blank = r#"target="_blank" rel="noopener noreferrer""#;
+    let mut html = String::new();
+    render_md(&mut html, md, &Config::default()).unwrap();
+    assert!(
+        html.contains(&format!(
+            r#"<a href="https://example.com/a" title="Title" {blank}>a</a>"#
+        )),
+        "{html}"
+    );
+    assert!(
+        html.contains(&format!(r#"<a href="HTTP://example.com" {blank}>b</a>"#)),
+        "{html}"
+    );
+    assert_eq!(html.matches("_blank").count(), 2, "{html}");
+
+    let config = Config {
+        external_blank: false,
+        ..Config::default()
+    };
+    render_md(&mut html, md, &config).unwrap();
+    assert!(!html.contains("_blank"), "{html}");
+}
+
+#[test]
+fn strict_commonmark_leaves_out_every_extension() {
+    let md = "| a |\n| - |\n| b |\n\nTerm\n: definition\n\n::: note\n~~struck~~ \"quoted\"\n:::\n";
+    let config = Config {
+        emoji: true,
+        autolink: true,
+        ..Config::default()
+    }
+    .strict_commonmark();
+    let mut html = String::new();
+    render_md(&mut html, md, &config).unwrap();
+    for extended in ["<table>", "<dl>", "<div", "<del>", "“"] {
+        assert!(!html.contains(extended), "{extended} in {html}");
+    }
+    assert!(html.contains("<p>| a |"), "{html}");
+}
+
+#[test]
+fn headings_are_numbered_within_the_page() {
+    let md = "## Intro\n\n### Setup\n\n#### Deep\n\n### Use\n\n## Next\n";
+    let config = Config {
+        number_headings: true,
+        toc: true,
+        ..Config::default()
+    };
+    let mut html = String::new();
+    let summary = render_md(&mut html, md, &config).unwrap();
+    let number = |n: &str| format!(r#"<span class="heading-number">{n}</span> "#);
+    assert!(
+        html.contains(&format!(r#"<h2 id="intro">{}Intro</h2>"#, number("1."))),
+        "{html}"
+    );
+    for (n, heading) in [
+        ("1.1", "Setup"),
+        ("1.1.1", "Deep"),
+        ("1.2", "Use"),
+        ("2.", "Next"),
+    ] {
+        assert!(html.contains(&format!("{}{heading}<", number(n))), "{html}");
+    }
+    let toc = summary.toc.unwrap();
+    assert!(toc.contains(r##"<a href="#setup">1.1 Setup</a>"##), "{toc}");
+    assert_eq!(summary.heading, None);
+
+    // pages can opt out
+    let md = format!("---\nnumber_headings: false\n---\n{md}");
+    render_md(&mut html, &md, &config).unwrap();
+    assert!(!html.contains("heading-number"), "{html}");
+}
+
+#[test]
+fn hard_breaks_break_every_line() {
+    let md = "one\ntwo\n\n```\nthree\nfour\n```\n\n| a |\n| - |\n| b |\n";
+    let config = Config {
+        hard_breaks: true,
+        ..Config::default()
+    };
+    let mut html = String::new();
+    render_md(&mut html, md, &config).unwrap();
+    assert!(html.contains("<p>one<br />\ntwo</p>"), "{html}");
+    assert!(html.contains("<code>three\nfour\n</code>"), "{html}");
+    assert!(html.contains("<td>b</td>"), "{html}");
+
+    render_md(&mut html, md, &Config::default()).unwrap();
+    assert!(html.contains("<p>one\ntwo</p>"), "{html}");
+}
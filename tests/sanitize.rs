@@ -0,0 +1,86 @@
+mod common;
+
+use mdflc::sanitize::{builder, clean, DROPPED_WITH_CONTENT};
+
+#[test]
+fn the_allowlist_keeps_formatting_only() {
+    let builder = builder();
+    let tags = builder.clone_tags();
+    for tag in [
+        "p", "em", "strong", "a", "img", "table", "td", "pre", "code", "input",
+    ] {
+        assert!(tags.contains(tag), "{tag} is dropped");
+    }
+    for tag in ["script", "iframe", "style", "object", "form", "button"] {
+        assert!(!tags.contains(tag), "{tag} is kept");
+    }
+    assert!(DROPPED_WITH_CONTENT.contains(&"script"));
+    assert!(builder.clone_clean_content_tags().contains("iframe"));
+
+    assert_eq!(clean("<p class=\"note\">x</p>"), "<p class=\"note\">x</p>");
+    assert_eq!(
+        clean("<div aria-label=\"x\">y</div>"),
+        "<div aria-label=\"x\">y</div>"
+    );
+    assert_eq!(clean("<p onclick=\"steal()\">x</p>"), "<p>x</p>");
+    assert_eq!(
+        clean("<div style=\"position: fixed\">x</div>"),
+        "<div>x</div>"
+    );
+    assert_eq!(
+        clean("<table><tr><td style=\"text-align: center; position: fixed\">x</td></tr></table>"),
+        "<table><tbody><tr><td style=\"text-align:center\">x</td></tr></tbody></table>"
+    );
+    assert_eq!(clean("<p href=\"https://example.com\">x</p>"), "<p>x</p>");
+    assert_eq!(
+        clean("<input type=\"text\" checked=\"\">"),
+        "<input checked=\"\" type=\"checkbox\">"
+    );
+
+    let href = |url: &str| clean(&format!("<a href=\"{url}\">x</a>"));
+    for url in [
+        "https://example.com/a:b",
+        "other.md#top:x",
+        "mailto:me@example.com",
+    ] {
+        assert_eq!(href(url), format!("<a href=\"{url}\">x</a>"));
+    }
+    for url in [
+        "javascript:alert(1)",
+        " JavaScript:alert(1)",
+        "java\tscript:alert(1)",
+        "jav&#x61;script:alert(1)",
+        "data:text/html,x",
+    ] {
+        assert_eq!(href(url), "<a>x</a>", "{url}");
+    }
+}
+
+#[test]
+fn broken_html_is_cleaned_up() {
+    assert_eq!(
+        clean("<p class=\"a\" onclick=\"x\">hi</p><script>bad</script>ok"),
+        "<p class=\"a\">hi</p>ok"
+    );
+    assert_eq!(clean("a < b <c"), "a &lt; b ");
+    assert_eq!(clean("<script>never closed"), "");
+    assert_eq!(clean("<br/><hr />"), "<br><hr>");
+}
+
+#[tokio::test]
+async fn rerendered_pages_stay_sanitized() {
+    let base = common::base();
+    let page = base.path().join("page.md");
+    std::fs::write(&page, "<script>one()</script>\n\nfirst\n").unwrap();
+    let (api, _) = common::serve(base.path(), &["--sanitize"]).await;
+    let html = api.get_md("page").unwrap();
+    assert!(html.contains("first") && !html.contains("one()"), "{html}");
+
+    std::fs::write(&page, "<b onclick=\"two()\">second</b>\n").unwrap();
+    api.update_paths([page.as_path()]).unwrap();
+    let html = api.get_md("page").unwrap();
+    assert!(
+        html.contains("<b>second</b>") && !html.contains("two()"),
+        "{html}"
+    );
+}
@@ -0,0 +1,264 @@
+mod common;
+
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use mdflc::{
+    scan::{self, Disk, Entry, Walk},
+    settings::Limits,
+    warnings::{Category, Warnings},
+    Api,
+};
+
+/// Reads from disk, but blocks every read after the first until told to go on
+struct Slow {
+    disk: Disk,
+    reads: Mutex<usize>,
+    resume: Mutex<Receiver<()>>,
+}
+
+impl Walk for Slow {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let first = {
+            let mut reads = self.reads.lock().unwrap();
+            *reads += 1;
+            *reads == 1
+        };
+        if !first {
+            self.resume.lock().unwrap().recv().unwrap();
+        }
+        self.disk.read_dir(dir)
+    }
+}
+
+/// Sets the mtime of `path`, `age` seconds ago
+fn age(path: &Path, age: u64) {
+    let time = SystemTime::now() - Duration::from_secs(age);
+    File::open(path).unwrap().set_modified(time).unwrap();
+}
+
+/// A base with pages at several depths, `new` was changed more recently than `old`
+fn tree() -> tempfile::TempDir {
+    let base = common::base();
+    let path = base.path();
+    fs::write(path.join("top.md"), "# Top\n").unwrap();
+    for dir in ["old", "new", "new/deep"] {
+        fs::create_dir(path.join(dir)).unwrap();
+        fs::write(path.join(dir).join("page.md"), "# Page\n").unwrap();
+    }
+    age(&path.join("new/deep"), 10);
+    age(&path.join("new"), 10);
+    age(&path.join("old"), 100);
+    base
+}
+
+#[test]
+fn shallow_and_recent_directories_come_first() {
+    let base = tree();
+    let disk = Disk {
+        follow_symlinks: false,
+    };
+    let mut order = Vec::new();
+    scan::prioritized(&disk, base.path(), &Warnings::default(), |path| {
+        order.push(path.strip_prefix(base.path()).unwrap().to_owned());
+    });
+
+    let position = |path: &str| {
+        order
+            .iter()
+            .position(|p| p.as_path() == Path::new(path))
+            .unwrap()
+    };
+    assert_eq!(order.len(), 5, "{order:?}");
+    assert!(
+        position("index.md") < 2 && position("top.md") < 2,
+        "{order:?}"
+    );
+    assert_eq!(position("new/page.md"), 2, "{order:?}");
+    assert_eq!(position("old/page.md"), 3, "{order:?}");
+    assert_eq!(position("new/deep/page.md"), 4, "{order:?}");
+}
+
+#[test]
+fn pages_past_the_budget_render_on_request() {
+    let base = tree();
+    let api = Arc::new(Api::new(&common::args(base.path(), &["--scan-budget", "0"])).unwrap());
+    let (resume, resumed) = mpsc::channel();
+    let slow = Slow {
+        disk: Disk {
+            follow_symlinks: false,
+        },
+        reads: Mutex::new(0),
+        resume: Mutex::new(resumed),
+    };
+
+    std::thread::scope(|s| {
+        let scanning = s.spawn(|| api.scan_within(&slow, Duration::ZERO));
+        // the walk is stuck reading `new`, after listing base
+        while *slow.reads.lock().unwrap() < 2 {
+            std::thread::yield_now();
+        }
+        let status = api.scan_status();
+        assert!(status.walking);
+        assert_eq!((status.found, status.rendered), (2, 1));
+        assert_eq!(status.percent, Some(50));
+
+        // the index is rendered up front, top is only listed until requested
+        let index = api.get_md("index").unwrap();
+        assert!(index.contains(r#"<h1 id="index">Index</h1>"#), "{index}");
+        assert!(api.list().iter().any(|(key, _)| key == "top"));
+        let top = api.get_md("top").unwrap();
+        assert!(top.contains(r#"<h1 id="top">Top</h1>"#), "{top}");
+        assert_eq!(api.scan_status().rendered, 2);
+        assert!(!api.is_ready());
+
+        for _ in 0..3 {
+            resume.send(()).unwrap();
+        }
+        scanning.join().unwrap().unwrap();
+    });
+
+    let status = api.scan_status();
+    assert!(!status.walking);
+    assert_eq!((status.found, status.rendered), (5, 5));
+    assert_eq!(status.percent, Some(100));
+    assert!(api.is_ready());
+    let deep = api.get_md("new/deep/page").unwrap();
+    assert!(deep.contains(r#"<h1 id="page">Page</h1>"#), "{deep}");
+}
+
+#[tokio::test]
+async fn health_reports_scan_progress() {
+    let base = common::base();
+    let (_, addr) = common::serve(base.path(), &["--scan-budget", "60"]).await;
+    let (status, body) = common::get(addr, "/healthz").await;
+    assert_eq!(status, 200);
+    assert!(
+        body.contains(r#""scan":{"found":1,"rendered":1,"walking":false,"percent":100}"#),
+        "{body}"
+    );
+}
+
+#[test]
+fn files_that_fail_to_render_are_left_out() {
+    let base = common::base();
+    let bad = base.path().join("bad.md");
+    fs::write(&bad, b"# Bad \xff\n").unwrap();
+    let api = Api::new(&common::args(base.path(), &[])).unwrap();
+    api.scan().unwrap();
+
+    let failed = api.failed_files();
+    assert_eq!(failed.len(), 1, "{failed:?}");
+    assert_eq!((failed[0].key.as_str(), &failed[0].path), ("bad", &bad));
+    assert!(api.get_md("bad").is_none());
+    assert!(api.get_md("index").is_some());
+
+    fs::write(&bad, "# Fixed\n").unwrap();
+    api.update_paths([bad.as_path()]).unwrap();
+    assert!(api.failed_files().is_empty());
+    // a later failure keeps the last render
+    fs::write(&bad, b"# Broken \xff\n").unwrap();
+    api.update_paths([bad.as_path()]).unwrap();
+    assert_eq!(api.failed_files()[0].key, "bad");
+    assert!(api.get_md("bad").unwrap().contains("Fixed"));
+}
+
+#[test]
+fn scans_fail_only_when_no_file_renders() {
+    let base = common::base();
+    fs::write(base.path().join("index.md"), b"\xff").unwrap();
+    let api = Api::new(&common::args(base.path(), &[])).unwrap();
+    let e = api.scan().unwrap_err();
+    assert!(e.to_string().contains("none of the 1"), "{e}");
+}
+
+#[test]
+fn pages_rendered_before_the_scan_keep_their_key() {
+    let base = common::base();
+    fs::create_dir(base.path().join("docs")).unwrap();
+    fs::write(base.path().join("docs/setup.md"), "# Setup\n").unwrap();
+    let api = Api::new(&common::args(base.path(), &[])).unwrap();
+
+    assert!(api.get_md("docs//setup").is_none());
+    assert!(api.get_md("docs/setup/").is_some());
+    let keys: Vec<_> = api.list().into_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, ["docs/setup"]);
+}
+
+#[test]
+fn files_over_the_limits_are_skipped_until_raised() {
+    let base = common::base();
+    fs::write(
+        base.path().join("big.md"),
+        format!("# Big\n\n{}\n", "x".repeat(2048)),
+    )
+    .unwrap();
+    fs::create_dir_all(base.path().join("a/b")).unwrap();
+    fs::write(base.path().join("a/b/deep.md"), "# Deep\n").unwrap();
+    let args = common::args(base.path(), &["--max-file-size", "1K", "--max-depth", "1"]);
+    let api = Api::new(&args).unwrap();
+    api.scan().unwrap();
+
+    for key in ["big", "a/b/deep"] {
+        let html = api.get_md(key).unwrap();
+        assert!(html.contains("This file was skipped"), "{html}");
+        let warnings = api.warnings.list();
+        assert!(
+            warnings
+                .iter()
+                .any(|w| w.category == Category::Skipped && w.key == key),
+            "{warnings:?}"
+        );
+    }
+
+    api.set_limits(Limits {
+        max_file_size: 1 << 20,
+        max_depth: 16,
+    });
+    api.rescan().unwrap();
+    assert!(api
+        .get_md("big")
+        .unwrap()
+        .contains(r#"<h1 id="big">Big</h1>"#));
+    assert!(api
+        .get_md("a/b/deep")
+        .unwrap()
+        .contains(r#"<h1 id="deep">Deep</h1>"#));
+}
+
+#[test]
+fn followed_symlinks_stay_under_base() {
+    let base = common::base();
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("page.md"), "# Linked\n").unwrap();
+    std::os::unix::fs::symlink(outside.path(), base.path().join("docs")).unwrap();
+    std::os::unix::fs::symlink(base.path(), base.path().join("loop")).unwrap();
+    std::os::unix::fs::symlink(base.path().join("gone.md"), base.path().join("dangling.md"))
+        .unwrap();
+    let api = Api::new(&common::args(base.path(), &["--follow-symlinks"])).unwrap();
+    api.scan().unwrap();
+
+    let mut keys: Vec<_> = api.list().into_iter().map(|(key, _)| key).collect();
+    keys.sort();
+    assert_eq!(keys, ["docs/page", "index"]);
+    assert!(api.get_md("docs/page").unwrap().contains("Linked"));
+    let warnings = api.warnings.list();
+    let symlink = |key: &str| {
+        warnings
+            .iter()
+            .find(|w| w.category == Category::Symlink && w.key == key)
+            .unwrap_or_else(|| panic!("no warning for {key} in {warnings:?}"))
+            .message
+            .clone()
+    };
+    assert!(symlink("loop").contains("loops"));
+    assert!(symlink("dangling.md").contains("unable to follow"));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::fs;
+
+use mdflc::search;
+
+#[tokio::test]
+async fn pages_are_searched_by_their_text() {
+    let base = common::base();
+    fs::write(
+        base.path().join("notes.md"),
+        "# Notes\n\nSome *Rust* code:\n\n```\nfn rust() {}\n```\n",
+    )
+    .unwrap();
+    fs::write(
+        base.path().join("rust.md"),
+        "# Rust\n\nAbout the language.\n",
+    )
+    .unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/api/search?q=RUST").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    // matching titles come first
+    assert_eq!(json[0]["key"], "rust");
+    assert_eq!(json[1]["key"], "notes");
+    assert_eq!(json[1]["title"], "Notes");
+    assert_eq!(
+        json[1]["snippet"],
+        "Notes Some <mark>Rust</mark> code: fn <mark>rust</mark>() {}"
+    );
+    assert_eq!(json.as_array().unwrap().len(), 2);
+
+    for query in ["", "%20%20", "nowhere"] {
+        let (_, body) = common::get(addr, &format!("/api/search?q={query}")).await;
+        assert_eq!(body, "[]", "{query}");
+    }
+    let (_, body) = common::get(addr, "/api/search").await;
+    assert_eq!(body, "[]");
+
+    let path = base.path().join("notes.md");
+    fs::write(&path, "# Notes\n\nNothing left & done.\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert_eq!(api.search("rust").len(), 1);
+    assert_eq!(
+        api.search("left &")[0].snippet,
+        "Notes Nothing <mark>left &amp;</mark> done."
+    );
+}
+
+#[test]
+fn snippets_are_cut_around_the_match() {
+    let text = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+    let found = search::find(&text, "NEEDLE").unwrap();
+    assert_eq!(&text[found.clone()], "needle");
+    let snippet = search::snippet(&text, found, "needle");
+    assert!(snippet.starts_with("…a a"), "{snippet}");
+    assert!(snippet.ends_with("b b…"), "{snippet}");
+    assert!(snippet.contains(" <mark>needle</mark> "), "{snippet}");
+
+    assert_eq!(search::find("Ünïcode", "üNÏ"), Some(0..5));
+    assert_eq!(search::find("short", "shorter"), None);
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use std::{fs, sync::Arc, time::Duration};
+
+use mdflc::{Api, ChangeKind};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn subscribers_see_file_changes() {
+    let base = common::base();
+    let api = Arc::new(Api::new(&common::args(base.path(), &[])).unwrap());
+    api.scan().unwrap();
+    tokio::spawn(api.clone().process_updates());
+    let wx = api.watcher().unwrap();
+    let _watching = wx.main();
+    let mut events = api.subscribe();
+
+    // the watcher may still be starting up, keep writing until it notices
+    let page = base.path().join("new.md");
+    let event = timeout(Duration::from_secs(10), async {
+        for n in 0.. {
+            fs::write(&page, format!("# New {n}\n")).unwrap();
+            if let Ok(event) = timeout(Duration::from_millis(500), events.recv()).await {
+                return event.unwrap();
+            }
+        }
+        unreachable!()
+    })
+    .await
+    .expect("no change event");
+
+    assert_eq!(event.key, "new");
+    assert_eq!(event.kind, ChangeKind::Added);
+    assert_eq!(event.hash, api.get_hash("new"));
+    assert_eq!(event.generation, api.generation());
+}
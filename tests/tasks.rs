@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs;
+
+use mdflc::tasks::{apply, TaskEdit};
+use pulldown_cmark::Options;
+
+const TODO: &str = "---\r\ntitle: Todo\r\n---\r\n- [ ] one\r\n- [x] two\r\n\r\n```\r\n- [ ] code\r\n```\r\n\r\n  - [ ] three\r\n";
+
+fn edit(index: usize, checked: Option<bool>) -> TaskEdit {
+    TaskEdit { index, checked }
+}
+
+#[test]
+fn items_are_counted_the_way_they_render() {
+    let options = Options::all();
+    let flipped = apply(TODO, options, edit(0, None)).unwrap();
+    assert_eq!(flipped, TODO.replacen("[ ] one", "[x] one", 1));
+    let flipped = apply(TODO, options, edit(1, None)).unwrap();
+    assert_eq!(flipped, TODO.replacen("[x] two", "[ ] two", 1));
+    // code blocks aren't task lists
+    let checked = apply(TODO, options, edit(2, Some(true))).unwrap();
+    assert_eq!(checked, TODO.replacen("[ ] three", "[x] three", 1));
+    assert_eq!(apply(TODO, options, edit(1, Some(true))).unwrap(), TODO);
+    assert_eq!(apply(TODO, options, edit(3, None)), None);
+}
+
+#[tokio::test]
+async fn checking_items_writes_the_file() {
+    let base = common::base();
+    let path = base.path().join("todo.md");
+    fs::write(&path, "# Todo\n\n- [ ] one\n- [ ] two\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &["--edit-tasks"]).await;
+    let (_, html) = common::get(addr, "/todo").await;
+    assert!(
+        html.contains(r#"data-task="1""#) && !html.contains("disabled"),
+        "{html}"
+    );
+
+    let (status, _) = common::post(addr, "/api/task/todo", r#"{"index":1}"#).await;
+    assert_eq!(status, 204);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# Todo\n\n- [ ] one\n- [x] two\n"
+    );
+    api.update_paths([path.as_path()]).unwrap();
+    let (_, html) = common::get(addr, "/todo").await;
+    assert!(html.contains(r#"data-task="1" checked"#), "{html}");
+
+    let (status, _) = common::post(addr, "/api/task/todo", r#"{"index":2}"#).await;
+    assert_eq!(status, 400);
+    let (status, _) = common::post(addr, "/api/task/missing", r#"{"index":0}"#).await;
+    assert_eq!(status, 404);
+    // no temporary files are left behind
+    let names: Vec<_> = fs::read_dir(base.path()).unwrap().collect();
+    assert_eq!(names.len(), 2);
+}
+
+#[tokio::test]
+async fn tasks_are_read_only_by_default() {
+    let base = common::base();
+    fs::write(base.path().join("todo.md"), "- [ ] one\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &[]).await;
+    let (_, html) = common::get(addr, "/todo").await;
+    assert!(
+        html.contains("disabled") && !html.contains("data-task"),
+        "{html}"
+    );
+    let (status, _) = common::post(addr, "/api/task/todo", r#"{"index":0}"#).await;
+    assert_eq!(status, 404);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn files_outside_base_are_left_alone() {
+    let base = common::base();
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().join("todo.md");
+    fs::write(&target, "- [ ] one\n").unwrap();
+    std::os::unix::fs::symlink(&target, base.path().join("todo.md")).unwrap();
+    let (_, addr) = common::serve(base.path(), &["--edit-tasks", "--follow-symlinks"]).await;
+
+    let (status, body) = common::post(addr, "/api/task/todo", r#"{"index":0}"#).await;
+    assert_eq!(status, 403, "{body}");
+    assert_eq!(fs::read_to_string(&target).unwrap(), "- [ ] one\n");
+}
@@ -0,0 +1,264 @@
+mod common;
+
+use mdflc::{
+    settings::{Settings, Theme},
+    Placeholder, Template, TemplateMismatch,
+};
+
+fn settings(extra: &[&str]) -> Settings {
+    let base = common::base();
+    Settings::new(&common::args(base.path(), extra)).unwrap()
+}
+
+#[test]
+fn the_embedded_template_fits_every_feature_set() {
+    let settings = settings(&["--no-status-indicator", "--strict"]);
+    let template = Template::new(settings.status_indicator);
+    assert!(template.placeholders().contains(&Placeholder::Status));
+    assert_eq!(template.check(&settings), []);
+}
+
+#[test]
+fn mismatched_placeholders_are_reported() {
+    let template = Template::parse(
+        "<title>{{title}}</title>{{md}}{{status}}{{sidebar}}{{md}}",
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        template.placeholders(),
+        [Placeholder::Title, Placeholder::Md, Placeholder::Status]
+    );
+
+    let mismatches = template.check(&settings(&["--no-status-indicator", "--toc"]));
+    assert_eq!(
+        mismatches,
+        [
+            TemplateMismatch::Unplaced(Placeholder::Generation, "live reload"),
+            TemplateMismatch::Unplaced(Placeholder::Page, "live reload"),
+            TemplateMismatch::Unplaced(Placeholder::Meta, "link previews"),
+            TemplateMismatch::AlwaysEmpty(Placeholder::Status, "--no-status-indicator is set"),
+            TemplateMismatch::Unplaced(Placeholder::Banner, "the partial problem banner"),
+            TemplateMismatch::Unplaced(Placeholder::Toc, "the table of contents"),
+            TemplateMismatch::Unknown("sidebar".to_owned()),
+        ]
+    );
+    assert_eq!(
+        mismatches[3].to_string(),
+        "{{status}} always renders empty, --no-status-indicator is set"
+    );
+}
+
+#[test]
+fn templates_need_a_md_placeholder() {
+    let e = Template::parse("<p>{{title}}</p>", true).unwrap_err();
+    assert!(e.to_string().contains("{{md}}"), "{e}");
+}
+
+#[tokio::test]
+async fn toc_is_filled_in_above_the_page() {
+    let base = common::base();
+    std::fs::write(base.path().join("index.md"), "# Index\n\n## Part\n").unwrap();
+    let (_, addr) = common::serve(base.path(), &["--toc"]).await;
+    let (_, body) = common::get(addr, "/index").await;
+    let toc = body
+        .find(r##"<nav class="toc"><ul><li><a href="#index">Index</a><ul><li><a href="#part">Part</a>"##)
+        .expect("no toc");
+    assert!(toc < body.find("id=\"root\"").unwrap(), "{body}");
+}
+
+#[tokio::test]
+async fn pages_are_titled_by_their_first_heading() {
+    let base = common::base();
+    let dir = base.path();
+    std::fs::write(dir.join("notes.md"), "Intro\n\n## Minor\n\n# Q&A `notes`\n").unwrap();
+    std::fs::write(dir.join("todo-list.md"), "- [ ] nothing\n").unwrap();
+    let (_, addr) = common::serve(dir, &[]).await;
+
+    let (_, body) = common::get(addr, "/notes").await;
+    assert!(body.contains("<title>Q&amp;A notes</title>"), "{body}");
+    // without one, the file name stands in
+    let (_, body) = common::get(addr, "/todo-list").await;
+    assert!(body.contains("<title>Todo List</title>"), "{body}");
+    let (status, body) = common::get(addr, "/missing").await;
+    assert_eq!(status, 404);
+    assert!(body.contains("<title>Not Found</title>"), "{body}");
+}
+
+#[tokio::test]
+async fn front_matter_classes_mark_the_content() {
+    let base = common::base();
+    let dir = base.path();
+    std::fs::write(
+        dir.join("draft.md"),
+        "---\nclass: draft\nlayout: wide\n---\n# Draft\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("odd.md"),
+        "---\nclass: [a, b]\nlayout: 3\n---\n# Odd\n",
+    )
+    .unwrap();
+    let (_, addr) = common::serve(dir, &[]).await;
+
+    let (_, body) = common::get(addr, "/draft").await;
+    assert!(
+        body.contains(r#"<div id="root" class="draft wide""#),
+        "{body}"
+    );
+    // only strings are classes
+    let (_, body) = common::get(addr, "/odd").await;
+    assert!(body.contains(r#"<div id="root" class="""#), "{body}");
+}
+
+#[tokio::test]
+async fn pages_show_their_reading_time() {
+    let base = common::base();
+    let dir = base.path();
+    let words = "word ".repeat(450);
+    std::fs::write(
+        dir.join("long.md"),
+        format!("---\ntitle: not counted\n---\n# Long\n\n{words}\n\n```\nnot counted\n```\n"),
+    )
+    .unwrap();
+    std::fs::write(dir.join("empty.md"), "").unwrap();
+    let (api, addr) = common::serve(dir, &[]).await;
+    assert_eq!(api.inspect("long").unwrap().title, "not counted");
+
+    let (_, body) = common::get(addr, "/long").await;
+    assert!(
+        body.contains(r#"<p class="reading-stats" data-words="451">3 min read · 451 words</p>"#),
+        "{body}"
+    );
+    let (_, body) = common::get(addr, "/empty").await;
+    assert!(
+        body.contains(r#"<p class="reading-stats" data-words=""> ·  words</p>"#),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn pages_tell_when_their_source_was_modified() {
+    use std::time::{Duration, SystemTime};
+
+    let base = common::base();
+    let path = base.path().join("note.md");
+    std::fs::write(&path, "# Note\n").unwrap();
+    let hour_ago = SystemTime::now() - Duration::from_secs(3 * 3600 + 60);
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(hour_ago).unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+
+    let stamp = mdflc::time::Timestamp::from_system(hour_ago);
+    let html = api.get_md("note").unwrap();
+    assert!(
+        html.contains(&format!(
+            r#"<footer class="modified">Last modified <time datetime="{stamp}">{stamp}</time></footer>"#
+        )),
+        "{html}"
+    );
+    // the time relative to now would go stale in cached pages
+    assert!(!html.contains(" ago"), "{html}");
+
+    // rerendering the page picks up the new time
+    std::fs::write(&path, "# Note\n\nmore\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    let html = api.get_md("note").unwrap();
+    assert!(!html.contains(&stamp.to_string()), "{html}");
+
+    // pages that aren't files have no footer
+    assert!(!api.recent().contains("<footer"));
+}
+
+#[tokio::test]
+async fn template_files_replace_the_embedded_one() {
+    let base = common::base();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("page.html");
+    std::fs::write(&path, "<main data-page=\"{{page}}\">{{md}}</main>").unwrap();
+    let template = path.to_str().unwrap();
+    let (api, addr) = common::serve(base.path(), &["--template", template]).await;
+    let path = path.canonicalize().unwrap();
+    assert!(api.watch_paths().contains(&path));
+
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(body.starts_with("<main data-page=\"index\"><h1"), "{body}");
+
+    let mut changes = api.subscribe();
+    std::fs::write(&path, "<article>{{md}}</article>").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert_eq!(changes.try_recv().unwrap().key, "index");
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(body.starts_with("<article><h1"), "{body}");
+
+    // templates that can't be used leave the previous one in place
+    std::fs::write(&path, "<article></article>").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(body.starts_with("<article><h1"), "{body}");
+    let warnings = api.warnings.list();
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w.message.contains("the previous one is kept")
+                && w.message.contains("{{md}}")),
+        "{warnings:?}"
+    );
+}
+
+#[test]
+fn template_files_need_a_md_placeholder() {
+    let base = common::base();
+    let path = base.path().join("bad.html");
+    std::fs::write(&path, "<p>{{title}}</p>").unwrap();
+    let args = common::args(base.path(), &["--template", path.to_str().unwrap()]);
+    let e = mdflc::Api::new(&args).unwrap_err();
+    let e = format!("{e:#}");
+    assert!(
+        e.contains("invalid template") && e.contains("{{md}}"),
+        "{e}"
+    );
+}
+
+#[tokio::test]
+async fn themes_pick_the_linked_stylesheet() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(
+        body.contains("<link href=\"/theme-light.css\" rel=\"stylesheet\" />"),
+        "{body}"
+    );
+    let (status, css) = common::get(addr, "/theme-dark.css").await;
+    assert_eq!(status, 200);
+    assert!(css.contains("background: #0d1117"), "{css}");
+
+    let mut changes = api.subscribe();
+    api.set_theme(Theme::Dark);
+    assert_eq!(changes.try_recv().unwrap().key, "index");
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(body.contains("href=\"/theme-dark.css\""), "{body}");
+    assert!(!body.contains("theme-light.css"), "{body}");
+
+    let auto = common::base();
+    let (api, addr) = common::serve(auto.path(), &["--theme", "auto"]).await;
+    assert_eq!(api.theme(), Theme::Auto);
+    let (_, body) = common::get(addr, "/index").await;
+    assert!(
+        body.contains(
+            "<link href=\"/theme-light.css\" rel=\"stylesheet\" media=\"(prefers-color-scheme: light)\" />\
+            <link href=\"/theme-dark.css\" rel=\"stylesheet\" media=\"(prefers-color-scheme: dark)\" />"
+        ),
+        "{body}"
+    );
+}
+
+#[test]
+fn themes_need_a_styles_placeholder() {
+    let template = Template::parse("<main>{{md}}</main>", true).unwrap();
+    let styles = TemplateMismatch::Unplaced(Placeholder::Styles, "the theme or custom stylesheet");
+    assert!(!template.check(&settings(&[])).contains(&styles));
+    assert!(template
+        .check(&settings(&["--theme", "dark"]))
+        .contains(&styles));
+}
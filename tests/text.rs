@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use mdflc::warnings::Category;
+
+#[tokio::test]
+async fn text_files_are_served_preformatted() {
+    let base = common::base();
+    let path = base.path().join("notes.txt");
+    fs::write(&path, "first <line> & more\n  indented *not emphasis*\n").unwrap();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let (status, body) = common::get(addr, "/notes").await;
+    assert_eq!(status, 200);
+    assert!(
+        body.contains(
+            "<pre class=\"plain-text\">first &lt;line&gt; &amp; more\n  indented *not emphasis*\n</pre>"
+        ),
+        "{body}"
+    );
+    assert!(body.contains("<title>Notes"), "{body}");
+
+    let mut changes = api.subscribe();
+    fs::write(&path, "changed\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert!(api.get_md("notes").unwrap().contains(">changed\n</pre>"));
+    assert_eq!(changes.try_recv().unwrap().key, "notes");
+}
+
+#[tokio::test]
+async fn markdown_is_preferred_over_text() {
+    let base = common::base();
+    let md = base.path().join("both.md");
+    let txt = base.path().join("both.txt");
+    fs::write(&md, "# From markdown\n").unwrap();
+    fs::write(&txt, "from text\n").unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+
+    assert!(api.get_md("both").unwrap().contains("From markdown"));
+    let warnings = api.warnings.list();
+    assert!(
+        warnings.iter().any(|w| w.category == Category::Collision
+            && w.message == "both.txt is left out for both.md"),
+        "{warnings:?}"
+    );
+
+    // changes to the text are ignored while the markdown is there
+    fs::write(&txt, "still text\n").unwrap();
+    api.update_paths([txt.as_path()]).unwrap();
+    assert!(api.get_md("both").unwrap().contains("From markdown"));
+
+    fs::remove_file(&md).unwrap();
+    api.update_paths([md.as_path()]).unwrap();
+    let html = api.get_md("both").unwrap();
+    assert!(html.contains(">still text\n</pre>"), "{html}");
+    assert!(api
+        .warnings
+        .list()
+        .iter()
+        .all(|w| w.category != Category::Collision));
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use mdflc::time::Timestamp;
+
+const NOW: Timestamp = Timestamp(1_700_000_000);
+
+fn ago(secs: u64) -> String {
+    Timestamp(NOW.0 - secs).ago(NOW)
+}
+
+#[test]
+fn relative_boundaries() {
+    assert_eq!(ago(0), "just now");
+    assert_eq!(ago(4), "just now");
+    assert_eq!(ago(59), "59s ago");
+    assert_eq!(ago(60), "1m ago");
+    assert_eq!(ago(61), "1m ago");
+    assert_eq!(ago(3599), "59m ago");
+    assert_eq!(ago(25 * 3600), "1d ago");
+    assert_eq!(ago(23 * 3600 + 59 * 60), "23h ago");
+    assert_eq!(ago(8 * 86400), "8d ago");
+}
+
+#[test]
+fn future_times_are_just_now() {
+    assert_eq!(Timestamp(NOW.0 + 3600).ago(NOW), "just now");
+}
+
+#[test]
+fn rfc3339() {
+    assert_eq!(Timestamp(0).rfc3339(), "1970-01-01T00:00:00Z");
+    assert_eq!(NOW.rfc3339(), "2023-11-14T22:13:20Z");
+    // leap days and the end of a year
+    assert_eq!(Timestamp(951_782_400).rfc3339(), "2000-02-29T00:00:00Z");
+    assert_eq!(Timestamp(1_735_689_599).rfc3339(), "2024-12-31T23:59:59Z");
+    assert_eq!(
+        serde_json::to_string(&NOW).unwrap(),
+        "\"2023-11-14T22:13:20Z\""
+    );
+}
+
+#[test]
+fn times_before_the_epoch_clamp() {
+    let before = UNIX_EPOCH - Duration::from_secs(10);
+    assert_eq!(Timestamp::from_system(before), Timestamp(0));
+}
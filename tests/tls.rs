@@ -0,0 +1,61 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use mdflc::{cli::Args, settings::Settings, tls::Tls};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn tls(cert: &str, key: &str) -> Tls {
+    Tls {
+        cert: fixture(cert),
+        key: fixture(key),
+    }
+}
+
+#[test]
+fn certificates_are_checked_before_serving() {
+    let config = tls("cert.pem", "key.pem").server_config().unwrap();
+    assert_eq!(config.alpn_protocols, [b"http/1.1".to_vec()]);
+
+    let err = tls("cert.pem", "other-key.pem")
+        .server_config()
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("doesn't match"), "{err:#}");
+    let err = tls("key.pem", "key.pem").server_config().unwrap_err();
+    assert!(
+        format!("{err:#}").contains("no pem encoded certificate"),
+        "{err:#}"
+    );
+    let err = tls("cert.pem", "cert.pem").server_config().unwrap_err();
+    assert!(
+        format!("{err:#}").contains("no pem encoded private key"),
+        "{err:#}"
+    );
+    let err = tls("missing.pem", "key.pem").server_config().unwrap_err();
+    assert!(format!("{err:#}").contains("unable to read"), "{err:#}");
+}
+
+#[test]
+fn urls_use_https_with_a_certificate() {
+    let base = common::base();
+    let cert = fixture("cert.pem");
+    let key = fixture("key.pem");
+    let (cert, key) = (cert.to_str().unwrap(), key.to_str().unwrap());
+
+    let plain = Settings::new(&common::args(base.path(), &[])).unwrap();
+    assert!(plain.url.starts_with("http://"), "{}", plain.url);
+    assert!(plain.tls.is_none());
+    let args = common::args(base.path(), &["--tls-cert", cert, "--tls-key", key]);
+    let settings = Settings::new(&args).unwrap();
+    assert!(settings.url.starts_with("https://"), "{}", settings.url);
+    assert_eq!(settings.tls, Some(tls("cert.pem", "key.pem")));
+
+    // one without the other is refused
+    assert!(Args::try_parse_from(["mdflc", ".", "--tls-cert", cert]).is_err());
+}
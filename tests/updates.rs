@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs;
+
+use mdflc::{updates::QUEUE_CAPACITY, ChangeKind};
+
+#[tokio::test]
+async fn queued_changes_are_coalesced() {
+    let base = common::base();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    let mut events = api.subscribe();
+
+    let path = base.path().join("a.md");
+    fs::write(&path, "# One\n").unwrap();
+    assert!(api.updates.push(path.clone()));
+    fs::write(&path, "# Two\n").unwrap();
+    assert!(api.updates.push(path.clone()));
+    assert_eq!(api.updates.stats().depth, 2);
+
+    tokio::spawn(api.clone().process_updates());
+    let event = events.recv().await.unwrap();
+    assert_eq!((event.key.as_str(), event.kind), ("a", ChangeKind::Added));
+    assert!(api.get_md("a").unwrap().contains("Two"));
+
+    // stats are recorded once the batch is done
+    common::wait_for(|| api.updates.stats().coalesced == 1).await;
+    let stats = api.updates.stats();
+    assert_eq!((stats.depth, stats.overflows), (0, 0));
+    assert!(
+        events.try_recv().is_err(),
+        "intermediate states were rendered"
+    );
+}
+
+#[tokio::test]
+async fn overflowing_the_queue_rescans() {
+    let base = common::base();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    let mut events = api.subscribe();
+
+    let path = base.path().join("a.md");
+    fs::write(&path, "# A\n").unwrap();
+    for _ in 0..QUEUE_CAPACITY {
+        assert!(api.updates.push(path.clone()));
+    }
+    // the dropped change is only found by rescanning
+    fs::write(base.path().join("b.md"), "# B\n").unwrap();
+    assert!(!api.updates.push(base.path().join("b.md")));
+
+    tokio::spawn(api.clone().process_updates());
+    let mut keys: Vec<_> = Vec::new();
+    for _ in 0..2 {
+        keys.push(events.recv().await.unwrap().key);
+    }
+    keys.sort_unstable();
+    assert_eq!(keys, ["a", "b"]);
+    assert_eq!(api.updates.stats().overflows, 1);
+    assert_eq!(api.updates.stats().depth, 0);
+}
+
+#[tokio::test]
+async fn updates_render_like_the_initial_scan() {
+    let base = common::base();
+    let path = base.path().join("glossary.md");
+    let glossary = "Term\n: A definition.\n\n  More of it.\n";
+    fs::write(&path, glossary).unwrap();
+    let (api, _) = common::serve(base.path(), &[]).await;
+    // the generation in the template and the modified time change with every update
+    let body = |html: String| {
+        let list = html.split_once("<dl>").unwrap().1;
+        list.split_once("</dl>").unwrap().0.to_owned()
+    };
+    let scanned = body(api.get_md("glossary").unwrap());
+    assert!(scanned.contains("<dt>Term</dt>"), "{scanned}");
+
+    fs::write(&path, "# Changed\n").unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    fs::write(&path, glossary).unwrap();
+    api.update_paths([path.as_path()]).unwrap();
+    assert_eq!(body(api.get_md("glossary").unwrap()), scanned);
+}
@@ -0,0 +1,305 @@
+mod common;
+
+use std::{
+    fs,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use mdflc::{
+    changes::ChangeEvent,
+    watch::{self, WatchPolicy, WatchState},
+    Api, ChangeKind,
+};
+use tempfile::TempDir;
+use tokio::{
+    sync::broadcast::{error::TryRecvError, Receiver},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+
+/// A served base with a running watcher
+struct Harness {
+    base: TempDir,
+    api: Arc<Api>,
+    addr: SocketAddr,
+    events: Receiver<ChangeEvent>,
+    _watching: JoinHandle<Result<(), watchexec::error::CriticalError>>,
+    _wx: watchexec::Watchexec,
+}
+
+impl Harness {
+    /// Serves a fresh base, returning once the watcher reports changes
+    async fn start() -> Self {
+        let base = common::base();
+        // directories created while watching take a moment to be watched themselves
+        fs::create_dir(base.path().join("docs")).unwrap();
+        let (api, addr) = common::serve(base.path(), &[]).await;
+        tokio::spawn(api.clone().process_updates());
+        let wx = api.watcher().unwrap();
+        let watching = wx.main();
+        let mut harness = Self {
+            events: api.subscribe(),
+            base,
+            api,
+            addr,
+            _watching: watching,
+            _wx: wx,
+        };
+
+        // the watcher may still be starting up, keep writing until it notices
+        let primer = harness.path("primer.md");
+        timeout(Duration::from_secs(10), async {
+            for n in 0.. {
+                put(&primer, &format!("# Primer {n}\n"));
+                let next = timeout(Duration::from_millis(500), harness.events.recv());
+                if next.await.is_ok() {
+                    return;
+                }
+            }
+        })
+        .await
+        .expect("the watcher never started");
+        fs::remove_file(&primer).unwrap();
+        harness.expect("primer", ChangeKind::Removed).await;
+        harness.settle().await;
+        harness
+    }
+
+    fn path(&self, file: &str) -> std::path::PathBuf {
+        self.base.path().join(file)
+    }
+
+    /// Waits for an event for `key` of `kind`, returning every event up to and including it
+    async fn expect(&mut self, key: &str, kind: ChangeKind) -> Vec<ChangeEvent> {
+        let wait = async {
+            let mut events = Vec::new();
+            loop {
+                let event = self.events.recv().await.unwrap();
+                let found = event.key == key && event.kind == kind;
+                events.push(event);
+                if found {
+                    return events;
+                }
+            }
+        };
+        timeout(Duration::from_secs(5), wait)
+            .await
+            .unwrap_or_else(|_| panic!("no {kind:?} event for {key}"))
+    }
+
+    /// Collects events until none arrive for a while
+    async fn settle(&mut self) -> Vec<ChangeEvent> {
+        let mut events = Vec::new();
+        let mut quiet_since = Instant::now();
+        while quiet_since.elapsed() < Duration::from_millis(500) {
+            match self.events.try_recv() {
+                Ok(event) => {
+                    events.push(event);
+                    quiet_since = Instant::now();
+                }
+                Err(TryRecvError::Empty) => sleep(Duration::from_millis(25)).await,
+                Err(e) => panic!("{e}"),
+            }
+        }
+        events
+    }
+
+    /// Waits for the events of a single change, asserting they are exactly `expected`
+    async fn assert_events(&mut self, expected: &[(&str, ChangeKind)]) {
+        let (key, kind) = expected[0];
+        let mut events = self.expect(key, kind).await;
+        events.extend(self.settle().await);
+
+        let mut seen: Vec<_> = events.iter().map(|e| (e.key.as_str(), e.kind)).collect();
+        let mut expected = expected.to_vec();
+        seen.sort_by_key(|(key, _)| *key);
+        expected.sort_by_key(|(key, _)| *key);
+        assert_eq!(seen, expected, "{events:?}");
+    }
+}
+
+/// A watcher task that has already failed
+fn failed_watcher() -> JoinHandle<Result<(), watchexec::error::CriticalError>> {
+    let handle = tokio::spawn(std::future::pending());
+    handle.abort();
+    handle
+}
+
+/// Writes `text` to `path` in one step, so the watcher never sees a partial file
+fn put(path: &Path, text: &str) {
+    let staging = path.with_extension("staging");
+    fs::write(&staging, text).unwrap();
+    fs::rename(staging, path).unwrap();
+}
+
+#[tokio::test]
+async fn created_pages_are_served() {
+    let mut h = Harness::start().await;
+    put(&h.path("new.md"), "# New\n\nfresh content\n");
+    put(&h.path("docs/nested.md"), "# Nested\n");
+
+    h.assert_events(&[
+        ("new", ChangeKind::Added),
+        ("docs/nested", ChangeKind::Added),
+    ])
+    .await;
+    assert!(h.api.get_hash("docs/nested").is_some());
+    let (status, body) = common::get(h.addr, "/new").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("fresh content"), "{body}");
+}
+
+#[tokio::test]
+async fn modified_pages_are_rerendered() {
+    let mut h = Harness::start().await;
+    let generation = h.api.generation();
+    put(&h.path("index.md"), "# Index\n\nchanged content\n");
+
+    h.assert_events(&[("index", ChangeKind::Modified)]).await;
+    assert!(h.api.generation() > generation);
+    let (_, body) = common::get(h.addr, "/index").await;
+    assert!(body.contains("changed content"), "{body}");
+}
+
+#[tokio::test]
+async fn unchanged_writes_are_ignored() {
+    let mut h = Harness::start().await;
+    put(&h.path("other.md"), "# Other\n");
+    h.assert_events(&[("other", ChangeKind::Added)]).await;
+
+    // rewriting identical content and touching non-markdown files changes nothing
+    put(&h.path("other.md"), "# Other\n");
+    fs::write(h.path("notes.log"), "not markdown").unwrap();
+    put(&h.path("index.md"), "# Index\n\nafter\n");
+    h.assert_events(&[("index", ChangeKind::Modified)]).await;
+    assert!(h.api.get_hash("notes").is_none());
+}
+
+#[tokio::test]
+async fn removed_pages_are_not_found() {
+    let mut h = Harness::start().await;
+    put(&h.path("gone.md"), "# Gone\n");
+    h.assert_events(&[("gone", ChangeKind::Added)]).await;
+
+    fs::remove_file(h.path("gone.md")).unwrap();
+    h.assert_events(&[("gone", ChangeKind::Removed)]).await;
+    assert!(h.api.get_hash("gone").is_none());
+    let (status, _) = common::get(h.addr, "/gone").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn renamed_pages_move() {
+    let mut h = Harness::start().await;
+    put(&h.path("before.md"), "# Moving\n\nmoving content\n");
+    h.assert_events(&[("before", ChangeKind::Added)]).await;
+
+    fs::rename(h.path("before.md"), h.path("after.md")).unwrap();
+    h.assert_events(&[
+        ("before", ChangeKind::Removed),
+        ("after", ChangeKind::Added),
+    ])
+    .await;
+    assert!(h.api.get_hash("before").is_none());
+    let (_, body) = common::get(h.addr, "/after").await;
+    assert!(body.contains("moving content"), "{body}");
+}
+
+#[tokio::test]
+async fn failed_watchers_are_retried_while_serving() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    let config = (*api.watcher().unwrap().config).clone();
+
+    let policy = WatchPolicy {
+        exit_on_failure: false,
+        retry_for: Duration::from_secs(60),
+    };
+    tokio::spawn(watch::supervise(
+        api.clone(),
+        config.clone(),
+        failed_watcher(),
+        policy,
+    ));
+    common::wait_for(|| api.watch_state() != WatchState::Watching).await;
+    let (_, body) = common::get(addr, "/healthz").await;
+    assert!(body.contains(r#""state":"degraded""#), "{body}");
+    let (status, _) = common::get(addr, "/index").await;
+    assert_eq!(status, 200);
+    // restarted after the first retry
+    timeout(Duration::from_secs(5), async {
+        while api.watch_state() != WatchState::Watching {
+            sleep(Duration::from_millis(25)).await;
+        }
+    })
+    .await
+    .expect("the watcher was never restarted");
+
+    let policy = WatchPolicy {
+        exit_on_failure: false,
+        retry_for: Duration::ZERO,
+    };
+    tokio::spawn(watch::supervise(
+        api.clone(),
+        config,
+        failed_watcher(),
+        policy,
+    ));
+    common::wait_for(|| matches!(api.watch_state(), WatchState::Failed(_))).await;
+    let (_, body) = common::get(addr, "/healthz").await;
+    assert!(body.contains(r#""state":"failed""#), "{body}");
+    let (status, _) = common::get(addr, "/index").await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn failed_watchers_can_stop_the_server() {
+    let base = common::base();
+    let (api, _) = common::serve(base.path(), &["--exit-on-watch-failure"]).await;
+    let config = (*api.watcher().unwrap().config).clone();
+    let policy = api.settings().watch;
+    assert!(policy.exit_on_failure);
+
+    let supervised = watch::supervise(api.clone(), config, failed_watcher(), policy);
+    let e = timeout(Duration::from_secs(5), supervised)
+        .await
+        .expect("the server kept running")
+        .unwrap_err();
+    assert!(e.to_string().contains("watcher failed"), "{e}");
+    assert_eq!(api.watch_state(), WatchState::Watching);
+}
+
+#[tokio::test]
+async fn diffs_show_the_last_update() {
+    let mut h = Harness::start().await;
+    let (status, body) = common::get(h.addr, "/diff/index").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("No previous version"), "{body}");
+
+    put(&h.path("index.md"), "# Index\n\nfirst\n");
+    h.assert_events(&[("index", ChangeKind::Modified)]).await;
+    put(&h.path("index.md"), "# Index\n\nsecond\n");
+    h.assert_events(&[("index", ChangeKind::Modified)]).await;
+
+    let (status, body) = common::get(h.addr, "/diff/index").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("<h1>Changes to Index</h1>"), "{body}");
+    assert!(
+        body.contains(r#"<span class="diff-removed">- first</span>"#),
+        "{body}"
+    );
+    assert!(
+        body.contains(r#"<span class="diff-added">+ second</span>"#),
+        "{body}"
+    );
+    assert!(
+        body.contains(r#"<span class="diff-equal">  # Index</span>"#),
+        "{body}"
+    );
+
+    let (status, _) = common::get(h.addr, "/diff/missing").await;
+    assert_eq!(status, 404);
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{protocol::frame::coding::CloseCode, Message},
+};
+
+fn subscribe() -> Message {
+    Message::Text(r#"{"type":"subscribe","page":"index"}"#.into())
+}
+
+/// Reads messages until the server closes the socket, returning its close code
+async fn close_code<S>(socket: &mut S) -> Option<CloseCode>
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    while let Some(Ok(message)) = socket.next().await {
+        if let Message::Close(frame) = message {
+            return frame.map(|f| f.code);
+        }
+    }
+    None
+}
+
+#[tokio::test]
+async fn reconnect_storm_restores_socket_count() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    let url = format!("ws://{addr}/refresh-ws");
+
+    for round in 0..20 {
+        let mut sockets = Vec::new();
+        for _ in 0..10 {
+            let (mut socket, _) = connect_async(&url).await.unwrap();
+            let Some(Ok(Message::Text(first))) = socket.next().await else {
+                panic!("expected a generation message");
+            };
+            assert!(first.contains(r#""type":"generation""#), "{first}");
+            sockets.push(socket);
+        }
+        common::wait_for(|| api.sockets() == 10).await;
+
+        // alternate between abrupt drops and graceful closes
+        for mut socket in sockets {
+            if round % 2 == 0 {
+                drop(socket);
+            } else {
+                socket.close(None).await.unwrap();
+            }
+        }
+        common::wait_for(|| api.sockets() == 0).await;
+    }
+
+    let (mut socket, _) = connect_async(&url).await.unwrap();
+    let Some(Ok(Message::Text(first))) = socket.next().await else {
+        panic!("expected a generation message");
+    };
+    assert!(first.contains(&api.generation().to_string()), "{first}");
+    socket.send(Message::Close(None)).await.unwrap();
+    common::wait_for(|| api.sockets() == 0).await;
+}
+
+#[tokio::test]
+async fn changes_refresh_sockets() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+    tokio::spawn(api.clone().process_updates());
+    let wx = api.watcher().unwrap();
+    let _watching = wx.main();
+
+    let (mut socket, _) = connect_async(format!("ws://{addr}/refresh-ws"))
+        .await
+        .unwrap();
+    let Some(Ok(Message::Text(_))) = socket.next().await else {
+        panic!("expected a generation message");
+    };
+    socket.send(subscribe()).await.unwrap();
+    // the watcher may still be starting up, keep writing until it notices
+    let page = base.path().join("index.md");
+    let refresh = tokio::time::timeout(Duration::from_secs(10), async {
+        for n in 0.. {
+            std::fs::write(&page, format!("# Index {n}\n")).unwrap();
+            let next = tokio::time::timeout(Duration::from_millis(500), socket.next());
+            if let Ok(Some(Ok(Message::Text(text)))) = next.await {
+                return text;
+            }
+        }
+        unreachable!()
+    })
+    .await
+    .expect("no refresh message");
+    assert!(refresh.contains(r#""type":"refresh""#), "{refresh}");
+}
+
+#[tokio::test]
+async fn idle_sockets_are_closed() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &["--ws-grace", "1"]).await;
+    let url = format!("ws://{addr}/refresh-ws");
+
+    let (mut idle, _) = connect_async(&url).await.unwrap();
+    let (mut subscribed, _) = connect_async(&url).await.unwrap();
+    subscribed.send(subscribe()).await.unwrap();
+
+    let code = tokio::time::timeout(Duration::from_secs(5), close_code(&mut idle))
+        .await
+        .expect("idle socket was not closed");
+    assert_eq!(code, Some(CloseCode::Policy));
+    common::wait_for(|| api.sockets() == 1).await;
+
+    // well past the grace period, the subscribed socket stays open
+    let next = tokio::time::timeout(Duration::from_millis(1500), subscribed.next()).await;
+    assert!(
+        !matches!(next, Ok(Some(Ok(Message::Close(_))) | None)),
+        "{next:?}"
+    );
+    assert_eq!(api.sockets(), 1);
+}
+
+#[tokio::test]
+async fn sockets_are_closed_after_max_lifetime() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &["--ws-max-lifetime", "1"]).await;
+
+    let (mut socket, _) = connect_async(format!("ws://{addr}/refresh-ws"))
+        .await
+        .unwrap();
+    socket.send(subscribe()).await.unwrap();
+
+    let code = tokio::time::timeout(Duration::from_secs(5), close_code(&mut socket))
+        .await
+        .expect("socket outlived its maximum lifetime");
+    assert_eq!(code, Some(CloseCode::Away));
+    common::wait_for(|| api.sockets() == 0).await;
+}
+
+/// Opens a subscribed refresh socket, past its generation message
+async fn subscribed(
+    addr: std::net::SocketAddr,
+) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
+    let (mut socket, _) = connect_async(format!("ws://{addr}/refresh-ws"))
+        .await
+        .unwrap();
+    let Some(Ok(Message::Text(_))) = socket.next().await else {
+        panic!("expected a generation message");
+    };
+    socket.send(subscribe()).await.unwrap();
+    socket
+}
+
+/// Reads the type of the next message from the server
+async fn next_type<S>(socket: &mut S) -> String
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let next = tokio::time::timeout(Duration::from_secs(5), socket.next()).await;
+    let Ok(Some(Ok(Message::Text(text)))) = next else {
+        panic!("expected a message, got {next:?}");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    json["type"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn style_only_batches_reload_css() {
+    let base = common::base();
+    let theme = tempfile::tempdir().unwrap();
+    let theme_dir = theme.path().canonicalize().unwrap();
+    let css = theme_dir.join("style.css");
+    std::fs::write(&css, "body { color: red; }").unwrap();
+    let mount = format!("theme={}", theme_dir.display());
+    let (api, addr) = common::serve(base.path(), &["--mount", &mount, "--watch-mounts"]).await;
+    let index = base.path().canonicalize().unwrap().join("index.md");
+
+    // a stylesheet alone leaves the page in place
+    let mut socket = subscribed(addr).await;
+    std::fs::write(&css, "body { color: blue; }").unwrap();
+    api.update_paths([css.as_path()]).unwrap();
+    assert_eq!(next_type(&mut socket).await, "css");
+
+    // content still refreshes the same socket
+    std::fs::write(&index, "# Index\n\nchanged\n").unwrap();
+    api.update_paths([index.as_path()]).unwrap();
+    assert_eq!(next_type(&mut socket).await, "refresh");
+
+    // mixed batches count as content
+    let mut socket = subscribed(addr).await;
+    std::fs::write(&index, "# Index\n\nchanged again\n").unwrap();
+    api.update_paths([css.as_path(), index.as_path()]).unwrap();
+    assert_eq!(next_type(&mut socket).await, "refresh");
+}
+
+#[tokio::test]
+async fn clients_are_registered_while_connected() {
+    let base = common::base();
+    let (api, addr) = common::serve(base.path(), &[]).await;
+
+    let socket = subscribed(addr).await;
+    common::wait_for(|| api.clients.list().iter().any(|c| c.page.is_some())).await;
+    let clients = api.clients.list();
+    assert_eq!(clients.len(), 1);
+    let client = &clients[0];
+    assert_eq!(client.page.as_deref(), Some("index"));
+    assert!(client.addr.is_some_and(|a| a.ip().is_loopback()));
+    assert_eq!(client.sent, 1);
+
+    let (status, body) = common::get(addr, "/api/clients").await;
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json[0]["page"], "index");
+    assert_eq!(json[0]["sent"], 1);
+
+    drop(socket);
+    common::wait_for(|| api.clients.is_empty()).await;
+}